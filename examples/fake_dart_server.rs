@@ -0,0 +1,167 @@
+//! Stand-in for `dart language-server` used by the LSP proxy tests
+//!
+//! Pass it to the proxy with `--dart-binary`. It answers `initialize` and
+//! `shutdown`, exits on `exit`, and answers any other request with `null`.
+//! Behaviour is controlled through environment variables, which the proxy
+//! passes on to it, or through `--fake-<name>=<value>` arguments before
+//! `language-server` (e.g. `--fake-log=/tmp/log` for `FAKE_DART_LOG`):
+//!
+//! - `FAKE_DART_LOG`: append every message received to this file, one JSON
+//!   document per line
+//! - `FAKE_DART_CRASH_ON_START`: exit immediately with status 1
+//! - `FAKE_DART_IGNORE_SHUTDOWN`: never answer `shutdown` and ignore `exit`
+//!   and end of input, so the proxy has to kill the process
+//! - `FAKE_DART_SCRIPT`: JSON file with canned replies, see below
+//!
+//! A `fake/crash` request makes it exit with status 3 without answering.
+//!
+//! The script is an array of rules, and the first rule whose `method`
+//! matches an incoming message applies:
+//!
+//! ```json
+//! [
+//!   {"method": "textDocument/hover", "result": {"contents": "docs"}},
+//!   {"method": "textDocument/didOpen", "send": [
+//!     {"jsonrpc": "2.0", "method": "textDocument/publishDiagnostics",
+//!      "params": {"uri": "file:///a.dart", "diagnostics": []}}
+//!   ]}
+//! ]
+//! ```
+//!
+//! Messages in `send` are written first, in order. Requests are then
+//! answered with the rule's `error` if it has one, else its `result`
+//! (`null` when missing). With `"respond": false` they are never answered.
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+
+fn read_message(reader: &mut impl BufRead) -> Option<Value> {
+    let mut content_length = None;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).ok()? == 0 {
+            return None;
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().ok();
+            }
+        }
+    }
+
+    let mut content = vec![0; content_length?];
+    reader.read_exact(&mut content).ok()?;
+    serde_json::from_slice(&content).ok()
+}
+
+fn write_message(message: &Value) {
+    let content = message.to_string();
+    let mut stdout = std::io::stdout().lock();
+    write!(
+        stdout,
+        "Content-Length: {}\r\n\r\n{}",
+        content.len(),
+        content
+    )
+    .unwrap();
+    stdout.flush().unwrap();
+}
+
+/// Value of a setting from the command line or the environment
+fn setting(name: &str) -> Option<String> {
+    let flag = format!("--fake-{}=", name.to_lowercase().replace('_', "-"));
+    std::env::args()
+        .find_map(|arg| arg.strip_prefix(&flag).map(str::to_string))
+        .or_else(|| std::env::var(format!("FAKE_DART_{}", name)).ok())
+}
+
+fn log(message: &Value) {
+    let Some(path) = setting("LOG") else {
+        return;
+    };
+    let mut file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .unwrap();
+    writeln!(file, "{}", message).unwrap();
+}
+
+fn load_script() -> Vec<Value> {
+    let Some(path) = setting("SCRIPT") else {
+        return Vec::new();
+    };
+    let script = std::fs::read_to_string(&path).unwrap();
+    serde_json::from_str(&script).unwrap()
+}
+
+/// Block until the proxy kills us
+fn hang() -> ! {
+    loop {
+        std::thread::sleep(std::time::Duration::from_secs(60));
+    }
+}
+
+fn main() {
+    if setting("CRASH_ON_START").is_some() {
+        std::process::exit(1);
+    }
+    let ignore_shutdown = setting("IGNORE_SHUTDOWN").is_some();
+    let script = load_script();
+
+    let mut stdin = BufReader::new(std::io::stdin());
+    while let Some(message) = read_message(&mut stdin) {
+        log(&message);
+
+        let method = message["method"].as_str().unwrap_or_default();
+        let rule = script.iter().find(|rule| rule["method"] == method);
+        if let Some(rule) = rule {
+            for scripted in rule["send"].as_array().into_iter().flatten() {
+                write_message(scripted);
+            }
+        }
+
+        let Some(id) = message.get("id").filter(|_| !method.is_empty()) else {
+            if method == "exit" && !ignore_shutdown {
+                std::process::exit(0);
+            }
+            continue;
+        };
+
+        if let Some(rule) = rule {
+            if rule["respond"] == false {
+                continue;
+            }
+            let mut response = json!({"jsonrpc": "2.0", "id": id});
+            match rule.get("error") {
+                Some(error) => response["error"] = error.clone(),
+                None => response["result"] = rule.get("result").cloned().unwrap_or(Value::Null),
+            }
+            write_message(&response);
+            continue;
+        }
+
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "positionEncoding": "utf-16",
+                    "textDocumentSync": 2,
+                    "codeActionProvider": true,
+                },
+                "serverInfo": {"name": "fake-dart"},
+            }),
+            "shutdown" if ignore_shutdown => continue,
+            "fake/crash" => std::process::exit(3),
+            _ => Value::Null,
+        };
+        write_message(&json!({"jsonrpc": "2.0", "id": id, "result": result}));
+    }
+
+    if ignore_shutdown {
+        hang();
+    }
+}
@@ -0,0 +1,206 @@
+//! The import graph of a project
+//!
+//! Nodes are the files of a [`ProjectIndex`], and edges their `import` and
+//! `export` directives between them. Directives resolving outside the
+//! indexed files, like `dart:` libraries and other packages, are left out.
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::analyzer::index::{IndexedDirective, ProjectIndex};
+
+pub struct ImportGraph<'a> {
+    index: &'a ProjectIndex,
+    /// Outgoing edges of each file, as target and directive, in source order
+    edges: Vec<Vec<(usize, &'a IndexedDirective)>>,
+}
+
+impl<'a> ImportGraph<'a> {
+    pub fn new(index: &'a ProjectIndex) -> Self {
+        let edges = index
+            .files
+            .iter()
+            .map(|file| {
+                file.directives
+                    .iter()
+                    .filter_map(|directive| Some((directive.target?, directive)))
+                    .collect()
+            })
+            .collect();
+        Self { index, edges }
+    }
+
+    /// Strongly connected components with a cycle: several files importing
+    /// each other, or a file importing itself
+    ///
+    /// Each component is sorted by path, and components are ordered by their
+    /// first path.
+    pub fn cycles(&self) -> Vec<Vec<usize>> {
+        let mut cycles: Vec<Vec<usize>> = Tarjan::new(self)
+            .components()
+            .into_iter()
+            .filter(|component| {
+                component.len() > 1 || self.directive(component[0], component[0]).is_some()
+            })
+            .map(|mut component| {
+                component.sort_by_key(|&file| self.index.path(file));
+                component
+            })
+            .collect();
+        cycles.sort_by_key(|component| self.index.path(component[0]));
+        cycles
+    }
+
+    /// The shortest cycle from `start` back to itself through `component`,
+    /// without repeating `start` at the end
+    pub fn shortest_cycle(&self, start: usize, component: &[usize]) -> Vec<usize> {
+        let mut previous: HashMap<usize, usize> = HashMap::new();
+        let mut queue = VecDeque::from([start]);
+        while let Some(node) = queue.pop_front() {
+            for &(to, _) in &self.edges[node] {
+                if to == start {
+                    let mut cycle = vec![node];
+                    while let Some(&before) = cycle.last().and_then(|n| previous.get(n)) {
+                        cycle.push(before);
+                    }
+                    cycle.reverse();
+                    return cycle;
+                }
+                if component.contains(&to) && !previous.contains_key(&to) {
+                    previous.insert(to, node);
+                    queue.push_back(to);
+                }
+            }
+        }
+        vec![start]
+    }
+
+    /// The first directive of `from` importing or exporting `to`
+    pub fn directive(&self, from: usize, to: usize) -> Option<&'a IndexedDirective> {
+        self.edges[from]
+            .iter()
+            .find(|edge| edge.0 == to)
+            .map(|edge| edge.1)
+    }
+}
+
+/// Tarjan's strongly connected components algorithm
+struct Tarjan<'a, 'b> {
+    graph: &'b ImportGraph<'a>,
+    index: Vec<Option<usize>>,
+    low_link: Vec<usize>,
+    on_stack: Vec<bool>,
+    stack: Vec<usize>,
+    next_index: usize,
+    components: Vec<Vec<usize>>,
+}
+
+impl<'a, 'b> Tarjan<'a, 'b> {
+    fn new(graph: &'b ImportGraph<'a>) -> Self {
+        let count = graph.edges.len();
+        Self {
+            graph,
+            index: vec![None; count],
+            low_link: vec![0; count],
+            on_stack: vec![false; count],
+            stack: Vec::new(),
+            next_index: 0,
+            components: Vec::new(),
+        }
+    }
+
+    fn components(mut self) -> Vec<Vec<usize>> {
+        for node in 0..self.graph.edges.len() {
+            if self.index[node].is_none() {
+                self.visit(node);
+            }
+        }
+        self.components
+    }
+
+    fn visit(&mut self, node: usize) {
+        self.index[node] = Some(self.next_index);
+        self.low_link[node] = self.next_index;
+        self.next_index += 1;
+        self.stack.push(node);
+        self.on_stack[node] = true;
+
+        let graph = self.graph;
+        for &(to, _) in &graph.edges[node] {
+            match self.index[to] {
+                None => {
+                    self.visit(to);
+                    self.low_link[node] = self.low_link[node].min(self.low_link[to]);
+                }
+                Some(index) if self.on_stack[to] => {
+                    self.low_link[node] = self.low_link[node].min(index);
+                }
+                Some(_) => {}
+            }
+        }
+
+        if Some(self.low_link[node]) == self.index[node] {
+            let mut component = Vec::new();
+            while let Some(member) = self.stack.pop() {
+                self.on_stack[member] = false;
+                component.push(member);
+                if member == node {
+                    break;
+                }
+            }
+            self.components.push(component);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::DartFile;
+
+    fn index(files: &[(&str, &str)]) -> ProjectIndex {
+        let files: Vec<DartFile> = files
+            .iter()
+            .map(|(path, content)| DartFile {
+                path: path.to_string(),
+                content: content.to_string(),
+            })
+            .collect();
+        ProjectIndex::build(&files)
+    }
+
+    #[test]
+    fn test_cycles() {
+        let index = index(&[
+            ("/app/lib/a.dart", "import 'b.dart';\nimport 'dart:io';\n"),
+            ("/app/lib/b.dart", "import './src/c.dart';\n"),
+            (
+                "/app/lib/src/c.dart",
+                "export '../a.dart';\nimport 'd.dart';\n",
+            ),
+            ("/app/lib/src/d.dart", "import 'd.dart';\n"),
+            ("/app/lib/e.dart", "import 'a.dart';\n"),
+        ]);
+        let graph = ImportGraph::new(&index);
+
+        assert_eq!(graph.edges[0].len(), 1);
+        assert!(graph.edges[2][0].1.is_export);
+        assert_eq!(graph.cycles(), vec![vec![0, 1, 2], vec![3]]);
+        assert_eq!(graph.shortest_cycle(0, &[0, 1, 2]), vec![0, 1, 2]);
+        assert_eq!(graph.shortest_cycle(3, &[3]), vec![3]);
+        assert_eq!(graph.directive(2, 0).map(|d| d.location.line), Some(1));
+    }
+
+    #[test]
+    fn test_shortest_cycle_in_larger_component() {
+        // a -> b -> a is shorter than a -> b -> c -> a
+        let index = index(&[
+            ("/app/lib/a.dart", "import 'b.dart';\n"),
+            ("/app/lib/b.dart", "import 'c.dart';\nimport 'a.dart';\n"),
+            ("/app/lib/c.dart", "import 'a.dart';\n"),
+        ]);
+        let graph = ImportGraph::new(&index);
+
+        assert_eq!(graph.cycles(), vec![vec![0, 1, 2]]);
+        assert_eq!(graph.shortest_cycle(0, &[0, 1, 2]), vec![0, 1]);
+    }
+}
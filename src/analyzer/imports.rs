@@ -0,0 +1,528 @@
+//! Import and export directives, and the names they bring into scope
+//!
+//! URIs are resolved without a Dart SDK or a pub cache: relative URIs
+//! against the importing file, and `package:` URIs of the package the
+//! importing file belongs to against its `lib/` directory. Other `package:`
+//! and `dart:` URIs are unresolvable, and what they declare is unknown.
+
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+use tree_sitter::{Node, Tree};
+
+use crate::treesitter;
+
+/// An `import` or `export` directive
+#[derive(Debug)]
+pub struct Directive<'a> {
+    /// The URI, without quotes
+    pub uri: String,
+    pub is_export: bool,
+    /// The `as` prefix of an import
+    pub prefix: Option<String>,
+    /// Names listed in `show` combinators
+    pub show: Vec<String>,
+    /// Names listed in `hide` combinators
+    pub hide: Vec<String>,
+    pub node: Node<'a>,
+}
+
+impl Directive<'_> {
+    /// Whether `name`, declared by the target library, passes the combinators
+    pub fn exposes(&self, name: &str) -> bool {
+        (self.show.is_empty() || self.show.iter().any(|n| n == name))
+            && !self.hide.iter().any(|n| n == name)
+    }
+}
+
+/// Names a library makes visible to the files importing it
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct LibraryNames {
+    pub names: HashSet<String>,
+    /// False if the library re-exports something unresolvable, so it may
+    /// declare more than `names`
+    pub complete: bool,
+}
+
+/// The import and export directives of a file
+pub fn directives<'a>(tree: &'a Tree, source: &str) -> Vec<Directive<'a>> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    root.children(&mut cursor)
+        .filter(|n| n.kind() == "import_or_export")
+        .filter_map(|n| n.named_child(0))
+        .filter_map(|n| match n.kind() {
+            "library_import" => n.named_child(0).map(|spec| directive(spec, source, false)),
+            "library_export" => Some(directive(n, source, true)),
+            _ => None,
+        })
+        .flatten()
+        .collect()
+}
+
+fn directive<'a>(node: Node<'a>, source: &str, is_export: bool) -> Option<Directive<'a>> {
+    let text = |n: Node| {
+        n.utf8_text(source.as_bytes())
+            .unwrap_or_default()
+            .to_string()
+    };
+    let mut directive = Directive {
+        uri: String::new(),
+        is_export,
+        prefix: None,
+        show: Vec::new(),
+        hide: Vec::new(),
+        node,
+    };
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        match child.kind() {
+            "uri" => directive.uri = unquote(&text(child)),
+            "configurable_uri" => {
+                let uri = child.named_child(0).filter(|n| n.kind() == "uri")?;
+                directive.uri = unquote(&text(uri));
+            }
+            "identifier" => directive.prefix = Some(text(child)),
+            "combinator" => {
+                let mut names_cursor = child.walk();
+                let names = child
+                    .named_children(&mut names_cursor)
+                    .filter(|n| n.kind() == "identifier")
+                    .map(text);
+                match child.child(0).map(|n| n.kind()) {
+                    Some("show") => directive.show.extend(names),
+                    Some("hide") => directive.hide.extend(names),
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+    (!directive.uri.is_empty()).then_some(directive)
+}
+
+fn unquote(literal: &str) -> String {
+    literal.trim().trim_matches(['\'', '"']).to_string()
+}
+
+/// The first string literal of a directive's quoted URI, leaving out the
+/// configurations of conditional imports
+pub fn uri_string(uri: &str) -> Option<&str> {
+    let start = uri.find(['\'', '"'])?;
+    let quote = uri[start..].chars().next()?;
+    let rest = &uri[start + 1..];
+    Some(&rest[..rest.find(quote)?])
+}
+
+/// The file a URI in `from` refers to, if it is in the same package
+pub fn resolve(from: &Path, uri: &str) -> Option<PathBuf> {
+    if let Some(package_path) = uri.strip_prefix("package:") {
+        let (package, path) = package_path.split_once('/')?;
+        let root = package_root(from)?;
+        return (package_name(&root)? == package).then(|| normalize(&root.join("lib").join(path)));
+    }
+    if uri.contains(':') {
+        // `dart:` libraries and absolute URIs of other schemes
+        return None;
+    }
+    Some(normalize(&from.parent()?.join(uri)))
+}
+
+/// `path` without `.` and `..` components, which relative URIs leave in
+pub fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// The directory of the `pubspec.yaml` owning `file`
+pub fn package_root(file: &Path) -> Option<PathBuf> {
+    file.ancestors()
+        .skip(1)
+        .find(|dir| dir.join("pubspec.yaml").is_file())
+        .map(Path::to_path_buf)
+}
+
+/// The `name` declared in a package's `pubspec.yaml`
+fn package_name(root: &Path) -> Option<String> {
+    let pubspec = std::fs::read_to_string(root.join("pubspec.yaml")).ok()?;
+    pubspec.lines().find_map(|line| {
+        let name = line.strip_prefix("name:")?;
+        Some(unquote(name.split('#').next()?))
+    })
+}
+
+/// Names the library at `path` makes visible to importers: its public
+/// declarations and those of its parts, and what it re-exports
+///
+/// Returns `None` if the file cannot be read or parsed.
+pub fn library_names(path: &Path) -> Option<LibraryNames> {
+    let mut visited = HashSet::new();
+    library_names_from(path, &mut visited)
+}
+
+fn library_names_from(path: &Path, visited: &mut HashSet<PathBuf>) -> Option<LibraryNames> {
+    let key = path.canonicalize().ok()?;
+    if !visited.insert(key) {
+        // Export cycles add nothing new
+        return Some(LibraryNames {
+            names: HashSet::new(),
+            complete: true,
+        });
+    }
+
+    let source = std::fs::read_to_string(path).ok()?;
+    let tree = treesitter::parse_dart(&source).ok()?;
+    let mut library = LibraryNames {
+        names: declared_names(&tree, &source),
+        complete: true,
+    };
+
+    for part in parts(&tree, &source) {
+        let part_names = resolve(path, &part)
+            .and_then(|part| std::fs::read_to_string(part).ok())
+            .and_then(|source| {
+                let tree = treesitter::parse_dart(&source).ok()?;
+                Some(declared_names(&tree, &source))
+            });
+        match part_names {
+            Some(names) => library.names.extend(names),
+            None => library.complete = false,
+        }
+    }
+
+    for export in directives(&tree, &source)
+        .into_iter()
+        .filter(|d| d.is_export)
+    {
+        let exported = resolve(path, &export.uri).and_then(|p| library_names_from(&p, visited));
+        match exported {
+            Some(exported) => {
+                library.complete &= exported.complete;
+                library
+                    .names
+                    .extend(exported.names.into_iter().filter(|n| export.exposes(n)));
+            }
+            None => library.complete = false,
+        }
+    }
+
+    Some(library)
+}
+
+/// URIs of the `part` directives of a file
+pub fn parts(tree: &Tree, source: &str) -> Vec<String> {
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    root.children(&mut cursor)
+        .filter(|n| n.kind() == "part_directive")
+        .filter_map(|n| {
+            let mut uri_cursor = n.walk();
+            let uri = n
+                .named_children(&mut uri_cursor)
+                .find(|c| c.kind() == "uri");
+            uri
+        })
+        .filter_map(|uri| uri.utf8_text(source.as_bytes()).ok())
+        .map(unquote)
+        .collect()
+}
+
+/// Public top-level names declared in a file
+///
+/// Members of extensions are included, since an import of an extension is
+/// used through them.
+pub fn declared_names(tree: &Tree, source: &str) -> HashSet<String> {
+    let mut names = HashSet::new();
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    for node in root.children(&mut cursor) {
+        declaration_names(node, &mut |name: Node| {
+            if let Ok(name) = name.utf8_text(source.as_bytes()) {
+                if !name.starts_with('_') {
+                    names.insert(name.to_string());
+                }
+            }
+        });
+    }
+    names
+}
+
+fn declaration_names(node: Node, add: &mut impl FnMut(Node)) {
+    let first_named = |kind: &str| {
+        let mut cursor = node.walk();
+        let found = node.named_children(&mut cursor).find(|n| n.kind() == kind);
+        found
+    };
+    match node.kind() {
+        "class_definition" | "mixin_declaration" | "enum_declaration" => {
+            if let Some(name) = node
+                .child_by_field_name("name")
+                .or_else(|| first_named("identifier"))
+            {
+                add(name);
+            }
+        }
+        "extension_declaration" => {
+            if let Some(name) = first_named("identifier") {
+                add(name);
+            }
+            if let Some(body) = first_named("extension_body") {
+                let mut cursor = body.walk();
+                for member in body.named_children(&mut cursor) {
+                    member_names(member, add);
+                }
+            }
+        }
+        "type_alias" => {
+            // The name comes before `=`, or before the parameters of an
+            // old-style `typedef int Callback(int x);`
+            let mut cursor = node.walk();
+            let name = node
+                .children(&mut cursor)
+                .take_while(|n| n.kind() != "=" && n.kind() != "formal_parameter_list")
+                .filter(|n| n.kind() == "type_identifier")
+                .last();
+            if let Some(name) = name {
+                add(name);
+            }
+        }
+        "lambda_expression" | "external_declaration" => {
+            if let Some(signature) = first_named("function_signature") {
+                declaration_names(signature, add);
+            }
+        }
+        "function_signature" | "getter_signature" | "setter_signature" => {
+            if let Some(name) = node
+                .child_by_field_name("name")
+                .or_else(|| first_named("identifier"))
+            {
+                add(name);
+            }
+        }
+        // Top-level variables parse as local declarations
+        "local_variable_declaration" => {
+            let mut cursor = node.walk();
+            for definition in node.named_children(&mut cursor) {
+                let mut definition_cursor = definition.walk();
+                for child in definition.named_children(&mut definition_cursor) {
+                    match child.kind() {
+                        "identifier" => add(child),
+                        "initialized_identifier" => {
+                            if let Some(name) =
+                                child.named_child(0).filter(|n| n.kind() == "identifier")
+                            {
+                                add(name);
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Names declared by a member of an extension body
+fn member_names(member: Node, add: &mut impl FnMut(Node)) {
+    let mut cursor = member.walk();
+    for child in member.named_children(&mut cursor) {
+        match child.kind() {
+            "method_signature" => {
+                if let Some(signature) = child.named_child(0) {
+                    declaration_names(signature, add);
+                }
+            }
+            "declaration" => {
+                let mut declaration_cursor = child.walk();
+                for part in child.named_children(&mut declaration_cursor) {
+                    match part.kind() {
+                        "function_signature" | "getter_signature" | "setter_signature" => {
+                            declaration_names(part, add)
+                        }
+                        "initialized_identifier_list" | "static_final_declaration_list" => {
+                            let mut list_cursor = part.walk();
+                            for item in part.named_children(&mut list_cursor) {
+                                if let Some(name) =
+                                    item.named_child(0).filter(|n| n.kind() == "identifier")
+                                {
+                                    add(name);
+                                }
+                            }
+                        }
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Every name referenced in a file outside of its directives, including
+/// `[Name]` references in documentation comments
+pub fn referenced_names(tree: &Tree, source: &str) -> HashSet<String> {
+    references(tree, source)
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect()
+}
+
+/// Every reference of [`referenced_names`], with the start byte of the
+/// identifier or comment it is in
+pub fn references(tree: &Tree, source: &str) -> Vec<(String, usize)> {
+    let mut references = Vec::new();
+    collect_references(tree.root_node(), source, &mut references);
+    references
+}
+
+fn collect_references(node: Node, source: &str, references: &mut Vec<(String, usize)>) {
+    match node.kind() {
+        "import_or_export" | "library_name" | "part_directive" | "part_of_directive" => return,
+        // `identifier_dollar_escaped` is the name in a `$name` interpolation
+        "identifier" | "type_identifier" | "identifier_dollar_escaped" => {
+            if let Ok(name) = node.utf8_text(source.as_bytes()) {
+                references.push((name.to_string(), node.start_byte()));
+            }
+        }
+        "documentation_comment" => {
+            if let Ok(text) = node.utf8_text(source.as_bytes()) {
+                references.extend(doc_references(text).map(|name| (name, node.start_byte())));
+            }
+        }
+        _ => {}
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_references(child, source, references);
+    }
+}
+
+/// First segments of `[Name]` and `[Name.member]` references in a comment
+fn doc_references(comment: &str) -> impl Iterator<Item = String> + '_ {
+    comment.split('[').skip(1).filter_map(|rest| {
+        let reference = &rest[..rest.find(']')?];
+        let name = reference.split('.').next()?;
+        let valid = !name.is_empty()
+            && !name.starts_with(|c: char| c.is_ascii_digit())
+            && name
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '_' || c == '$');
+        valid.then(|| name.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_directives() {
+        let source = "import 'package:a/a.dart' show Foo, bar;\n\
+                      import 'b.dart' deferred as b hide Baz;\n\
+                      export \"src/c.dart\";\n";
+        let tree = treesitter::parse_dart(source).unwrap();
+        let directives = directives(&tree, source);
+
+        assert_eq!(directives.len(), 3);
+        assert_eq!(directives[0].uri, "package:a/a.dart");
+        assert_eq!(directives[0].show, vec!["Foo", "bar"]);
+        assert_eq!(directives[1].prefix.as_deref(), Some("b"));
+        assert_eq!(directives[1].hide, vec!["Baz"]);
+        assert!(!directives[1].exposes("Baz"));
+        assert!(directives[2].is_export);
+        assert_eq!(directives[2].uri, "src/c.dart");
+    }
+
+    #[test]
+    fn test_uri_helpers() {
+        assert_eq!(uri_string("'a.dart'"), Some("a.dart"));
+        assert_eq!(
+            uri_string("\"stub.dart\" if (dart.library.io) 'io.dart'"),
+            Some("stub.dart")
+        );
+        assert_eq!(
+            normalize(Path::new("/app/lib/ui/../domain/./user.dart")),
+            PathBuf::from("/app/lib/domain/user.dart")
+        );
+    }
+
+    #[test]
+    fn test_declared_names() {
+        let source = "class A {}\nmixin M {}\nenum E { a }\ntypedef int Old(int x);\n\
+                      typedef New = void Function();\nextension on String { int get twice => 2; }\n\
+                      void f() {}\nint get g => 1;\nfinal v = 1, w = 2;\nclass _Private {}\n";
+        let tree = treesitter::parse_dart(source).unwrap();
+        let mut names: Vec<String> = declared_names(&tree, source).into_iter().collect();
+        names.sort();
+
+        assert_eq!(
+            names,
+            vec!["A", "E", "M", "New", "Old", "f", "g", "twice", "v", "w"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_package_uris_within_the_package() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("pubspec.yaml"), "name: my_app\n").unwrap();
+        let file = dir.path().join("lib/src/a.dart");
+
+        assert_eq!(
+            resolve(&file, "package:my_app/b.dart"),
+            Some(dir.path().join("lib/b.dart"))
+        );
+        assert_eq!(
+            resolve(&file, "../c.dart"),
+            Some(dir.path().join("lib/c.dart"))
+        );
+        assert_eq!(resolve(&file, "package:http/http.dart"), None);
+        assert_eq!(resolve(&file, "dart:async"), None);
+    }
+
+    #[test]
+    fn test_library_names_follow_exports_and_parts() {
+        let dir = tempfile::tempdir().unwrap();
+        let write =
+            |name: &str, content: &str| std::fs::write(dir.path().join(name), content).unwrap();
+        write(
+            "a.dart",
+            "export 'b.dart' hide Hidden;\npart 'a_part.dart';\nclass A {}\n",
+        );
+        write("a_part.dart", "part of 'a.dart';\nclass FromPart {}\n");
+        write("b.dart", "export 'a.dart';\nclass B {}\nclass Hidden {}\n");
+        write("c.dart", "export 'package:http/http.dart';\nclass C {}\n");
+
+        let a = library_names(&dir.path().join("a.dart")).unwrap();
+        let mut names: Vec<&str> = a.names.iter().map(String::as_str).collect();
+        names.sort();
+        assert_eq!(names, vec!["A", "B", "FromPart"]);
+        assert!(a.complete);
+
+        let c = library_names(&dir.path().join("c.dart")).unwrap();
+        assert!(!c.complete);
+        assert!(library_names(&dir.path().join("missing.dart")).is_none());
+    }
+
+    #[test]
+    fn test_referenced_names_skip_directives() {
+        let source = "import 'foo.dart' show Foo;\n/// See [Bar.baz].\nvoid f(Qux q) => q.run();\n";
+        let tree = treesitter::parse_dart(source).unwrap();
+        let names = referenced_names(&tree, source);
+
+        assert!(!names.contains("Foo"));
+        for name in ["Bar", "Qux", "f", "q", "run"] {
+            assert!(names.contains(name), "{} not referenced", name);
+        }
+    }
+}
@@ -0,0 +1,503 @@
+//! An index of every file of a project, for rules that look across files
+//!
+//! Each file is parsed once, and what cross-file rules need is kept: its
+//! declarations, the names it references, its `part` directives, and its
+//! import and export directives with the project file they resolve to, if
+//! any. Syntax trees are not kept, so the index can be shared between rules
+//! and threads.
+
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use tree_sitter::Node;
+
+use crate::analyzer::imports;
+use crate::error::Location;
+use crate::parser::DartFile;
+use crate::treesitter;
+
+/// What a declaration declares
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeclarationKind {
+    Class,
+    /// A top-level function
+    Function,
+    /// A method of a class
+    Method,
+    Field,
+    /// A top-level variable
+    Variable,
+}
+
+/// A named declaration of a file
+#[derive(Debug, Clone)]
+pub struct Declaration {
+    pub name: String,
+    pub kind: DeclarationKind,
+    /// The class declaring a method or field
+    pub class: Option<String>,
+    /// The declared name
+    pub location: Location,
+    /// Byte range of the whole declaration, with its body, documentation
+    /// comment and annotations
+    ///
+    /// Fields and variables declared together share it.
+    pub start_byte: usize,
+    pub end_byte: usize,
+}
+
+impl Declaration {
+    pub fn is_private(&self) -> bool {
+        self.name.starts_with('_')
+    }
+}
+
+/// An `import` or `export` directive of a file
+#[derive(Debug, Clone)]
+pub struct IndexedDirective {
+    /// The URI, without quotes
+    pub uri: String,
+    pub is_export: bool,
+    /// Names listed in `show` combinators
+    pub show: Vec<String>,
+    /// Names listed in `hide` combinators
+    pub hide: Vec<String>,
+    /// Index of the project file the URI resolves to
+    pub target: Option<usize>,
+    /// The directive
+    pub location: Location,
+}
+
+impl IndexedDirective {
+    /// Whether `name`, declared by the target library, passes the combinators
+    pub fn exposes(&self, name: &str) -> bool {
+        (self.show.is_empty() || self.show.iter().any(|n| n == name))
+            && !self.hide.iter().any(|n| n == name)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct IndexedFile {
+    /// The path the file was loaded from
+    pub path: String,
+    pub content: String,
+    pub declarations: Vec<Declaration>,
+    pub directives: Vec<IndexedDirective>,
+    /// Project files of the `part` directives, `None` for parts that aren't
+    /// indexed
+    pub parts: Vec<Option<usize>>,
+    /// Whether the file is `part of` another library
+    pub is_part: bool,
+    /// Start bytes of the references to each name, as in
+    /// [`imports::references`], leaving out the declared names themselves
+    pub references: HashMap<String, Vec<usize>>,
+}
+
+impl IndexedFile {
+    /// The location of a byte range of the file
+    pub fn location(&self, start_byte: usize, end_byte: usize) -> Location {
+        let position = |byte: usize| {
+            let before = &self.content[..byte];
+            let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+            (before.matches('\n').count() + 1, byte - line_start + 1)
+        };
+        let (line, column) = position(start_byte);
+        let (end_line, end_column) = position(end_byte);
+        Location {
+            file: self.path.clone(),
+            line,
+            column,
+            end_line: Some(end_line),
+            end_column: Some(end_column),
+        }
+    }
+}
+
+/// The parsed files of a project
+#[derive(Debug, Default)]
+pub struct ProjectIndex {
+    pub files: Vec<IndexedFile>,
+    /// Normalized path to index in `files`
+    by_path: HashMap<PathBuf, usize>,
+    /// Part file to the file it is a part of
+    owners: HashMap<usize, usize>,
+}
+
+impl ProjectIndex {
+    /// Index `files`, leaving out the declarations and directives of files
+    /// that fail to parse
+    pub fn build(files: &[DartFile]) -> Self {
+        let by_path: HashMap<PathBuf, usize> = files
+            .iter()
+            .enumerate()
+            .map(|(i, file)| (imports::normalize(Path::new(&file.path)), i))
+            .collect();
+
+        let files: Vec<IndexedFile> = files
+            .par_iter()
+            .map(|file| index_file(file, &by_path))
+            .collect();
+
+        let owners = files
+            .iter()
+            .enumerate()
+            .flat_map(|(owner, file)| file.parts.iter().flatten().map(move |&part| (part, owner)))
+            .filter(|&(part, _)| files[part].is_part)
+            .collect();
+
+        Self {
+            files,
+            by_path,
+            owners,
+        }
+    }
+
+    /// Index of the file at `path`
+    pub fn file(&self, path: &Path) -> Option<usize> {
+        self.by_path.get(&imports::normalize(path)).copied()
+    }
+
+    /// The project file at `index`, by its normalized path
+    pub fn path(&self, index: usize) -> PathBuf {
+        imports::normalize(Path::new(&self.files[index].path))
+    }
+
+    /// The files of the library `file` belongs to, its defining file first
+    /// and then its parts
+    ///
+    /// Returns `None` if the defining file or one of the parts isn't indexed.
+    pub fn library(&self, file: usize) -> Option<Vec<usize>> {
+        let defining = match self.files[file].is_part {
+            true => *self.owners.get(&file)?,
+            false => file,
+        };
+        std::iter::once(Some(defining))
+            .chain(self.files[defining].parts.iter().copied())
+            .collect()
+    }
+}
+
+fn index_file(file: &DartFile, by_path: &HashMap<PathBuf, usize>) -> IndexedFile {
+    let mut indexed = IndexedFile {
+        path: file.path.clone(),
+        content: file.content.clone(),
+        declarations: Vec::new(),
+        directives: Vec::new(),
+        parts: Vec::new(),
+        is_part: false,
+        references: HashMap::new(),
+    };
+    let Ok(tree) = treesitter::parse_dart(&file.content) else {
+        return indexed;
+    };
+    let content = file.content.as_str();
+    let path = imports::normalize(Path::new(&file.path));
+    let location = |node: Node| node_location(&file.path, node);
+    let target =
+        |uri: &str| imports::resolve(&path, uri).and_then(|target| by_path.get(&target).copied());
+
+    for directive in imports::directives(&tree, content) {
+        indexed.directives.push(IndexedDirective {
+            target: target(&directive.uri),
+            location: location(directive.node),
+            uri: directive.uri,
+            is_export: directive.is_export,
+            show: directive.show,
+            hide: directive.hide,
+        });
+    }
+    indexed.parts = imports::parts(&tree, content)
+        .iter()
+        .map(|uri| target(uri))
+        .collect();
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    indexed.is_part = root
+        .children(&mut cursor)
+        .any(|n| n.kind() == "part_of_directive");
+
+    let mut names = HashSet::new();
+    let mut declare = |name: &str, kind, class, name_node: Node, node: Node| {
+        names.insert(name_node.start_byte());
+        let (start_byte, end_byte) = extent(node);
+        indexed.declarations.push(Declaration {
+            name: name.to_string(),
+            kind,
+            class,
+            location: location(name_node),
+            start_byte,
+            end_byte,
+        });
+    };
+
+    for class in treesitter::extract_classes(&tree, content) {
+        if let Some(name) = class.node.child_by_field_name("name") {
+            declare(&class.name, DeclarationKind::Class, None, name, class.node);
+        }
+    }
+    for method in treesitter::extract_methods(&tree, content) {
+        let Some(name) = method.node.child_by_field_name("name") else {
+            continue;
+        };
+        match container(method.node, content) {
+            Container::TopLevel => declare(
+                &method.name,
+                DeclarationKind::Function,
+                None,
+                name,
+                method.node,
+            ),
+            Container::Class(class) => declare(
+                &method.name,
+                DeclarationKind::Method,
+                Some(class),
+                name,
+                method.node,
+            ),
+            Container::Body | Container::Other => {}
+        }
+    }
+    for field in treesitter::extract_fields(&tree, content) {
+        if let Container::Class(class) = container(field.node, content) {
+            declare(
+                &field.name,
+                DeclarationKind::Field,
+                Some(class),
+                field.name_node,
+                field.node,
+            );
+        }
+    }
+    for variable in treesitter::extract_variables(&tree, content) {
+        if container(variable.node, content) != Container::TopLevel {
+            continue;
+        }
+        for name in variable_names(variable.node) {
+            if let Ok(text) = name.utf8_text(content.as_bytes()) {
+                declare(text, DeclarationKind::Variable, None, name, variable.node);
+            }
+        }
+    }
+
+    for (name, byte) in imports::references(&tree, content) {
+        if !names.contains(&byte) {
+            indexed.references.entry(name).or_default().push(byte);
+        }
+    }
+
+    indexed
+}
+
+/// Where a declaration is
+#[derive(Debug, PartialEq, Eq)]
+enum Container {
+    TopLevel,
+    /// In the class of that name
+    Class(String),
+    /// Local to a function body
+    Body,
+    /// In a mixin, enum or extension
+    Other,
+}
+
+fn container(node: Node, source: &str) -> Container {
+    let mut parent = node.parent();
+    while let Some(node) = parent {
+        match node.kind() {
+            "function_body" | "function_expression_body" => return Container::Body,
+            "class_definition" => {
+                return node
+                    .child_by_field_name("name")
+                    .and_then(|name| name.utf8_text(source.as_bytes()).ok())
+                    .map_or(Container::Other, |name| Container::Class(name.to_string()));
+            }
+            "mixin_declaration" | "enum_declaration" | "extension_declaration" => {
+                return Container::Other
+            }
+            _ => parent = node.parent(),
+        }
+    }
+    Container::TopLevel
+}
+
+/// Byte range of the top-level or class member declaration containing
+/// `node`
+///
+/// Bodies of top-level getters follow their signature, and documentation
+/// comments and annotations of class members and classes precede them, as
+/// siblings.
+fn extent(node: Node) -> (usize, usize) {
+    let mut member = node;
+    while let Some(parent) = member.parent() {
+        if matches!(parent.kind(), "program" | "class_body") {
+            break;
+        }
+        member = parent;
+    }
+
+    let end = member
+        .next_named_sibling()
+        .filter(|next| next.kind() == "function_body")
+        .map_or(member.end_byte(), |body| body.end_byte());
+    let mut start = member.start_byte();
+    let mut previous = member.prev_named_sibling();
+    while let Some(sibling) = previous.filter(|sibling| {
+        matches!(
+            sibling.kind(),
+            "documentation_comment" | "marker_annotation" | "annotation"
+        )
+    }) {
+        start = sibling.start_byte();
+        previous = sibling.prev_named_sibling();
+    }
+    (start, end)
+}
+
+/// Names declared by a variable definition, like `a` and `b` in
+/// `int a = 0, b = 1;`
+fn variable_names(definition: Node) -> Vec<Node> {
+    let mut cursor = definition.walk();
+    let children: Vec<Node> = definition.named_children(&mut cursor).collect();
+    children
+        .into_iter()
+        .filter_map(|child| match child.kind() {
+            "identifier" => Some(child),
+            "initialized_identifier" => child.named_child(0).filter(|n| n.kind() == "identifier"),
+            _ => None,
+        })
+        .collect()
+}
+
+fn node_location(file: &str, node: Node) -> Location {
+    let start = node.start_position();
+    let end = node.end_position();
+    Location {
+        file: file.to_string(),
+        line: start.row + 1,
+        column: start.column + 1,
+        end_line: Some(end.row + 1),
+        end_column: Some(end.column + 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dart_file(path: &str, content: &str) -> DartFile {
+        DartFile {
+            path: path.to_string(),
+            content: content.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_index() {
+        let files = [
+            dart_file(
+                "/app/lib/a.dart",
+                "import 'dart:io';\nexport 'src/b.dart' show B;\n\
+                 final greeting = 'hi';\n\
+                 void main() {\n  void local() {}\n  var x = 1;\n}\n\
+                 class A {\n  int _count = 0;\n  A();\n  void run() {}\n}\n",
+            ),
+            dart_file("/app/lib/src/b.dart", "import '../a.dart';\n"),
+        ];
+        let index = ProjectIndex::build(&files);
+
+        let a = &index.files[0];
+        let directives: Vec<(&str, bool, Option<usize>)> = a
+            .directives
+            .iter()
+            .map(|d| (d.uri.as_str(), d.is_export, d.target))
+            .collect();
+        assert_eq!(
+            directives,
+            vec![("dart:io", false, None), ("src/b.dart", true, Some(1))]
+        );
+        assert!(a.directives[1].exposes("B") && !a.directives[1].exposes("C"));
+        assert_eq!(index.files[1].directives[0].target, Some(0));
+        assert_eq!(index.file(Path::new("/app/lib/src/../a.dart")), Some(0));
+
+        let mut declarations: Vec<(&str, DeclarationKind, Option<&str>, usize)> = a
+            .declarations
+            .iter()
+            .map(|d| (d.name.as_str(), d.kind, d.class.as_deref(), d.location.line))
+            .collect();
+        declarations.sort_by_key(|d| d.3);
+        assert_eq!(
+            declarations,
+            vec![
+                ("greeting", DeclarationKind::Variable, None, 3),
+                ("main", DeclarationKind::Function, None, 4),
+                ("A", DeclarationKind::Class, None, 8),
+                ("_count", DeclarationKind::Field, Some("A"), 9),
+                ("run", DeclarationKind::Method, Some("A"), 11),
+            ]
+        );
+        assert!(a.declarations.iter().any(|d| d.is_private()));
+
+        // Declared names aren't references, but the constructor is
+        assert!(!a.references.contains_key("greeting"));
+        assert_eq!(a.references["A"].len(), 1);
+        assert!(a.references.contains_key("x"));
+    }
+
+    #[test]
+    fn test_declaration_extents() {
+        let content = "/// Doc\n@visibleForTesting\nvoid _run() {}\n\
+                       int get _value => 1;\n\
+                       @immutable\nclass A {\n  /// Doc\n  @override\n  void m() {}\n  int _a = 0, _b = 1;\n}\n\
+                       final _x = 1, y = 2;\n";
+        let index = ProjectIndex::build(&[dart_file("/app/lib/a.dart", content)]);
+        let extent = |name: &str| {
+            let declaration = index.files[0]
+                .declarations
+                .iter()
+                .find(|d| d.name == name)
+                .unwrap();
+            &content[declaration.start_byte..declaration.end_byte]
+        };
+
+        assert_eq!(
+            extent("_run"),
+            "/// Doc\n@visibleForTesting\nvoid _run() {}"
+        );
+        assert!(extent("A").starts_with("@immutable\nclass A {"));
+        assert_eq!(extent("m"), "/// Doc\n  @override\n  void m() {}");
+        assert_eq!(extent("_a"), "int _a = 0, _b = 1;");
+        assert_eq!(extent("_b"), extent("_a"));
+        assert_eq!(extent("_x"), "final _x = 1, y = 2;");
+        assert_eq!(extent("y"), extent("_x"));
+
+        let location = index.files[0].location(8, 26);
+        assert_eq!(
+            (
+                location.line,
+                location.column,
+                location.end_line,
+                location.end_column
+            ),
+            (2, 1, Some(2), Some(19))
+        );
+    }
+
+    #[test]
+    fn test_libraries() {
+        let index = ProjectIndex::build(&[
+            dart_file("/app/lib/a.dart", "part 'a_part.dart';\npart 'a.g.dart';\n"),
+            dart_file("/app/lib/a_part.dart", "part of 'a.dart';\n"),
+            dart_file("/app/lib/b.dart", "part 'b_part.dart';\n"),
+            dart_file("/app/lib/b_part.dart", "part of 'b.dart';\n"),
+            dart_file("/app/lib/c_part.dart", "part of 'c.dart';\n"),
+        ]);
+
+        assert!(index.files[1].is_part);
+        assert_eq!(index.files[0].parts, vec![Some(1), None]);
+        // a.g.dart isn't indexed
+        assert_eq!(index.library(0), None);
+        assert_eq!(index.library(3), Some(vec![2, 3]));
+        assert_eq!(index.library(2), Some(vec![2, 3]));
+        assert_eq!(index.library(4), None);
+    }
+}
@@ -1,7 +1,61 @@
 use crate::error::{Diagnostic, Result};
+use index::ProjectIndex;
+use std::collections::HashMap;
 use std::path::Path;
+use std::sync::Arc;
+
+pub mod graph;
+pub mod imports;
+pub mod index;
+pub mod suppression;
 
 pub trait Rule: Send + Sync {
     fn name(&self) -> &str;
     fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>>;
 }
+
+/// A rule over all the files of a project at once, run after the per-file
+/// rules
+///
+/// Its diagnostics may be on any file of the index.
+pub trait ProjectRule: Send + Sync {
+    fn name(&self) -> &str;
+    fn check_project(&self, index: &ProjectIndex) -> Result<Vec<Diagnostic>>;
+}
+
+/// Run every rule against a file, dropping diagnostics silenced by
+/// `// ignore:` and `// ignore_for_file:` comments
+pub fn check_file(file_path: &Path, content: &str, rules: &[Arc<dyn Rule>]) -> Vec<Diagnostic> {
+    let diagnostics = rules
+        .iter()
+        .flat_map(|rule| rule.check(file_path, content).unwrap_or_default())
+        .collect();
+    suppression::filter(content, diagnostics)
+}
+
+/// Run every project rule against the index, dropping diagnostics silenced
+/// by comments in the files they are on
+pub fn check_project(index: &ProjectIndex, rules: &[Arc<dyn ProjectRule>]) -> Vec<Diagnostic> {
+    let mut by_file: HashMap<String, Vec<Diagnostic>> = HashMap::new();
+    for diagnostic in rules
+        .iter()
+        .flat_map(|rule| rule.check_project(index).unwrap_or_default())
+    {
+        by_file
+            .entry(diagnostic.location.file.clone())
+            .or_default()
+            .push(diagnostic);
+    }
+
+    let mut diagnostics: Vec<Diagnostic> = by_file
+        .into_iter()
+        .flat_map(|(file, diagnostics)| match index.file(Path::new(&file)) {
+            Some(i) => suppression::filter(&index.files[i].content, diagnostics),
+            None => diagnostics,
+        })
+        .collect();
+    diagnostics.sort_by(|a, b| {
+        (&a.location.file, a.location.line).cmp(&(&b.location.file, b.location.line))
+    });
+    diagnostics
+}
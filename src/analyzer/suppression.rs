@@ -0,0 +1,132 @@
+//! Suppression comments, compatible with the Dart analyzer's syntax
+//!
+//! - `// ignore: rule_a, rule_b` at the end of a line silences those rules on
+//!   that line. On a line of its own, it silences them on the next line.
+//! - `// ignore_for_file: rule_a, rule_b` anywhere silences them in the file.
+
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+use crate::error::Diagnostic;
+
+fn ignore_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"//\s*ignore:\s*(.+)$").expect("Invalid regex pattern"))
+}
+
+fn ignore_for_file_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(r"^\s*//\s*ignore_for_file:\s*(.+)$").expect("Invalid regex pattern")
+    })
+}
+
+/// Rules silenced in a file, by line
+#[derive(Debug, Default)]
+pub struct Suppressions {
+    file: HashSet<String>,
+    /// 1-based line number to the rules silenced on it
+    lines: HashMap<usize, HashSet<String>>,
+}
+
+impl Suppressions {
+    pub fn parse(content: &str) -> Self {
+        let mut suppressions = Self::default();
+
+        for (line_num, line) in content.lines().enumerate() {
+            if let Some(caps) = ignore_for_file_regex().captures(line) {
+                suppressions.file.extend(rule_list(&caps[1]));
+            } else if let Some(caps) = ignore_regex().captures(line) {
+                let own_line = line.trim_start().starts_with("//");
+                // Line numbers are 1-based, so the next line is line_num + 2
+                let target = if own_line { line_num + 2 } else { line_num + 1 };
+                suppressions
+                    .lines
+                    .entry(target)
+                    .or_default()
+                    .extend(rule_list(&caps[1]));
+            }
+        }
+
+        suppressions
+    }
+
+    pub fn is_suppressed(&self, diagnostic: &Diagnostic) -> bool {
+        self.file.contains(&diagnostic.rule_id)
+            || self
+                .lines
+                .get(&diagnostic.location.line)
+                .is_some_and(|rules| rules.contains(&diagnostic.rule_id))
+    }
+}
+
+fn rule_list(list: &str) -> impl Iterator<Item = String> + '_ {
+    list.split(',')
+        .map(str::trim)
+        .filter(|rule| !rule.is_empty())
+        .map(str::to_string)
+}
+
+/// Remove the diagnostics silenced by comments in `content`
+pub fn filter(content: &str, diagnostics: Vec<Diagnostic>) -> Vec<Diagnostic> {
+    if !content.contains("ignore") {
+        return diagnostics;
+    }
+    let suppressions = Suppressions::parse(content);
+    diagnostics
+        .into_iter()
+        .filter(|d| !suppressions.is_suppressed(d))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{Location, RuleCategory, Severity};
+
+    fn diagnostic(rule_id: &str, line: usize) -> Diagnostic {
+        Diagnostic::new(
+            rule_id,
+            "message",
+            Severity::Warning,
+            RuleCategory::Runtime,
+            Location {
+                file: "test.dart".to_string(),
+                line,
+                column: 1,
+                end_line: None,
+                end_column: None,
+            },
+        )
+    }
+
+    #[test]
+    fn test_ignore_next_line() {
+        let content = "void main() {\n  // ignore: avoid_print, avoid_dynamic\n  print('x');\n  print('y');\n}\n";
+        let suppressions = Suppressions::parse(content);
+        assert!(suppressions.is_suppressed(&diagnostic("avoid_print", 3)));
+        assert!(suppressions.is_suppressed(&diagnostic("avoid_dynamic", 3)));
+        assert!(!suppressions.is_suppressed(&diagnostic("avoid_print", 4)));
+        assert!(!suppressions.is_suppressed(&diagnostic("line_length", 3)));
+    }
+
+    #[test]
+    fn test_ignore_same_line() {
+        let content = "void main() {\n  print('x'); // ignore: avoid_print\n}\n";
+        let suppressions = Suppressions::parse(content);
+        assert!(suppressions.is_suppressed(&diagnostic("avoid_print", 2)));
+        assert!(!suppressions.is_suppressed(&diagnostic("avoid_print", 3)));
+    }
+
+    #[test]
+    fn test_ignore_for_file() {
+        let content = "// ignore_for_file: avoid_print\nvoid main() {\n  print('x');\n}\n";
+        let diagnostics = filter(
+            content,
+            vec![diagnostic("avoid_print", 3), diagnostic("avoid_dynamic", 3)],
+        );
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].rule_id, "avoid_dynamic");
+    }
+}
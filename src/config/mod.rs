@@ -1,8 +1,8 @@
-use crate::error::{AnalyzerError, Result};
+use crate::error::{AnalyzerError, Result, RuleCategory};
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AnalyzerConfig {
     #[serde(default = "default_enabled")]
     pub enabled: bool,
@@ -16,14 +16,32 @@ pub struct AnalyzerConfig {
     #[serde(default)]
     pub runtime_rules: RuleSetConfig,
 
+    /// Only applied to packages that depend on Flutter
+    #[serde(default)]
+    pub flutter_rules: RuleSetConfig,
+
     #[serde(default = "default_max_line_length")]
     pub max_line_length: usize,
 
+    #[serde(default)]
+    pub private_fields: PrivateFieldConfig,
+
+    /// Types whose fields must be released in `dispose` or `close`, on top
+    /// of the built-in subscriptions, controllers and timers
+    #[serde(default)]
+    pub disposable_types: Vec<String>,
+
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+
+    #[serde(default)]
+    pub architecture: ArchitectureConfig,
+
     #[serde(default = "default_parallel")]
     pub parallel: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RuleSetConfig {
     #[serde(default = "default_enabled")]
     pub enabled: bool,
@@ -32,6 +50,62 @@ pub struct RuleSetConfig {
     pub disabled_rules: Vec<String>,
 }
 
+/// Checks of the `private_field_underscore` rule
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrivateFieldConfig {
+    /// Flag public class fields that are neither final nor const
+    #[serde(default = "default_enabled")]
+    pub public_mutable_fields: bool,
+
+    /// Flag private fields exposed through a same-named getter with no logic
+    #[serde(default = "default_enabled")]
+    pub trivial_getters: bool,
+
+    /// Flag local variables and parameters starting with an underscore
+    #[serde(default = "default_enabled")]
+    pub underscore_locals: bool,
+}
+
+/// Thresholds of the complexity metric rules, reported when exceeded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MetricsConfig {
+    pub max_cyclomatic_complexity: usize,
+    pub max_cognitive_complexity: usize,
+    pub max_nesting_depth: usize,
+    pub max_parameters: usize,
+    /// Lines of code of a function, not counting blank and comment lines
+    pub max_function_lines: usize,
+    pub max_class_methods: usize,
+    pub max_class_fields: usize,
+}
+
+/// Layers of the package checked by the `layer_dependencies` rule
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArchitectureConfig {
+    #[serde(default)]
+    pub layers: Vec<LayerConfig>,
+}
+
+/// A layer, and what its files may import
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LayerConfig {
+    pub name: String,
+
+    /// Globs of the layer's files relative to the package root, e.g.
+    /// `lib/domain/**`. A file belongs to the first layer matching it.
+    pub paths: Vec<String>,
+
+    /// Other layers whose files this layer may import
+    #[serde(default)]
+    pub may_import: Vec<String>,
+
+    /// Packages this layer may not import, e.g. `flutter`, or `dart:`
+    /// libraries, e.g. `dart:io`
+    #[serde(default)]
+    pub banned_packages: Vec<String>,
+}
+
 impl Default for AnalyzerConfig {
     fn default() -> Self {
         Self {
@@ -44,7 +118,12 @@ impl Default for AnalyzerConfig {
             ],
             style_rules: RuleSetConfig::default(),
             runtime_rules: RuleSetConfig::default(),
+            flutter_rules: RuleSetConfig::default(),
             max_line_length: 120,
+            private_fields: PrivateFieldConfig::default(),
+            disposable_types: Vec::new(),
+            metrics: MetricsConfig::default(),
+            architecture: ArchitectureConfig::default(),
             parallel: true,
         }
     }
@@ -59,6 +138,30 @@ impl Default for RuleSetConfig {
     }
 }
 
+impl Default for PrivateFieldConfig {
+    fn default() -> Self {
+        Self {
+            public_mutable_fields: true,
+            trivial_getters: true,
+            underscore_locals: true,
+        }
+    }
+}
+
+impl Default for MetricsConfig {
+    fn default() -> Self {
+        Self {
+            max_cyclomatic_complexity: 10,
+            max_cognitive_complexity: 15,
+            max_nesting_depth: 4,
+            max_parameters: 6,
+            max_function_lines: 60,
+            max_class_methods: 30,
+            max_class_fields: 20,
+        }
+    }
+}
+
 fn default_enabled() -> bool {
     true
 }
@@ -86,12 +189,11 @@ impl AnalyzerConfig {
         Ok(())
     }
 
-    #[allow(dead_code)]
-    pub fn is_rule_enabled(&self, rule_name: &str, is_style: bool) -> bool {
-        let rule_set = if is_style {
-            &self.style_rules
-        } else {
-            &self.runtime_rules
+    pub fn is_rule_enabled(&self, rule_name: &str, category: RuleCategory) -> bool {
+        let rule_set = match category {
+            RuleCategory::Style => &self.style_rules,
+            RuleCategory::Runtime => &self.runtime_rules,
+            RuleCategory::Flutter => &self.flutter_rules,
         };
 
         rule_set.enabled && !rule_set.disabled_rules.contains(&rule_name.to_string())
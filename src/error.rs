@@ -42,6 +42,7 @@ impl fmt::Display for Severity {
 pub enum RuleCategory {
     Style,
     Runtime,
+    Flutter,
 }
 
 impl fmt::Display for RuleCategory {
@@ -49,11 +50,17 @@ impl fmt::Display for RuleCategory {
         match self {
             RuleCategory::Style => write!(f, "style"),
             RuleCategory::Runtime => write!(f, "runtime"),
+            RuleCategory::Flutter => write!(f, "flutter"),
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Span of source a diagnostic applies to
+///
+/// Lines are 1-based. Columns are 1-based byte offsets into the line, and the
+/// end position is exclusive. The LSP proxy converts them into the
+/// negotiated position encoding.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 pub struct Location {
     pub file: String,
     pub line: usize,
@@ -62,6 +69,22 @@ pub struct Location {
     pub end_column: Option<usize>,
 }
 
+/// Replacement of the text covered by a location
+///
+/// A location without an end span inserts at its start.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FixEdit {
+    pub location: Location,
+    pub replacement: String,
+}
+
+/// Automatic fix for a diagnostic, offered as a quick fix by the LSP proxy
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Fix {
+    pub description: String,
+    pub edits: Vec<FixEdit>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Diagnostic {
     pub rule_id: String,
@@ -70,6 +93,8 @@ pub struct Diagnostic {
     pub category: RuleCategory,
     pub location: Location,
     pub suggestion: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fix: Option<Fix>,
 }
 
 impl Diagnostic {
@@ -87,6 +112,7 @@ impl Diagnostic {
             category,
             location,
             suggestion: None,
+            fix: None,
         }
     }
 
@@ -94,6 +120,11 @@ impl Diagnostic {
         self.suggestion = Some(suggestion.into());
         self
     }
+
+    pub fn with_fix(mut self, fix: Fix) -> Self {
+        self.fix = Some(fix);
+        self
+    }
 }
 
 pub type Result<T> = std::result::Result<T, AnalyzerError>;
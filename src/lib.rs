@@ -3,6 +3,7 @@ pub mod config;
 pub mod error;
 pub mod lsp;
 pub mod mcp;
+pub mod metrics;
 pub mod parser;
 pub mod rules;
 pub mod treesitter;
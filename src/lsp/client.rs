@@ -1,210 +1,753 @@
 //! LSP client for communicating with Dart Analysis Server
 //!
-//! This module provides the client implementation for communicating with
-//! the Dart Analysis Server via the Language Server Protocol.
-//!
-//! Note: This is a foundational stub. Full implementation would require:
-//! - Starting the Dart Analysis Server process
-//! - JSON-RPC communication over stdio
-//! - Handling async requests/responses
-//! - Managing server lifecycle
+//! Runs `dart language-server --protocol=lsp` as a subprocess and talks
+//! JSON-RPC to it over stdio. Requests get ids of our own and are matched to
+//! their responses through a pending-request map; they time out, and are
+//! cancelled with `$/cancelRequest` when the caller stops waiting.
+//! Notifications can be subscribed to per method. Everything the client does
+//! not consume itself (server requests, notifications nobody subscribed to,
+//! responses to forwarded messages) is available as a stream of
+//! [`ServerEvent`]s, which is what [`LspProxy`](super::LspProxy) relays to
+//! the editor.
+
+use anyhow::{Context, Result};
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thiserror::Error;
+use tokio::process::{Child, Command};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender, WeakUnboundedSender};
+use tokio::sync::oneshot;
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+use super::framing::LspCodec;
+use super::lifecycle::{EXIT_TIMEOUT, SHUTDOWN_TIMEOUT};
+use super::trace::{Direction, Tracer};
 
-use anyhow::Result;
-use std::path::PathBuf;
-use std::process::Command;
+/// Name of the `dart` executable inside an SDK's `bin` directory
+const DART_EXECUTABLE: &str = if cfg!(windows) { "dart.exe" } else { "dart" };
+
+/// Prefix of the ids of requests we send, so they never collide with ids
+/// of editor requests forwarded through the same connection
+const ID_PREFIX: &str = "dart-re-analyzer/";
 
 /// Configuration for the Dart Analysis Server
-#[allow(dead_code)]
 #[derive(Debug, Clone)]
 pub struct DartAnalysisServerConfig {
     /// Path to the Dart SDK
     pub dart_sdk_path: PathBuf,
     /// Path to the analysis server snapshot
     pub analysis_server_snapshot: PathBuf,
+    /// Executable to run instead of the SDK's `bin/dart`
+    pub dart_binary: Option<PathBuf>,
     /// Additional VM arguments
     pub vm_args: Vec<String>,
     /// Enable verbose logging
+    ///
+    /// Passes the server's stderr through instead of discarding it.
     pub verbose: bool,
+    /// How long [`DartAnalysisServerClient::send_request`] waits for a response
+    pub request_timeout: Duration,
+    /// Records every message exchanged with the server
+    pub trace: Option<Tracer>,
 }
 
 impl Default for DartAnalysisServerConfig {
     fn default() -> Self {
-        // Platform-specific defaults
-        // Note: In production, these should be discovered or required as config
-        let (sdk_path, snapshot_path) = if cfg!(target_os = "windows") {
-            (
-                PathBuf::from("C:\\tools\\dart-sdk"),
-                PathBuf::from("C:\\tools\\dart-sdk\\bin\\snapshots\\analysis_server.dart.snapshot"),
-            )
-        } else if cfg!(target_os = "macos") {
-            (
-                PathBuf::from("/usr/local/opt/dart"),
-                PathBuf::from(
-                    "/usr/local/opt/dart/libexec/bin/snapshots/analysis_server.dart.snapshot",
-                ),
-            )
-        } else {
-            // Linux/Unix default
-            (
-                PathBuf::from("/usr/lib/dart"),
-                PathBuf::from("/usr/lib/dart/bin/snapshots/analysis_server.dart.snapshot"),
-            )
-        };
+        let sdk_path = find_dart_sdk().unwrap_or_else(|_| default_sdk_path());
+        Self::from_sdk(sdk_path)
+    }
+}
+
+impl DartAnalysisServerConfig {
+    /// Configuration for the SDK installed at `dart_sdk_path`
+    pub fn from_sdk(dart_sdk_path: PathBuf) -> Self {
+        let analysis_server_snapshot = dart_sdk_path
+            .join("bin")
+            .join("snapshots")
+            .join("analysis_server.dart.snapshot");
 
         Self {
-            dart_sdk_path: sdk_path,
-            analysis_server_snapshot: snapshot_path,
+            dart_sdk_path,
+            analysis_server_snapshot,
+            dart_binary: None,
             vm_args: vec![],
             verbose: false,
+            request_timeout: Duration::from_secs(30),
+            trace: None,
+        }
+    }
+
+    /// Configuration for the SDK a project uses, see [`find_dart_sdk_for`]
+    pub fn for_project(project_root: &Path) -> Self {
+        let sdk_path = find_dart_sdk_for(project_root).unwrap_or_else(|_| default_sdk_path());
+        Self::from_sdk(sdk_path)
+    }
+
+    /// The `dart` executable to start
+    ///
+    /// Falls back to `dart` on the `PATH` when the SDK does not exist.
+    pub fn dart_executable(&self) -> PathBuf {
+        if let Some(binary) = &self.dart_binary {
+            return binary.clone();
+        }
+        let sdk_dart = self.dart_sdk_path.join("bin").join(DART_EXECUTABLE);
+        if sdk_dart.is_file() {
+            sdk_dart
+        } else {
+            PathBuf::from("dart")
         }
     }
 }
 
-/// Client for communicating with Dart Analysis Server
+/// Platform-specific SDK location used when no SDK can be found
+fn default_sdk_path() -> PathBuf {
+    if cfg!(target_os = "windows") {
+        PathBuf::from("C:\\tools\\dart-sdk")
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from("/usr/local/opt/dart/libexec")
+    } else {
+        PathBuf::from("/usr/lib/dart")
+    }
+}
+
+/// Errors from requests to the Dart Analysis Server
 ///
-/// This is a stub implementation showing the intended architecture.
-/// A full implementation would:
-/// 1. Start the analysis server as a subprocess
-/// 2. Communicate via JSON-RPC over stdin/stdout
-/// 3. Handle notifications and requests asynchronously
-/// 4. Manage server lifecycle (start, restart, shutdown)
-#[allow(dead_code)]
+/// Returned inside [`anyhow::Error`]; use `downcast_ref` to inspect them.
+#[derive(Debug, Error)]
+pub enum ClientError {
+    #[error("Dart Analysis Server is not running")]
+    NotRunning,
+
+    #[error("Request '{method}' timed out after {timeout:?}")]
+    Timeout { method: String, timeout: Duration },
+
+    #[error("Dart Analysis Server exited before answering '{0}'")]
+    Exited(String),
+
+    #[error("Dart Analysis Server error {code}: {message}")]
+    Server { code: i64, message: String },
+}
+
+/// Output of the Dart server that the client did not consume itself
+#[derive(Debug)]
+pub enum ServerEvent {
+    /// A raw JSON-RPC message
+    Message(String),
+    /// The server closed its stdout, normally because it exited
+    Exited,
+}
+
+/// State shared with the reader task
+struct Shared {
+    /// Senders waiting for the response to each of our requests, by id
+    pending: Mutex<HashMap<String, oneshot::Sender<Value>>>,
+    /// Notification subscribers, by method
+    subscribers: Mutex<HashMap<String, Vec<UnboundedSender<Value>>>>,
+    events: UnboundedSender<ServerEvent>,
+    /// Whether someone took the event stream
+    forward_events: AtomicBool,
+}
+
+/// Client for communicating with Dart Analysis Server
 pub struct DartAnalysisServerClient {
     config: DartAnalysisServerConfig,
-    // In a full implementation, this would include:
-    // - process: Child
-    // - stdin: ChildStdin
-    // - stdout_reader: BufReader<ChildStdout>
-    // - request_id: AtomicU64
-    // - pending_requests: Arc<Mutex<HashMap<u64, Sender<Response>>>>
+    process: Option<Child>,
+    /// Messages for the writer task, which owns the server's stdin
+    outgoing: Option<UnboundedSender<String>>,
+    shared: Arc<Shared>,
+    events: Option<UnboundedReceiver<ServerEvent>>,
+    next_id: AtomicU64,
 }
 
-#[allow(dead_code)]
 impl DartAnalysisServerClient {
     /// Create a new client with the given configuration
     pub fn new(config: DartAnalysisServerConfig) -> Result<Self> {
-        Ok(Self { config })
+        let (events_tx, events_rx) = mpsc::unbounded_channel();
+        Ok(Self {
+            config,
+            process: None,
+            outgoing: None,
+            shared: Arc::new(Shared {
+                pending: Mutex::new(HashMap::new()),
+                subscribers: Mutex::new(HashMap::new()),
+                events: events_tx,
+                forward_events: AtomicBool::new(false),
+            }),
+            events: Some(events_rx),
+            next_id: AtomicU64::new(1),
+        })
     }
 
     /// Start the Dart Analysis Server
     ///
-    /// This is a stub that shows how to start the server.
-    /// A full implementation would manage the process and communication.
-    pub fn start(&mut self) -> Result<()> {
-        // In a full implementation, this would:
-        // 1. Verify dart SDK exists
-        // 2. Start the analysis server process
-        // 3. Initialize LSP communication
-        // 4. Send initialization request
-        // 5. Wait for initialized notification
-
-        let _dart_path = self.config.dart_sdk_path.join("bin").join("dart");
-
-        // Example command that would be used:
-        // let mut cmd = Command::new(dart_path)
-        //     .arg(&self.config.analysis_server_snapshot)
-        //     .stdin(Stdio::piped())
-        //     .stdout(Stdio::piped())
-        //     .stderr(if self.config.verbose { Stdio::inherit() } else { Stdio::null() })
-        //     .spawn()
-        //     .context("Failed to start Dart Analysis Server")?;
+    /// Must be called from within a tokio runtime. The LSP handshake is left
+    /// to the caller, see [`initialize`](Self::initialize).
+    pub async fn start(&mut self) -> Result<()> {
+        let dart = self.config.dart_executable();
+        let mut child = Command::new(&dart)
+            .args(&self.config.vm_args)
+            .arg("language-server")
+            .arg("--protocol=lsp")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(if self.config.verbose {
+                Stdio::inherit()
+            } else {
+                Stdio::null()
+            })
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to start {}", dart.display()))?;
 
+        let stdin = child.stdin.take().context("Failed to capture stdin")?;
+        let stdout = child.stdout.take().context("Failed to capture stdout")?;
+
+        let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<String>();
+        let trace = self.config.trace.clone();
+        tokio::spawn(async move {
+            let mut writer = FramedWrite::new(stdin, LspCodec::default());
+            while let Some(content) = outgoing_rx.recv().await {
+                if let Some(trace) = &trace {
+                    trace.record(Direction::ToServer, &content);
+                }
+                if let Err(e) = writer.send(content).await {
+                    eprintln!("Error writing to Dart server: {}", e);
+                    break;
+                }
+            }
+            // Dropping stdin tells the server we are gone
+        });
+
+        let shared = Arc::clone(&self.shared);
+        let replies = outgoing.downgrade();
+        let trace = self.config.trace.clone();
+        tokio::spawn(async move {
+            let mut reader = FramedRead::new(stdout, LspCodec::default());
+            loop {
+                match reader.next().await.transpose() {
+                    Ok(Some(content)) => {
+                        if let Some(trace) = &trace {
+                            trace.record(Direction::FromServer, &content);
+                        }
+                        route(content, &shared, &replies)
+                    }
+                    Ok(None) => break, // EOF
+                    Err(e) => {
+                        eprintln!("Error reading from Dart server: {}", e);
+                        break;
+                    }
+                }
+            }
+            // Wake every waiter; their requests will never be answered
+            shared.pending.lock().unwrap().clear();
+            let _ = shared.events.send(ServerEvent::Exited);
+        });
+
+        self.process = Some(child);
+        self.outgoing = Some(outgoing);
         Ok(())
     }
 
+    /// Whether the server was started and not stopped since
+    pub fn is_running(&self) -> bool {
+        self.outgoing.is_some()
+    }
+
+    /// Take the stream of server output the client does not consume
+    ///
+    /// Until this is called, requests from the server are answered with
+    /// `null` and other unconsumed messages are dropped.
+    pub fn take_events(&mut self) -> Option<UnboundedReceiver<ServerEvent>> {
+        self.shared.forward_events.store(true, Ordering::SeqCst);
+        self.events.take()
+    }
+
+    /// Receive the `params` of every notification with `method`
+    pub fn subscribe(&self, method: &str) -> UnboundedReceiver<Value> {
+        let (tx, rx) = mpsc::unbounded_channel();
+        self.shared
+            .subscribers
+            .lock()
+            .unwrap()
+            .entry(method.to_string())
+            .or_default()
+            .push(tx);
+        rx
+    }
+
+    /// Perform the LSP handshake: `initialize`, then `initialized`
+    ///
+    /// Returns the server's `InitializeResult`.
+    pub async fn initialize(&self, root_uri: Option<&str>, capabilities: Value) -> Result<Value> {
+        let result = self
+            .send_request(
+                "initialize",
+                json!({
+                    "processId": std::process::id(),
+                    "rootUri": root_uri,
+                    "capabilities": capabilities,
+                    "clientInfo": {
+                        "name": "dart-re-analyzer",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                }),
+            )
+            .await?;
+        self.send_notification("initialized", json!({}))?;
+        Ok(result)
+    }
+
     /// Send a request to the analysis server
     ///
-    /// Stub showing the intended interface.
-    pub async fn send_request(
+    /// Waits up to the configured `request_timeout`. Dropping the returned
+    /// future cancels the request.
+    pub async fn send_request(&self, method: &str, params: Value) -> Result<Value> {
+        self.send_request_with_timeout(method, params, self.config.request_timeout)
+            .await
+    }
+
+    /// Send a request and wait up to `timeout` for the response
+    ///
+    /// On timeout the request is cancelled with `$/cancelRequest`.
+    pub async fn send_request_with_timeout(
         &self,
-        _method: &str,
-        _params: serde_json::Value,
-    ) -> Result<serde_json::Value> {
-        // In a full implementation:
-        // 1. Generate request ID
-        // 2. Create oneshot channel for response
-        // 3. Store in pending_requests map
-        // 4. Serialize and send request over stdin
-        // 5. Await response on channel
-        // 6. Return response or timeout error
+        method: &str,
+        params: Value,
+        timeout: Duration,
+    ) -> Result<Value> {
+        let outgoing = self.outgoing.as_ref().ok_or(ClientError::NotRunning)?;
+        let id = json!(format!(
+            "{}{}",
+            ID_PREFIX,
+            self.next_id.fetch_add(1, Ordering::SeqCst)
+        ));
+
+        let (tx, rx) = oneshot::channel();
+        self.shared
+            .pending
+            .lock()
+            .unwrap()
+            .insert(id.to_string(), tx);
+        let mut cancel = CancelOnDrop {
+            id: id.clone(),
+            shared: Arc::clone(&self.shared),
+            outgoing: outgoing.clone(),
+            armed: true,
+        };
+
+        let request = json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params});
+        outgoing
+            .send(request.to_string())
+            .map_err(|_| ClientError::NotRunning)?;
+
+        let response = match tokio::time::timeout(timeout, rx).await {
+            Ok(Ok(response)) => response,
+            Ok(Err(_)) => {
+                cancel.armed = false;
+                return Err(ClientError::Exited(method.to_string()).into());
+            }
+            Err(_) => {
+                return Err(ClientError::Timeout {
+                    method: method.to_string(),
+                    timeout,
+                }
+                .into())
+            }
+        };
+        cancel.armed = false;
 
-        Ok(serde_json::json!({}))
+        if let Some(error) = response.get("error") {
+            return Err(ClientError::Server {
+                code: error.get("code").and_then(|c| c.as_i64()).unwrap_or(0),
+                message: error
+                    .get("message")
+                    .and_then(|m| m.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+            }
+            .into());
+        }
+        Ok(response.get("result").cloned().unwrap_or(Value::Null))
     }
 
     /// Send a notification to the analysis server
-    ///
-    /// Stub showing the intended interface.
-    pub fn send_notification(&self, _method: &str, _params: serde_json::Value) -> Result<()> {
-        // In a full implementation:
-        // 1. Serialize notification
-        // 2. Send over stdin
-        // 3. Return immediately (no response expected)
+    pub fn send_notification(&self, method: &str, params: Value) -> Result<()> {
+        let notification = json!({"jsonrpc": "2.0", "method": method, "params": params});
+        self.send_raw(&notification.to_string())
+    }
 
+    /// Send an already serialized JSON-RPC message as-is
+    ///
+    /// Responses to requests sent this way arrive as [`ServerEvent`]s.
+    pub fn send_raw(&self, content: &str) -> Result<()> {
+        let outgoing = self.outgoing.as_ref().ok_or(ClientError::NotRunning)?;
+        outgoing
+            .send(content.to_string())
+            .map_err(|_| ClientError::NotRunning)?;
         Ok(())
     }
 
     /// Shutdown the analysis server gracefully
-    pub fn shutdown(&mut self) -> Result<()> {
-        // In a full implementation:
-        // 1. Send shutdown request
-        // 2. Wait for response
-        // 3. Send exit notification
-        // 4. Wait for process to exit or timeout and kill
-
+    ///
+    /// Sends `shutdown` and `exit`, then waits for the process to exit,
+    /// killing it if it does not comply in time.
+    pub async fn shutdown(&mut self) -> Result<()> {
+        if !self.is_running() {
+            return Ok(());
+        }
+        if let Err(e) = self
+            .send_request_with_timeout("shutdown", Value::Null, SHUTDOWN_TIMEOUT)
+            .await
+        {
+            eprintln!("Dart Analysis Server did not shut down cleanly: {}", e);
+        }
+        let _ = self.send_notification("exit", Value::Null);
+        self.stop(EXIT_TIMEOUT).await;
         Ok(())
     }
+
+    /// Close the server's stdin and wait up to `timeout` for it to exit,
+    /// then kill it
+    pub async fn stop(&mut self, timeout: Duration) {
+        self.outgoing = None;
+        let Some(mut child) = self.process.take() else {
+            return;
+        };
+        if tokio::time::timeout(timeout, child.wait()).await.is_err() {
+            eprintln!("Dart Analysis Server did not exit in time, killing it");
+            let _ = child.kill().await;
+        }
+    }
 }
 
-/// Example helper to find Dart SDK
-#[allow(dead_code)]
-pub fn find_dart_sdk() -> Result<PathBuf> {
-    // Try common locations
-    let mut possible_paths = vec![
-        PathBuf::from("/usr/lib/dart"),
-        PathBuf::from("/usr/local/opt/dart"),
-    ];
+/// Cancels a request whose caller stopped waiting for the response
+struct CancelOnDrop {
+    id: Value,
+    shared: Arc<Shared>,
+    outgoing: UnboundedSender<String>,
+    armed: bool,
+}
 
-    // Add home directory path if available
-    if let Some(home) = dirs::home_dir() {
-        possible_paths.push(home.join(".pub-cache").join("bin"));
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        self.shared
+            .pending
+            .lock()
+            .unwrap()
+            .remove(&self.id.to_string());
+        let cancel = json!({
+            "jsonrpc": "2.0",
+            "method": "$/cancelRequest",
+            "params": {"id": self.id},
+        });
+        let _ = self.outgoing.send(cancel.to_string());
     }
+}
 
-    for path in possible_paths {
-        if path.exists() {
-            return Ok(path);
+/// Dispatch a message from the server
+fn route(content: String, shared: &Shared, replies: &WeakUnboundedSender<String>) {
+    let forward = shared.forward_events.load(Ordering::SeqCst);
+    let Ok(msg) = serde_json::from_str::<Value>(&content) else {
+        if forward {
+            let _ = shared.events.send(ServerEvent::Message(content));
         }
-    }
+        return;
+    };
+    let method = msg.get("method").and_then(|m| m.as_str());
 
-    // Try to find via PATH
-    if let Ok(output) = Command::new("which").arg("dart").output() {
-        if output.status.success() {
-            if let Ok(dart_path) = String::from_utf8(output.stdout) {
-                let path = PathBuf::from(dart_path.trim());
-                if let Some(sdk_path) = path.parent().and_then(|p| p.parent()) {
-                    return Ok(sdk_path.to_path_buf());
+    match (method, msg.get("id")) {
+        // Response to one of our requests
+        (None, Some(id)) => {
+            if let Some(waiter) = shared.pending.lock().unwrap().remove(&id.to_string()) {
+                let _ = waiter.send(msg);
+                return;
+            }
+        }
+        (Some(method), None) => {
+            let mut subscribers = shared.subscribers.lock().unwrap();
+            if let Some(senders) = subscribers.get_mut(method) {
+                let params = msg.get("params").cloned().unwrap_or(Value::Null);
+                senders.retain(|tx| tx.send(params.clone()).is_ok());
+                if !senders.is_empty() {
+                    return;
                 }
             }
         }
+        // A request from the server that nobody will see
+        (Some(_), Some(id)) if !forward => {
+            if let Some(replies) = replies.upgrade() {
+                let response = json!({"jsonrpc": "2.0", "id": id, "result": null});
+                let _ = replies.send(response.to_string());
+            }
+            return;
+        }
+        _ => {}
+    }
+
+    if forward {
+        let _ = shared.events.send(ServerEvent::Message(content));
+    }
+}
+
+/// Find the Dart SDK, see [`find_dart_sdk_for`]
+///
+/// Uses the current directory as the project.
+pub fn find_dart_sdk() -> Result<PathBuf> {
+    let project = std::env::current_dir().unwrap_or_default();
+    find_dart_sdk_for(&project)
+}
+
+/// Find the Dart SDK a project uses
+///
+/// Looks, in order, at:
+/// 1. `DART_SDK`
+/// 2. The Dart SDK bundled with Flutter at `FLUTTER_ROOT`
+/// 3. The project's fvm-pinned Flutter (`.fvm/flutter_sdk`), then fvm's
+///    global default (`~/fvm/default`)
+/// 4. The newest Dart, then Flutter, installed by asdf
+/// 5. `dart` on the `PATH`, resolving Flutter's wrapper script
+/// 6. Common install locations
+pub fn find_dart_sdk_for(project_root: &Path) -> Result<PathBuf> {
+    let env = |name: &str| std::env::var_os(name).map(PathBuf::from);
+    sdk_candidates(project_root, env, dirs::home_dir())
+        .into_iter()
+        .find(|sdk| is_dart_sdk(sdk))
+        .context("Could not find Dart SDK")
+}
+
+/// Possible SDK locations, most specific first
+fn sdk_candidates(
+    project_root: &Path,
+    env: impl Fn(&str) -> Option<PathBuf>,
+    home: Option<PathBuf>,
+) -> Vec<PathBuf> {
+    let flutter_sdk = |flutter: PathBuf| flutter.join("bin").join("cache").join("dart-sdk");
+    let mut candidates = Vec::new();
+
+    candidates.extend(env("DART_SDK"));
+    candidates.extend(env("FLUTTER_ROOT").map(flutter_sdk));
+
+    // fvm
+    candidates.push(flutter_sdk(project_root.join(".fvm").join("flutter_sdk")));
+    let fvm_home = env("FVM_CACHE_PATH")
+        .or_else(|| env("FVM_HOME"))
+        .or_else(|| home.as_ref().map(|h| h.join("fvm")));
+    if let Some(fvm_home) = fvm_home {
+        candidates.push(flutter_sdk(fvm_home.join("default")));
+    }
+
+    // asdf
+    let asdf_data = env("ASDF_DATA_DIR").or_else(|| home.as_ref().map(|h| h.join(".asdf")));
+    if let Some(installs) = asdf_data.map(|d| d.join("installs")) {
+        candidates.extend(
+            newest_versions(&installs.join("dart"))
+                .into_iter()
+                .map(|v| v.join("dart-sdk")),
+        );
+        candidates.extend(
+            newest_versions(&installs.join("flutter"))
+                .into_iter()
+                .map(flutter_sdk),
+        );
+    }
+
+    // `dart` on the PATH, which for Flutter is a script in `<flutter>/bin`
+    if let Some(path) = env("PATH") {
+        for dir in std::env::split_paths(&path) {
+            let dart = dir.join(DART_EXECUTABLE);
+            if !dart.is_file() {
+                continue;
+            }
+            let dart = dart.canonicalize().unwrap_or(dart);
+            if let Some(root) = dart.parent().and_then(Path::parent) {
+                candidates.push(flutter_sdk(root.to_path_buf()));
+                candidates.push(root.to_path_buf());
+            }
+        }
     }
 
-    anyhow::bail!("Could not find Dart SDK")
+    candidates.extend([
+        PathBuf::from("/usr/lib/dart"),
+        PathBuf::from("/usr/local/opt/dart/libexec"),
+        PathBuf::from("/opt/homebrew/opt/dart/libexec"),
+        PathBuf::from("C:\\tools\\dart-sdk"),
+    ]);
+    candidates
+}
+
+/// Version directories under `dir`, newest first
+fn newest_versions(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    let mut versions: Vec<PathBuf> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.is_dir())
+        .collect();
+
+    let key = |path: &PathBuf| -> Vec<u64> {
+        path.file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default()
+            .split(|c: char| !c.is_ascii_digit())
+            .filter_map(|part| part.parse().ok())
+            .collect()
+    };
+    versions.sort_by_key(|v| std::cmp::Reverse(key(v)));
+    versions
+}
+
+fn is_dart_sdk(path: &Path) -> bool {
+    path.join("bin").join(DART_EXECUTABLE).is_file()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// Create a fake SDK with a `bin/dart` at `path`
+    fn fake_sdk(path: &Path) {
+        std::fs::create_dir_all(path.join("bin")).unwrap();
+        std::fs::write(path.join("bin").join(DART_EXECUTABLE), "").unwrap();
+    }
+
+    fn find(project: &Path, vars: &[(&str, &Path)], home: Option<&Path>) -> Option<PathBuf> {
+        let env = |name: &str| {
+            vars.iter()
+                .find(|(key, _)| *key == name)
+                .map(|(_, value)| value.to_path_buf())
+        };
+        sdk_candidates(project, env, home.map(Path::to_path_buf))
+            .into_iter()
+            .find(|sdk| is_dart_sdk(sdk))
+    }
+
     #[test]
     fn test_config_default() {
         let config = DartAnalysisServerConfig::default();
         assert!(!config.verbose);
         assert!(config.vm_args.is_empty());
+        assert_eq!(config.request_timeout, Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_dart_executable_prefers_explicit_binary() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut config = DartAnalysisServerConfig::from_sdk(dir.path().join("missing"));
+        assert_eq!(config.dart_executable(), PathBuf::from("dart"));
+
+        fake_sdk(dir.path());
+        config.dart_sdk_path = dir.path().to_path_buf();
+        assert_eq!(
+            config.dart_executable(),
+            dir.path().join("bin").join(DART_EXECUTABLE)
+        );
+
+        config.dart_binary = Some(PathBuf::from("/opt/custom/dart"));
+        assert_eq!(config.dart_executable(), PathBuf::from("/opt/custom/dart"));
     }
 
     #[test]
     fn test_client_creation() {
         let config = DartAnalysisServerConfig::default();
-        let result = DartAnalysisServerClient::new(config);
-        assert!(result.is_ok());
+        let mut client = DartAnalysisServerClient::new(config).unwrap();
+        assert!(!client.is_running());
+        assert!(client.take_events().is_some());
+        assert!(client.take_events().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_requests_fail_before_start() {
+        let client = DartAnalysisServerClient::new(DartAnalysisServerConfig::default()).unwrap();
+        let error = client.send_request("hover", Value::Null).await.unwrap_err();
+        assert!(matches!(
+            error.downcast_ref::<ClientError>(),
+            Some(ClientError::NotRunning)
+        ));
+    }
+
+    #[test]
+    fn test_find_sdk_from_env() {
+        let dir = tempfile::tempdir().unwrap();
+        let dart_sdk = dir.path().join("dart-sdk");
+        let flutter = dir.path().join("flutter");
+        let flutter_sdk = flutter.join("bin").join("cache").join("dart-sdk");
+        fake_sdk(&dart_sdk);
+        fake_sdk(&flutter_sdk);
+
+        let project = dir.path().join("project");
+        assert_eq!(
+            find(
+                &project,
+                &[("DART_SDK", &dart_sdk), ("FLUTTER_ROOT", &flutter)],
+                None
+            ),
+            Some(dart_sdk)
+        );
+        assert_eq!(
+            find(&project, &[("FLUTTER_ROOT", &flutter)], None),
+            Some(flutter_sdk)
+        );
+    }
+
+    #[test]
+    fn test_find_sdk_from_fvm() {
+        let dir = tempfile::tempdir().unwrap();
+        let home = dir.path().join("home");
+        let global = home.join("fvm").join("default");
+        fake_sdk(&global.join("bin").join("cache").join("dart-sdk"));
+
+        let project = dir.path().join("project");
+        std::fs::create_dir_all(&project).unwrap();
+        assert_eq!(
+            find(&project, &[], Some(&home)),
+            Some(global.join("bin").join("cache").join("dart-sdk"))
+        );
+
+        // A project-pinned version wins over the global default
+        let pinned = project.join(".fvm").join("flutter_sdk");
+        fake_sdk(&pinned.join("bin").join("cache").join("dart-sdk"));
+        assert_eq!(
+            find(&project, &[], Some(&home)),
+            Some(pinned.join("bin").join("cache").join("dart-sdk"))
+        );
+    }
+
+    #[test]
+    fn test_find_sdk_from_asdf_prefers_newest() {
+        let dir = tempfile::tempdir().unwrap();
+        let installs = dir.path().join(".asdf").join("installs").join("dart");
+        for version in ["2.19.6", "3.10.1", "3.9.0"] {
+            fake_sdk(&installs.join(version).join("dart-sdk"));
+        }
+
+        assert_eq!(
+            find(dir.path(), &[], Some(dir.path())),
+            Some(installs.join("3.10.1").join("dart-sdk"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_find_sdk_through_flutter_on_path() {
+        let dir = tempfile::tempdir().unwrap();
+        let flutter = dir.path().join("flutter");
+        let sdk = flutter.join("bin").join("cache").join("dart-sdk");
+        fake_sdk(&sdk);
+        // Flutter's `bin/dart` is a wrapper script, not the SDK's binary
+        std::fs::write(flutter.join("bin").join("dart"), "#!/bin/sh\n").unwrap();
+
+        let path = flutter.join("bin");
+        assert_eq!(find(dir.path(), &[("PATH", &path)], None), Some(sdk));
     }
 }
@@ -0,0 +1,355 @@
+//! Quick fixes for our diagnostics
+//!
+//! For every diagnostic in the requested range the proxy offers the rule's
+//! own [`Fix`](crate::error::Fix), when it has one, plus actions that insert
+//! `// ignore:` and `// ignore_for_file:` suppression comments. These are
+//! appended to the Dart Analysis Server's `textDocument/codeAction` result.
+
+use lsp_types::{Position, Range};
+use serde_json::{json, Value};
+
+use super::position::{self, PositionEncoding};
+use crate::error::Diagnostic;
+
+/// Kind of every action we offer
+pub const QUICK_FIX: &str = "quickfix";
+
+/// Whether a `CodeActionContext.only` filter admits quick fixes
+pub fn wants_quick_fixes(only: Option<&Value>) -> bool {
+    let Some(only) = only.and_then(|o| o.as_array()) else {
+        return true;
+    };
+    only.iter()
+        .filter_map(|kind| kind.as_str())
+        .any(|kind| kind.is_empty() || kind == QUICK_FIX)
+}
+
+/// Build code actions for the diagnostics that overlap `range`
+///
+/// `text` is the content the diagnostics were computed from. `to_lsp`
+/// converts a diagnostic into the form the client received, so actions can
+/// point back at it.
+pub fn code_actions(
+    uri: &str,
+    text: Option<&str>,
+    diagnostics: &[Diagnostic],
+    range: Range,
+    encoding: PositionEncoding,
+    to_lsp: impl Fn(&Diagnostic) -> Value,
+) -> Vec<Value> {
+    let mut actions = Vec::new();
+    let mut file_actions = Vec::new();
+
+    for diagnostic in diagnostics {
+        let diagnostic_range = position::location_to_range(&diagnostic.location, text, encoding);
+        if !overlaps(diagnostic_range, range) {
+            continue;
+        }
+        let lsp_diagnostic = to_lsp(diagnostic);
+
+        if let Some(fix) = &diagnostic.fix {
+            let edits: Vec<Value> = fix
+                .edits
+                .iter()
+                .map(|edit| {
+                    text_edit(
+                        position::location_to_range(&edit.location, text, encoding),
+                        &edit.replacement,
+                    )
+                })
+                .collect();
+            actions.push(action(&fix.description, uri, edits, &lsp_diagnostic, true));
+        }
+
+        let line = diagnostic.location.line.saturating_sub(1);
+        actions.push(action(
+            &format!("Ignore '{}' for this line", diagnostic.rule_id),
+            uri,
+            vec![ignore_line_edit(text, line, &diagnostic.rule_id, encoding)],
+            &lsp_diagnostic,
+            false,
+        ));
+
+        if !file_actions.contains(&diagnostic.rule_id) {
+            file_actions.push(diagnostic.rule_id.clone());
+            actions.push(action(
+                &format!("Ignore '{}' for this file", diagnostic.rule_id),
+                uri,
+                vec![ignore_file_edit(text, &diagnostic.rule_id, encoding)],
+                &lsp_diagnostic,
+                false,
+            ));
+        }
+    }
+
+    // Keep file-level actions after the line-level ones
+    actions.sort_by_key(|a| {
+        a["title"]
+            .as_str()
+            .is_some_and(|t| t.ends_with("for this file"))
+    });
+    actions
+}
+
+fn overlaps(a: Range, b: Range) -> bool {
+    let start = |r: Range| (r.start.line, r.start.character);
+    let end = |r: Range| (r.end.line, r.end.character);
+    start(a) <= end(b) && start(b) <= end(a)
+}
+
+fn action(title: &str, uri: &str, edits: Vec<Value>, diagnostic: &Value, preferred: bool) -> Value {
+    let mut action = json!({
+        "title": title,
+        "kind": QUICK_FIX,
+        "diagnostics": [diagnostic],
+        "edit": {"changes": {uri: edits}},
+    });
+    if preferred {
+        action["isPreferred"] = json!(true);
+    }
+    action
+}
+
+fn text_edit(range: Range, new_text: &str) -> Value {
+    json!({"range": range, "newText": new_text})
+}
+
+fn insert(line: u32, character: u32, new_text: &str) -> Value {
+    let position = Position::new(line, character);
+    text_edit(Range::new(position, position), new_text)
+}
+
+/// Add the rule to an `// ignore:` comment on the line above, or insert one
+/// with the same indentation as the flagged line
+fn ignore_line_edit(
+    text: Option<&str>,
+    line: usize,
+    rule_id: &str,
+    encoding: PositionEncoding,
+) -> Value {
+    let lines: Vec<&str> = text.map(|t| t.lines().collect()).unwrap_or_default();
+
+    if let Some(previous) = line.checked_sub(1).and_then(|l| lines.get(l)) {
+        let trimmed = previous.trim_end();
+        if trimmed.trim_start().starts_with("// ignore:") {
+            return append_to_comment(lines[line - 1], line, trimmed, rule_id, encoding);
+        }
+    }
+
+    let indent: String = lines
+        .get(line)
+        .map(|l| l.chars().take_while(|c| c.is_whitespace()).collect())
+        .unwrap_or_default();
+    insert(
+        line as u32,
+        0,
+        &format!("{}// ignore: {}\n", indent, rule_id),
+    )
+}
+
+/// Add the rule to an existing `// ignore_for_file:` comment, or insert one
+/// at the top of the file
+fn ignore_file_edit(text: Option<&str>, rule_id: &str, encoding: PositionEncoding) -> Value {
+    let lines: Vec<&str> = text.map(|t| t.lines().collect()).unwrap_or_default();
+
+    if let Some((line, existing)) = lines
+        .iter()
+        .enumerate()
+        .find(|(_, l)| l.trim_start().starts_with("// ignore_for_file:"))
+    {
+        return append_to_comment(existing, line + 1, existing.trim_end(), rule_id, encoding);
+    }
+
+    insert(0, 0, &format!("// ignore_for_file: {}\n", rule_id))
+}
+
+/// Insert `, rule_id` after the last rule of a comment on a 1-based line
+fn append_to_comment(
+    line_text: &str,
+    line: usize,
+    trimmed: &str,
+    rule_id: &str,
+    encoding: PositionEncoding,
+) -> Value {
+    let character = position::byte_to_character(line_text, trimmed.len(), encoding);
+    insert((line - 1) as u32, character, &format!(", {}", rule_id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{Fix, FixEdit, Location, RuleCategory, Severity};
+
+    fn diagnostic(rule_id: &str, line: usize, column: usize, end_column: usize) -> Diagnostic {
+        Diagnostic::new(
+            rule_id,
+            "message",
+            Severity::Warning,
+            RuleCategory::Runtime,
+            Location {
+                file: "/a.dart".to_string(),
+                line,
+                column,
+                end_line: Some(line),
+                end_column: Some(end_column),
+            },
+        )
+    }
+
+    fn to_lsp(diag: &Diagnostic) -> Value {
+        json!({"code": diag.rule_id})
+    }
+
+    fn range(start: (u32, u32), end: (u32, u32)) -> Range {
+        Range::new(Position::new(start.0, start.1), Position::new(end.0, end.1))
+    }
+
+    fn edits(action: &Value) -> &Vec<Value> {
+        action["edit"]["changes"]["file:///a.dart"]
+            .as_array()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_ignore_actions_for_diagnostic_in_range() {
+        let text = "void main() {\n  print('x');\n}\n";
+        let diagnostics = vec![diagnostic("avoid_print", 2, 3, 8)];
+
+        let actions = code_actions(
+            "file:///a.dart",
+            Some(text),
+            &diagnostics,
+            range((1, 4), (1, 4)),
+            PositionEncoding::Utf16,
+            to_lsp,
+        );
+        assert_eq!(actions.len(), 2);
+
+        assert_eq!(actions[0]["title"], "Ignore 'avoid_print' for this line");
+        assert_eq!(actions[0]["kind"], "quickfix");
+        assert_eq!(actions[0]["diagnostics"][0]["code"], "avoid_print");
+        assert_eq!(
+            edits(&actions[0])[0],
+            json!({
+                "range": {"start": {"line": 1, "character": 0}, "end": {"line": 1, "character": 0}},
+                "newText": "  // ignore: avoid_print\n"
+            })
+        );
+
+        assert_eq!(actions[1]["title"], "Ignore 'avoid_print' for this file");
+        assert_eq!(
+            edits(&actions[1])[0]["newText"],
+            "// ignore_for_file: avoid_print\n"
+        );
+    }
+
+    #[test]
+    fn test_skips_diagnostics_outside_range() {
+        let diagnostics = vec![diagnostic("avoid_print", 2, 3, 8)];
+        let actions = code_actions(
+            "file:///a.dart",
+            None,
+            &diagnostics,
+            range((0, 0), (0, 5)),
+            PositionEncoding::Utf16,
+            to_lsp,
+        );
+        assert!(actions.is_empty());
+    }
+
+    #[test]
+    fn test_appends_to_existing_comments() {
+        let text = "// ignore_for_file: avoid_dynamic\nvoid main() {\n  // ignore: avoid_dynamic\n  print('x');\n}\n";
+        let diagnostics = vec![diagnostic("avoid_print", 4, 3, 8)];
+
+        let actions = code_actions(
+            "file:///a.dart",
+            Some(text),
+            &diagnostics,
+            range((3, 0), (3, 20)),
+            PositionEncoding::Utf16,
+            to_lsp,
+        );
+
+        assert_eq!(
+            edits(&actions[0])[0],
+            json!({
+                "range": {"start": {"line": 2, "character": 26}, "end": {"line": 2, "character": 26}},
+                "newText": ", avoid_print"
+            })
+        );
+        assert_eq!(
+            edits(&actions[1])[0],
+            json!({
+                "range": {"start": {"line": 0, "character": 33}, "end": {"line": 0, "character": 33}},
+                "newText": ", avoid_print"
+            })
+        );
+    }
+
+    #[test]
+    fn test_rule_fix_is_preferred() {
+        let text = "import 'package:foo/foo.dart';\n\nvoid main() {}\n";
+        let diagnostics = vec![diagnostic("unused_import", 1, 1, 31).with_fix(Fix {
+            description: "Remove unused import".to_string(),
+            edits: vec![FixEdit {
+                location: Location {
+                    file: "/a.dart".to_string(),
+                    line: 1,
+                    column: 1,
+                    end_line: Some(2),
+                    end_column: Some(1),
+                },
+                replacement: String::new(),
+            }],
+        })];
+
+        let actions = code_actions(
+            "file:///a.dart",
+            Some(text),
+            &diagnostics,
+            range((0, 0), (0, 0)),
+            PositionEncoding::Utf16,
+            to_lsp,
+        );
+        assert_eq!(actions.len(), 3);
+        assert_eq!(actions[0]["title"], "Remove unused import");
+        assert_eq!(actions[0]["isPreferred"], true);
+        assert_eq!(
+            edits(&actions[0])[0]["range"],
+            json!({"start": {"line": 0, "character": 0}, "end": {"line": 1, "character": 0}})
+        );
+    }
+
+    #[test]
+    fn test_file_action_offered_once_per_rule() {
+        let text = "void main() {\n  print('x'); print('y');\n}\n";
+        let diagnostics = vec![
+            diagnostic("avoid_print", 2, 3, 8),
+            diagnostic("avoid_print", 2, 15, 20),
+        ];
+        let actions = code_actions(
+            "file:///a.dart",
+            Some(text),
+            &diagnostics,
+            range((1, 0), (1, 30)),
+            PositionEncoding::Utf16,
+            to_lsp,
+        );
+        let file_actions = actions
+            .iter()
+            .filter(|a| a["title"] == "Ignore 'avoid_print' for this file")
+            .count();
+        assert_eq!(file_actions, 1);
+    }
+
+    #[test]
+    fn test_wants_quick_fixes() {
+        assert!(wants_quick_fixes(None));
+        assert!(wants_quick_fixes(Some(&json!(["quickfix"]))));
+        assert!(!wants_quick_fixes(Some(&json!([
+            "refactor",
+            "source.organizeImports"
+        ]))));
+    }
+}
@@ -0,0 +1,919 @@
+//! [`SemanticAnalyzer`] backed by the Dart Analysis Server
+//!
+//! Drives the server's `textDocument/hover`, `definition` and `references`
+//! requests and collects its `publishDiagnostics`, mapping the answers into
+//! [`TypeInfo`], [`SymbolInfo`] and [`SemanticDiagnostic`]. Positions follow
+//! the rules' convention of 1-based lines and 1-based byte columns.
+//!
+//! The trait is synchronous, so the analyzer owns a small tokio runtime for
+//! the client and blocks the calling thread until each answer arrives.
+
+use anyhow::{Context, Result};
+use lsp_types::Position;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc as std_mpsc, Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use tokio::runtime::Runtime;
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use super::client::{DartAnalysisServerClient, DartAnalysisServerConfig};
+use super::lifecycle::{EXIT_TIMEOUT, SHUTDOWN_TIMEOUT};
+use super::position::{byte_to_character, character_to_byte, PositionEncoding};
+use super::semantic::{
+    DiagnosticSeverity, SemanticAnalyzer, SemanticDiagnostic, SymbolInfo, SymbolKind, TypeInfo,
+};
+use super::uri::{cache_key, path_to_uri, uri_to_path};
+
+/// Default time [`DartSemanticAnalyzer::get_diagnostics`] waits for analysis
+const DIAGNOSTICS_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A document we opened in the Dart server, keyed by [`cache_key`]
+struct OpenDocument {
+    version: i32,
+    text: String,
+    /// Value of [`Published::analysis_done`] when the text was last sent
+    synced_at: u64,
+}
+
+/// What the server reported since it started
+#[derive(Default)]
+struct Published {
+    /// Latest diagnostics per file, keyed by [`cache_key`]
+    diagnostics: HashMap<String, Vec<SemanticDiagnostic>>,
+    /// How often the server reported that analysis finished
+    analysis_done: u64,
+}
+
+type Documents = Arc<Mutex<HashMap<String, OpenDocument>>>;
+
+/// Semantic analyzer that asks a running Dart Analysis Server
+///
+/// Files are opened in the server on first use, from disk unless
+/// [`update_file`](SemanticAnalyzer::update_file) provided their content.
+/// Dropping the analyzer shuts the server down.
+pub struct DartSemanticAnalyzer {
+    runtime: Option<Runtime>,
+    client: Option<Arc<DartAnalysisServerClient>>,
+    encoding: PositionEncoding,
+    documents: Documents,
+    published: Arc<(Mutex<Published>, Condvar)>,
+    diagnostics_timeout: Duration,
+}
+
+impl DartSemanticAnalyzer {
+    /// Start a Dart Analysis Server for the project at `project_root`
+    ///
+    /// Returns once the server has answered `initialize`.
+    pub fn start(config: DartAnalysisServerConfig, project_root: &Path) -> Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("dart-semantic")
+            .enable_all()
+            .build()
+            .context("Failed to create runtime for the Dart Analysis Server client")?;
+
+        let mut analyzer = Self {
+            runtime: Some(runtime),
+            client: None,
+            encoding: PositionEncoding::default(),
+            documents: Arc::new(Mutex::new(HashMap::new())),
+            published: Arc::new((Mutex::new(Published::default()), Condvar::new())),
+            diagnostics_timeout: DIAGNOSTICS_TIMEOUT,
+        };
+
+        let root_uri = path_to_uri(project_root);
+        let (client, result, diagnostics, status) = analyzer.block_on(async move {
+            let mut client = DartAnalysisServerClient::new(config)?;
+            client.start().await?;
+            let diagnostics = client.subscribe("textDocument/publishDiagnostics");
+            let status = client.subscribe("$/analyzerStatus");
+            let result = client
+                .initialize(root_uri.as_deref(), client_capabilities())
+                .await
+                .context("Dart Analysis Server failed to initialize")?;
+            Ok::<_, anyhow::Error>((client, result, diagnostics, status))
+        })??;
+
+        analyzer.encoding = PositionEncoding::from_server_capabilities(&result["capabilities"]);
+        analyzer.client = Some(Arc::new(client));
+        if let Some(runtime) = &analyzer.runtime {
+            runtime.spawn(collect_published(
+                diagnostics,
+                status,
+                analyzer.published.clone(),
+                analyzer.documents.clone(),
+                analyzer.encoding,
+            ));
+        }
+        Ok(analyzer)
+    }
+
+    /// How long [`get_diagnostics`](SemanticAnalyzer::get_diagnostics) waits
+    /// for the server to analyze a file
+    pub fn with_diagnostics_timeout(mut self, timeout: Duration) -> Self {
+        self.diagnostics_timeout = timeout;
+        self
+    }
+
+    /// Run `future` on our runtime and wait for its output
+    fn block_on<T: Send + 'static>(
+        &self,
+        future: impl Future<Output = T> + Send + 'static,
+    ) -> Result<T> {
+        let runtime = self.runtime.as_ref().context("Analyzer was shut down")?;
+        let (tx, rx) = std_mpsc::channel();
+        runtime.spawn(async move {
+            let _ = tx.send(future.await);
+        });
+        rx.recv()
+            .context("Dart Analysis Server client task was dropped")
+    }
+
+    fn client(&self) -> Result<Arc<DartAnalysisServerClient>> {
+        self.client
+            .clone()
+            .context("Dart Analysis Server is not running")
+    }
+
+    fn request(&self, method: &str, params: Value) -> Result<Value> {
+        let client = self.client()?;
+        let method = method.to_string();
+        self.block_on(async move { client.send_request(&method, params).await })?
+    }
+
+    /// Send `content` to the server unless it already has it
+    fn sync(&self, file: &Path, content: &str) -> Result<()> {
+        let key = cache_key(file);
+        let uri = path_to_uri(file)
+            .with_context(|| format!("Cannot build a URI for {}", file.display()))?;
+
+        let mut documents = self.documents.lock().unwrap();
+        let (method, params) = match documents.get_mut(&key) {
+            Some(document) if document.text == content => return Ok(()),
+            Some(document) => {
+                document.version += 1;
+                document.text = content.to_string();
+                (
+                    "textDocument/didChange",
+                    json!({
+                        "textDocument": {"uri": uri, "version": document.version},
+                        "contentChanges": [{"text": content}],
+                    }),
+                )
+            }
+            None => {
+                documents.insert(
+                    key.clone(),
+                    OpenDocument {
+                        version: 1,
+                        text: content.to_string(),
+                        synced_at: 0,
+                    },
+                );
+                (
+                    "textDocument/didOpen",
+                    json!({
+                        "textDocument": {
+                            "uri": uri,
+                            "languageId": "dart",
+                            "version": 1,
+                            "text": content,
+                        }
+                    }),
+                )
+            }
+        };
+
+        // Diagnostics for the old text are stale now
+        let mut published = self.published.0.lock().unwrap();
+        published.diagnostics.remove(&key);
+        if let Some(document) = documents.get_mut(&key) {
+            document.synced_at = published.analysis_done;
+        }
+        drop(published);
+        drop(documents);
+
+        self.client()?.send_notification(method, params)
+    }
+
+    /// Open `file` from disk unless it is open already, returning its text
+    fn ensure_open(&self, file: &Path) -> Result<String> {
+        if let Some(text) = self.text(file) {
+            return Ok(text);
+        }
+        let text = std::fs::read_to_string(file)
+            .with_context(|| format!("Failed to read {}", file.display()))?;
+        self.sync(file, &text)?;
+        Ok(text)
+    }
+
+    /// Our copy of an open file's text
+    fn text(&self, file: &Path) -> Option<String> {
+        document_text(&self.documents, file)
+    }
+
+    /// `TextDocumentPositionParams` for a 1-based line and byte column
+    fn position_params(&self, file: &Path, line: usize, column: usize) -> Result<(Value, String)> {
+        let text = self.ensure_open(file)?;
+        let uri = path_to_uri(file)
+            .with_context(|| format!("Cannot build a URI for {}", file.display()))?;
+        let position = to_position(&text, line, column, self.encoding);
+        let params = json!({"textDocument": {"uri": uri}, "position": position});
+        Ok((params, text))
+    }
+
+    fn hover(&self, file: &Path, line: usize, column: usize) -> Result<Option<String>> {
+        let (params, _) = self.position_params(file, line, column)?;
+        let hover = self.request("textDocument/hover", params)?;
+        Ok(hover_text(&hover))
+    }
+
+    /// Name, kind and type of the symbol at a position, for [`SymbolInfo`]s
+    fn describe(
+        &self,
+        file: &Path,
+        line: usize,
+        column: usize,
+    ) -> Result<(String, SymbolKind, Option<String>)> {
+        let text = self.ensure_open(file)?;
+        let name = identifier_at(&text, line, column).unwrap_or_default();
+        let hover = self.hover(file, line, column)?.unwrap_or_default();
+        let kind = hover_signature(&hover)
+            .map(|s| symbol_kind(&s))
+            .unwrap_or(SymbolKind::Variable);
+        Ok((name, kind, hover_type(&hover)))
+    }
+
+    /// Symbols at each of the `Location`s or `LocationLink`s in `locations`
+    fn symbols_at(
+        &self,
+        locations: &Value,
+        name: &str,
+        kind: SymbolKind,
+        resolved_type: &Option<String>,
+    ) -> Vec<SymbolInfo> {
+        let locations = match locations {
+            Value::Array(locations) => locations.iter().collect(),
+            Value::Null => Vec::new(),
+            location => vec![location],
+        };
+
+        locations
+            .into_iter()
+            .filter_map(|location| {
+                let uri = location
+                    .get("targetUri")
+                    .or_else(|| location.get("uri"))?
+                    .as_str()?;
+                let range = location
+                    .get("targetSelectionRange")
+                    .or_else(|| location.get("range"))?;
+                let path = uri_to_path(uri)?;
+                let text = self
+                    .text(&path)
+                    .or_else(|| std::fs::read_to_string(&path).ok());
+                let (line, column) = from_position(text.as_deref(), &range["start"], self.encoding);
+                Some(SymbolInfo {
+                    name: name.to_string(),
+                    kind,
+                    resolved_type: resolved_type.clone(),
+                    is_nullable: resolved_type
+                        .as_deref()
+                        .and_then(parse_type)
+                        .is_some_and(|t| t.is_nullable),
+                    definition_file: path,
+                    definition_line: line,
+                    definition_column: column,
+                })
+            })
+            .collect()
+    }
+}
+
+impl SemanticAnalyzer for DartSemanticAnalyzer {
+    fn update_file(&self, file: &Path, content: &str) -> Result<()> {
+        self.sync(file, content)
+    }
+
+    fn resolve_type(&self, file: &Path, line: usize, column: usize) -> Result<Option<TypeInfo>> {
+        let hover = self.hover(file, line, column)?;
+        Ok(hover
+            .as_deref()
+            .and_then(hover_type)
+            .as_deref()
+            .and_then(parse_type))
+    }
+
+    fn get_diagnostics(&self, file: &Path) -> Result<Vec<SemanticDiagnostic>> {
+        self.ensure_open(file)?;
+        let key = cache_key(file);
+        let synced_at = self
+            .documents
+            .lock()
+            .unwrap()
+            .get(&key)
+            .map(|d| d.synced_at)
+            .unwrap_or_default();
+
+        // Wait for diagnostics for the current text, or for the server to
+        // finish analyzing without publishing any
+        let deadline = Instant::now() + self.diagnostics_timeout;
+        let (lock, changed) = &*self.published;
+        let mut published = lock.lock().unwrap();
+        loop {
+            if let Some(diagnostics) = published.diagnostics.get(&key) {
+                return Ok(diagnostics.clone());
+            }
+            let now = Instant::now();
+            if published.analysis_done > synced_at || now >= deadline {
+                return Ok(Vec::new());
+            }
+            published = changed.wait_timeout(published, deadline - now).unwrap().0;
+        }
+    }
+
+    fn find_definition(
+        &self,
+        file: &Path,
+        line: usize,
+        column: usize,
+    ) -> Result<Option<SymbolInfo>> {
+        let (params, _) = self.position_params(file, line, column)?;
+        let locations = self.request("textDocument/definition", params)?;
+        let (name, kind, resolved_type) = self.describe(file, line, column)?;
+        Ok(self
+            .symbols_at(&locations, &name, kind, &resolved_type)
+            .into_iter()
+            .next())
+    }
+
+    fn find_references(&self, file: &Path, line: usize, column: usize) -> Result<Vec<SymbolInfo>> {
+        let (mut params, _) = self.position_params(file, line, column)?;
+        params["context"] = json!({"includeDeclaration": false});
+        let locations = self.request("textDocument/references", params)?;
+        let (name, kind, resolved_type) = self.describe(file, line, column)?;
+        Ok(self.symbols_at(&locations, &name, kind, &resolved_type))
+    }
+
+    fn get_hover(&self, file: &Path, line: usize, column: usize) -> Result<Option<String>> {
+        self.hover(file, line, column)
+    }
+}
+
+impl Drop for DartSemanticAnalyzer {
+    fn drop(&mut self) {
+        let Some(runtime) = self.runtime.take() else {
+            return;
+        };
+        if let Some(mut client) = self.client.take().and_then(Arc::into_inner) {
+            let (tx, rx) = std_mpsc::channel();
+            runtime.spawn(async move {
+                let _ = client.shutdown().await;
+                let _ = tx.send(());
+            });
+            let _ = rx.recv_timeout(SHUTDOWN_TIMEOUT + EXIT_TIMEOUT);
+        }
+        // Never blocks, so dropping the analyzer is safe in async code too
+        runtime.shutdown_background();
+    }
+}
+
+/// What we tell the server we support
+fn client_capabilities() -> Value {
+    json!({
+        "general": {"positionEncodings": ["utf-8", "utf-16"]},
+        "textDocument": {
+            "hover": {"contentFormat": ["markdown", "plaintext"]},
+            "definition": {"linkSupport": true},
+            "publishDiagnostics": {},
+        },
+    })
+}
+
+/// Record `publishDiagnostics` and `$/analyzerStatus` notifications
+async fn collect_published(
+    mut diagnostics: UnboundedReceiver<Value>,
+    mut status: UnboundedReceiver<Value>,
+    published: Arc<(Mutex<Published>, Condvar)>,
+    documents: Documents,
+    encoding: PositionEncoding,
+) {
+    loop {
+        tokio::select! {
+            Some(params) = diagnostics.recv() => {
+                let Some(path) = params["uri"].as_str().and_then(uri_to_path) else {
+                    continue;
+                };
+                let text = document_text(&documents, &path)
+                    .or_else(|| std::fs::read_to_string(&path).ok());
+                let mapped = params["diagnostics"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .map(|d| map_diagnostic(&path, d, text.as_deref(), encoding))
+                    .collect();
+
+                let (lock, changed) = &*published;
+                lock.lock().unwrap().diagnostics.insert(cache_key(&path), mapped);
+                changed.notify_all();
+            }
+            Some(params) = status.recv() => {
+                if params["isAnalyzing"] == false {
+                    let (lock, changed) = &*published;
+                    lock.lock().unwrap().analysis_done += 1;
+                    changed.notify_all();
+                }
+            }
+            else => break,
+        }
+    }
+}
+
+fn document_text(documents: &Documents, file: &Path) -> Option<String> {
+    documents
+        .lock()
+        .unwrap()
+        .get(&cache_key(file))
+        .map(|d| d.text.clone())
+}
+
+fn line_of(text: &str, line: usize) -> &str {
+    text.split('\n')
+        .nth(line)
+        .unwrap_or_default()
+        .trim_end_matches('\r')
+}
+
+/// LSP position of a 1-based line and byte column
+fn to_position(text: &str, line: usize, column: usize, encoding: PositionEncoding) -> Position {
+    let line = line.saturating_sub(1);
+    Position {
+        line: line as u32,
+        character: byte_to_character(line_of(text, line), column.saturating_sub(1), encoding),
+    }
+}
+
+/// 1-based line and byte column of an LSP position
+///
+/// Without the file's text, characters are taken as bytes.
+fn from_position(
+    text: Option<&str>,
+    position: &Value,
+    encoding: PositionEncoding,
+) -> (usize, usize) {
+    let line = position["line"].as_u64().unwrap_or_default() as usize;
+    let character = position["character"].as_u64().unwrap_or_default() as u32;
+    let byte = match text {
+        Some(text) => character_to_byte(line_of(text, line), character, encoding),
+        None => character as usize,
+    };
+    (line + 1, byte + 1)
+}
+
+/// The identifier around a 1-based line and byte column
+fn identifier_at(text: &str, line: usize, column: usize) -> Option<String> {
+    let line = line_of(text, line.saturating_sub(1));
+    let at = column.saturating_sub(1).min(line.len());
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_' || c == '$';
+    let start = line[..at]
+        .rfind(|c: char| !is_ident(c))
+        .map(|i| i + 1)
+        .unwrap_or(0);
+    let end = line[at..]
+        .find(|c: char| !is_ident(c))
+        .map(|i| at + i)
+        .unwrap_or(line.len());
+    (start < end).then(|| line[start..end].to_string())
+}
+
+/// Map an LSP diagnostic published for `file`
+///
+/// Fixes are left empty: the server offers them through code actions.
+pub fn map_diagnostic(
+    file: &Path,
+    diagnostic: &Value,
+    text: Option<&str>,
+    encoding: PositionEncoding,
+) -> SemanticDiagnostic {
+    let (start_line, start_column) = from_position(text, &diagnostic["range"]["start"], encoding);
+    let (end_line, end_column) = from_position(text, &diagnostic["range"]["end"], encoding);
+    let severity = match diagnostic["severity"].as_u64() {
+        Some(1) => DiagnosticSeverity::Error,
+        Some(3) => DiagnosticSeverity::Info,
+        Some(4) => DiagnosticSeverity::Hint,
+        _ => DiagnosticSeverity::Warning,
+    };
+    let code = match &diagnostic["code"] {
+        Value::String(code) => Some(code.clone()),
+        Value::Number(code) => Some(code.to_string()),
+        _ => None,
+    };
+
+    SemanticDiagnostic {
+        message: diagnostic["message"]
+            .as_str()
+            .unwrap_or_default()
+            .to_string(),
+        severity,
+        file: PathBuf::from(file),
+        start_line,
+        start_column,
+        end_line,
+        end_column,
+        code,
+        fixes: vec![],
+    }
+}
+
+/// Text of a hover's `contents`
+///
+/// Handles `MarkupContent`, a single `MarkedString` and arrays of them.
+pub fn hover_text(hover: &Value) -> Option<String> {
+    fn marked(value: &Value) -> Option<String> {
+        match value {
+            Value::String(text) => Some(text.clone()),
+            Value::Object(_) => value["value"]
+                .as_str()
+                .map(|v| match value["language"].as_str() {
+                    Some(language) => format!("```{}\n{}\n```", language, v),
+                    None => v.to_string(),
+                }),
+            _ => None,
+        }
+    }
+
+    let text = match &hover["contents"] {
+        Value::Array(parts) => parts
+            .iter()
+            .filter_map(marked)
+            .collect::<Vec<_>>()
+            .join("\n\n"),
+        contents => marked(contents)?,
+    };
+    (!text.trim().is_empty()).then_some(text)
+}
+
+/// The declaration the Dart server shows in the first code block of a hover
+fn hover_signature(hover: &str) -> Option<String> {
+    let start = hover.find("```")?;
+    let block = &hover[start + 3..];
+    let block = &block[block.find('\n')? + 1..];
+    let end = block.find("```").unwrap_or(block.len());
+    let signature = block[..end]
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    (!signature.is_empty()).then_some(signature)
+}
+
+/// Type of the hovered element
+///
+/// Prefers the server's ``Type: `T` `` line and otherwise derives the type
+/// from the declaration in the hover.
+pub fn hover_type(hover: &str) -> Option<String> {
+    let explicit = hover.lines().find_map(|line| {
+        let rest = line.trim().strip_prefix("Type:")?.trim();
+        Some(rest.trim_matches('`').trim().to_string())
+    });
+    explicit.or_else(|| signature_type(&hover_signature(hover)?))
+}
+
+/// Modifiers that may precede the type in a declaration
+const MODIFIERS: &[&str] = &[
+    "abstract",
+    "const",
+    "covariant",
+    "external",
+    "factory",
+    "final",
+    "late",
+    "required",
+    "static",
+    "var",
+];
+
+/// Keywords that start a declaration without a value type
+const TYPE_DECLARATIONS: &[&str] = &[
+    "base",
+    "class",
+    "enum",
+    "extension",
+    "interface",
+    "mixin",
+    "sealed",
+    "typedef",
+];
+
+fn strip_modifiers(mut declaration: &str) -> &str {
+    while let Some((word, rest)) = declaration.split_once(' ') {
+        if !MODIFIERS.contains(&word) {
+            break;
+        }
+        declaration = rest.trim_start();
+    }
+    declaration
+}
+
+/// Type of the value a declaration such as `String? name` or
+/// `int length(String s)` introduces
+fn signature_type(signature: &str) -> Option<String> {
+    let declaration = strip_modifiers(signature.trim());
+    if TYPE_DECLARATIONS.contains(&declaration.split_whitespace().next()?) {
+        return None;
+    }
+
+    if let Some(getter) = find_top_level(declaration, " get ") {
+        return Some(declaration[..getter].trim().to_string());
+    }
+
+    if let Some(open) = find_top_level(declaration, "(") {
+        // A function or method: the return type precedes the name
+        let close = declaration.rfind(')')?;
+        let head = declaration[..open].trim_end();
+        let head = match head.strip_suffix('>') {
+            Some(_) => &head[..find_top_level(head, "<")?],
+            None => head,
+        };
+        let name_start = head.rfind(' ')?;
+        let return_type = head[..name_start].trim();
+        return Some(format!(
+            "{} Function{}",
+            return_type,
+            &declaration[open..=close]
+        ));
+    }
+
+    let name_start = find_last_top_level(declaration, ' ')?;
+    let declared = declaration[..name_start].trim();
+    (!declared.is_empty()).then(|| declared.to_string())
+}
+
+/// Kind of symbol a hover declaration describes
+fn symbol_kind(signature: &str) -> SymbolKind {
+    let declaration = strip_modifiers(signature);
+    // Class modifiers such as `sealed` or `interface` precede the keyword
+    let keyword = declaration
+        .split_whitespace()
+        .find(|word| !matches!(*word, "base" | "interface" | "sealed"))
+        .unwrap_or_default();
+    match keyword {
+        "class" => SymbolKind::Class,
+        "enum" => SymbolKind::Enum,
+        "mixin" => SymbolKind::Mixin,
+        "extension" => SymbolKind::Extension,
+        _ if find_top_level(declaration, " get ").is_some() => SymbolKind::Field,
+        _ if find_top_level(declaration, "(").is_some() => SymbolKind::Function,
+        _ => SymbolKind::Variable,
+    }
+}
+
+/// Parse a Dart type such as `Map<String, List<int>>?` or
+/// `void Function(String)`
+pub fn parse_type(text: &str) -> Option<TypeInfo> {
+    let text = text.trim();
+    let (text, nullable) = match text.strip_suffix('?') {
+        Some(inner) => (inner.trim_end(), true),
+        None => (text, false),
+    };
+    if text.is_empty() {
+        return None;
+    }
+
+    if let Some(at) = function_keyword(text) {
+        let open = at + text[at..].find('(')?;
+        let close = text.rfind(')')?;
+        let parameter_types = split_top_level(&text[open + 1..close])
+            .into_iter()
+            .filter_map(|p| parse_type(parameter_type(p)))
+            .collect();
+        return Some(TypeInfo {
+            name: "Function".to_string(),
+            is_nullable: nullable,
+            type_arguments: vec![],
+            is_function: true,
+            return_type: parse_type(&text[..at]).map(Box::new),
+            parameter_types,
+        });
+    }
+
+    let (name, type_arguments) = match text.find('<') {
+        Some(open) if text.ends_with('>') && !text.starts_with('(') => (
+            &text[..open],
+            split_top_level(&text[open + 1..text.len() - 1])
+                .into_iter()
+                .filter_map(parse_type)
+                .collect(),
+        ),
+        _ => (text, vec![]),
+    };
+
+    Some(TypeInfo {
+        name: name.to_string(),
+        // `dynamic` and `Null` admit null without a `?`
+        is_nullable: nullable || name == "dynamic" || name == "Null",
+        type_arguments,
+        is_function: false,
+        return_type: None,
+        parameter_types: vec![],
+    })
+}
+
+/// Offset of a top-level `Function` keyword in a type
+fn function_keyword(text: &str) -> Option<usize> {
+    let mut from = 0;
+    while let Some(found) = find_top_level(&text[from..], "Function") {
+        let at = from + found;
+        let before_ok = at == 0 || text[..at].ends_with(' ');
+        let after_ok = text[at + "Function".len()..].starts_with(['(', '<']);
+        if before_ok && after_ok {
+            return Some(at);
+        }
+        from = at + "Function".len();
+    }
+    None
+}
+
+/// Type of a parameter such as `{required int count = 0}`
+fn parameter_type(parameter: &str) -> &str {
+    let parameter = parameter.trim().trim_start_matches(['{', '[']);
+    let parameter = parameter.trim_end_matches(['}', ']']).trim();
+    let parameter = match find_top_level(parameter, "=") {
+        Some(default) => parameter[..default].trim(),
+        None => parameter,
+    };
+    let parameter = strip_modifiers(parameter);
+    match find_last_top_level(parameter, ' ') {
+        Some(name) => parameter[..name].trim(),
+        None => parameter,
+    }
+}
+
+fn depth_change(c: char) -> i32 {
+    match c {
+        '<' | '(' | '[' | '{' => 1,
+        '>' | ')' | ']' | '}' => -1,
+        _ => 0,
+    }
+}
+
+/// Offset of the first `pattern` outside any brackets
+fn find_top_level(text: &str, pattern: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        if depth == 0 && text[i..].starts_with(pattern) {
+            return Some(i);
+        }
+        depth += depth_change(c);
+    }
+    None
+}
+
+/// Offset of the last `c` outside any brackets
+fn find_last_top_level(text: &str, c: char) -> Option<usize> {
+    let mut depth = 0;
+    let mut last = None;
+    for (i, ch) in text.char_indices() {
+        if depth == 0 && ch == c {
+            last = Some(i);
+        }
+        depth += depth_change(ch);
+    }
+    last
+}
+
+/// Split a comma-separated list, ignoring commas inside brackets
+fn split_top_level(text: &str) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in text.char_indices() {
+        if depth == 0 && c == ',' {
+            parts.push(&text[start..i]);
+            start = i + 1;
+        }
+        depth += depth_change(c);
+    }
+    parts.push(&text[start..]);
+    parts
+        .into_iter()
+        .map(str::trim)
+        .filter(|p| !p.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_type_generics_and_nullability() {
+        let map = parse_type("Map<String, List<int?>>?").unwrap();
+        assert_eq!(map.name, "Map");
+        assert!(map.is_nullable);
+        assert_eq!(map.type_arguments.len(), 2);
+        let list = &map.type_arguments[1];
+        assert_eq!(list.name, "List");
+        assert!(!list.is_nullable);
+        assert!(list.type_arguments[0].is_nullable);
+
+        assert!(parse_type("dynamic").unwrap().is_nullable);
+        assert!(!parse_type("String").unwrap().is_nullable);
+        assert!(parse_type("").is_none());
+    }
+
+    #[test]
+    fn test_parse_function_type() {
+        let function = parse_type("Future<void> Function(String, {required int count})?").unwrap();
+        assert!(function.is_function);
+        assert!(function.is_nullable);
+        assert_eq!(function.return_type.unwrap().name, "Future");
+        let parameters: Vec<&str> = function
+            .parameter_types
+            .iter()
+            .map(|p| p.name.as_str())
+            .collect();
+        assert_eq!(parameters, vec!["String", "int"]);
+    }
+
+    #[test]
+    fn test_hover_type_from_signature() {
+        let hover = "```dart\nfinal String? name\n```\n*lib/main.dart*";
+        assert_eq!(hover_type(hover).as_deref(), Some("String?"));
+
+        let hover = "```dart\nList<int> get items\n```";
+        assert_eq!(hover_type(hover).as_deref(), Some("List<int>"));
+
+        let hover = "```dart\nint parse<T>(String source, {int? radix})\n```";
+        assert_eq!(
+            hover_type(hover).as_deref(),
+            Some("int Function(String source, {int? radix})")
+        );
+        assert!(parse_type(&hover_type(hover).unwrap()).unwrap().is_function);
+
+        assert_eq!(hover_type("```dart\nclass Foo\n```"), None);
+    }
+
+    #[test]
+    fn test_hover_type_prefers_type_line() {
+        let hover = "```dart\nvar count\n```\nType: `int?`\n\n---\nDocs";
+        assert_eq!(hover_type(hover).as_deref(), Some("int?"));
+    }
+
+    #[test]
+    fn test_hover_text_formats() {
+        let markup = json!({"contents": {"kind": "markdown", "value": "docs"}});
+        assert_eq!(hover_text(&markup).as_deref(), Some("docs"));
+
+        let marked = json!({"contents": [{"language": "dart", "value": "int x"}, "more"]});
+        assert_eq!(
+            hover_text(&marked).as_deref(),
+            Some("```dart\nint x\n```\n\nmore")
+        );
+
+        assert_eq!(hover_text(&Value::Null), None);
+    }
+
+    #[test]
+    fn test_symbol_kind_from_signature() {
+        assert_eq!(symbol_kind("abstract class Foo"), SymbolKind::Class);
+        assert_eq!(symbol_kind("enum Color"), SymbolKind::Enum);
+        assert_eq!(symbol_kind("void main()"), SymbolKind::Function);
+        assert_eq!(symbol_kind("int get length"), SymbolKind::Field);
+        assert_eq!(symbol_kind("final int count"), SymbolKind::Variable);
+    }
+
+    #[test]
+    fn test_map_diagnostic_converts_positions() {
+        let text = "var s = 'é';\nfoo();\n";
+        let diagnostic = json!({
+            "range": {
+                "start": {"line": 0, "character": 10},
+                "end": {"line": 1, "character": 3}
+            },
+            "severity": 1,
+            "code": "undefined_identifier",
+            "message": "Undefined name 'foo'"
+        });
+        let mapped = map_diagnostic(
+            Path::new("a.dart"),
+            &diagnostic,
+            Some(text),
+            PositionEncoding::Utf16,
+        );
+        assert_eq!(mapped.severity, DiagnosticSeverity::Error);
+        assert_eq!((mapped.start_line, mapped.start_column), (1, 12));
+        assert_eq!((mapped.end_line, mapped.end_column), (2, 4));
+        assert_eq!(mapped.code.as_deref(), Some("undefined_identifier"));
+    }
+
+    #[test]
+    fn test_identifier_at() {
+        let text = "void main() {\n  value!.length;\n}\n";
+        assert_eq!(identifier_at(text, 2, 3).as_deref(), Some("value"));
+        assert_eq!(identifier_at(text, 2, 6).as_deref(), Some("value"));
+        assert_eq!(identifier_at(text, 5, 1), None);
+    }
+}
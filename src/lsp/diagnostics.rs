@@ -0,0 +1,557 @@
+//! Per-document diagnostics merged from both analyzers
+//!
+//! The proxy keeps the latest diagnostics reported by dart-re-analyzer and by
+//! the Dart Analysis Server for every document, and publishes the merged set
+//! to the client whenever either side changes. The last published set is
+//! remembered so unchanged results are not sent twice.
+//!
+//! Everything is keyed by the canonical path from [`uri::cache_key`], so the
+//! same file reported under differently encoded URIs or through a symlink
+//! ends up in one entry. The URI a document was last referred to by is kept
+//! for publishing.
+//!
+//! Clients supporting LSP 3.17 pull diagnostics ask for the same merged sets
+//! with `textDocument/diagnostic` and `workspace/diagnostic` instead. Each
+//! report carries a `resultId` hashed from its diagnostics, so a document
+//! whose diagnostics did not change is answered with an `unchanged` report.
+//! In that mode changes are announced with `workspace/diagnostic/refresh`
+//! rather than pushed.
+
+use serde_json::{json, Value};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeSet, HashMap};
+use std::hash::{Hash, Hasher};
+
+use super::position::{self, PositionEncoding};
+use super::uri;
+use crate::error::{Diagnostic, Severity};
+
+/// Source name used on diagnostics produced by our rules
+pub const SOURCE: &str = "dart-re-analyzer";
+
+/// Convert one of our diagnostics to the LSP format
+///
+/// `text` is the content the diagnostic was reported against, used to
+/// convert byte columns into `encoding` units.
+pub fn to_lsp(diag: &Diagnostic, text: Option<&str>, encoding: PositionEncoding) -> Value {
+    let severity = match diag.severity {
+        Severity::Error => 1,   // Error
+        Severity::Warning => 2, // Warning
+        Severity::Info => 3,    // Information
+    };
+
+    json!({
+        "range": position::location_to_range(&diag.location, text, encoding),
+        "severity": severity,
+        "code": diag.rule_id,
+        "source": SOURCE,
+        "message": diag.message,
+    })
+}
+
+/// Id of the `workspace/diagnostic/refresh` requests sent to the client
+pub const REFRESH_REQUEST_ID: &str = "dart-re-analyzer/diagnosticRefresh";
+
+/// Whether the client asks for diagnostics instead of receiving them
+pub fn supports_pull(initialize_params: &Value) -> bool {
+    initialize_params
+        .pointer("/capabilities/textDocument/diagnostic")
+        .is_some_and(Value::is_object)
+}
+
+/// Whether the client re-pulls diagnostics when asked to
+pub fn supports_refresh(initialize_params: &Value) -> bool {
+    initialize_params.pointer("/capabilities/workspace/diagnostics/refreshSupport")
+        == Some(&json!(true))
+}
+
+/// The `diagnosticProvider` server capability
+pub fn provider_capability() -> Value {
+    json!({
+        "identifier": SOURCE,
+        // The Dart server's diagnostics depend on other files
+        "interFileDependencies": true,
+        "workspaceDiagnostics": true,
+    })
+}
+
+/// `workspace/diagnostic/refresh` request asking the client to pull again
+pub fn refresh_request() -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": REFRESH_REQUEST_ID,
+        "method": "workspace/diagnostic/refresh",
+    })
+}
+
+/// `resultId` identifying a set of diagnostics
+pub fn result_id(diagnostics: &[Value]) -> String {
+    let mut hasher = DefaultHasher::new();
+    for diagnostic in diagnostics {
+        diagnostic.to_string().hash(&mut hasher);
+    }
+    format!("{:016x}", hasher.finish())
+}
+
+/// Latest `publishDiagnostics` payload received from the Dart server
+struct DartDiagnostics {
+    version: Option<Value>,
+    diagnostics: Vec<Value>,
+}
+
+/// Diagnostics from both sources, keyed per document
+#[derive(Default)]
+pub struct DiagnosticsStore {
+    /// Our diagnostics from per-file rules
+    ours: HashMap<String, Vec<Diagnostic>>,
+    /// Our diagnostics from project rules, which edits to a single document
+    /// leave in place
+    project: HashMap<String, Vec<Diagnostic>>,
+    /// Dart Analysis Server diagnostics
+    dart: HashMap<String, DartDiagnostics>,
+    /// Diagnostics last sent to the client, or in pull mode, last changed to
+    published: HashMap<String, Vec<Value>>,
+    /// URI the client or server last used for each document
+    uris: HashMap<String, String>,
+    /// Whether the client pulls diagnostics
+    pull: bool,
+    /// Whether pulled diagnostics changed since the last refresh request
+    refresh_pending: bool,
+}
+
+impl DiagnosticsStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Let the client pull diagnostics instead of publishing them
+    pub fn set_pull(&mut self, pull: bool) {
+        self.pull = pull;
+    }
+
+    /// Whether the client pulls diagnostics
+    pub fn is_pull(&self) -> bool {
+        self.pull
+    }
+
+    /// Whether pulled diagnostics changed since the last call
+    pub fn take_refresh(&mut self) -> bool {
+        std::mem::take(&mut self.refresh_pending)
+    }
+
+    /// Remember the URI used to refer to a document
+    pub fn remember_uri(&mut self, key: &str, uri: &str) {
+        self.uris.insert(key.to_string(), uri.to_string());
+    }
+
+    /// URI to publish a document's diagnostics under
+    pub fn uri(&self, key: &str) -> Option<String> {
+        self.uris
+            .get(key)
+            .cloned()
+            .or_else(|| uri::path_to_uri(std::path::Path::new(key)))
+    }
+
+    /// Replace our diagnostics for a file
+    pub fn set_ours(&mut self, path: &str, diagnostics: Vec<Diagnostic>) {
+        if diagnostics.is_empty() {
+            self.ours.remove(path);
+        } else {
+            self.ours.insert(path.to_string(), diagnostics);
+        }
+    }
+
+    /// Our diagnostics for a file, from per-file and project rules
+    pub fn ours(&self, path: &str) -> Vec<Diagnostic> {
+        self.ours
+            .get(path)
+            .into_iter()
+            .chain(self.project.get(path))
+            .flatten()
+            .cloned()
+            .collect()
+    }
+
+    /// Replace our diagnostics for every file except those in `keep`
+    ///
+    /// Returns the paths whose diagnostics may have changed.
+    pub fn replace_ours(
+        &mut self,
+        results: HashMap<String, Vec<Diagnostic>>,
+        keep: &[String],
+    ) -> Vec<String> {
+        let mut changed: Vec<String> = self
+            .ours
+            .keys()
+            .filter(|path| !keep.contains(path))
+            .cloned()
+            .collect();
+        self.ours.retain(|path, _| keep.contains(path));
+
+        for (path, diagnostics) in results {
+            if keep.contains(&path) {
+                continue;
+            }
+            if !changed.contains(&path) {
+                changed.push(path.clone());
+            }
+            self.set_ours(&path, diagnostics);
+        }
+
+        changed
+    }
+
+    /// Replace the diagnostics of project rules for every file
+    ///
+    /// Returns the paths whose diagnostics may have changed.
+    pub fn replace_project(&mut self, results: HashMap<String, Vec<Diagnostic>>) -> Vec<String> {
+        let keys: BTreeSet<String> = self.project.keys().chain(results.keys()).cloned().collect();
+        self.project = results;
+        self.project
+            .retain(|_, diagnostics| !diagnostics.is_empty());
+        keys.into_iter().collect()
+    }
+
+    /// Number of files with diagnostics from our rules
+    pub fn files_with_diagnostics(&self) -> usize {
+        self.ours
+            .keys()
+            .chain(self.project.keys())
+            .collect::<BTreeSet<_>>()
+            .len()
+    }
+
+    /// Record a `publishDiagnostics` payload from the Dart server
+    pub fn set_dart(
+        &mut self,
+        key: &str,
+        uri: &str,
+        version: Option<Value>,
+        diagnostics: Vec<Value>,
+    ) {
+        self.remember_uri(key, uri);
+        self.dart.insert(
+            key.to_string(),
+            DartDiagnostics {
+                version,
+                diagnostics,
+            },
+        );
+    }
+
+    /// Forget every Dart server diagnostic, returning the affected keys
+    ///
+    /// Used when the server exits: a restarted server only republishes
+    /// files that still have problems.
+    pub fn clear_dart(&mut self) -> Vec<String> {
+        self.dart.drain().map(|(key, _)| key).collect()
+    }
+
+    /// Merge both sources for a document, without duplicates
+    pub fn merged(&self, key: &str, to_lsp: impl Fn(&Diagnostic) -> Value) -> Vec<Value> {
+        let mut merged: Vec<Value> = Vec::new();
+
+        let dart = self
+            .dart
+            .get(key)
+            .map(|d| d.diagnostics.as_slice())
+            .unwrap_or_default();
+        // Never carry over our own diagnostics if they come back from the server
+        let dart = dart
+            .iter()
+            .filter(|d| d.get("source").and_then(|s| s.as_str()) != Some(SOURCE))
+            .cloned();
+
+        for diagnostic in dart.chain(self.ours(key).iter().map(to_lsp)) {
+            if !merged.contains(&diagnostic) {
+                merged.push(diagnostic);
+            }
+        }
+
+        merged
+    }
+
+    /// Documents with diagnostics from either source
+    pub fn keys(&self) -> Vec<String> {
+        let keys: BTreeSet<&String> = self
+            .ours
+            .keys()
+            .chain(self.project.keys())
+            .chain(self.dart.keys())
+            .collect();
+        keys.into_iter().cloned().collect()
+    }
+
+    /// `DocumentDiagnosticReport` for a document
+    ///
+    /// Unchanged if the merged set still has `previous_result_id`.
+    pub fn report(
+        &self,
+        key: &str,
+        previous_result_id: Option<&str>,
+        to_lsp: impl Fn(&Diagnostic) -> Value,
+    ) -> Value {
+        let merged = self.merged(key, to_lsp);
+        let result_id = result_id(&merged);
+        if previous_result_id == Some(result_id.as_str()) {
+            json!({"kind": "unchanged", "resultId": result_id})
+        } else {
+            json!({"kind": "full", "resultId": result_id, "items": merged})
+        }
+    }
+
+    /// `WorkspaceDocumentDiagnosticReport` for a document, see [`report`](Self::report)
+    pub fn workspace_report(
+        &self,
+        key: &str,
+        previous_result_id: Option<&str>,
+        to_lsp: impl Fn(&Diagnostic) -> Value,
+    ) -> Option<Value> {
+        let mut report = self.report(key, previous_result_id, to_lsp);
+        report["uri"] = json!(self.uri(key)?);
+        report["version"] = self
+            .dart
+            .get(key)
+            .and_then(|d| d.version.clone())
+            .unwrap_or(Value::Null);
+        Some(report)
+    }
+
+    /// Build the `publishDiagnostics` params for a document if the merged
+    /// set differs from what the client last received
+    ///
+    /// In pull mode nothing is published; a change marks a refresh as
+    /// pending instead, see [`take_refresh`](Self::take_refresh).
+    pub fn publish(&mut self, key: &str, to_lsp: impl Fn(&Diagnostic) -> Value) -> Option<Value> {
+        let merged = self.merged(key, to_lsp);
+
+        let previous = self
+            .published
+            .get(key)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        if previous == merged.as_slice() {
+            return None;
+        }
+
+        let uri = self.uri(key)?;
+        let mut params = json!({
+            "uri": uri,
+            "diagnostics": merged,
+        });
+        if let Some(version) = self.dart.get(key).and_then(|d| d.version.clone()) {
+            params["version"] = version;
+        }
+
+        if merged.is_empty() {
+            self.published.remove(key);
+        } else {
+            self.published.insert(key.to_string(), merged);
+        }
+
+        if self.pull {
+            self.refresh_pending = true;
+            return None;
+        }
+        Some(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{Location, RuleCategory};
+
+    fn ours(rule_id: &str) -> Diagnostic {
+        Diagnostic::new(
+            rule_id,
+            "message",
+            Severity::Warning,
+            RuleCategory::Style,
+            Location {
+                file: "/a.dart".to_string(),
+                line: 1,
+                column: 1,
+                end_line: None,
+                end_column: None,
+            },
+        )
+    }
+
+    fn to_lsp(diag: &Diagnostic) -> Value {
+        json!({"code": diag.rule_id, "source": SOURCE})
+    }
+
+    #[test]
+    fn test_publish_merges_both_sources() {
+        let mut store = DiagnosticsStore::new();
+        store.set_ours("/a.dart", vec![ours("rule_a")]);
+        store.set_dart(
+            "/a.dart",
+            "file:///a.dart",
+            Some(json!(3)),
+            vec![json!({"code": "dart_error", "source": "dart"})],
+        );
+
+        let params = store.publish("/a.dart", to_lsp).unwrap();
+        assert_eq!(params["uri"], "file:///a.dart");
+        assert_eq!(params["version"], 3);
+        let diagnostics = params["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0]["code"], "dart_error");
+        assert_eq!(diagnostics[1]["code"], "rule_a");
+    }
+
+    #[test]
+    fn test_publish_without_dart_diagnostics() {
+        let mut store = DiagnosticsStore::new();
+        store.set_ours("/a.dart", vec![ours("rule_a")]);
+
+        let params = store.publish("/a.dart", to_lsp).unwrap();
+        assert_eq!(params["diagnostics"].as_array().unwrap().len(), 1);
+        assert!(params.get("version").is_none());
+    }
+
+    #[test]
+    fn test_publish_skips_unchanged() {
+        let mut store = DiagnosticsStore::new();
+        store.set_ours("/a.dart", vec![ours("rule_a")]);
+
+        assert!(store.publish("/a.dart", to_lsp).is_some());
+        assert!(store.publish("/a.dart", to_lsp).is_none());
+
+        // Clearing our diagnostics publishes an empty set once
+        store.set_ours("/a.dart", vec![]);
+        let params = store.publish("/a.dart", to_lsp).unwrap();
+        assert!(params["diagnostics"].as_array().unwrap().is_empty());
+        assert!(store.publish("/a.dart", to_lsp).is_none());
+    }
+
+    #[test]
+    fn test_clear_dart_republishes_ours_only() {
+        let mut store = DiagnosticsStore::new();
+        store.set_ours("/a.dart", vec![ours("rule_a")]);
+        store.set_dart(
+            "/a.dart",
+            "file:///a.dart",
+            None,
+            vec![json!({"code": "dart_error", "source": "dart"})],
+        );
+        store.publish("/a.dart", to_lsp).unwrap();
+
+        assert_eq!(store.clear_dart(), vec!["/a.dart"]);
+        let params = store.publish("/a.dart", to_lsp).unwrap();
+        let diagnostics = params["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0]["code"], "rule_a");
+    }
+
+    #[test]
+    fn test_publish_uses_remembered_uri() {
+        let mut store = DiagnosticsStore::new();
+        store.set_ours("/work/my app/a.dart", vec![ours("rule_a")]);
+        store.remember_uri("/work/my app/a.dart", "file:///work/my%20app/a.dart");
+
+        let params = store.publish("/work/my app/a.dart", to_lsp).unwrap();
+        assert_eq!(params["uri"], "file:///work/my%20app/a.dart");
+    }
+
+    #[test]
+    fn test_merged_removes_duplicates() {
+        let mut store = DiagnosticsStore::new();
+        store.set_ours("/a.dart", vec![ours("rule_a"), ours("rule_a")]);
+        // A stale copy of our own diagnostic echoed back by the server
+        store.set_dart(
+            "/a.dart",
+            "file:///a.dart",
+            None,
+            vec![json!({"code": "rule_a", "source": SOURCE})],
+        );
+
+        let merged = store.merged("/a.dart", to_lsp);
+        assert_eq!(merged.len(), 1);
+    }
+
+    #[test]
+    fn test_pull_reports_use_result_ids() {
+        let mut store = DiagnosticsStore::new();
+        store.set_pull(true);
+        store.set_ours("/a.dart", vec![ours("rule_a")]);
+
+        // Changes are not pushed but mark a refresh as pending
+        assert!(store.publish("/a.dart", to_lsp).is_none());
+        assert!(store.take_refresh());
+        assert!(!store.take_refresh());
+
+        let full = store.report("/a.dart", None, to_lsp);
+        assert_eq!(full["kind"], "full");
+        assert_eq!(full["items"].as_array().unwrap().len(), 1);
+
+        let result_id = full["resultId"].as_str().unwrap();
+        let unchanged = store.report("/a.dart", Some(result_id), to_lsp);
+        assert_eq!(
+            unchanged,
+            json!({"kind": "unchanged", "resultId": result_id})
+        );
+
+        store.set_ours("/a.dart", vec![]);
+        let cleared = store
+            .workspace_report("/a.dart", Some(result_id), to_lsp)
+            .unwrap();
+        assert_eq!(cleared["kind"], "full");
+        assert_eq!(cleared["uri"], "file:///a.dart");
+        assert_eq!(cleared["items"], json!([]));
+        assert_eq!(cleared["version"], Value::Null);
+    }
+
+    #[test]
+    fn test_replace_ours_keeps_open_documents() {
+        let mut store = DiagnosticsStore::new();
+        store.set_ours("/open.dart", vec![ours("buffer")]);
+        store.set_ours("/stale.dart", vec![ours("stale")]);
+
+        let mut results = HashMap::new();
+        results.insert("/open.dart".to_string(), vec![ours("disk")]);
+        results.insert("/new.dart".to_string(), vec![ours("new")]);
+
+        let mut changed = store.replace_ours(results, &["/open.dart".to_string()]);
+        changed.sort();
+
+        assert_eq!(changed, vec!["/new.dart", "/stale.dart"]);
+        assert_eq!(store.ours("/open.dart")[0].rule_id, "buffer");
+        assert!(store.ours("/stale.dart").is_empty());
+        assert_eq!(store.files_with_diagnostics(), 2);
+    }
+
+    #[test]
+    fn test_project_diagnostics_survive_document_edits() {
+        let mut store = DiagnosticsStore::new();
+        store.set_ours("/a.dart", vec![ours("rule_a")]);
+        let mut results = HashMap::new();
+        results.insert("/a.dart".to_string(), vec![ours("import_cycles")]);
+        results.insert("/b.dart".to_string(), vec![ours("import_cycles")]);
+
+        assert_eq!(store.replace_project(results), vec!["/a.dart", "/b.dart"]);
+        assert_eq!(store.files_with_diagnostics(), 2);
+        assert_eq!(store.keys(), vec!["/a.dart", "/b.dart"]);
+
+        // Re-analyzing the document replaces only its per-file diagnostics
+        store.set_ours("/a.dart", vec![]);
+        let rules: Vec<String> = store
+            .ours("/a.dart")
+            .into_iter()
+            .map(|d| d.rule_id)
+            .collect();
+        assert_eq!(rules, vec!["import_cycles"]);
+        assert_eq!(
+            store.publish("/a.dart", to_lsp).unwrap()["diagnostics"][0]["code"],
+            "import_cycles"
+        );
+
+        // Files whose project diagnostics are gone are reported as changed
+        let mut results = HashMap::new();
+        results.insert("/a.dart".to_string(), vec![ours("import_cycles")]);
+        assert_eq!(store.replace_project(results), vec!["/a.dart", "/b.dart"]);
+        assert!(store.ours("/b.dart").is_empty());
+    }
+}
@@ -0,0 +1,278 @@
+//! In-memory store of documents opened by the editor
+//!
+//! Tracks `textDocument/didOpen`, `didChange` and `didClose` so that
+//! diagnostics reflect unsaved buffers rather than the file on disk. Each
+//! document keeps an [`IncrementalParser`] so range edits only re-parse the
+//! affected part of the tree.
+
+use anyhow::{Context, Result};
+use lsp_types::TextDocumentContentChangeEvent;
+use std::collections::HashMap;
+
+use super::position::{position_to_offset, PositionEncoding};
+use crate::treesitter::{Edit, IncrementalParser};
+
+/// A document currently open in the editor
+pub struct Document {
+    pub version: i32,
+    parser: IncrementalParser,
+}
+
+impl Document {
+    fn new(version: i32, text: &str) -> Result<Self> {
+        let mut parser = IncrementalParser::new()?;
+        parser.parse(text)?;
+        Ok(Self { version, parser })
+    }
+
+    /// Current buffer contents
+    pub fn text(&self) -> &str {
+        self.parser.source()
+    }
+
+    /// Current parse tree of the buffer
+    pub fn tree(&self) -> Option<&tree_sitter::Tree> {
+        self.parser.tree()
+    }
+
+    /// Apply a batch of content changes in order and re-parse
+    ///
+    /// Range changes are converted into tree-sitter edits and applied
+    /// incrementally. A change without a range replaces the whole buffer.
+    fn apply_changes(
+        &mut self,
+        changes: &[TextDocumentContentChangeEvent],
+        encoding: PositionEncoding,
+    ) -> Result<()> {
+        let mut text = self.parser.source().to_string();
+        let mut edits = Vec::new();
+
+        for change in changes {
+            match change.range {
+                Some(range) => {
+                    let start = position_to_offset(&text, range.start, encoding);
+                    let old_end = position_to_offset(&text, range.end, encoding).max(start);
+
+                    let start_position = offset_to_point(&text, start);
+                    let old_end_position = offset_to_point(&text, old_end);
+
+                    text.replace_range(start..old_end, &change.text);
+
+                    let new_end = start + change.text.len();
+                    edits.push(Edit {
+                        start_byte: start,
+                        old_end_byte: old_end,
+                        new_end_byte: new_end,
+                        start_position,
+                        old_end_position,
+                        new_end_position: offset_to_point(&text, new_end),
+                    });
+                }
+                None => {
+                    // Full document sync: earlier edits no longer apply
+                    text = change.text.clone();
+                    edits.clear();
+                    self.parser.parse(&text)?;
+                }
+            }
+        }
+
+        if !edits.is_empty() {
+            self.parser.reparse_with_edits(&edits, &text)?;
+        } else if text != self.parser.source() {
+            self.parser.parse(&text)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Open documents keyed by URI
+#[derive(Default)]
+pub struct DocumentStore {
+    documents: HashMap<String, Document>,
+    /// Encoding of the `character` offsets in change ranges
+    encoding: PositionEncoding,
+}
+
+impl DocumentStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Encoding negotiated for the session
+    pub fn position_encoding(&self) -> PositionEncoding {
+        self.encoding
+    }
+
+    pub fn set_position_encoding(&mut self, encoding: PositionEncoding) {
+        self.encoding = encoding;
+    }
+
+    /// Start tracking a document with its initial contents
+    pub fn open(&mut self, uri: &str, version: i32, text: &str) -> Result<&Document> {
+        let document = Document::new(version, text)?;
+        self.documents.insert(uri.to_string(), document);
+        Ok(&self.documents[uri])
+    }
+
+    /// Apply changes to an open document
+    pub fn change(
+        &mut self,
+        uri: &str,
+        version: i32,
+        changes: &[TextDocumentContentChangeEvent],
+    ) -> Result<&Document> {
+        let document = self
+            .documents
+            .get_mut(uri)
+            .with_context(|| format!("Document not open: {}", uri))?;
+        document.apply_changes(changes, self.encoding)?;
+        document.version = version;
+        Ok(document)
+    }
+
+    /// Stop tracking a document
+    pub fn close(&mut self, uri: &str) -> Option<Document> {
+        self.documents.remove(uri)
+    }
+
+    pub fn get(&self, uri: &str) -> Option<&Document> {
+        self.documents.get(uri)
+    }
+
+    pub fn is_open(&self, uri: &str) -> bool {
+        self.documents.contains_key(uri)
+    }
+
+    pub fn uris(&self) -> impl Iterator<Item = &String> {
+        self.documents.keys()
+    }
+}
+
+/// Convert a byte offset into a tree-sitter (row, byte column) pair
+fn offset_to_point(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset];
+    let row = before.matches('\n').count();
+    let column = before.rfind('\n').map(|i| offset - i - 1).unwrap_or(offset);
+    (row, column)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lsp_types::{Position, Range};
+
+    fn change(start: (u32, u32), end: (u32, u32), text: &str) -> TextDocumentContentChangeEvent {
+        TextDocumentContentChangeEvent {
+            range: Some(Range::new(
+                Position::new(start.0, start.1),
+                Position::new(end.0, end.1),
+            )),
+            range_length: None,
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_open_and_close() {
+        let mut store = DocumentStore::new();
+        store.open("file:///a.dart", 1, "class A {}").unwrap();
+        assert!(store.is_open("file:///a.dart"));
+        assert_eq!(store.get("file:///a.dart").unwrap().text(), "class A {}");
+
+        store.close("file:///a.dart");
+        assert!(!store.is_open("file:///a.dart"));
+    }
+
+    #[test]
+    fn test_incremental_changes() {
+        let mut store = DocumentStore::new();
+        store
+            .open("file:///a.dart", 1, "class A {}\nclass B {}\n")
+            .unwrap();
+
+        let document = store
+            .change(
+                "file:///a.dart",
+                2,
+                &[
+                    change((0, 7), (0, 7), " extends Object"),
+                    change((1, 6), (1, 7), "Renamed"),
+                ],
+            )
+            .unwrap();
+
+        assert_eq!(document.version, 2);
+        assert_eq!(
+            document.text(),
+            "class A extends Object {}\nclass Renamed {}\n"
+        );
+
+        let tree = document.tree().unwrap();
+        assert!(!tree.root_node().has_error());
+        let classes = crate::treesitter::extract_classes(tree, document.text());
+        assert_eq!(classes[1].name, "Renamed");
+    }
+
+    #[test]
+    fn test_multiline_change() {
+        let mut store = DocumentStore::new();
+        store.open("file:///a.dart", 1, "void main() {}\n").unwrap();
+
+        let document = store
+            .change(
+                "file:///a.dart",
+                2,
+                &[change((0, 13), (0, 13), "\n  print('hi');\n")],
+            )
+            .unwrap();
+
+        assert_eq!(document.text(), "void main() {\n  print('hi');\n}\n");
+        assert!(!document.tree().unwrap().root_node().has_error());
+    }
+
+    #[test]
+    fn test_full_sync_change() {
+        let mut store = DocumentStore::new();
+        store.open("file:///a.dart", 1, "class A {}").unwrap();
+
+        let document = store
+            .change(
+                "file:///a.dart",
+                2,
+                &[TextDocumentContentChangeEvent {
+                    range: None,
+                    range_length: None,
+                    text: "class B {}".to_string(),
+                }],
+            )
+            .unwrap();
+
+        assert_eq!(document.text(), "class B {}");
+    }
+
+    #[test]
+    fn test_change_unopened_document() {
+        let mut store = DocumentStore::new();
+        assert!(store.change("file:///missing.dart", 1, &[]).is_err());
+    }
+
+    #[test]
+    fn test_change_uses_negotiated_encoding() {
+        // '😀' is two UTF-16 code units and four UTF-8 bytes
+        let mut store = DocumentStore::new();
+        store.open("file:///a.dart", 1, "var s = '😀';\n").unwrap();
+
+        let document = store
+            .change("file:///a.dart", 2, &[change((0, 12), (0, 13), ",")])
+            .unwrap();
+        assert_eq!(document.text(), "var s = '😀',\n");
+
+        store.set_position_encoding(PositionEncoding::Utf8);
+        let document = store
+            .change("file:///a.dart", 3, &[change((0, 14), (0, 15), ";")])
+            .unwrap();
+        assert_eq!(document.text(), "var s = '😀';\n");
+    }
+}
@@ -0,0 +1,395 @@
+//! LSP base protocol framing
+//!
+//! Every LSP message is a header section followed by a JSON body:
+//!
+//! ```text
+//! Content-Length: 52\r\n
+//! Content-Type: application/vscode-jsonrpc; charset=utf-8\r\n
+//! \r\n
+//! {"jsonrpc":"2.0","id":1,"method":"shutdown"}
+//! ```
+//!
+//! [`LspCodec`] decodes and encodes such frames for tokio's
+//! [`FramedRead`](tokio_util::codec::FramedRead) and
+//! [`FramedWrite`](tokio_util::codec::FramedWrite). Header names are
+//! matched case-insensitively, unknown headers are ignored, and messages
+//! larger than the codec's limit are rejected before their body is buffered.
+//! [`FrameReader`] and [`write_message`] use the same codec over blocking
+//! readers and writers, and [`spawn_writer`] hands what is written to a
+//! task that writes it to an async writer.
+
+use bytes::{Buf, BytesMut};
+use std::io::{Read, Write};
+use thiserror::Error;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc::{self, UnboundedSender};
+use tokio::task::JoinHandle;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Largest message body accepted by default
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024 * 1024;
+
+/// Largest header section accepted
+const MAX_HEADER_SIZE: usize = 8 * 1024;
+
+/// Errors reading or writing LSP frames
+#[derive(Debug, Error)]
+pub enum FramingError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Missing Content-Length header")]
+    MissingContentLength,
+
+    #[error("Invalid header: {0}")]
+    InvalidHeader(String),
+
+    #[error("Unsupported charset in Content-Type: {0}")]
+    UnsupportedCharset(String),
+
+    #[error("Message of {size} bytes exceeds the limit of {limit} bytes")]
+    TooLarge { size: usize, limit: usize },
+
+    #[error("Header section exceeds {0} bytes")]
+    HeadersTooLarge(usize),
+
+    #[error("Message body is not valid UTF-8")]
+    InvalidUtf8,
+}
+
+/// Codec for `Content-Length` framed LSP messages, yielding their bodies
+#[derive(Debug, Clone)]
+pub struct LspCodec {
+    max_size: usize,
+    /// Length of the body being read, once its headers were parsed
+    content_length: Option<usize>,
+}
+
+impl Default for LspCodec {
+    fn default() -> Self {
+        Self::with_max_size(MAX_MESSAGE_SIZE)
+    }
+}
+
+impl LspCodec {
+    /// Codec rejecting message bodies over `max_size` bytes
+    pub fn with_max_size(max_size: usize) -> Self {
+        Self {
+            max_size,
+            content_length: None,
+        }
+    }
+}
+
+/// Length of the header section in `buf` including the blank line that
+/// ends it, if it is complete
+fn header_section_len(buf: &[u8]) -> Option<usize> {
+    let mut line_start = 0;
+    for (i, byte) in buf.iter().enumerate() {
+        if *byte != b'\n' {
+            continue;
+        }
+        let line = &buf[line_start..i];
+        if line.is_empty() || line == b"\r" {
+            return Some(i + 1);
+        }
+        line_start = i + 1;
+    }
+    None
+}
+
+/// Body length from a complete header section
+fn parse_headers(headers: &[u8]) -> Result<usize, FramingError> {
+    let headers = std::str::from_utf8(headers)
+        .map_err(|_| FramingError::InvalidHeader("not valid UTF-8".to_string()))?;
+    let mut content_length = None;
+
+    for line in headers.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        let (name, value) = line
+            .split_once(':')
+            .ok_or_else(|| FramingError::InvalidHeader(line.to_string()))?;
+        let value = value.trim();
+
+        if name.trim().eq_ignore_ascii_case("Content-Length") {
+            let length = value
+                .parse()
+                .map_err(|_| FramingError::InvalidHeader(line.to_string()))?;
+            content_length = Some(length);
+        } else if name.trim().eq_ignore_ascii_case("Content-Type") {
+            let charset = value.split(';').skip(1).find_map(|param| {
+                let (key, value) = param.split_once('=')?;
+                key.trim()
+                    .eq_ignore_ascii_case("charset")
+                    .then(|| value.trim().trim_matches('"'))
+            });
+            // `utf8` is accepted for backwards compatibility, as the spec asks
+            if let Some(charset) = charset {
+                if !charset.eq_ignore_ascii_case("utf-8") && !charset.eq_ignore_ascii_case("utf8") {
+                    return Err(FramingError::UnsupportedCharset(charset.to_string()));
+                }
+            }
+        }
+    }
+
+    content_length.ok_or(FramingError::MissingContentLength)
+}
+
+impl Decoder for LspCodec {
+    type Item = String;
+    type Error = FramingError;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<String>, FramingError> {
+        let length = match self.content_length {
+            Some(length) => length,
+            None => {
+                let Some(header_len) = header_section_len(src) else {
+                    if src.len() > MAX_HEADER_SIZE {
+                        return Err(FramingError::HeadersTooLarge(MAX_HEADER_SIZE));
+                    }
+                    return Ok(None);
+                };
+                if header_len > MAX_HEADER_SIZE {
+                    return Err(FramingError::HeadersTooLarge(MAX_HEADER_SIZE));
+                }
+                let length = parse_headers(&src[..header_len])?;
+                if length > self.max_size {
+                    return Err(FramingError::TooLarge {
+                        size: length,
+                        limit: self.max_size,
+                    });
+                }
+                src.advance(header_len);
+                self.content_length = Some(length);
+                length
+            }
+        };
+
+        if src.len() < length {
+            src.reserve(length - src.len());
+            return Ok(None);
+        }
+        self.content_length = None;
+        let body = src.split_to(length);
+        String::from_utf8(body.to_vec())
+            .map(Some)
+            .map_err(|_| FramingError::InvalidUtf8)
+    }
+
+    fn decode_eof(&mut self, src: &mut BytesMut) -> Result<Option<String>, FramingError> {
+        match self.decode(src)? {
+            Some(message) => Ok(Some(message)),
+            None if src.is_empty() && self.content_length.is_none() => Ok(None),
+            None => Err(FramingError::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "stream ended in the middle of a message",
+            ))),
+        }
+    }
+}
+
+impl<T: AsRef<str>> Encoder<T> for LspCodec {
+    type Error = FramingError;
+
+    fn encode(&mut self, content: T, dst: &mut BytesMut) -> Result<(), FramingError> {
+        let content = content.as_ref();
+        let header = format!("Content-Length: {}\r\n\r\n", content.len());
+        dst.reserve(header.len() + content.len());
+        dst.extend_from_slice(header.as_bytes());
+        dst.extend_from_slice(content.as_bytes());
+        Ok(())
+    }
+}
+
+/// Reads LSP messages from a blocking reader with [`LspCodec`]
+pub struct FrameReader<R> {
+    reader: R,
+    codec: LspCodec,
+    buf: BytesMut,
+}
+
+impl<R: Read> FrameReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self::with_codec(reader, LspCodec::default())
+    }
+
+    pub fn with_codec(reader: R, codec: LspCodec) -> Self {
+        Self {
+            reader,
+            codec,
+            buf: BytesMut::new(),
+        }
+    }
+
+    /// The next message's body, or `None` at the end of the stream
+    pub fn read_message(&mut self) -> Result<Option<String>, FramingError> {
+        let mut chunk = [0u8; 8192];
+        loop {
+            if let Some(message) = self.codec.decode(&mut self.buf)? {
+                return Ok(Some(message));
+            }
+            let read = self.reader.read(&mut chunk)?;
+            if read == 0 {
+                return self.codec.decode_eof(&mut self.buf);
+            }
+            self.buf.extend_from_slice(&chunk[..read]);
+        }
+    }
+}
+
+/// Write one framed message to a blocking writer and flush it
+pub fn write_message<W: Write>(writer: &mut W, content: &str) -> Result<(), FramingError> {
+    let mut frame = BytesMut::new();
+    LspCodec::default().encode(content, &mut frame)?;
+    writer.write_all(&frame)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// A blocking writer that sends what it buffered to the task of
+/// [`spawn_writer`] on every flush, without waiting for it to be written
+pub struct ChannelWriter {
+    buf: Vec<u8>,
+    tx: UnboundedSender<Vec<u8>>,
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        self.tx
+            .send(std::mem::take(&mut self.buf))
+            .map_err(|_| std::io::Error::from(std::io::ErrorKind::BrokenPipe))
+    }
+}
+
+/// Write everything flushed to the returned [`ChannelWriter`] to `writer`,
+/// in order, from a task of its own
+///
+/// A slow reader on the other end only holds up that task. It ends once the
+/// writer is dropped and everything sent was written, or on the first
+/// write error, after which flushes fail.
+pub fn spawn_writer<W>(mut writer: W) -> (ChannelWriter, JoinHandle<std::io::Result<()>>)
+where
+    W: AsyncWrite + Unpin + Send + 'static,
+{
+    let (tx, mut rx) = mpsc::unbounded_channel::<Vec<u8>>();
+    let task = tokio::spawn(async move {
+        while let Some(bytes) = rx.recv().await {
+            writer.write_all(&bytes).await?;
+            writer.flush().await?;
+        }
+        Ok(())
+    });
+    (
+        ChannelWriter {
+            buf: Vec::new(),
+            tx,
+        },
+        task,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decode_all(input: &[u8], codec: &mut LspCodec) -> Result<Vec<String>, FramingError> {
+        let mut buf = BytesMut::from(input);
+        let mut messages = Vec::new();
+        while let Some(message) = codec.decode_eof(&mut buf)? {
+            messages.push(message);
+        }
+        Ok(messages)
+    }
+
+    #[test]
+    fn test_headers_are_case_insensitive() {
+        let input = b"content-length: 2\r\nCONTENT-TYPE: application/vscode-jsonrpc; charset=utf-8\r\n\r\n{}\
+                      Content-Length:2\r\n\r\n[]";
+        let messages = decode_all(input, &mut LspCodec::default()).unwrap();
+        assert_eq!(messages, vec!["{}", "[]"]);
+    }
+
+    #[test]
+    fn test_content_type_charset() {
+        let input = b"Content-Type: application/vscode-jsonrpc; charset=utf8\r\nContent-Length: 2\r\n\r\n{}";
+        assert_eq!(
+            decode_all(input, &mut LspCodec::default()).unwrap(),
+            vec!["{}"]
+        );
+
+        let input = b"Content-Length: 2\r\nContent-Type: text/plain; charset=latin1\r\n\r\n{}";
+        assert!(matches!(
+            decode_all(input, &mut LspCodec::default()),
+            Err(FramingError::UnsupportedCharset(charset)) if charset == "latin1"
+        ));
+    }
+
+    #[test]
+    fn test_rejects_invalid_frames() {
+        let missing = decode_all(b"Content-Type: x\r\n\r\n{}", &mut LspCodec::default());
+        assert!(matches!(missing, Err(FramingError::MissingContentLength)));
+
+        let invalid = decode_all(b"Content-Length: many\r\n\r\n{}", &mut LspCodec::default());
+        assert!(matches!(invalid, Err(FramingError::InvalidHeader(_))));
+
+        let endless = vec![b'x'; MAX_HEADER_SIZE + 1];
+        let endless = decode_all(&endless, &mut LspCodec::default());
+        assert!(matches!(endless, Err(FramingError::HeadersTooLarge(_))));
+    }
+
+    #[test]
+    fn test_rejects_oversized_messages_before_reading_them() {
+        let mut codec = LspCodec::with_max_size(4);
+        let mut buf = BytesMut::from(&b"Content-Length: 5\r\n\r\n"[..]);
+        assert!(matches!(
+            codec.decode(&mut buf),
+            Err(FramingError::TooLarge { size: 5, limit: 4 })
+        ));
+    }
+
+    #[test]
+    fn test_frame_reader_and_writer() {
+        let mut framed = Vec::new();
+        write_message(&mut framed, r#"{"id":1}"#).unwrap();
+        write_message(&mut framed, "ünïcödé").unwrap();
+
+        let mut reader = FrameReader::new(&framed[..]);
+        assert_eq!(reader.read_message().unwrap().unwrap(), r#"{"id":1}"#);
+        assert_eq!(reader.read_message().unwrap().unwrap(), "ünïcödé");
+        assert!(reader.read_message().unwrap().is_none());
+
+        // A stream cut off mid-message is an error, not a clean end
+        let mut reader = FrameReader::new(&framed[..framed.len() - 1]);
+        reader.read_message().unwrap();
+        assert!(reader.read_message().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_spawn_writer_writes_in_order_after_the_writer_is_dropped() {
+        let (client, mut server) = tokio::io::duplex(16);
+        let (mut writer, task) = spawn_writer(client);
+        write_message(&mut writer, r#"{"id":1}"#).unwrap();
+        // Larger than the pipe, so it's written while the reader catches up
+        let long = "x".repeat(100);
+        write_message(&mut writer, &long).unwrap();
+        drop(writer);
+
+        let mut framed = Vec::new();
+        tokio::io::AsyncReadExt::read_to_end(&mut server, &mut framed)
+            .await
+            .unwrap();
+        task.await.unwrap().unwrap();
+
+        let mut reader = FrameReader::new(&framed[..]);
+        assert_eq!(reader.read_message().unwrap().unwrap(), r#"{"id":1}"#);
+        assert_eq!(reader.read_message().unwrap().unwrap(), long);
+        assert!(reader.read_message().unwrap().is_none());
+    }
+}
@@ -0,0 +1,245 @@
+//! Dart Analysis Server process lifecycle
+//!
+//! The proxy forwards `shutdown` and `exit` to the Dart server and kills it
+//! if it does not comply in time. When the server exits on its own, the
+//! proxy restarts it with exponential backoff and replays the session so far
+//! (`initialize`, `initialized` and a `didOpen` for every open document), so
+//! the editor never notices. After too many failed attempts the proxy gives
+//! up and keeps serving our own diagnostics.
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::documents::DocumentStore;
+
+/// How long the Dart server gets to answer `shutdown`
+pub const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How long the Dart server gets to exit after `exit`
+pub const EXIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// JSON-RPC `RequestFailed` error code
+pub const REQUEST_FAILED: i64 = -32803;
+
+/// Exponential backoff between restarts of the Dart server
+pub struct RestartPolicy {
+    max_attempts: u32,
+    initial_delay: Duration,
+    /// A server that stayed up this long starts a fresh series of attempts
+    stable_after: Duration,
+    attempts: u32,
+    last_restart: Option<Instant>,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(250),
+            stable_after: Duration::from_secs(60),
+            attempts: 0,
+            last_restart: None,
+        }
+    }
+}
+
+impl RestartPolicy {
+    /// Delay before the next restart, or `None` once we should give up
+    pub fn next_delay(&mut self, now: Instant) -> Option<Duration> {
+        if self
+            .last_restart
+            .is_some_and(|last| now.duration_since(last) >= self.stable_after)
+        {
+            self.attempts = 0;
+        }
+        if self.attempts >= self.max_attempts {
+            return None;
+        }
+
+        let delay = self.initial_delay * 2u32.pow(self.attempts);
+        self.attempts += 1;
+        self.last_restart = Some(now);
+        Some(delay)
+    }
+}
+
+/// What a restarted Dart server needs to catch up with the client
+#[derive(Default)]
+pub struct Session {
+    /// The client's `initialize` request
+    pub initialize: Option<String>,
+    /// The client's `initialized` notification
+    pub initialized: Option<String>,
+    /// Client requests forwarded to the Dart server and not yet answered,
+    /// keyed by the string form of their id
+    pub in_flight: HashMap<String, Value>,
+    /// Id of the client's `shutdown` request, once received
+    pub shutdown_id: Option<Value>,
+}
+
+impl Session {
+    /// Remember a client request that the Dart server now owes a response
+    pub fn track_request(&mut self, msg: &Value) {
+        if let (Some(id), Some(_)) = (msg.get("id"), msg.get("method")) {
+            self.in_flight.insert(id.to_string(), id.clone());
+        }
+    }
+
+    /// Forget a request once the Dart server has answered it
+    pub fn track_response(&mut self, msg: &Value) {
+        if msg.get("method").is_none() {
+            if let Some(id) = msg.get("id") {
+                self.in_flight.remove(&id.to_string());
+            }
+        }
+    }
+}
+
+/// Messages replayed to a restarted Dart server, in order
+pub struct Replay {
+    /// Id of our copy of `initialize`; its response is not forwarded
+    pub initialize_id: Value,
+    /// `initialized` and one `didOpen` per open document
+    pub messages: Vec<String>,
+}
+
+impl Replay {
+    /// Build the replay for a server from the session so far
+    ///
+    /// Returns the `initialize` request to send right away and the replay
+    /// to finish once the server answers it.
+    pub fn new(
+        session: &Session,
+        documents: &DocumentStore,
+        generation: u64,
+    ) -> Result<Option<(String, Self)>> {
+        let Some(initialize) = &session.initialize else {
+            return Ok(None);
+        };
+
+        let mut request: Value =
+            serde_json::from_str(initialize).context("Invalid initialize request")?;
+        let initialize_id = json!(format!("dart-re-analyzer/reinitialize/{}", generation));
+        request["id"] = initialize_id.clone();
+
+        let mut messages: Vec<String> = session.initialized.iter().cloned().collect();
+        for uri in documents.uris() {
+            let Some(document) = documents.get(uri) else {
+                continue;
+            };
+            let notification = json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/didOpen",
+                "params": {
+                    "textDocument": {
+                        "uri": uri,
+                        "languageId": "dart",
+                        "version": document.version,
+                        "text": document.text(),
+                    }
+                }
+            });
+            messages.push(serde_json::to_string(&notification)?);
+        }
+
+        Ok(Some((
+            serde_json::to_string(&request)?,
+            Self {
+                initialize_id,
+                messages,
+            },
+        )))
+    }
+}
+
+/// Error response for a request the Dart server can no longer answer
+pub fn request_failed(id: &Value, message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": id,
+        "error": {"code": REQUEST_FAILED, "message": message},
+    })
+}
+
+/// `window/showMessage` notification with an error for the user
+pub fn show_error(message: &str) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "window/showMessage",
+        "params": {"type": 1, "message": message},
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff_doubles_then_gives_up() {
+        let mut policy = RestartPolicy::default();
+        let now = Instant::now();
+        assert_eq!(policy.next_delay(now), Some(Duration::from_millis(250)));
+        assert_eq!(policy.next_delay(now), Some(Duration::from_millis(500)));
+        assert_eq!(policy.next_delay(now), Some(Duration::from_millis(1000)));
+        assert_eq!(policy.next_delay(now), None);
+    }
+
+    #[test]
+    fn test_backoff_resets_after_stable_run() {
+        let mut policy = RestartPolicy::default();
+        let now = Instant::now();
+        for _ in 0..3 {
+            policy.next_delay(now);
+        }
+        let later = now + Duration::from_secs(61);
+        assert_eq!(policy.next_delay(later), Some(Duration::from_millis(250)));
+    }
+
+    #[test]
+    fn test_replay_reinitializes_and_reopens_documents() {
+        let mut session = Session {
+            initialize: Some(
+                r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"rootUri":null}}"#
+                    .to_string(),
+            ),
+            initialized: Some(
+                r#"{"jsonrpc":"2.0","method":"initialized","params":{}}"#.to_string(),
+            ),
+            ..Default::default()
+        };
+        let mut documents = DocumentStore::new();
+        documents
+            .open("file:///tmp/a.dart", 3, "void main() {}\n")
+            .unwrap();
+
+        let (initialize, replay) = Replay::new(&session, &documents, 2).unwrap().unwrap();
+        let initialize: Value = serde_json::from_str(&initialize).unwrap();
+        assert_eq!(initialize["id"], "dart-re-analyzer/reinitialize/2");
+        assert_eq!(initialize["method"], "initialize");
+        assert_eq!(replay.initialize_id, initialize["id"]);
+
+        assert_eq!(replay.messages.len(), 2);
+        let open: Value = serde_json::from_str(&replay.messages[1]).unwrap();
+        assert_eq!(open["method"], "textDocument/didOpen");
+        assert_eq!(open["params"]["textDocument"]["version"], 3);
+        assert_eq!(open["params"]["textDocument"]["text"], "void main() {}\n");
+
+        // Nothing to replay before the client initialized
+        session.initialize = None;
+        assert!(Replay::new(&session, &documents, 3).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_session_tracks_in_flight_requests() {
+        let mut session = Session::default();
+        session.track_request(&json!({"id": 4, "method": "textDocument/hover"}));
+        // Responses to server requests are not ours to track
+        session.track_request(&json!({"id": 5, "result": null}));
+        assert_eq!(session.in_flight.len(), 1);
+
+        session.track_response(&json!({"id": 4, "result": null}));
+        assert!(session.in_flight.is_empty());
+    }
+}
@@ -6,48 +6,101 @@
 //!    while injecting additional diagnostics from dart-re-analyzer rules
 //!
 //! 2. **Semantic Analysis** (`semantic` module) - Foundational types for semantic analysis
-//!    including type resolution, symbol information, and diagnostics
+//!    including type resolution, symbol information, and diagnostics, answered by the
+//!    Dart Analysis Server in the `dart_semantic` module
 //!
-//! 3. **Client** (`client` module) - Dart Analysis Server client stub
+//! 3. **Client** (`client` module) - Async JSON-RPC client for the Dart Analysis Server
+//!
+//! 4. **Documents** (`documents` module) - In-memory store of open editor buffers
+//!
+//! 5. **Diagnostics** (`diagnostics` module) - Per-document merge of our diagnostics
+//!    with the Dart Analysis Server's
+//!
+//! 6. **URIs** (`uri` module) - `file:` URI and path conversion, canonical cache keys
+//!
+//! 7. **Positions** (`position` module) - Byte column to LSP position conversion in the
+//!    negotiated `positionEncoding`
+//!
+//! 8. **Code Actions** (`code_actions` module) - Quick fixes and suppression comments for
+//!    our diagnostics
+//!
+//! 9. **Standalone Server** (`standalone` module) - Language server without the Dart SDK,
+//!    serving diagnostics, code actions, document symbols (`symbols` module) and folding
+//!    ranges from tree-sitter
+//!
+//! 10. **Lifecycle** (`lifecycle` module) - Shutdown, crash detection and restart of the
+//!     Dart Analysis Server process
+//!
+//! 11. **Settings** (`settings` module) - Rule settings from the config file, overridden
+//!     by the editor's `dartReAnalyzer` section and reloaded when either changes
+//!
+//! 12. **Tracing** (`trace` module) - JSONL recording of proxy sessions and their replay
+//!
+//! 13. **Framing** (`framing` module) - `Content-Length` message framing shared by the proxy,
+//!     the standalone server and the client
 
 use anyhow::{Context, Result};
+use futures::StreamExt;
+use lsp_types::{
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+};
 use serde_json::{json, Value};
-use std::collections::HashMap;
-use std::io::{BufRead, BufReader, Write};
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
 use std::path::{Path, PathBuf};
-use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::UnboundedSender;
 use tokio::sync::Mutex;
+use tokio_util::codec::FramedRead;
 
-use crate::analyzer::Rule;
+use crate::analyzer::index::ProjectIndex;
+use crate::analyzer::{ProjectRule, Rule};
 use crate::config::AnalyzerConfig;
 use crate::error::Diagnostic;
 use crate::parser;
 use crate::rules;
+use client::{DartAnalysisServerClient, DartAnalysisServerConfig, ServerEvent};
+use diagnostics::DiagnosticsStore;
+use documents::DocumentStore;
+use framing::{ChannelWriter, LspCodec};
+use lifecycle::{Replay, RestartPolicy, Session, EXIT_TIMEOUT, SHUTDOWN_TIMEOUT};
+use position::PositionEncoding;
+use settings::Settings;
+use trace::{Direction, TracedWriter, Tracer};
 
 // Export submodules
 pub mod client;
+pub mod code_actions;
+pub mod dart_semantic;
+pub mod diagnostics;
+pub mod documents;
+pub mod framing;
+pub mod lifecycle;
+pub mod position;
 pub mod semantic;
-
-/// LSP message header
-#[derive(Debug)]
-struct LspMessage {
-    #[allow(dead_code)]
-    content_length: usize,
-    content: String,
-}
+pub mod settings;
+pub mod standalone;
+pub mod symbols;
+pub mod trace;
+pub mod uri;
 
 /// LSP Proxy that forwards messages between client and Dart Analysis Server,
 /// injecting additional diagnostics from dart-re-analyzer
 pub struct LspProxy {
-    dart_process: Option<Child>,
-    dart_stdin: Option<ChildStdin>,
-    dart_stdout: Option<BufReader<ChildStdout>>,
     dart_binary: Option<String>,
     config: AnalyzerConfig, // TODO: Use config.exclude_patterns for file filtering
+    /// File `config` was loaded from, watched for changes
+    config_path: Option<PathBuf>,
+    /// Records the session's messages when set
+    trace: Option<Tracer>,
     rules: Vec<Arc<dyn Rule>>,
+    project_rules: Vec<Arc<dyn ProjectRule>>,
     workspace_root: PathBuf,
-    diagnostics_cache: Arc<Mutex<HashMap<String, Vec<Diagnostic>>>>,
+    diagnostics_cache: Arc<Mutex<DiagnosticsStore>>,
+    documents: Arc<Mutex<DocumentStore>>,
+    /// Whether the client sent `shutdown` before `exit`
+    shutdown_received: bool,
 }
 
 impl LspProxy {
@@ -57,152 +110,207 @@ impl LspProxy {
         config: AnalyzerConfig,
         workspace_root: PathBuf,
     ) -> Self {
-        let rules = rules::get_all_rules();
+        let rules = rules::get_rules_for_config(&config);
+        let project_rules = rules::get_project_rules_for_config(&config);
 
         Self {
-            dart_process: None,
-            dart_stdin: None,
-            dart_stdout: None,
             dart_binary,
             config,
+            config_path: None,
+            trace: None,
             rules,
+            project_rules,
             workspace_root,
-            diagnostics_cache: Arc::new(Mutex::new(HashMap::new())),
+            diagnostics_cache: Arc::new(Mutex::new(DiagnosticsStore::new())),
+            documents: Arc::new(Mutex::new(DocumentStore::new())),
+            shutdown_received: false,
         }
     }
 
-    /// Start the Dart Analysis Server process
-    pub fn start_dart_server(&mut self) -> Result<()> {
-        let dart_cmd = self
-            .dart_binary
-            .clone()
-            .unwrap_or_else(|| "dart".to_string());
-
-        let mut child = Command::new(&dart_cmd)
-            .arg("language-server")
-            .arg("--protocol=lsp")
-            .stdin(Stdio::piped())
-            .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
-            .spawn()
-            .context(format!(
-                "Failed to start Dart Analysis Server. Make sure '{}' is in your PATH.",
-                dart_cmd
-            ))?;
-
-        let stdin = child.stdin.take().context("Failed to capture stdin")?;
-        let stdout = child.stdout.take().context("Failed to capture stdout")?;
-
-        self.dart_stdin = Some(stdin);
-        self.dart_stdout = Some(BufReader::new(stdout));
-        self.dart_process = Some(child);
+    /// Watch the file the config was loaded from and reload it on changes
+    pub fn with_config_path(mut self, config_path: Option<PathBuf>) -> Self {
+        self.config_path = config_path;
+        self
+    }
 
-        eprintln!("Dart Analysis Server started successfully");
-        Ok(())
+    /// Record every message of the session with `tracer`
+    pub fn with_trace(mut self, tracer: Option<Tracer>) -> Self {
+        self.trace = tracer;
+        self
     }
 
-    /// Read an LSP message from a reader
-    ///
-    /// Note: This uses blocking I/O with read_exact(). In production environments with
-    /// unreliable connections, consider adding timeout mechanisms or using async I/O.
-    fn read_message<R: BufRead>(reader: &mut R) -> Result<Option<LspMessage>> {
-        let mut content_length: Option<usize> = None;
-        let mut line = String::new();
+    /// Start the Dart Analysis Server, forwarding its output as events
+    /// tagged with `generation`
+    async fn start_dart_server(
+        &self,
+        generation: u64,
+        server_tx: &UnboundedSender<(u64, ServerEvent)>,
+    ) -> Result<DartAnalysisServerClient> {
+        let mut config = DartAnalysisServerConfig::for_project(&self.workspace_root);
+        config.dart_binary = self.dart_binary.clone().map(PathBuf::from);
+        config.verbose = true;
+        config.trace = self.trace.clone();
+        let dart = config.dart_executable();
 
-        // Read headers
-        loop {
-            line.clear();
-            let bytes_read = reader.read_line(&mut line)?;
+        let mut client = DartAnalysisServerClient::new(config)?;
+        let mut events = client.take_events().context("Event stream already taken")?;
+        client.start().await.context(format!(
+            "Failed to start Dart Analysis Server. Make sure '{}' is in your PATH.",
+            dart.display()
+        ))?;
 
-            if bytes_read == 0 {
-                return Ok(None); // EOF
+        let server_tx = server_tx.clone();
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                let exited = matches!(event, ServerEvent::Exited);
+                if server_tx.send((generation, event)).is_err() || exited {
+                    break;
+                }
             }
+        });
 
-            let line = line.trim();
-
-            if line.is_empty() {
-                break; // End of headers
-            }
+        eprintln!("Dart Analysis Server started successfully");
+        Ok(client)
+    }
 
-            if let Some(value) = line.strip_prefix("Content-Length: ") {
-                content_length = Some(value.parse()?);
+    /// Restart the Dart server after it exited, backing off between attempts
+    ///
+    /// Returns `None` once the restart policy gives up.
+    async fn restart_dart_server(
+        &self,
+        generation: &mut u64,
+        server_tx: &UnboundedSender<(u64, ServerEvent)>,
+        restarts: &mut RestartPolicy,
+    ) -> Option<DartAnalysisServerClient> {
+        while let Some(delay) = restarts.next_delay(Instant::now()) {
+            eprintln!("Restarting Dart Analysis Server in {:?}", delay);
+            tokio::time::sleep(delay).await;
+            *generation += 1;
+            match self.start_dart_server(*generation, server_tx).await {
+                Ok(client) => return Some(client),
+                Err(e) => eprintln!("Error restarting Dart Analysis Server: {:#}", e),
             }
         }
+        None
+    }
 
-        let content_length = content_length.context("Missing Content-Length header")?;
-
-        // Read content
-        let mut content = vec![0u8; content_length];
-        reader.read_exact(&mut content)?;
+    /// Close the Dart server's stdin and wait for it to exit, killing it
+    /// after `timeout`
+    async fn stop_dart_server(dart: &mut Option<DartAnalysisServerClient>, timeout: Duration) {
+        if let Some(mut client) = dart.take() {
+            client.stop(timeout).await;
+        }
+    }
 
-        Ok(Some(LspMessage {
-            content_length,
-            content: String::from_utf8(content)?,
-        }))
+    /// Exit code for the process once [`run`](Self::run) returns
+    ///
+    /// Non-zero when the client exited without a `shutdown` request first,
+    /// as the LSP specification asks.
+    pub fn exit_code(&self) -> i32 {
+        if self.shutdown_received {
+            0
+        } else {
+            1
+        }
     }
 
     /// Write an LSP message to a writer
     fn write_message<W: Write>(writer: &mut W, content: &str) -> Result<()> {
-        write!(
-            writer,
-            "Content-Length: {}\r\n\r\n{}",
-            content.len(),
-            content
-        )?;
-        writer.flush()?;
-        Ok(())
+        Ok(framing::write_message(writer, content)?)
+    }
+
+    /// Forward a message to the Dart server, if one is running
+    ///
+    /// Errors are only logged: a server that went away is noticed through
+    /// its stdout closing, which triggers a restart.
+    fn send_to_server(dart: &Option<DartAnalysisServerClient>, content: &str) {
+        if let Some(client) = dart {
+            if let Err(e) = client.send_raw(content) {
+                eprintln!("Error writing to Dart server: {}", e);
+            }
+        }
+    }
+
+    /// Forward client messages held back while a restarted server caught up
+    fn flush_queued(
+        queued: &mut Vec<String>,
+        session: &mut Session,
+        dart: &Option<DartAnalysisServerClient>,
+    ) {
+        for content in queued.drain(..) {
+            if let Ok(msg) = serde_json::from_str::<Value>(&content) {
+                session.track_request(&msg);
+            }
+            Self::send_to_server(dart, &content);
+        }
     }
 
-    /// Convert dart-re-analyzer diagnostic to LSP diagnostic format
-    fn diagnostic_to_lsp(diag: &Diagnostic) -> Value {
-        let severity = match diag.severity {
-            crate::error::Severity::Error => 1,   // Error
-            crate::error::Severity::Warning => 2, // Warning
-            crate::error::Severity::Info => 3,    // Information
+    /// Answer a client message while no Dart server is running
+    ///
+    /// Code action requests get our actions alone, other requests fail and
+    /// notifications are dropped.
+    async fn answer_without_server<W: Write>(
+        writer: &mut W,
+        msg: &Value,
+        cache: &Arc<Mutex<DiagnosticsStore>>,
+        documents: &Arc<Mutex<DocumentStore>>,
+    ) -> Result<()> {
+        let (Some(id), Some(method)) = (msg.get("id"), msg.get("method").and_then(|m| m.as_str()))
+        else {
+            return Ok(());
         };
 
-        json!({
-            "range": {
-                "start": {
-                    "line": diag.location.line.saturating_sub(1), // LSP is 0-indexed
-                    "character": diag.location.column.saturating_sub(1)
-                },
-                "end": {
-                    "line": diag.location.line.saturating_sub(1),
-                    "character": diag.location.column.saturating_sub(1) + 1
-                }
-            },
-            "severity": severity,
-            "code": diag.rule_id,
-            "source": "dart-re-analyzer",
-            "message": diag.message,
-        })
+        let response = match method {
+            "shutdown" => json!({"jsonrpc": "2.0", "id": id, "result": null}),
+            "textDocument/codeAction" => {
+                let params = msg.get("params").cloned().unwrap_or(Value::Null);
+                let ours = Self::code_actions_static(&params, cache, documents).await;
+                json!({"jsonrpc": "2.0", "id": id, "result": ours})
+            }
+            _ => lifecycle::request_failed(id, "Dart Analysis Server is not running"),
+        };
+        Self::write_message(writer, &serde_json::to_string(&response)?)
     }
 
     /// Run the LSP proxy loop
+    ///
+    /// Returns once the client sends `exit` or closes the connection, and
+    /// every message to the client was written.
     pub async fn run(&mut self) -> Result<()> {
-        // Start dart server
-        self.start_dart_server()?;
+        // Written from a task of its own, so an editor slow to read doesn't
+        // block the event loop
+        let (writer, output) = framing::spawn_writer(tokio::io::stdout());
+        let result = self
+            .run_loop(TracedWriter::new(writer, self.trace.clone()))
+            .await;
+        let written = output
+            .await
+            .map_err(|e| anyhow::anyhow!("Client writer failed: {}", e));
+        result?;
+        Ok(written??)
+    }
+
+    async fn run_loop(&mut self, mut stdout: TracedWriter<ChannelWriter>) -> Result<()> {
+        let (server_tx, mut server_rx) =
+            tokio::sync::mpsc::unbounded_channel::<(u64, ServerEvent)>();
 
-        // Take ownership of the streams
-        let mut dart_stdin = self.dart_stdin.take().context("Dart stdin not available")?;
-        let dart_stdout = self
-            .dart_stdout
-            .take()
-            .context("Dart stdout not available")?;
+        // Bumped on every restart so events from a dead server are ignored
+        let mut generation = 0;
+        let mut dart = Some(self.start_dart_server(generation, &server_tx).await?);
 
         let (client_tx, mut client_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
-        let (server_tx, mut server_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
 
-        // Spawn thread to read from client (stdin)
-        let client_tx_clone = client_tx.clone();
-        std::thread::spawn(move || {
-            let stdin = std::io::stdin();
-            let mut reader = BufReader::new(stdin);
+        // Read from the client (stdin)
+        let trace = self.trace.clone();
+        tokio::spawn(async move {
+            let mut reader = FramedRead::new(tokio::io::stdin(), LspCodec::default());
             loop {
-                match LspProxy::read_message(&mut reader) {
-                    Ok(Some(msg)) => {
-                        if client_tx_clone.send(msg.content).is_err() {
+                match reader.next().await.transpose() {
+                    Ok(Some(content)) => {
+                        if let Some(trace) = &trace {
+                            trace.record(Direction::FromClient, &content);
+                        }
+                        if client_tx.send(content).is_err() {
                             break;
                         }
                     }
@@ -215,90 +323,398 @@ impl LspProxy {
             }
         });
 
-        // Spawn thread to read from Dart server
-        let server_tx_clone = server_tx.clone();
-        let mut dart_stdout_reader = dart_stdout;
-        std::thread::spawn(move || {
-            loop {
-                match LspProxy::read_message(&mut dart_stdout_reader) {
-                    Ok(Some(msg)) => {
-                        if server_tx_clone.send(msg.content).is_err() {
-                            break;
-                        }
-                    }
-                    Ok(None) => break, // EOF
-                    Err(e) => {
-                        eprintln!("Error reading from Dart server: {}", e);
-                        break;
-                    }
-                }
-            }
-        });
+        let (publish_tx, mut publish_rx) = tokio::sync::mpsc::unbounded_channel::<String>();
 
         let mut initialized = false;
+        let mut initialize_id: Option<Value> = None;
+        // Code action requests awaiting the Dart server's response, by id
+        let mut pending_code_actions: HashMap<String, Value> = HashMap::new();
+        let mut session = Session::default();
+        let mut restarts = RestartPolicy::default();
+        // Set while a restarted server catches up; client messages wait in `queued`
+        let mut replay: Option<Replay> = None;
+        let mut queued: Vec<String> = Vec::new();
+        // Set while waiting for the Dart server to answer `shutdown`
+        let mut shutdown_deadline: Option<tokio::time::Instant> = None;
         let diagnostics_cache = Arc::clone(&self.diagnostics_cache);
-        let workspace_root = self.workspace_root.clone();
-        let rules = self.rules.clone();
+        let documents = Arc::clone(&self.documents);
+        let mut workspace_roots = vec![self.workspace_root.clone()];
+        let mut rules = self.rules.clone();
+        let mut project_rules = self.project_rules.clone();
+        let mut settings = Settings::new(self.config.clone(), self.config_path.clone());
+        let mut initialize_params = Value::Null;
+        let mut configuration_requests = 0;
+        // Set while the client has not answered our last diagnostic refresh
+        let mut refresh_in_flight = false;
 
-        // Main event loop
-        let mut stdout = std::io::stdout();
+        // Main event loop. Every message to the client is written here, so
+        // diagnostics are always published from the latest merged state.
         loop {
+            // Pulled diagnostics changed: ask the client to pull again
+            if !refresh_in_flight
+                && diagnostics::supports_refresh(&initialize_params)
+                && diagnostics_cache.lock().await.take_refresh()
+            {
+                let request = diagnostics::refresh_request();
+                Self::write_message(&mut stdout, &serde_json::to_string(&request)?)?;
+                refresh_in_flight = true;
+            }
+
             tokio::select! {
                 // Message from client to server
-                Some(content) = client_rx.recv() => {
+                content = client_rx.recv() => {
+                    let Some(content) = content else {
+                        eprintln!("Client closed the connection");
+                        Self::stop_dart_server(&mut dart, EXIT_TIMEOUT).await;
+                        break;
+                    };
+
                     // Parse message
-                    if let Ok(msg) = serde_json::from_str::<Value>(&content) {
-                        // Check for initialize request
-                        if let Some(method) = msg.get("method").and_then(|m| m.as_str()) {
-                            if method == "initialize" {
+                    let Ok(msg) = serde_json::from_str::<Value>(&content) else {
+                        continue;
+                    };
+
+                    // Responses to our own requests to the editor stay here
+                    if let Some(id) = msg
+                        .get("id")
+                        .and_then(|id| id.as_str())
+                        .filter(|id| msg.get("method").is_none() && id.starts_with(settings::REQUEST_PREFIX))
+                    {
+                        if id == diagnostics::REFRESH_REQUEST_ID {
+                            refresh_in_flight = false;
+                        } else if settings::is_configuration_response(id) {
+                            let section = msg.pointer("/result/0").cloned().unwrap_or(Value::Null);
+                            if Self::rebuild_rules(settings.set_overrides(section), &settings, &mut rules, &mut project_rules) && initialized {
+                                Self::reanalyze(&mut stdout, &workspace_roots, &rules, &project_rules, &diagnostics_cache, &documents, &publish_tx).await?;
+                            }
+                        }
+                        continue;
+                    }
+
+                    if let Some(method) = msg.get("method").and_then(|m| m.as_str()) {
+                        match method {
+                            "initialize" => {
                                 eprintln!("LSP initialize request received");
+                                initialize_id = msg.get("id").cloned();
+                                session.initialize = Some(content.clone());
                                 // We'll run analysis after initialized
+                                if let Some(params) = msg.get("params") {
+                                    workspace_roots =
+                                        uri::workspace_roots(params, &self.workspace_root);
+                                    initialize_params = params.clone();
+                                }
+                                diagnostics_cache
+                                    .lock()
+                                    .await
+                                    .set_pull(diagnostics::supports_pull(&initialize_params));
+                                if let Some(section) = initialize_params
+                                    .get("initializationOptions")
+                                    .and_then(settings::section)
+                                {
+                                    Self::rebuild_rules(settings.set_overrides(section), &settings, &mut rules, &mut project_rules);
+                                }
+                            }
+                            "initialized" if !initialized => {
+                                initialized = true;
+                                session.initialized = Some(content.clone());
+                                Self::spawn_workspace_analysis(
+                                    workspace_roots.clone(),
+                                    rules.clone(),
+                                    project_rules.clone(),
+                                    Arc::clone(&diagnostics_cache),
+                                    Arc::clone(&documents),
+                                    publish_tx.clone(),
+                                );
+
+                                if settings::supports_file_watches(&initialize_params) {
+                                    let request = settings::watch_request(settings.config_path());
+                                    Self::write_message(&mut stdout, &serde_json::to_string(&request)?)?;
+                                }
+                                if settings::supports_configuration(&initialize_params) {
+                                    configuration_requests += 1;
+                                    let request = settings::configuration_request(configuration_requests);
+                                    Self::write_message(&mut stdout, &serde_json::to_string(&request)?)?;
+                                }
+                            }
+                            "workspace/didChangeConfiguration" => {
+                                let section = msg.pointer("/params/settings").and_then(settings::section);
+                                if let Some(section) = section {
+                                    if Self::rebuild_rules(settings.set_overrides(section), &settings, &mut rules, &mut project_rules) && initialized {
+                                        Self::reanalyze(&mut stdout, &workspace_roots, &rules, &project_rules, &diagnostics_cache, &documents, &publish_tx).await?;
+                                    }
+                                } else if settings::supports_configuration(&initialize_params) {
+                                    // Editors using the pull model only
+                                    // notify that something changed
+                                    configuration_requests += 1;
+                                    let request = settings::configuration_request(configuration_requests);
+                                    Self::write_message(&mut stdout, &serde_json::to_string(&request)?)?;
+                                }
+                            }
+                            "workspace/didChangeWatchedFiles" => {
+                                let config_files: Vec<PathBuf> = msg
+                                    .pointer("/params/changes")
+                                    .and_then(|c| c.as_array())
+                                    .into_iter()
+                                    .flatten()
+                                    .filter_map(|change| change["uri"].as_str().and_then(uri::uri_to_path))
+                                    .filter(|path| settings.is_config_file(path, &workspace_roots))
+                                    .collect();
+                                let mut changed = false;
+                                for path in config_files {
+                                    changed |= Self::rebuild_rules(settings.reload_file(&path), &settings, &mut rules, &mut project_rules);
+                                }
+                                if changed && initialized {
+                                    Self::reanalyze(&mut stdout, &workspace_roots, &rules, &project_rules, &diagnostics_cache, &documents, &publish_tx).await?;
+                                }
+                            }
+                            "shutdown" => {
+                                session.shutdown_id = msg.get("id").cloned();
+                                if dart.is_some() {
+                                    shutdown_deadline =
+                                        Some(tokio::time::Instant::now() + SHUTDOWN_TIMEOUT);
+                                }
+                            }
+                            "exit" => {
+                                self.shutdown_received = session.shutdown_id.is_some();
+                                Self::send_to_server(&dart, &content);
+                                Self::stop_dart_server(&mut dart, EXIT_TIMEOUT).await;
+                                break;
                             }
+                            // The Dart server only pushes diagnostics, so
+                            // pulls are answered from the merged store
+                            "textDocument/diagnostic" | "workspace/diagnostic" => {
+                                let params = msg.get("params").cloned().unwrap_or(Value::Null);
+                                let result = if method == "workspace/diagnostic" {
+                                    Self::workspace_diagnostics_static(&params, &diagnostics_cache, &documents).await
+                                } else {
+                                    Self::document_diagnostics_static(&params, &diagnostics_cache, &documents).await
+                                };
+                                let response = json!({"jsonrpc": "2.0", "id": msg["id"], "result": result});
+                                Self::write_message(&mut stdout, &serde_json::to_string(&response)?)?;
+                                continue;
+                            }
+                            "$/cancelRequest" => {
+                                // Let the server's response to a cancelled
+                                // code action through untouched
+                                if let Some(id) = msg.pointer("/params/id") {
+                                    pending_code_actions.remove(&id.to_string());
+                                }
+                            }
+                            "workspace/didChangeWorkspaceFolders" => {
+                                if let Some(event) = msg.pointer("/params/event") {
+                                    uri::apply_folder_changes(&mut workspace_roots, event);
+                                    if initialized {
+                                        Self::spawn_workspace_analysis(
+                                            workspace_roots.clone(),
+                                            rules.clone(),
+                                            project_rules.clone(),
+                                            Arc::clone(&diagnostics_cache),
+                                            Arc::clone(&documents),
+                                            publish_tx.clone(),
+                                        );
+                                    }
+                                }
+                            }
+                            "textDocument/codeAction" => {
+                                if let (Some(id), Some(params)) = (msg.get("id"), msg.get("params")) {
+                                    pending_code_actions.insert(id.to_string(), params.clone());
+                                }
+                            }
+                            // Project rules look at every file, so they
+                            // run on saves rather than on each edit
+                            "textDocument/didSave" if initialized => {
+                                Self::spawn_project_analysis(
+                                    workspace_roots.clone(),
+                                    project_rules.clone(),
+                                    Arc::clone(&diagnostics_cache),
+                                    Arc::clone(&documents),
+                                    publish_tx.clone(),
+                                );
+                            }
+                            "textDocument/didOpen"
+                            | "textDocument/didChange"
+                            | "textDocument/didClose" => {
+                                match Self::track_document_static(
+                                    &msg,
+                                    &rules,
+                                    &diagnostics_cache,
+                                    &documents,
+                                ).await {
+                                    Ok(Some(key)) => {
+                                        Self::publish_static(&mut stdout, &key, &diagnostics_cache, &documents).await?;
+                                    }
+                                    Ok(None) => {}
+                                    Err(e) => eprintln!("Error tracking document: {}", e),
+                                }
+                            }
+                            _ => {}
                         }
+                    }
 
-                        // Forward to Dart server
-                        Self::write_message(&mut dart_stdin, &content)?;
+                    // Forward to Dart server
+                    if replay.is_some() {
+                        queued.push(content);
+                    } else if dart.is_some() {
+                        session.track_request(&msg);
+                        Self::send_to_server(&dart, &content);
+                    } else {
+                        if let Some(id) = msg.get("id") {
+                            pending_code_actions.remove(&id.to_string());
+                        }
+                        Self::answer_without_server(&mut stdout, &msg, &diagnostics_cache, &documents).await?;
                     }
                 }
 
                 // Message from server to client
-                Some(content) = server_rx.recv() => {
-                    // Parse message
-                    if let Ok(mut msg) = serde_json::from_str::<Value>(&content) {
-                        // Check for initialized notification
-                        if let Some(method) = msg.get("method").and_then(|m| m.as_str()) {
-                            if method == "initialized" && !initialized {
-                                initialized = true;
-                                // Analyze workspace in background
-                                let cache_clone = Arc::clone(&diagnostics_cache);
-                                let workspace_clone = workspace_root.clone();
-                                let rules_clone = rules.clone();
-                                tokio::spawn(async move {
-                                    if let Err(e) = Self::analyze_workspace_static(
-                                        &workspace_clone,
-                                        &rules_clone,
-                                        cache_clone
-                                    ).await {
-                                        eprintln!("Error analyzing workspace: {}", e);
+                Some((event_generation, event)) = server_rx.recv() => {
+                    if event_generation != generation {
+                        continue;
+                    }
+                    let mut content = match event {
+                        ServerEvent::Message(content) => content,
+                        ServerEvent::Exited => {
+                            dart = None;
+                            replay = None;
+
+                            if let Some(id) = &session.shutdown_id {
+                                // Expected after shutdown; answer for the
+                                // server if it went away first
+                                if shutdown_deadline.take().is_some() {
+                                    let response = json!({"jsonrpc": "2.0", "id": id, "result": null});
+                                    Self::write_message(&mut stdout, &serde_json::to_string(&response)?)?;
+                                }
+                                continue;
+                            }
+                            eprintln!("Dart Analysis Server exited unexpectedly");
+
+                            // Requests the dead server will never answer
+                            for (key, id) in session.in_flight.drain() {
+                                let response = match pending_code_actions.remove(&key) {
+                                    Some(params) => {
+                                        let ours = Self::code_actions_static(&params, &diagnostics_cache, &documents).await;
+                                        json!({"jsonrpc": "2.0", "id": id, "result": ours})
                                     }
-                                });
+                                    None => lifecycle::request_failed(&id, "Dart Analysis Server exited"),
+                                };
+                                Self::write_message(&mut stdout, &serde_json::to_string(&response)?)?;
+                            }
+
+                            // A new server republishes whatever still applies
+                            let stale = diagnostics_cache.lock().await.clear_dart();
+                            for key in stale {
+                                Self::publish_static(&mut stdout, &key, &diagnostics_cache, &documents).await?;
+                            }
+
+                            let Some(client) = self
+                                .restart_dart_server(&mut generation, &server_tx, &mut restarts)
+                                .await
+                            else {
+                                let message = lifecycle::show_error(
+                                    "The Dart Analysis Server keeps exiting and could not be \
+                                     restarted. Only dart-re-analyzer diagnostics are available.",
+                                );
+                                Self::write_message(&mut stdout, &serde_json::to_string(&message)?)?;
+                                for content in queued.drain(..) {
+                                    if let Ok(msg) = serde_json::from_str::<Value>(&content) {
+                                        Self::answer_without_server(&mut stdout, &msg, &diagnostics_cache, &documents).await?;
+                                    }
+                                }
+                                continue;
+                            };
+                            dart = Some(client);
+
+                            let started = Replay::new(&session, &*documents.lock().await, generation);
+                            match started {
+                                Ok(Some((initialize, started))) => {
+                                    Self::send_to_server(&dart, &initialize);
+                                    replay = Some(started);
+                                }
+                                Ok(None) => {
+                                    Self::flush_queued(&mut queued, &mut session, &dart);
+                                }
+                                Err(e) => eprintln!("Error replaying session: {}", e),
                             }
+                            continue;
+                        }
+                    };
+
+                    let msg = serde_json::from_str::<Value>(&content).ok();
+                    let method = msg.as_ref().and_then(|m| m.get("method")).and_then(|m| m.as_str());
+                    let response_id = msg.as_ref().filter(|_| method.is_none()).and_then(|m| m.get("id"));
+
+                    // A restarted server answered our `initialize`: finish
+                    // the replay and release what the client sent meanwhile
+                    if let Some(started) = replay.take_if(|r| response_id == Some(&r.initialize_id)) {
+                        for message in &started.messages {
+                            Self::send_to_server(&dart, message);
+                        }
+                        Self::flush_queued(&mut queued, &mut session, &dart);
+                        eprintln!("Dart Analysis Server restarted");
+                        continue;
+                    }
+
+                    if let Some(msg) = &msg {
+                        session.track_response(msg);
+                    }
+                    if response_id.is_some() && response_id == session.shutdown_id.as_ref() {
+                        shutdown_deadline = None;
+                    }
+
+                    // The Dart server picks the position encoding from the
+                    // client's capabilities; our ranges must use the same one
+                    if let Some(msg) = msg.as_ref().filter(|m| {
+                        initialize_id.is_some() && m.get("id") == initialize_id.as_ref()
+                    }) {
+                        if let Some(capabilities) = msg.pointer("/result/capabilities") {
+                            let encoding = PositionEncoding::from_server_capabilities(capabilities);
+                            documents.lock().await.set_position_encoding(encoding);
+                        }
+                        if diagnostics_cache.lock().await.is_pull() {
+                            let mut response = msg.clone();
+                            response["result"]["capabilities"]["diagnosticProvider"] =
+                                diagnostics::provider_capability();
+                            content = serde_json::to_string(&response)?;
                         }
+                        initialize_id = None;
+                    }
 
-                        // Inject our diagnostics if applicable
-                        Self::inject_diagnostics_static(&mut msg, &diagnostics_cache).await?;
+                    let pending = response_id.and_then(|id| pending_code_actions.remove(&id.to_string()));
 
-                        // Write to client
-                        let modified_content = serde_json::to_string(&msg)?;
-                        Self::write_message(&mut stdout, &modified_content)?;
+                    if let (Some(params), Some(response)) = (pending, msg.clone()) {
+                        // Add our quick fixes to the Dart server's actions
+                        let ours = Self::code_actions_static(&params, &diagnostics_cache, &documents).await;
+                        let response = Self::merge_code_actions(response, ours);
+                        Self::write_message(&mut stdout, &serde_json::to_string(&response)?)?;
+                    } else if method == Some("textDocument/publishDiagnostics") {
+                        // Replaced by a merged notification with our diagnostics
+                        if let Some(key) = Self::record_dart_diagnostics_static(
+                            msg.as_ref().unwrap(),
+                            &diagnostics_cache,
+                        ).await {
+                            Self::publish_static(&mut stdout, &key, &diagnostics_cache, &documents).await?;
+                        }
                     } else {
-                        // Forward as-is if we can't parse
+                        // Forward everything else as-is
                         Self::write_message(&mut stdout, &content)?;
                     }
                 }
 
-                else => {
-                    break;
+                // Our diagnostics changed outside of a client message
+                Some(key) = publish_rx.recv() => {
+                    Self::publish_static(&mut stdout, &key, &diagnostics_cache, &documents).await?;
+                }
+
+                // The Dart server did not answer `shutdown` in time
+                _ = tokio::time::sleep_until(
+                    shutdown_deadline.unwrap_or_else(tokio::time::Instant::now)
+                ), if shutdown_deadline.is_some() => {
+                    eprintln!("Dart Analysis Server did not answer shutdown in time");
+                    shutdown_deadline = None;
+                    if let Some(id) = &session.shutdown_id {
+                        let response = json!({"jsonrpc": "2.0", "id": id, "result": null});
+                        Self::write_message(&mut stdout, &serde_json::to_string(&response)?)?;
+                    }
+                    Self::stop_dart_server(&mut dart, Duration::ZERO).await;
                 }
             }
         }
@@ -306,99 +722,849 @@ impl LspProxy {
         Ok(())
     }
 
-    /// Static version of analyze_workspace
-    async fn analyze_workspace_static(
-        workspace_root: &Path,
+    /// Analyze the workspace roots in the background and queue the
+    /// documents whose diagnostics changed for publishing
+    fn spawn_workspace_analysis(
+        roots: Vec<PathBuf>,
+        rules: Vec<Arc<dyn Rule>>,
+        project_rules: Vec<Arc<dyn ProjectRule>>,
+        cache: Arc<Mutex<DiagnosticsStore>>,
+        documents: Arc<Mutex<DocumentStore>>,
+        publish_tx: tokio::sync::mpsc::UnboundedSender<String>,
+    ) {
+        tokio::spawn(async move {
+            let analysis =
+                Self::analyze_workspace_static(&roots, &rules, &project_rules, cache, documents);
+            match analysis.await {
+                Ok(changed) => {
+                    for key in changed {
+                        let _ = publish_tx.send(key);
+                    }
+                }
+                Err(e) => eprintln!("Error analyzing workspace: {}", e),
+            }
+        });
+    }
+
+    /// Run the project rules in the background and queue the documents
+    /// whose diagnostics changed for publishing
+    fn spawn_project_analysis(
+        roots: Vec<PathBuf>,
+        project_rules: Vec<Arc<dyn ProjectRule>>,
+        cache: Arc<Mutex<DiagnosticsStore>>,
+        documents: Arc<Mutex<DocumentStore>>,
+        publish_tx: tokio::sync::mpsc::UnboundedSender<String>,
+    ) {
+        tokio::spawn(async move {
+            match Self::analyze_project_static(&roots, &project_rules, &cache, &documents).await {
+                Ok(changed) => {
+                    for key in changed {
+                        let _ = publish_tx.send(key);
+                    }
+                }
+                Err(e) => eprintln!("Error analyzing project: {}", e),
+            }
+        });
+    }
+
+    /// Rebuild the rules if an update changed the settings in effect
+    ///
+    /// Returns whether the rules changed. Invalid settings are reported and
+    /// ignored.
+    fn rebuild_rules(
+        update: Result<bool>,
+        settings: &Settings,
+        rules: &mut Vec<Arc<dyn Rule>>,
+        project_rules: &mut Vec<Arc<dyn ProjectRule>>,
+    ) -> bool {
+        match update {
+            Ok(true) => {
+                eprintln!("Settings changed, rebuilding rules");
+                *rules = rules::get_rules_for_config(settings.config());
+                *project_rules = rules::get_project_rules_for_config(settings.config());
+                true
+            }
+            Ok(false) => false,
+            Err(e) => {
+                eprintln!("Ignoring settings: {:#}", e);
+                false
+            }
+        }
+    }
+
+    /// Re-run new rules on the open documents right away and on the
+    /// workspace in the background, publishing what changed
+    async fn reanalyze<W: Write>(
+        writer: &mut W,
+        workspace_roots: &[PathBuf],
         rules: &[Arc<dyn Rule>],
-        cache: Arc<Mutex<HashMap<String, Vec<Diagnostic>>>>,
+        project_rules: &[Arc<dyn ProjectRule>],
+        cache: &Arc<Mutex<DiagnosticsStore>>,
+        documents: &Arc<Mutex<DocumentStore>>,
+        publish_tx: &UnboundedSender<String>,
     ) -> Result<()> {
-        eprintln!("Analyzing workspace: {}", workspace_root.display());
+        let checked: Vec<(String, String, Vec<Diagnostic>)> = {
+            let store = documents.lock().await;
+            store
+                .uris()
+                .filter_map(|uri| {
+                    let key = uri::uri_cache_key(uri);
+                    let document = store.get(uri)?;
+                    let diagnostics = Self::check_content(Path::new(&key), document.text(), rules);
+                    Some((key, uri.clone(), diagnostics))
+                })
+                .collect()
+        };
 
-        let files = parser::find_dart_files(workspace_root)?;
-        eprintln!("Found {} Dart files to analyze", files.len());
+        let mut keys = Vec::new();
+        {
+            let mut cache_lock = cache.lock().await;
+            for (key, uri, diagnostics) in checked {
+                cache_lock.remember_uri(&key, &uri);
+                cache_lock.set_ours(&key, diagnostics);
+                keys.push(key);
+            }
+        }
+        for key in keys {
+            Self::publish_static(writer, &key, cache, documents).await?;
+        }
 
-        let mut cache_lock = cache.lock().await;
-        cache_lock.clear();
+        Self::spawn_workspace_analysis(
+            workspace_roots.to_vec(),
+            rules.to_vec(),
+            project_rules.to_vec(),
+            Arc::clone(cache),
+            Arc::clone(documents),
+            publish_tx.clone(),
+        );
+        Ok(())
+    }
 
-        for file in &files {
-            let path = PathBuf::from(&file.path);
-            let mut file_diagnostics = Vec::new();
+    /// Static version of analyze_workspace
+    ///
+    /// Returns the cache keys of documents whose diagnostics may have changed.
+    async fn analyze_workspace_static(
+        workspace_roots: &[PathBuf],
+        rules: &[Arc<dyn Rule>],
+        project_rules: &[Arc<dyn ProjectRule>],
+        cache: Arc<Mutex<DiagnosticsStore>>,
+        documents: Arc<Mutex<DocumentStore>>,
+    ) -> Result<Vec<String>> {
+        let results = analyze_roots(workspace_roots, rules)?;
 
-            for rule in rules {
-                if let Ok(diags) = rule.check(&path, &file.content) {
-                    file_diagnostics.extend(diags);
-                }
-            }
+        // Open documents were analyzed from their buffers, which are newer
+        // than what is on disk
+        let open_keys: Vec<String> = documents
+            .lock()
+            .await
+            .uris()
+            .map(|uri| uri::uri_cache_key(uri))
+            .collect();
 
-            if !file_diagnostics.is_empty() {
-                cache_lock.insert(file.path.clone(), file_diagnostics);
+        let mut changed = cache.lock().await.replace_ours(results, &open_keys);
+        for key in
+            Self::analyze_project_static(workspace_roots, project_rules, &cache, &documents).await?
+        {
+            if !changed.contains(&key) {
+                changed.push(key);
             }
         }
 
         eprintln!(
             "Analysis complete. Found diagnostics in {} files",
-            cache_lock.len()
+            cache.lock().await.files_with_diagnostics()
         );
-        Ok(())
+        Ok(changed)
     }
 
-    /// Static version of inject_diagnostics
-    async fn inject_diagnostics_static(
-        message: &mut Value,
-        cache: &Arc<Mutex<HashMap<String, Vec<Diagnostic>>>>,
-    ) -> Result<()> {
-        // Check if this is a publishDiagnostics notification
-        if let Some(method) = message.get("method").and_then(|m| m.as_str()) {
-            if method == "textDocument/publishDiagnostics" {
-                if let Some(params) = message.get_mut("params") {
-                    if let Some(uri) = params.get("uri").and_then(|u| u.as_str()) {
-                        // Convert URI to file path
-                        let file_path = uri.strip_prefix("file://").unwrap_or(uri);
-
-                        // Get cached diagnostics for this file
-                        let cache_lock = cache.lock().await;
-                        if let Some(our_diagnostics) = cache_lock.get(file_path) {
-                            // Get existing diagnostics array
-                            let diagnostics_array =
-                                params.get_mut("diagnostics").and_then(|d| d.as_array_mut());
-
-                            if let Some(diags) = diagnostics_array {
-                                // Add our diagnostics
-                                for our_diag in our_diagnostics {
-                                    diags.push(LspProxy::diagnostic_to_lsp(our_diag));
-                                }
-                            }
-                        }
-                    }
+    /// Run the project rules over the workspace, with open documents read
+    /// from their buffers
+    ///
+    /// Returns the cache keys of documents whose diagnostics may have changed.
+    async fn analyze_project_static(
+        workspace_roots: &[PathBuf],
+        project_rules: &[Arc<dyn ProjectRule>],
+        cache: &Arc<Mutex<DiagnosticsStore>>,
+        documents: &Arc<Mutex<DocumentStore>>,
+    ) -> Result<Vec<String>> {
+        let open: HashMap<String, String> = {
+            let store = documents.lock().await;
+            store
+                .uris()
+                .filter_map(|uri| {
+                    let text = store.get(uri)?.text().to_string();
+                    Some((uri::uri_cache_key(uri), text))
+                })
+                .collect()
+        };
+        let results = check_project_roots(workspace_roots, project_rules, &open)?;
+        Ok(cache.lock().await.replace_project(results))
+    }
+
+    /// Run all rules against a single file's content
+    fn check_content(path: &Path, content: &str, rules: &[Arc<dyn Rule>]) -> Vec<Diagnostic> {
+        crate::analyzer::check_file(path, content, rules)
+    }
+
+    /// Update the document store from a didOpen/didChange/didClose
+    /// notification and re-run our rules on the affected document
+    ///
+    /// Returns the cache key of the document that was re-analyzed.
+    async fn track_document_static(
+        message: &Value,
+        rules: &[Arc<dyn Rule>],
+        cache: &Arc<Mutex<DiagnosticsStore>>,
+        documents: &Arc<Mutex<DocumentStore>>,
+    ) -> Result<Option<String>> {
+        let method = message.get("method").and_then(|m| m.as_str());
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        let (uri, content) = match method {
+            Some("textDocument/didOpen") => {
+                let params: DidOpenTextDocumentParams = serde_json::from_value(params)?;
+                let uri = params.text_document.uri.to_string();
+                let mut store = documents.lock().await;
+                let document = store.open(
+                    &uri,
+                    params.text_document.version,
+                    &params.text_document.text,
+                )?;
+                let content = document.text().to_string();
+                (uri, Some(content))
+            }
+            Some("textDocument/didChange") => {
+                let params: DidChangeTextDocumentParams = serde_json::from_value(params)?;
+                let uri = params.text_document.uri.to_string();
+                let mut store = documents.lock().await;
+                let document =
+                    store.change(&uri, params.text_document.version, &params.content_changes)?;
+                let content = document.text().to_string();
+                (uri, Some(content))
+            }
+            Some("textDocument/didClose") => {
+                let params: DidCloseTextDocumentParams = serde_json::from_value(params)?;
+                let uri = params.text_document.uri.to_string();
+                documents.lock().await.close(&uri);
+                // Fall back to the saved file, if there still is one
+                let content = uri::uri_to_path(&uri).and_then(|p| std::fs::read_to_string(p).ok());
+                (uri, content)
+            }
+            _ => return Ok(None),
+        };
+
+        let key = uri::uri_cache_key(&uri);
+        let file_diagnostics = content
+            .map(|content| Self::check_content(Path::new(&key), &content, rules))
+            .unwrap_or_default();
+
+        let mut cache_lock = cache.lock().await;
+        cache_lock.remember_uri(&key, &uri);
+        cache_lock.set_ours(&key, file_diagnostics);
+
+        Ok(Some(key))
+    }
+
+    /// Record the diagnostics from a Dart server `publishDiagnostics`
+    /// notification, returning the cache key it applies to
+    async fn record_dart_diagnostics_static(
+        message: &Value,
+        cache: &Arc<Mutex<DiagnosticsStore>>,
+    ) -> Option<String> {
+        let params = message.get("params")?;
+        let uri = params.get("uri").and_then(|u| u.as_str())?;
+        let diagnostics = params
+            .get("diagnostics")
+            .and_then(|d| d.as_array())
+            .cloned()
+            .unwrap_or_default();
+        let version = params.get("version").cloned();
+
+        let key = uri::uri_cache_key(uri);
+        cache.lock().await.set_dart(&key, uri, version, diagnostics);
+        Some(key)
+    }
+
+    /// Text our diagnostics for a document were computed from, and the
+    /// session's position encoding
+    ///
+    /// Prefers the open buffer; falls back to the file on disk when
+    /// `read_disk` is set.
+    async fn source_text(
+        key: &str,
+        uri: Option<&str>,
+        read_disk: bool,
+        documents: &Arc<Mutex<DocumentStore>>,
+    ) -> (PositionEncoding, Option<String>) {
+        let (encoding, text) = {
+            let store = documents.lock().await;
+            let text = uri
+                .and_then(|uri| store.get(uri))
+                .map(|document| document.text().to_string());
+            (store.position_encoding(), text)
+        };
+        let text = match text {
+            Some(text) => Some(text),
+            None if read_disk => std::fs::read_to_string(key).ok(),
+            None => None,
+        };
+        (encoding, text)
+    }
+
+    /// Conversion of a document's diagnostics from our rules to LSP
+    async fn converter(
+        key: &str,
+        cache: &Arc<Mutex<DiagnosticsStore>>,
+        documents: &Arc<Mutex<DocumentStore>>,
+    ) -> impl Fn(&Diagnostic) -> Value {
+        let (uri, has_ours) = {
+            let cache_lock = cache.lock().await;
+            (cache_lock.uri(key), !cache_lock.ours(key).is_empty())
+        };
+        let (encoding, text) = Self::source_text(key, uri.as_deref(), has_ours, documents).await;
+        move |diag| diagnostics::to_lsp(diag, text.as_deref(), encoding)
+    }
+
+    /// Answer a `textDocument/diagnostic` request
+    async fn document_diagnostics_static(
+        params: &Value,
+        cache: &Arc<Mutex<DiagnosticsStore>>,
+        documents: &Arc<Mutex<DocumentStore>>,
+    ) -> Value {
+        let Some(uri) = params.pointer("/textDocument/uri").and_then(|u| u.as_str()) else {
+            return json!({"kind": "full", "items": []});
+        };
+        let key = uri::uri_cache_key(uri);
+        let previous = params.get("previousResultId").and_then(|id| id.as_str());
+
+        let to_lsp = Self::converter(&key, cache, documents).await;
+        cache.lock().await.report(&key, previous, to_lsp)
+    }
+
+    /// Answer a `workspace/diagnostic` request
+    ///
+    /// Reports every document with diagnostics, and the documents the
+    /// client has results for, so cleared ones are emptied.
+    async fn workspace_diagnostics_static(
+        params: &Value,
+        cache: &Arc<Mutex<DiagnosticsStore>>,
+        documents: &Arc<Mutex<DocumentStore>>,
+    ) -> Value {
+        let mut previous: HashMap<String, String> = HashMap::new();
+        {
+            let mut cache_lock = cache.lock().await;
+            let previous_ids = params.get("previousResultIds").and_then(|p| p.as_array());
+            for item in previous_ids.into_iter().flatten() {
+                if let (Some(uri), Some(id)) = (item["uri"].as_str(), item["value"].as_str()) {
+                    let key = uri::uri_cache_key(uri);
+                    cache_lock.remember_uri(&key, uri);
+                    previous.insert(key, id.to_string());
                 }
             }
         }
+
+        let mut keys = cache.lock().await.keys();
+        for key in previous.keys() {
+            if !keys.contains(key) {
+                keys.push(key.clone());
+            }
+        }
+
+        let mut items = Vec::new();
+        for key in keys {
+            let to_lsp = Self::converter(&key, cache, documents).await;
+            let previous = previous.get(&key).map(String::as_str);
+            items.extend(cache.lock().await.workspace_report(&key, previous, to_lsp));
+        }
+        json!({"items": items})
+    }
+
+    /// Compute our code actions for a `textDocument/codeAction` request
+    async fn code_actions_static(
+        params: &Value,
+        cache: &Arc<Mutex<DiagnosticsStore>>,
+        documents: &Arc<Mutex<DocumentStore>>,
+    ) -> Vec<Value> {
+        if !code_actions::wants_quick_fixes(params.pointer("/context/only")) {
+            return Vec::new();
+        }
+        let Some(uri) = params.pointer("/textDocument/uri").and_then(|u| u.as_str()) else {
+            return Vec::new();
+        };
+        let Some(range) = params
+            .get("range")
+            .and_then(|r| serde_json::from_value::<lsp_types::Range>(r.clone()).ok())
+        else {
+            return Vec::new();
+        };
+
+        let key = uri::uri_cache_key(uri);
+        let diagnostics = cache.lock().await.ours(&key);
+        if diagnostics.is_empty() {
+            return Vec::new();
+        }
+        let (encoding, text) = Self::source_text(&key, Some(uri), true, documents).await;
+
+        code_actions::code_actions(
+            uri,
+            text.as_deref(),
+            &diagnostics,
+            range,
+            encoding,
+            |diag| diagnostics::to_lsp(diag, text.as_deref(), encoding),
+        )
+    }
+
+    /// Append our actions to the Dart server's `codeAction` response
+    ///
+    /// An error response is replaced by our actions when we have any.
+    fn merge_code_actions(mut response: Value, ours: Vec<Value>) -> Value {
+        if ours.is_empty() {
+            return response;
+        }
+        if let Some(object) = response.as_object_mut() {
+            object.remove("error");
+        }
+        match response.get_mut("result").and_then(|r| r.as_array_mut()) {
+            Some(actions) => actions.extend(ours),
+            None => response["result"] = Value::Array(ours),
+        }
+        response
+    }
+
+    /// Send the merged diagnostics for a document to the client, unless the
+    /// client already has exactly this set
+    async fn publish_static<W: Write>(
+        writer: &mut W,
+        key: &str,
+        cache: &Arc<Mutex<DiagnosticsStore>>,
+        documents: &Arc<Mutex<DocumentStore>>,
+    ) -> Result<()> {
+        let to_lsp = Self::converter(key, cache, documents).await;
+        let params = cache.lock().await.publish(key, to_lsp);
+
+        if let Some(params) = params {
+            let notification = json!({
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": params,
+            });
+            Self::write_message(writer, &serde_json::to_string(&notification)?)?;
+        }
         Ok(())
     }
 }
 
+/// Every Dart file under the workspace roots, with its cache key as path
+pub(crate) fn workspace_files(workspace_roots: &[PathBuf]) -> Result<Vec<parser::DartFile>> {
+    let mut files: Vec<parser::DartFile> = Vec::new();
+    let mut seen: HashSet<String> = HashSet::new();
+    for workspace_root in workspace_roots {
+        eprintln!("Analyzing workspace: {}", workspace_root.display());
+
+        let found = parser::find_dart_files(workspace_root)?;
+        eprintln!("Found {} Dart files to analyze", found.len());
+
+        for mut file in found {
+            // Nested roots may yield the same file twice
+            file.path = uri::cache_key(Path::new(&file.path));
+            if seen.insert(file.path.clone()) {
+                files.push(file);
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Run our rules over every Dart file under the workspace roots
+///
+/// Returns the diagnostics of each file with any, keyed by cache key.
+pub(crate) fn analyze_roots(
+    workspace_roots: &[PathBuf],
+    rules: &[Arc<dyn Rule>],
+) -> Result<HashMap<String, Vec<Diagnostic>>> {
+    let mut results = HashMap::new();
+    for file in workspace_files(workspace_roots)? {
+        let file_diagnostics =
+            crate::analyzer::check_file(Path::new(&file.path), &file.content, rules);
+        results.insert(file.path, file_diagnostics);
+    }
+    results.retain(|_, diagnostics| !diagnostics.is_empty());
+    Ok(results)
+}
+
+/// Run the project rules over every Dart file under the workspace roots,
+/// reading the open documents in `open`, by cache key, from their buffers
+///
+/// Returns the diagnostics of each file with any, keyed by cache key.
+pub(crate) fn check_project_roots(
+    workspace_roots: &[PathBuf],
+    project_rules: &[Arc<dyn ProjectRule>],
+    open: &HashMap<String, String>,
+) -> Result<HashMap<String, Vec<Diagnostic>>> {
+    let mut results: HashMap<String, Vec<Diagnostic>> = HashMap::new();
+    if project_rules.is_empty() {
+        return Ok(results);
+    }
+    let mut files = workspace_files(workspace_roots)?;
+    for file in &mut files {
+        if let Some(content) = open.get(&file.path) {
+            file.content = content.clone();
+        }
+    }
+    let index = ProjectIndex::build(&files);
+    for diagnostic in crate::analyzer::check_project(&index, project_rules) {
+        results
+            .entry(diagnostic.location.file.clone())
+            .or_default()
+            .push(diagnostic);
+    }
+    Ok(results)
+}
+
 impl Clone for LspProxy {
-    /// Creates a new LspProxy with the same configuration but no process handles.
-    /// The cloned instance will need start_dart_server() called before use.
+    /// Creates a new LspProxy with the same configuration, sharing the
+    /// diagnostics and document stores. Each instance starts its own Dart
+    /// server in [`run`](LspProxy::run).
     /// This is primarily used for spawning background analysis tasks.
     fn clone(&self) -> Self {
         Self {
-            dart_process: None,
-            dart_stdin: None,
-            dart_stdout: None,
             dart_binary: self.dart_binary.clone(),
             config: self.config.clone(),
+            config_path: self.config_path.clone(),
+            trace: self.trace.clone(),
             rules: self.rules.clone(),
+            project_rules: self.project_rules.clone(),
             workspace_root: self.workspace_root.clone(),
             diagnostics_cache: Arc::clone(&self.diagnostics_cache),
+            documents: Arc::clone(&self.documents),
+            shutdown_received: false,
         }
     }
 }
 
-impl Drop for LspProxy {
-    fn drop(&mut self) {
-        if let Some(mut child) = self.dart_process.take() {
-            let _ = child.kill();
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notification(method: &str, params: Value) -> Value {
+        json!({"jsonrpc": "2.0", "method": method, "params": params})
+    }
+
+    #[tokio::test]
+    async fn test_track_document_analyzes_buffer() {
+        let rules = rules::get_all_rules();
+        let cache = Arc::new(Mutex::new(DiagnosticsStore::new()));
+        let documents = Arc::new(Mutex::new(DocumentStore::new()));
+        let uri = "file:///tmp/unsaved_buffer.dart";
+
+        let open = notification(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": uri,
+                    "languageId": "dart",
+                    "version": 1,
+                    "text": "void main() {}\n"
+                }
+            }),
+        );
+        LspProxy::track_document_static(&open, &rules, &cache, &documents)
+            .await
+            .unwrap();
+        let key = uri::uri_cache_key(uri);
+        assert!(cache.lock().await.ours(&key).is_empty());
+
+        // Insert a print call into the function body
+        let change = notification(
+            "textDocument/didChange",
+            json!({
+                "textDocument": {"uri": uri, "version": 2},
+                "contentChanges": [{
+                    "range": {
+                        "start": {"line": 0, "character": 13},
+                        "end": {"line": 0, "character": 13}
+                    },
+                    "text": "\n  print('hi');\n"
+                }]
+            }),
+        );
+        LspProxy::track_document_static(&change, &rules, &cache, &documents)
+            .await
+            .unwrap();
+
+        let cache_lock = cache.lock().await;
+        let diagnostics = cache_lock.ours(&key);
+        assert!(diagnostics.iter().any(|d| d.rule_id == "avoid_print"));
+        drop(cache_lock);
+
+        // Closing a buffer with no file on disk drops its diagnostics
+        let close = notification(
+            "textDocument/didClose",
+            json!({"textDocument": {"uri": uri}}),
+        );
+        LspProxy::track_document_static(&close, &rules, &cache, &documents)
+            .await
+            .unwrap();
+        assert_eq!(cache.lock().await.files_with_diagnostics(), 0);
+        assert!(!documents.lock().await.is_open(uri));
+    }
+
+    #[tokio::test]
+    async fn test_publish_merges_dart_diagnostics() {
+        let cache = Arc::new(Mutex::new(DiagnosticsStore::new()));
+        let uri = "file:///tmp/merged.dart";
+
+        let publish = notification(
+            "textDocument/publishDiagnostics",
+            json!({
+                "uri": uri,
+                "version": 4,
+                "diagnostics": [{
+                    "range": {
+                        "start": {"line": 0, "character": 0},
+                        "end": {"line": 0, "character": 1}
+                    },
+                    "message": "from dart"
+                }]
+            }),
+        );
+        let key = LspProxy::record_dart_diagnostics_static(&publish, &cache)
+            .await
+            .unwrap();
+        assert_eq!(key, uri::uri_cache_key(uri));
+
+        let diagnostic = Diagnostic::new(
+            "avoid_print",
+            "from us",
+            crate::error::Severity::Info,
+            crate::error::RuleCategory::Runtime,
+            crate::error::Location {
+                file: "/tmp/merged.dart".to_string(),
+                line: 2,
+                column: 3,
+                end_line: None,
+                end_column: None,
+            },
+        );
+        cache.lock().await.set_ours(&key, vec![diagnostic]);
+
+        let documents = Arc::new(Mutex::new(DocumentStore::new()));
+        let mut output = Vec::new();
+        LspProxy::publish_static(&mut output, &key, &cache, &documents)
+            .await
+            .unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let (_, body) = output.split_once("\r\n\r\n").unwrap();
+        let message: Value = serde_json::from_str(body).unwrap();
+
+        assert_eq!(message["params"]["version"], 4);
+        let diagnostics = message["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(diagnostics[0]["message"], "from dart");
+        assert_eq!(diagnostics[1]["source"], "dart-re-analyzer");
+
+        // Publishing again without changes writes nothing
+        let mut output = Vec::new();
+        LspProxy::publish_static(&mut output, &key, &cache, &documents)
+            .await
+            .unwrap();
+        assert!(output.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_workspace_diagnostics_match_encoded_uri() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("my app").join("lib");
+        std::fs::create_dir_all(&lib).unwrap();
+        std::fs::write(lib.join("café.dart"), "void main() { print('hi'); }\n").unwrap();
+
+        let rules = rules::get_all_rules();
+        let cache = Arc::new(Mutex::new(DiagnosticsStore::new()));
+        let documents = Arc::new(Mutex::new(DocumentStore::new()));
+
+        let changed = LspProxy::analyze_workspace_static(
+            &[dir.path().join("my app")],
+            &rules,
+            &rules::get_project_rules(),
+            Arc::clone(&cache),
+            documents,
+        )
+        .await
+        .unwrap();
+        assert_eq!(changed.len(), 1);
+
+        // The Dart server reports the same file through an encoded URI
+        let uri = uri::path_to_uri(&lib.join("café.dart")).unwrap();
+        assert!(uri.contains("my%20app") && uri.contains("caf%C3%A9"));
+        let publish = notification(
+            "textDocument/publishDiagnostics",
+            json!({"uri": uri, "diagnostics": []}),
+        );
+        let key = LspProxy::record_dart_diagnostics_static(&publish, &cache)
+            .await
+            .unwrap();
+        assert_eq!(key, changed[0]);
+
+        let params = cache
+            .lock()
+            .await
+            .publish(&key, |d| {
+                diagnostics::to_lsp(d, None, PositionEncoding::default())
+            })
+            .unwrap();
+        assert_eq!(params["uri"], uri);
+        let diagnostics = params["diagnostics"].as_array().unwrap();
+        assert!(diagnostics.iter().any(|d| d["code"] == "avoid_print"));
+    }
+
+    #[tokio::test]
+    async fn test_publish_converts_columns_to_negotiated_encoding() {
+        let rules = rules::get_all_rules();
+        let cache = Arc::new(Mutex::new(DiagnosticsStore::new()));
+        let documents = Arc::new(Mutex::new(DocumentStore::new()));
+        let uri = "file:///tmp/encoded_columns.dart";
+
+        // The emoji is 4 bytes but 2 UTF-16 code units
+        let open = notification(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": uri,
+                    "languageId": "dart",
+                    "version": 1,
+                    "text": "void f() { var s = '😀'; dynamic x; }\n"
+                }
+            }),
+        );
+        let key = LspProxy::track_document_static(&open, &rules, &cache, &documents)
+            .await
+            .unwrap()
+            .unwrap();
+
+        let dynamic_range = |output: Vec<u8>| -> Value {
+            let output = String::from_utf8(output).unwrap();
+            let (_, body) = output.split_once("\r\n\r\n").unwrap();
+            let message: Value = serde_json::from_str(body).unwrap();
+            message["params"]["diagnostics"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|d| d["code"] == "avoid_dynamic")
+                .unwrap()["range"]
+                .clone()
+        };
+
+        let mut output = Vec::new();
+        LspProxy::publish_static(&mut output, &key, &cache, &documents)
+            .await
+            .unwrap();
+        assert_eq!(
+            dynamic_range(output),
+            json!({
+                "start": {"line": 0, "character": 25},
+                "end": {"line": 0, "character": 32}
+            })
+        );
+
+        // Force a republish in UTF-8 by changing the negotiated encoding
+        documents
+            .lock()
+            .await
+            .set_position_encoding(PositionEncoding::Utf8);
+        let mut output = Vec::new();
+        LspProxy::publish_static(&mut output, &key, &cache, &documents)
+            .await
+            .unwrap();
+        assert_eq!(
+            dynamic_range(output),
+            json!({
+                "start": {"line": 0, "character": 27},
+                "end": {"line": 0, "character": 34}
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_code_actions_for_open_document() {
+        let rules = rules::get_all_rules();
+        let cache = Arc::new(Mutex::new(DiagnosticsStore::new()));
+        let documents = Arc::new(Mutex::new(DocumentStore::new()));
+        let uri = "file:///tmp/code_actions.dart";
+
+        let open = notification(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": uri,
+                    "languageId": "dart",
+                    "version": 1,
+                    "text": "void main() {\n  print('hi');\n}\n"
+                }
+            }),
+        );
+        LspProxy::track_document_static(&open, &rules, &cache, &documents)
+            .await
+            .unwrap();
+
+        let params = json!({
+            "textDocument": {"uri": uri},
+            "range": {"start": {"line": 1, "character": 2}, "end": {"line": 1, "character": 2}},
+            "context": {"diagnostics": []}
+        });
+        let ours = LspProxy::code_actions_static(&params, &cache, &documents).await;
+        let titles: Vec<&str> = ours.iter().filter_map(|a| a["title"].as_str()).collect();
+        assert_eq!(
+            titles,
+            vec![
+                "Ignore 'avoid_print' for this line",
+                "Ignore 'avoid_print' for this file"
+            ]
+        );
+
+        // Merged after the Dart server's own actions
+        let response = json!({"jsonrpc": "2.0", "id": 7, "result": [{"title": "Dart fix"}]});
+        let merged = LspProxy::merge_code_actions(response, ours.clone());
+        let result = merged["result"].as_array().unwrap();
+        assert_eq!(result.len(), 3);
+        assert_eq!(result[0]["title"], "Dart fix");
+
+        let response = json!({"jsonrpc": "2.0", "id": 7, "result": null});
+        let merged = LspProxy::merge_code_actions(response, ours);
+        assert_eq!(merged["result"].as_array().unwrap().len(), 2);
+
+        // Actions other than quick fixes were requested
+        let mut params = params;
+        params["context"]["only"] = json!(["refactor"]);
+        assert!(LspProxy::code_actions_static(&params, &cache, &documents)
+            .await
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_project_analysis_reads_open_buffers() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.dart"), "import 'b.dart';\n").unwrap();
+        std::fs::write(dir.path().join("b.dart"), "import 'a.dart';\n").unwrap();
+        let roots = vec![dir.path().to_path_buf()];
+        let project_rules = rules::get_project_rules();
+        let cache = Arc::new(Mutex::new(DiagnosticsStore::new()));
+        let documents = Arc::new(Mutex::new(DocumentStore::new()));
+        let b_key = uri::cache_key(&dir.path().join("b.dart"));
+
+        let changed = LspProxy::analyze_project_static(&roots, &project_rules, &cache, &documents)
+            .await
+            .unwrap();
+        assert_eq!(changed, vec![b_key.clone()]);
+        let diagnostics = cache.lock().await.ours(&b_key);
+        assert_eq!(diagnostics[0].rule_id, "import_cycles");
+
+        // An open buffer without the import breaks the cycle
+        let b_uri = uri::path_to_uri(&dir.path().join("b.dart")).unwrap();
+        documents.lock().await.open(&b_uri, 1, "\n").unwrap();
+        let changed = LspProxy::analyze_project_static(&roots, &project_rules, &cache, &documents)
+            .await
+            .unwrap();
+        assert_eq!(changed, vec![b_key.clone()]);
+        assert!(cache.lock().await.ours(&b_key).is_empty());
     }
 }
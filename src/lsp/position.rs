@@ -0,0 +1,305 @@
+//! Conversion between byte offsets and LSP positions
+//!
+//! Our rules report 1-based lines and 1-based byte columns. LSP positions are
+//! 0-based and count characters in the session's negotiated
+//! `positionEncoding`, which defaults to UTF-16 code units.
+
+use lsp_types::{Position, Range};
+use serde_json::Value;
+
+use crate::error::Location;
+
+/// Unit that LSP `character` offsets are counted in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PositionEncoding {
+    Utf8,
+    #[default]
+    Utf16,
+    Utf32,
+}
+
+impl PositionEncoding {
+    /// Parse an LSP `PositionEncodingKind`
+    pub fn from_kind(kind: &str) -> Option<Self> {
+        match kind {
+            "utf-8" => Some(Self::Utf8),
+            "utf-16" => Some(Self::Utf16),
+            "utf-32" => Some(Self::Utf32),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Utf8 => "utf-8",
+            Self::Utf16 => "utf-16",
+            Self::Utf32 => "utf-32",
+        }
+    }
+
+    /// Pick an encoding from the client's `initialize` params
+    ///
+    /// Takes the first of `capabilities.general.positionEncodings` that we
+    /// support, in the client's order of preference, falling back to UTF-16.
+    pub fn negotiate(initialize_params: &Value) -> Self {
+        initialize_params
+            .pointer("/capabilities/general/positionEncodings")
+            .and_then(|e| e.as_array())
+            .into_iter()
+            .flatten()
+            .filter_map(|e| e.as_str())
+            .find_map(Self::from_kind)
+            .unwrap_or_default()
+    }
+
+    /// The encoding a server announced in its `initialize` result
+    pub fn from_server_capabilities(capabilities: &Value) -> Self {
+        capabilities
+            .get("positionEncoding")
+            .and_then(|e| e.as_str())
+            .and_then(Self::from_kind)
+            .unwrap_or_default()
+    }
+
+    fn char_len(&self, c: char) -> usize {
+        match self {
+            Self::Utf8 => c.len_utf8(),
+            Self::Utf16 => c.len_utf16(),
+            Self::Utf32 => 1,
+        }
+    }
+}
+
+/// Convert a byte offset within a line into an LSP character offset
+///
+/// Offsets past the end of the line clamp to the line end, and offsets
+/// inside a multi-byte character round down to its start.
+pub fn byte_to_character(line: &str, byte: usize, encoding: PositionEncoding) -> u32 {
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if i + c.len_utf8() > byte {
+            break;
+        }
+        units += encoding.char_len(c);
+    }
+    units as u32
+}
+
+/// Convert an LSP character offset within a line into a byte offset
+pub fn character_to_byte(line: &str, character: u32, encoding: PositionEncoding) -> usize {
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= character as usize {
+            return i;
+        }
+        units += encoding.char_len(c);
+    }
+    line.len()
+}
+
+/// Convert an LSP position into a byte offset in `text`
+///
+/// Positions past the end of a line clamp to the line end, and lines past
+/// the end of the document clamp to the document end.
+pub fn position_to_offset(text: &str, position: Position, encoding: PositionEncoding) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(newline) => line_start += newline + 1,
+            None => return text.len(),
+        }
+    }
+
+    let line_end = text[line_start..]
+        .find('\n')
+        .map(|i| line_start + i)
+        .unwrap_or(text.len());
+
+    line_start + character_to_byte(&text[line_start..line_end], position.character, encoding)
+}
+
+/// Convert a diagnostic location into an LSP range
+///
+/// `text` is the content of the file the location points into. Without it,
+/// byte columns are passed through unchanged. Locations without an end span
+/// cover a single character.
+pub fn location_to_range(
+    location: &Location,
+    text: Option<&str>,
+    encoding: PositionEncoding,
+) -> Range {
+    let lines: Vec<&str> = text
+        .map(|t| t.split('\n').map(|l| l.trim_end_matches('\r')).collect())
+        .unwrap_or_default();
+    let line_text = |line: usize| lines.get(line).copied();
+
+    let start_line = location.line.saturating_sub(1);
+    let start_byte = location.column.saturating_sub(1);
+    let start = match line_text(start_line) {
+        Some(line) => byte_to_character(line, start_byte, encoding),
+        None => start_byte as u32,
+    };
+
+    let end = match (location.end_line, location.end_column) {
+        (Some(end_line), Some(end_column)) => {
+            let end_line = end_line.saturating_sub(1);
+            let end_byte = end_column.saturating_sub(1);
+            let character = match line_text(end_line) {
+                Some(line) => byte_to_character(line, end_byte, encoding),
+                None => end_byte as u32,
+            };
+            Position::new(end_line as u32, character)
+        }
+        _ => {
+            let width = line_text(start_line)
+                .and_then(|line| line.get(start_byte..))
+                .and_then(|rest| rest.chars().next())
+                .map(|c| encoding.char_len(c))
+                .unwrap_or(1);
+            Position::new(start_line as u32, start + width as u32)
+        }
+    };
+
+    let start = Position::new(start_line as u32, start);
+    // Guard against inverted spans from a rule
+    if (end.line, end.character) < (start.line, start.character) {
+        Range::new(start, start)
+    } else {
+        Range::new(start, end)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn location(line: usize, column: usize, end: Option<(usize, usize)>) -> Location {
+        Location {
+            file: "test.dart".to_string(),
+            line,
+            column,
+            end_line: end.map(|e| e.0),
+            end_column: end.map(|e| e.1),
+        }
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let params = json!({
+            "capabilities": {"general": {"positionEncodings": ["utf-32", "utf-16"]}}
+        });
+        assert_eq!(
+            PositionEncoding::negotiate(&params),
+            PositionEncoding::Utf32
+        );
+
+        let params = json!({
+            "capabilities": {"general": {"positionEncodings": ["latin-1", "utf-8"]}}
+        });
+        assert_eq!(PositionEncoding::negotiate(&params), PositionEncoding::Utf8);
+
+        assert_eq!(
+            PositionEncoding::negotiate(&json!({"capabilities": {}})),
+            PositionEncoding::Utf16
+        );
+    }
+
+    #[test]
+    fn test_from_server_capabilities() {
+        assert_eq!(
+            PositionEncoding::from_server_capabilities(&json!({"positionEncoding": "utf-8"})),
+            PositionEncoding::Utf8
+        );
+        assert_eq!(
+            PositionEncoding::from_server_capabilities(&json!({})),
+            PositionEncoding::Utf16
+        );
+    }
+
+    #[test]
+    fn test_byte_to_character() {
+        // 'é' is 2 bytes / 1 UTF-16 unit, '😀' is 4 bytes / 2 UTF-16 units
+        let line = "é😀x";
+        assert_eq!(byte_to_character(line, 6, PositionEncoding::Utf8), 6);
+        assert_eq!(byte_to_character(line, 6, PositionEncoding::Utf16), 3);
+        assert_eq!(byte_to_character(line, 6, PositionEncoding::Utf32), 2);
+        // Past the end clamps
+        assert_eq!(byte_to_character(line, 100, PositionEncoding::Utf16), 4);
+    }
+
+    #[test]
+    fn test_character_to_byte() {
+        let line = "é😀x";
+        assert_eq!(character_to_byte(line, 3, PositionEncoding::Utf16), 6);
+        assert_eq!(character_to_byte(line, 2, PositionEncoding::Utf32), 6);
+        assert_eq!(character_to_byte(line, 6, PositionEncoding::Utf8), 6);
+        assert_eq!(character_to_byte(line, 100, PositionEncoding::Utf16), 7);
+    }
+
+    #[test]
+    fn test_position_to_offset() {
+        let text = "var s = '😀';\nx";
+        let utf16 = PositionEncoding::Utf16;
+        assert_eq!(position_to_offset(text, Position::new(0, 9), utf16), 9);
+        assert_eq!(position_to_offset(text, Position::new(0, 11), utf16), 13);
+        assert_eq!(position_to_offset(text, Position::new(1, 0), utf16), 16);
+        assert_eq!(
+            position_to_offset(text, Position::new(5, 0), utf16),
+            text.len()
+        );
+        assert_eq!(
+            position_to_offset(text, Position::new(0, 13), PositionEncoding::Utf8),
+            13
+        );
+    }
+
+    #[test]
+    fn test_location_to_range_uses_end_span() {
+        let text = "class A {}\n  dynamic x;\n";
+        // `dynamic` spans bytes 2..9 on line 2
+        let range = location_to_range(
+            &location(2, 3, Some((2, 10))),
+            Some(text),
+            PositionEncoding::Utf16,
+        );
+        assert_eq!(range, Range::new(Position::new(1, 2), Position::new(1, 9)));
+    }
+
+    #[test]
+    fn test_location_to_range_converts_utf16() {
+        // `dynamic` follows an emoji string, so byte and UTF-16 columns differ
+        let text = "var s = '😀'; dynamic x;";
+        let start = text.find("dynamic").unwrap();
+        let location = location(1, start + 1, Some((1, start + 8)));
+
+        let range = location_to_range(&location, Some(text), PositionEncoding::Utf16);
+        assert_eq!(range.start, Position::new(0, 14));
+        assert_eq!(range.end, Position::new(0, 21));
+
+        let range = location_to_range(&location, Some(text), PositionEncoding::Utf8);
+        assert_eq!(range.start, Position::new(0, 16));
+        assert_eq!(range.end, Position::new(0, 23));
+    }
+
+    #[test]
+    fn test_location_to_range_without_end() {
+        let text = "é = 1;";
+        let range = location_to_range(&location(1, 1, None), Some(text), PositionEncoding::Utf8);
+        assert_eq!(range, Range::new(Position::new(0, 0), Position::new(0, 2)));
+
+        let range = location_to_range(&location(3, 5, None), None, PositionEncoding::Utf16);
+        assert_eq!(range, Range::new(Position::new(2, 4), Position::new(2, 5)));
+    }
+
+    #[test]
+    fn test_location_to_range_multiline() {
+        let text = "try {\n} catch (e) {\n}\n";
+        let range = location_to_range(
+            &location(2, 3, Some((3, 2))),
+            Some(text),
+            PositionEncoding::Utf16,
+        );
+        assert_eq!(range, Range::new(Position::new(1, 2), Position::new(2, 1)));
+    }
+}
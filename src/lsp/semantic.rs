@@ -6,9 +6,8 @@
 //! - Symbol resolution across files
 //! - IDE-quality diagnostics
 //!
-//! Note: This is a foundational implementation that demonstrates the architecture.
-//! Full integration requires running the Dart Analysis Server as a separate process
-//! and communicating via JSON-RPC.
+//! [`DartSemanticAnalyzer`](super::dart_semantic::DartSemanticAnalyzer)
+//! implements the trait on top of a running Dart Analysis Server.
 
 use anyhow::Result;
 use serde::{Deserialize, Serialize};
@@ -104,9 +103,15 @@ pub struct TypeInfo {
 /// Interface for semantic analysis operations
 ///
 /// This trait defines the operations that a semantic analyzer should support.
-/// The actual implementation would communicate with the Dart Analysis Server.
+/// Lines and columns are 1-based, with columns counted in bytes like the
+/// [`Location`](crate::error::Location)s our rules report.
 #[allow(dead_code)]
-pub trait SemanticAnalyzer {
+pub trait SemanticAnalyzer: Send + Sync {
+    /// Provide the current content of a file, which may differ from disk
+    fn update_file(&self, _file: &Path, _content: &str) -> Result<()> {
+        Ok(())
+    }
+
     /// Resolve the type of a symbol at a given position
     fn resolve_type(&self, file: &Path, line: usize, column: usize) -> Result<Option<TypeInfo>>;
 
@@ -130,8 +135,8 @@ pub trait SemanticAnalyzer {
 
 /// Mock implementation for testing and demonstration
 ///
-/// In production, this would be replaced with an actual LSP client that
-/// communicates with the Dart Analysis Server.
+/// Use [`DartSemanticAnalyzer`](super::dart_semantic::DartSemanticAnalyzer)
+/// to get answers from the Dart Analysis Server.
 #[allow(dead_code)]
 #[derive(Default)]
 pub struct MockSemanticAnalyzer {
@@ -0,0 +1,274 @@
+//! Proxy settings from the config file and the editor
+//!
+//! The config file loaded at startup is the base. Editors override any of
+//! its fields with a `dartReAnalyzer` section, sent in `initializationOptions`
+//! or `workspace/didChangeConfiguration`, or returned from a
+//! `workspace/configuration` request. The section uses the config file's
+//! field names, e.g. `{"dartReAnalyzer": {"max_line_length": 100}}`. The
+//! config file itself is watched so edits to it apply without a restart.
+
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+use super::uri;
+use crate::config::AnalyzerConfig;
+
+/// Name of our section in the editor's settings
+pub const SECTION: &str = "dartReAnalyzer";
+
+/// Config files picked up from the workspace root without `--config`
+pub const CONFIG_FILE_NAMES: &[&str] = &["analyzer_config.json", ".dart_analyzer_config.json"];
+
+/// Prefix of the ids of requests the proxy sends to the editor itself, so
+/// their responses are not forwarded to the Dart server
+pub const REQUEST_PREFIX: &str = "dart-re-analyzer/";
+
+/// Id of the `client/registerCapability` request for the config file watch
+pub const WATCH_REQUEST_ID: &str = "dart-re-analyzer/watchConfig";
+
+/// Config file settings with the editor's overrides applied
+pub struct Settings {
+    file_config: AnalyzerConfig,
+    config_path: Option<PathBuf>,
+    overrides: Value,
+    effective: AnalyzerConfig,
+}
+
+impl Settings {
+    /// Start from `config`, loaded from `config_path` if it came from a file
+    pub fn new(config: AnalyzerConfig, config_path: Option<PathBuf>) -> Self {
+        Self {
+            effective: config.clone(),
+            file_config: config,
+            config_path,
+            overrides: Value::Null,
+        }
+    }
+
+    /// The settings in effect
+    pub fn config(&self) -> &AnalyzerConfig {
+        &self.effective
+    }
+
+    /// The config file, once known
+    pub fn config_path(&self) -> Option<&Path> {
+        self.config_path.as_deref()
+    }
+
+    /// Replace the editor's overrides with the `dartReAnalyzer` section
+    ///
+    /// `null` clears them. Returns whether the settings in effect changed;
+    /// invalid overrides are rejected and leave them unchanged.
+    pub fn set_overrides(&mut self, section: Value) -> Result<bool> {
+        let previous = std::mem::replace(&mut self.overrides, section);
+        self.update().inspect_err(|_| self.overrides = previous)
+    }
+
+    /// Whether `path` is the config file, or would be picked up as one when
+    /// created in a workspace root
+    pub fn is_config_file(&self, path: &Path, workspace_roots: &[PathBuf]) -> bool {
+        let key = uri::cache_key(path);
+        if let Some(config_path) = &self.config_path {
+            return uri::cache_key(config_path) == key;
+        }
+        let named_like_config = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| CONFIG_FILE_NAMES.contains(&n));
+        named_like_config
+            && path.parent().is_some_and(|dir| {
+                workspace_roots
+                    .iter()
+                    .any(|root| uri::cache_key(root) == uri::cache_key(dir))
+            })
+    }
+
+    /// Reload the config file after it changed on disk
+    ///
+    /// A deleted config file falls back to the defaults. Returns whether the
+    /// settings in effect changed.
+    pub fn reload_file(&mut self, path: &Path) -> Result<bool> {
+        self.file_config = if path.exists() {
+            AnalyzerConfig::load_from_file(path)
+                .with_context(|| format!("Failed to reload {}", path.display()))?
+        } else {
+            AnalyzerConfig::default()
+        };
+        self.config_path = Some(path.to_path_buf());
+        self.update()
+    }
+
+    fn update(&mut self) -> Result<bool> {
+        let mut merged = serde_json::to_value(&self.file_config)?;
+        merge(&mut merged, &self.overrides);
+        let effective: AnalyzerConfig = serde_json::from_value(merged)
+            .with_context(|| format!("Invalid '{}' settings", SECTION))?;
+
+        let changed = effective != self.effective;
+        self.effective = effective;
+        Ok(changed)
+    }
+}
+
+/// Recursively merge the fields of `overrides` into `base`
+fn merge(base: &mut Value, overrides: &Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (_, Value::Null) => {}
+        (base, overrides) => *base = overrides.clone(),
+    }
+}
+
+/// Our section of a settings object, if it has one
+pub fn section(settings: &Value) -> Option<Value> {
+    settings.get(SECTION).filter(|s| !s.is_null()).cloned()
+}
+
+/// Whether the editor answers `workspace/configuration` requests
+pub fn supports_configuration(initialize_params: &Value) -> bool {
+    initialize_params.pointer("/capabilities/workspace/configuration") == Some(&json!(true))
+}
+
+/// Whether the editor lets us register file watches
+pub fn supports_file_watches(initialize_params: &Value) -> bool {
+    initialize_params.pointer("/capabilities/workspace/didChangeWatchedFiles/dynamicRegistration")
+        == Some(&json!(true))
+}
+
+/// `workspace/configuration` request for our section
+pub fn configuration_request(sequence: u64) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "id": format!("{}configuration/{}", REQUEST_PREFIX, sequence),
+        "method": "workspace/configuration",
+        "params": {"items": [{"section": SECTION}]},
+    })
+}
+
+/// Whether a response from the editor answers one of our
+/// `workspace/configuration` requests
+pub fn is_configuration_response(id: &str) -> bool {
+    id.starts_with(&format!("{}configuration/", REQUEST_PREFIX))
+}
+
+/// `client/registerCapability` request watching the config file
+///
+/// Watches the `--config` file when there is one, and otherwise any file
+/// named like a config file.
+pub fn watch_request(config_path: Option<&Path>) -> Value {
+    let watchers: Vec<Value> = match config_path {
+        Some(path) => vec![json!({"globPattern": uri::absolute(path).to_string_lossy()})],
+        None => CONFIG_FILE_NAMES
+            .iter()
+            .map(|name| json!({"globPattern": format!("**/{}", name)}))
+            .collect(),
+    };
+    json!({
+        "jsonrpc": "2.0",
+        "id": WATCH_REQUEST_ID,
+        "method": "client/registerCapability",
+        "params": {"registrations": [{
+            "id": WATCH_REQUEST_ID,
+            "method": "workspace/didChangeWatchedFiles",
+            "registerOptions": {"watchers": watchers},
+        }]},
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overrides_apply_over_file_config() {
+        let mut settings = Settings::new(AnalyzerConfig::default(), None);
+        let changed = settings
+            .set_overrides(json!({
+                "max_line_length": 80,
+                "style_rules": {"disabled_rules": ["line_length"]}
+            }))
+            .unwrap();
+        assert!(changed);
+        assert_eq!(settings.config().max_line_length, 80);
+        assert_eq!(
+            settings.config().style_rules.disabled_rules,
+            vec!["line_length"]
+        );
+        // Fields the section leaves out keep the file's values
+        assert!(settings.config().style_rules.enabled);
+
+        // The same section again changes nothing
+        let unchanged = settings
+            .set_overrides(json!({
+                "max_line_length": 80,
+                "style_rules": {"disabled_rules": ["line_length"]}
+            }))
+            .unwrap();
+        assert!(!unchanged);
+
+        assert!(settings.set_overrides(Value::Null).unwrap());
+        assert_eq!(settings.config().max_line_length, 120);
+    }
+
+    #[test]
+    fn test_invalid_overrides_are_rejected() {
+        let mut settings = Settings::new(AnalyzerConfig::default(), None);
+        settings
+            .set_overrides(json!({"max_line_length": 100}))
+            .unwrap();
+        assert!(settings
+            .set_overrides(json!({"max_line_length": "wide"}))
+            .is_err());
+        assert_eq!(settings.config().max_line_length, 100);
+    }
+
+    #[test]
+    fn test_reload_config_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("analyzer_config.json");
+        let mut settings = Settings::new(AnalyzerConfig::default(), None);
+        let roots = vec![dir.path().to_path_buf()];
+
+        assert!(settings.is_config_file(&path, &roots));
+        assert!(!settings.is_config_file(&dir.path().join("lib/analyzer_config.json"), &roots));
+
+        std::fs::write(&path, r#"{"max_line_length": 90}"#).unwrap();
+        assert!(settings.reload_file(&path).unwrap());
+        assert_eq!(settings.config().max_line_length, 90);
+
+        // Overrides still win over the file
+        settings
+            .set_overrides(json!({"max_line_length": 70}))
+            .unwrap();
+        std::fs::write(&path, r#"{"max_line_length": 95}"#).unwrap();
+        assert!(!settings.reload_file(&path).unwrap());
+        assert_eq!(settings.config().max_line_length, 70);
+
+        // Without the file, the defaults apply
+        std::fs::remove_file(&path).unwrap();
+        settings.reload_file(&path).unwrap();
+        settings.set_overrides(Value::Null).unwrap();
+        assert_eq!(settings.config().max_line_length, 120);
+    }
+
+    #[test]
+    fn test_requests_to_the_editor() {
+        let request = configuration_request(3);
+        assert!(is_configuration_response(request["id"].as_str().unwrap()));
+        assert_eq!(request["params"]["items"][0]["section"], SECTION);
+
+        let watch = watch_request(None);
+        let watchers = &watch["params"]["registrations"][0]["registerOptions"]["watchers"];
+        assert_eq!(watchers[0]["globPattern"], "**/analyzer_config.json");
+    }
+}
@@ -0,0 +1,439 @@
+//! Standalone language server that works without the Dart SDK
+//!
+//! Serves our diagnostics, code actions, document symbols and folding
+//! ranges straight from tree-sitter, over the `lsp-server` crate. Useful in
+//! CI images and editors without a Dart installation, where the proxy in
+//! [`LspProxy`](super::LspProxy) cannot start.
+
+use anyhow::{Context, Result};
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, RequestId, Response};
+use lsp_types::{
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::code_actions;
+use super::diagnostics::{self, DiagnosticsStore};
+use super::documents::DocumentStore;
+use super::framing::{self, FrameReader};
+use super::position::PositionEncoding;
+use super::symbols;
+use super::uri;
+use crate::analyzer::{self, ProjectRule, Rule};
+use crate::config::AnalyzerConfig;
+use crate::error::Diagnostic;
+use crate::rules;
+
+/// Language server backed only by dart-re-analyzer
+pub struct StandaloneServer {
+    rules: Vec<Arc<dyn Rule>>,
+    project_rules: Vec<Arc<dyn ProjectRule>>,
+    workspace_root: PathBuf,
+    workspace_roots: Vec<PathBuf>,
+    documents: DocumentStore,
+    diagnostics: DiagnosticsStore,
+    /// Whether the client re-pulls diagnostics when asked to
+    refresh_supported: bool,
+    /// Set while the client has not answered our last diagnostic refresh
+    refresh_in_flight: bool,
+}
+
+impl StandaloneServer {
+    pub fn new(config: AnalyzerConfig, workspace_root: PathBuf) -> Self {
+        let rules = rules::get_rules_for_config(&config);
+        let project_rules = rules::get_project_rules_for_config(&config);
+
+        Self {
+            rules,
+            project_rules,
+            workspace_roots: vec![workspace_root.clone()],
+            workspace_root,
+            documents: DocumentStore::new(),
+            diagnostics: DiagnosticsStore::new(),
+            refresh_supported: false,
+            refresh_in_flight: false,
+        }
+    }
+
+    /// Serve over stdin/stdout until the client exits
+    pub fn run(self) -> Result<()> {
+        let (connection, client) = Connection::memory();
+        let Connection { sender, receiver } = client;
+
+        // Bridge stdio to the in-memory connection with our framing
+        let reader = std::thread::spawn(move || -> Result<()> {
+            let mut frames = FrameReader::new(std::io::stdin().lock());
+            while let Some(content) = frames.read_message()? {
+                let message: Message =
+                    serde_json::from_str(&content).context("Invalid message from client")?;
+                let exit = matches!(&message, Message::Notification(n) if n.method == "exit");
+                if sender.send(message).is_err() || exit {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        let writer = std::thread::spawn(move || -> Result<()> {
+            let mut stdout = std::io::stdout().lock();
+            for message in receiver {
+                framing::write_message(&mut stdout, &serde_json::to_string(&message)?)?;
+            }
+            Ok(())
+        });
+
+        self.serve(&connection)?;
+        drop(connection);
+        writer.join().expect("stdout thread panicked")?;
+        reader.join().expect("stdin thread panicked")?;
+        Ok(())
+    }
+
+    /// Serve a connection until the client sends `shutdown` and `exit`
+    pub fn serve(mut self, connection: &Connection) -> Result<()> {
+        let (id, params) = connection.initialize_start()?;
+
+        let encoding = PositionEncoding::negotiate(&params);
+        self.documents.set_position_encoding(encoding);
+        self.workspace_roots = uri::workspace_roots(&params, &self.workspace_root);
+        let pull = diagnostics::supports_pull(&params);
+        self.diagnostics.set_pull(pull);
+        self.refresh_supported = diagnostics::supports_refresh(&params);
+
+        connection.initialize_finish(
+            id,
+            json!({
+                "capabilities": Self::capabilities(encoding, pull),
+                "serverInfo": {
+                    "name": "dart-re-analyzer",
+                    "version": env!("CARGO_PKG_VERSION"),
+                },
+            }),
+        )?;
+
+        // `initialize_finish` has already consumed the `initialized` notification
+        self.analyze_workspace(connection)?;
+        self.request_refresh(connection)?;
+
+        for message in &connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if connection.handle_shutdown(&request)? {
+                        return Ok(());
+                    }
+                    let response = self.handle_request(request);
+                    connection.sender.send(response.into())?;
+                }
+                Message::Notification(notification) => {
+                    self.handle_notification(connection, notification)?;
+                }
+                Message::Response(response) => {
+                    if response.id == RequestId::from(diagnostics::REFRESH_REQUEST_ID.to_string()) {
+                        self.refresh_in_flight = false;
+                    }
+                }
+            }
+            self.request_refresh(connection)?;
+        }
+
+        Ok(())
+    }
+
+    /// Ask the client to pull diagnostics again if they changed
+    fn request_refresh(&mut self, connection: &Connection) -> Result<()> {
+        if self.refresh_supported && !self.refresh_in_flight && self.diagnostics.take_refresh() {
+            let request = Request::new(
+                diagnostics::REFRESH_REQUEST_ID.to_string().into(),
+                "workspace/diagnostic/refresh".to_string(),
+                Value::Null,
+            );
+            connection.sender.send(request.into())?;
+            self.refresh_in_flight = true;
+        }
+        Ok(())
+    }
+
+    fn capabilities(encoding: PositionEncoding, pull_diagnostics: bool) -> Value {
+        let mut capabilities = json!({
+            "positionEncoding": encoding.as_str(),
+            "textDocumentSync": {
+                "openClose": true,
+                "change": 2, // Incremental
+                "save": true,
+            },
+            "codeActionProvider": {
+                "codeActionKinds": [code_actions::QUICK_FIX],
+            },
+            "documentSymbolProvider": true,
+            "foldingRangeProvider": true,
+            "workspace": {
+                "workspaceFolders": {
+                    "supported": true,
+                    "changeNotifications": true,
+                },
+            },
+        });
+        if pull_diagnostics {
+            capabilities["diagnosticProvider"] = diagnostics::provider_capability();
+        }
+        capabilities
+    }
+
+    fn handle_request(&mut self, request: Request) -> Response {
+        let result = match request.method.as_str() {
+            "textDocument/codeAction" => Ok(self.code_actions(&request.params)),
+            "textDocument/documentSymbol" => self.with_tree(&request.params, |tree, text, enc| {
+                json!(symbols::document_symbols(tree, text, enc))
+            }),
+            "textDocument/foldingRange" => self.with_tree(&request.params, |tree, _, _| {
+                json!(symbols::folding_ranges(tree))
+            }),
+            "textDocument/diagnostic" => Ok(self.document_diagnostics(&request.params)),
+            "workspace/diagnostic" => Ok(self.workspace_diagnostics(&request.params)),
+            _ => {
+                return Response::new_err(
+                    request.id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("Unhandled method: {}", request.method),
+                )
+            }
+        };
+
+        match result {
+            Ok(result) => Response::new_ok(request.id, result),
+            Err(e) => Response::new_err(request.id, ErrorCode::InvalidParams as i32, e.to_string()),
+        }
+    }
+
+    fn handle_notification(
+        &mut self,
+        connection: &Connection,
+        notification: Notification,
+    ) -> Result<()> {
+        match notification.method.as_str() {
+            "workspace/didChangeWorkspaceFolders" => {
+                if let Some(event) = notification.params.get("event") {
+                    uri::apply_folder_changes(&mut self.workspace_roots, event);
+                    self.analyze_workspace(connection)?;
+                }
+            }
+            // Project rules look at every file, so they run on saves rather
+            // than on each edit
+            "textDocument/didSave" => self.analyze_project(connection)?,
+            "textDocument/didOpen" | "textDocument/didChange" | "textDocument/didClose" => {
+                match self.track_document(&notification) {
+                    Ok(key) => self.publish(connection, &key)?,
+                    Err(e) => eprintln!("Error tracking document: {}", e),
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Analyze every workspace root and publish what changed
+    fn analyze_workspace(&mut self, connection: &Connection) -> Result<()> {
+        let results = super::analyze_roots(&self.workspace_roots, &self.rules)?;
+        let open_keys: Vec<String> = self
+            .documents
+            .uris()
+            .map(|u| uri::uri_cache_key(u))
+            .collect();
+        for key in self.diagnostics.replace_ours(results, &open_keys) {
+            self.publish(connection, &key)?;
+        }
+        self.analyze_project(connection)
+    }
+
+    /// Run the project rules over every workspace root, with open documents
+    /// read from their buffers, and publish what changed
+    fn analyze_project(&mut self, connection: &Connection) -> Result<()> {
+        let open: HashMap<String, String> = self
+            .documents
+            .uris()
+            .filter_map(|u| {
+                let text = self.documents.get(u)?.text().to_string();
+                Some((uri::uri_cache_key(u), text))
+            })
+            .collect();
+        let results =
+            super::check_project_roots(&self.workspace_roots, &self.project_rules, &open)?;
+        for key in self.diagnostics.replace_project(results) {
+            self.publish(connection, &key)?;
+        }
+        Ok(())
+    }
+
+    /// Update the document store and re-run our rules on the document
+    ///
+    /// Returns the cache key of the document that was re-analyzed.
+    fn track_document(&mut self, notification: &Notification) -> Result<String> {
+        let params = notification.params.clone();
+        let (uri, content) = match notification.method.as_str() {
+            "textDocument/didOpen" => {
+                let params: DidOpenTextDocumentParams = serde_json::from_value(params)?;
+                let uri = params.text_document.uri.to_string();
+                let document = self.documents.open(
+                    &uri,
+                    params.text_document.version,
+                    &params.text_document.text,
+                )?;
+                (uri, Some(document.text().to_string()))
+            }
+            "textDocument/didChange" => {
+                let params: DidChangeTextDocumentParams = serde_json::from_value(params)?;
+                let uri = params.text_document.uri.to_string();
+                let document = self.documents.change(
+                    &uri,
+                    params.text_document.version,
+                    &params.content_changes,
+                )?;
+                (uri, Some(document.text().to_string()))
+            }
+            _ => {
+                let params: DidCloseTextDocumentParams = serde_json::from_value(params)?;
+                let uri = params.text_document.uri.to_string();
+                self.documents.close(&uri);
+                // Fall back to the saved file, if there still is one
+                let content = uri::uri_to_path(&uri).and_then(|p| std::fs::read_to_string(p).ok());
+                (uri, content)
+            }
+        };
+
+        let key = uri::uri_cache_key(&uri);
+        let file_diagnostics = content
+            .map(|content| analyzer::check_file(Path::new(&key), &content, &self.rules))
+            .unwrap_or_default();
+        self.diagnostics.remember_uri(&key, &uri);
+        self.diagnostics.set_ours(&key, file_diagnostics);
+        Ok(key)
+    }
+
+    /// Text our diagnostics for a document were computed from
+    fn source_text(&self, key: &str) -> Option<String> {
+        self.diagnostics
+            .uri(key)
+            .and_then(|uri| self.documents.get(&uri).map(|d| d.text().to_string()))
+            .or_else(|| std::fs::read_to_string(key).ok())
+    }
+
+    /// Conversion of a document's diagnostics to LSP
+    fn converter(&self, key: &str) -> impl Fn(&Diagnostic) -> Value {
+        let encoding = self.documents.position_encoding();
+        let text = if self.diagnostics.ours(key).is_empty() {
+            None
+        } else {
+            self.source_text(key)
+        };
+        move |diag| diagnostics::to_lsp(diag, text.as_deref(), encoding)
+    }
+
+    fn publish(&mut self, connection: &Connection, key: &str) -> Result<()> {
+        let to_lsp = self.converter(key);
+        let params = self.diagnostics.publish(key, to_lsp);
+        if let Some(params) = params {
+            let notification = Notification::new("textDocument/publishDiagnostics".into(), params);
+            connection.sender.send(notification.into())?;
+        }
+        Ok(())
+    }
+
+    fn document_diagnostics(&self, params: &Value) -> Value {
+        let Some(uri) = params.pointer("/textDocument/uri").and_then(|u| u.as_str()) else {
+            return json!({"kind": "full", "items": []});
+        };
+        let key = uri::uri_cache_key(uri);
+        let previous = params.get("previousResultId").and_then(|id| id.as_str());
+        self.diagnostics
+            .report(&key, previous, self.converter(&key))
+    }
+
+    /// Report every document with diagnostics, and the documents the client
+    /// has results for, so cleared ones are emptied
+    fn workspace_diagnostics(&mut self, params: &Value) -> Value {
+        let mut previous: HashMap<String, String> = HashMap::new();
+        let previous_ids = params.get("previousResultIds").and_then(|p| p.as_array());
+        for item in previous_ids.into_iter().flatten() {
+            if let (Some(uri), Some(id)) = (item["uri"].as_str(), item["value"].as_str()) {
+                let key = uri::uri_cache_key(uri);
+                self.diagnostics.remember_uri(&key, uri);
+                previous.insert(key, id.to_string());
+            }
+        }
+
+        let mut keys = self.diagnostics.keys();
+        for key in previous.keys() {
+            if !keys.contains(key) {
+                keys.push(key.clone());
+            }
+        }
+        let items: Vec<Value> = keys
+            .iter()
+            .filter_map(|key| {
+                let previous = previous.get(key).map(String::as_str);
+                self.diagnostics
+                    .workspace_report(key, previous, self.converter(key))
+            })
+            .collect();
+        json!({"items": items})
+    }
+
+    fn code_actions(&self, params: &Value) -> Value {
+        if !code_actions::wants_quick_fixes(params.pointer("/context/only")) {
+            return json!([]);
+        }
+        let Some(uri) = params.pointer("/textDocument/uri").and_then(|u| u.as_str()) else {
+            return json!([]);
+        };
+        let Some(range) = params
+            .get("range")
+            .and_then(|r| serde_json::from_value::<lsp_types::Range>(r.clone()).ok())
+        else {
+            return json!([]);
+        };
+
+        let key = uri::uri_cache_key(uri);
+        let diagnostics = self.diagnostics.ours(&key);
+        if diagnostics.is_empty() {
+            return json!([]);
+        }
+        let encoding = self.documents.position_encoding();
+        let text = self.source_text(&key);
+
+        json!(code_actions::code_actions(
+            uri,
+            text.as_deref(),
+            &diagnostics,
+            range,
+            encoding,
+            |diag| diagnostics::to_lsp(diag, text.as_deref(), encoding),
+        ))
+    }
+
+    /// Run `f` on the parse tree of an open document, or of the file on disk
+    fn with_tree(
+        &self,
+        params: &Value,
+        f: impl Fn(&tree_sitter::Tree, &str, PositionEncoding) -> Value,
+    ) -> Result<Value> {
+        let uri = params
+            .pointer("/textDocument/uri")
+            .and_then(|u| u.as_str())
+            .context("Missing textDocument.uri")?;
+        let encoding = self.documents.position_encoding();
+
+        if let Some(document) = self.documents.get(uri) {
+            if let Some(tree) = document.tree() {
+                return Ok(f(tree, document.text(), encoding));
+            }
+        }
+
+        let path = uri::uri_to_path(uri).context("Not a file URI")?;
+        let text = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let tree = crate::treesitter::parse_dart(&text)?;
+        Ok(f(&tree, &text, encoding))
+    }
+}
@@ -0,0 +1,566 @@
+//! Document symbols and folding ranges from the tree-sitter parse tree
+//!
+//! Used by the standalone language server, which has no Dart Analysis
+//! Server to answer `textDocument/documentSymbol` and
+//! `textDocument/foldingRange`.
+
+use lsp_types::{FoldingRangeKind, Position, Range, SymbolKind};
+use serde_json::{json, Value};
+use tree_sitter::{Node, Point, Tree};
+
+use super::position::{self, PositionEncoding};
+
+/// Converts tree-sitter points into LSP positions
+struct Lines<'a> {
+    lines: Vec<&'a str>,
+    encoding: PositionEncoding,
+}
+
+impl<'a> Lines<'a> {
+    fn new(source: &'a str, encoding: PositionEncoding) -> Self {
+        Self {
+            lines: source.split('\n').collect(),
+            encoding,
+        }
+    }
+
+    fn position(&self, point: Point) -> Position {
+        let character = match self.lines.get(point.row) {
+            Some(line) => position::byte_to_character(line, point.column, self.encoding),
+            None => point.column as u32,
+        };
+        Position::new(point.row as u32, character)
+    }
+
+    fn range(&self, start: Node, end: Node) -> Range {
+        Range::new(
+            self.position(start.start_position()),
+            self.position(end.end_position()),
+        )
+    }
+}
+
+/// Hierarchical `DocumentSymbol`s for a parsed document
+pub fn document_symbols(tree: &Tree, source: &str, encoding: PositionEncoding) -> Vec<Value> {
+    let lines = Lines::new(source, encoding);
+    container_symbols(tree.root_node(), source, &lines)
+}
+
+fn text<'a>(node: Node, source: &'a str) -> &'a str {
+    node.utf8_text(source.as_bytes()).unwrap_or_default()
+}
+
+fn symbol(
+    name: &str,
+    detail: Option<&str>,
+    kind: SymbolKind,
+    range: Range,
+    selection: Range,
+    children: Vec<Value>,
+) -> Value {
+    let mut symbol = json!({
+        "name": name,
+        "kind": kind,
+        "range": range,
+        "selectionRange": selection,
+    });
+    if let Some(detail) = detail {
+        symbol["detail"] = json!(detail);
+    }
+    if !children.is_empty() {
+        symbol["children"] = json!(children);
+    }
+    symbol
+}
+
+fn first_child_of_kind<'a>(node: Node<'a>, kinds: &[&str]) -> Option<Node<'a>> {
+    let mut cursor = node.walk();
+    let child = node
+        .named_children(&mut cursor)
+        .find(|c| kinds.contains(&c.kind()));
+    child
+}
+
+/// Name node of a declaration: its `name` field, or its first identifier
+fn name_node(node: Node) -> Option<Node> {
+    node.child_by_field_name("name")
+        .or_else(|| first_child_of_kind(node, &["identifier", "type_identifier"]))
+}
+
+/// Symbols declared directly in the program or a class-like body
+fn container_symbols(container: Node, source: &str, lines: &Lines) -> Vec<Value> {
+    let mut symbols = Vec::new();
+    let mut cursor = container.walk();
+    let children: Vec<Node> = container.named_children(&mut cursor).collect();
+
+    for (i, node) in children.iter().enumerate() {
+        let node = *node;
+        match node.kind() {
+            "class_definition" | "mixin_declaration" | "extension_declaration" => {
+                let Some(name) = name_node(node) else {
+                    continue;
+                };
+                let (kind, detail) = match node.kind() {
+                    "class_definition" => (SymbolKind::CLASS, None),
+                    "mixin_declaration" => (SymbolKind::CLASS, Some("mixin")),
+                    _ => (SymbolKind::NAMESPACE, Some("extension")),
+                };
+                let children = first_child_of_kind(node, &["class_body", "extension_body"])
+                    .map(|body| container_symbols(body, source, lines))
+                    .unwrap_or_default();
+                symbols.push(symbol(
+                    text(name, source),
+                    detail,
+                    kind,
+                    lines.range(node, node),
+                    lines.range(name, name),
+                    children,
+                ));
+            }
+            "enum_declaration" => {
+                let Some(name) = name_node(node) else {
+                    continue;
+                };
+                let mut constants = Vec::new();
+                if let Some(body) = node.child_by_field_name("body") {
+                    let mut cursor = body.walk();
+                    for constant in body.named_children(&mut cursor) {
+                        if constant.kind() != "enum_constant" {
+                            continue;
+                        }
+                        if let Some(constant_name) = name_node(constant) {
+                            constants.push(symbol(
+                                text(constant_name, source),
+                                None,
+                                SymbolKind::ENUM_MEMBER,
+                                lines.range(constant, constant),
+                                lines.range(constant_name, constant_name),
+                                Vec::new(),
+                            ));
+                        }
+                    }
+                }
+                symbols.push(symbol(
+                    text(name, source),
+                    None,
+                    SymbolKind::ENUM,
+                    lines.range(node, node),
+                    lines.range(name, name),
+                    constants,
+                ));
+            }
+            "type_alias" => {
+                if let Some(name) = name_node(node) {
+                    symbols.push(symbol(
+                        text(name, source),
+                        Some("typedef"),
+                        SymbolKind::CLASS,
+                        lines.range(node, node),
+                        lines.range(name, name),
+                        Vec::new(),
+                    ));
+                }
+            }
+            // Top-level functions
+            "lambda_expression" => {
+                if let Some(signature) = node.child_by_field_name("parameters") {
+                    symbols.extend(signature_symbol(signature, node, source, lines, false));
+                }
+            }
+            "function_signature" | "getter_signature" | "setter_signature" => {
+                // The body, when present, is the next sibling
+                let end = children
+                    .get(i + 1)
+                    .filter(|next| next.kind() == "function_body")
+                    .copied()
+                    .unwrap_or(node);
+                symbols.extend(signature_symbol(node, end, source, lines, false));
+            }
+            "local_variable_declaration" | "top_level_variable_declaration" => {
+                symbols.extend(variable_symbols(node, node, source, lines, false));
+            }
+            "class_member_definition" => {
+                symbols.extend(member_symbols(node, source, lines));
+            }
+            _ => {}
+        }
+    }
+
+    symbols
+}
+
+/// Symbol for a function, method, getter, setter or constructor signature
+///
+/// `end` is the last node the symbol's range should cover.
+fn signature_symbol(
+    signature: Node,
+    end: Node,
+    source: &str,
+    lines: &Lines,
+    in_class: bool,
+) -> Option<Value> {
+    let range = lines.range(signature, end);
+    let kind = signature.kind();
+
+    if kind.ends_with("constructor_signature") {
+        // Named constructors have several `name` children: `Foo`, `.`, `named`
+        let mut cursor = signature.walk();
+        let names: Vec<Node> = signature
+            .children_by_field_name("name", &mut cursor)
+            .collect();
+        let (first, last) = match (names.first(), names.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => {
+                let name = name_node(signature)?;
+                (name, name)
+            }
+        };
+        let name: String = names.iter().map(|n| text(*n, source)).collect();
+        let name = if name.is_empty() {
+            text(first, source).to_string()
+        } else {
+            name
+        };
+        return Some(symbol(
+            &name,
+            None,
+            SymbolKind::CONSTRUCTOR,
+            range,
+            lines.range(first, last),
+            Vec::new(),
+        ));
+    }
+
+    let signature = match kind {
+        "method_signature" => signature.named_child(0)?,
+        _ => signature,
+    };
+    if signature.kind().ends_with("constructor_signature") {
+        return signature_symbol(signature, end, source, lines, in_class);
+    }
+
+    let name = name_node(signature)?;
+    let (kind, detail) = match signature.kind() {
+        "getter_signature" => (SymbolKind::PROPERTY, Some("getter")),
+        "setter_signature" => (SymbolKind::PROPERTY, Some("setter")),
+        _ if in_class => (SymbolKind::METHOD, None),
+        _ => (SymbolKind::FUNCTION, None),
+    };
+    Some(symbol(
+        text(name, source),
+        detail,
+        kind,
+        range,
+        lines.range(name, name),
+        Vec::new(),
+    ))
+}
+
+/// Symbols for the variables or fields declared by a declaration
+fn variable_symbols(
+    declaration: Node,
+    range_node: Node,
+    source: &str,
+    lines: &Lines,
+    in_class: bool,
+) -> Vec<Value> {
+    let declaration_text = text(declaration, source);
+    let is_const =
+        declaration_text.starts_with("const ") || declaration_text.starts_with("static const ");
+    let kind = match (is_const, in_class) {
+        (true, _) => SymbolKind::CONSTANT,
+        (false, true) => SymbolKind::FIELD,
+        (false, false) => SymbolKind::VARIABLE,
+    };
+
+    let mut names = Vec::new();
+    collect_declared_names(declaration, &mut names);
+
+    names
+        .into_iter()
+        .map(|name| {
+            symbol(
+                text(name, source),
+                None,
+                kind,
+                lines.range(range_node, range_node),
+                lines.range(name, name),
+                Vec::new(),
+            )
+        })
+        .collect()
+}
+
+fn collect_declared_names<'a>(node: Node<'a>, names: &mut Vec<Node<'a>>) {
+    match node.kind() {
+        "initialized_variable_definition" => {
+            if let Some(name) = node.child_by_field_name("name") {
+                names.push(name);
+            }
+        }
+        "initialized_identifier" | "static_final_declaration" => {
+            if let Some(name) = first_child_of_kind(node, &["identifier"]) {
+                names.push(name);
+            }
+            return;
+        }
+        // Initializer expressions declare nothing
+        _ if node.kind().ends_with("expression") || node.kind().ends_with("literal") => return,
+        _ => {}
+    }
+
+    let mut cursor = node.walk();
+    for child in node.named_children(&mut cursor) {
+        collect_declared_names(child, names);
+    }
+}
+
+/// Symbols for a `class_member_definition`
+fn member_symbols(member: Node, source: &str, lines: &Lines) -> Vec<Value> {
+    let Some(first) = member.named_child(0) else {
+        return Vec::new();
+    };
+
+    match first.kind() {
+        "method_signature" => signature_symbol(first, member, source, lines, true)
+            .into_iter()
+            .collect(),
+        "declaration" => {
+            if let Some(signature) = first_child_of_kind(
+                first,
+                &[
+                    "constructor_signature",
+                    "constant_constructor_signature",
+                    "factory_constructor_signature",
+                    "redirecting_factory_constructor_signature",
+                    "function_signature",
+                    "getter_signature",
+                    "setter_signature",
+                ],
+            ) {
+                return signature_symbol(signature, member, source, lines, true)
+                    .into_iter()
+                    .collect();
+            }
+            variable_symbols(first, member, source, lines, true)
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Folding ranges for bodies, collection literals, import groups and
+/// multi-line comments
+pub fn folding_ranges(tree: &Tree) -> Vec<Value> {
+    let mut ranges: Vec<(usize, usize, Option<FoldingRangeKind>)> = Vec::new();
+    collect_folds(tree.root_node(), &mut ranges);
+
+    // Runs of imports and comments fold as a group
+    let root = tree.root_node();
+    let mut cursor = root.walk();
+    let mut group: Option<(usize, usize, FoldingRangeKind)> = None;
+    let close = |group: &mut Option<(usize, usize, FoldingRangeKind)>, ranges: &mut Vec<_>| {
+        if let Some((start, end, kind)) = group.take() {
+            if end > start {
+                ranges.push((start, end, Some(kind)));
+            }
+        }
+    };
+    for node in root.children(&mut cursor) {
+        let kind = match node.kind() {
+            "import_or_export" => Some(FoldingRangeKind::Imports),
+            "comment" | "documentation_comment" => Some(FoldingRangeKind::Comment),
+            _ => None,
+        };
+        let (start, end) = (node.start_position().row, node.end_position().row);
+        match (kind, &mut group) {
+            (Some(kind), Some((_, group_end, group_kind)))
+                if *group_kind == kind && start <= *group_end + 1 =>
+            {
+                *group_end = end;
+            }
+            (Some(kind), _) => {
+                close(&mut group, &mut ranges);
+                group = Some((start, end, kind));
+            }
+            (None, _) => close(&mut group, &mut ranges),
+        }
+    }
+    close(&mut group, &mut ranges);
+
+    // One fold per start line, keeping the outermost
+    ranges.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)));
+    ranges.dedup_by_key(|r| r.0);
+
+    ranges
+        .into_iter()
+        .map(|(start, end, kind)| {
+            let mut range = json!({"startLine": start, "endLine": end});
+            if let Some(kind) = kind {
+                range["kind"] = json!(kind);
+            }
+            range
+        })
+        .collect()
+}
+
+fn collect_folds(node: Node, ranges: &mut Vec<(usize, usize, Option<FoldingRangeKind>)>) {
+    let (start, end) = (node.start_position().row, node.end_position().row);
+    match node.kind() {
+        "class_body"
+        | "extension_body"
+        | "enum_body"
+        | "block"
+        | "switch_block"
+        | "list_literal"
+        | "set_or_map_literal"
+        | "arguments"
+        | "formal_parameter_list"
+            if end > start + 1 =>
+        {
+            // Keep the closing bracket visible
+            ranges.push((start, end - 1, None));
+        }
+        "comment" if end > start => {
+            ranges.push((start, end, Some(FoldingRangeKind::Comment)));
+        }
+        _ => {}
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_folds(child, ranges);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::treesitter::parse_dart;
+
+    const SOURCE: &str = r#"import 'a.dart';
+import 'b.dart';
+
+class Foo extends Bar {
+  final int x, y;
+  static const z = 1;
+  Foo(this.x, this.y);
+  Foo.named() : x = 0, y = 0;
+  int get value => x;
+  void method(int a) {
+    if (a > 0) {
+      print(a);
+    }
+  }
+}
+
+enum Color { red, green }
+
+mixin M on Object {
+  void m() {}
+}
+
+typedef Callback = void Function();
+
+const limit = 3;
+
+void main() {
+  final list = [
+    1,
+    2,
+  ];
+}
+"#;
+
+    fn names(symbols: &[Value]) -> Vec<&str> {
+        symbols.iter().filter_map(|s| s["name"].as_str()).collect()
+    }
+
+    #[test]
+    fn test_document_symbols() {
+        let tree = parse_dart(SOURCE).unwrap();
+        let symbols = document_symbols(&tree, SOURCE, PositionEncoding::Utf16);
+
+        assert_eq!(
+            names(&symbols),
+            vec!["Foo", "Color", "M", "Callback", "limit", "main"]
+        );
+
+        let foo = &symbols[0];
+        assert_eq!(foo["kind"], json!(SymbolKind::CLASS));
+        assert_eq!(foo["range"]["start"], json!({"line": 3, "character": 0}));
+        assert_eq!(foo["range"]["end"], json!({"line": 14, "character": 1}));
+        assert_eq!(
+            foo["selectionRange"],
+            json!({"start": {"line": 3, "character": 6}, "end": {"line": 3, "character": 9}})
+        );
+
+        let members = foo["children"].as_array().unwrap();
+        assert_eq!(
+            names(members),
+            vec!["x", "y", "z", "Foo", "Foo.named", "value", "method"]
+        );
+        let kinds: Vec<&Value> = members.iter().map(|m| &m["kind"]).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                &json!(SymbolKind::FIELD),
+                &json!(SymbolKind::FIELD),
+                &json!(SymbolKind::CONSTANT),
+                &json!(SymbolKind::CONSTRUCTOR),
+                &json!(SymbolKind::CONSTRUCTOR),
+                &json!(SymbolKind::PROPERTY),
+                &json!(SymbolKind::METHOD),
+            ]
+        );
+        // The method's range covers its body
+        assert_eq!(members[6]["range"]["end"]["line"], 13);
+
+        let color = &symbols[1];
+        assert_eq!(color["kind"], json!(SymbolKind::ENUM));
+        assert_eq!(
+            names(color["children"].as_array().unwrap()),
+            vec!["red", "green"]
+        );
+
+        assert_eq!(symbols[4]["kind"], json!(SymbolKind::CONSTANT));
+        assert_eq!(symbols[5]["kind"], json!(SymbolKind::FUNCTION));
+        assert_eq!(symbols[5]["range"]["end"]["line"], 31);
+    }
+
+    #[test]
+    fn test_document_symbols_use_encoding() {
+        let source = "var s = '😀'; class Foo {}\n";
+        let tree = parse_dart(source).unwrap();
+
+        let symbols = document_symbols(&tree, source, PositionEncoding::Utf16);
+        let foo = symbols.iter().find(|s| s["name"] == "Foo").unwrap();
+        assert_eq!(foo["selectionRange"]["start"]["character"], 20);
+
+        let symbols = document_symbols(&tree, source, PositionEncoding::Utf8);
+        let foo = symbols.iter().find(|s| s["name"] == "Foo").unwrap();
+        assert_eq!(foo["selectionRange"]["start"]["character"], 22);
+    }
+
+    #[test]
+    fn test_folding_ranges() {
+        let tree = parse_dart(SOURCE).unwrap();
+        let ranges = folding_ranges(&tree);
+
+        let find = |start: u64| ranges.iter().find(|r| r["startLine"] == start);
+
+        // Import group
+        assert_eq!(
+            find(0).unwrap(),
+            &json!({"startLine": 0, "endLine": 1, "kind": "imports"})
+        );
+        // Class body, up to the line before the closing brace
+        assert_eq!(find(3).unwrap(), &json!({"startLine": 3, "endLine": 13}));
+        // Method body and nested if block
+        assert_eq!(find(9).unwrap()["endLine"], 12);
+        assert_eq!(find(10).unwrap()["endLine"], 11);
+        // List literal
+        assert_eq!(find(27).unwrap()["endLine"], 29);
+        // Single-line bodies do not fold
+        assert!(find(16).is_none());
+    }
+}
@@ -0,0 +1,316 @@
+//! Recording and replaying proxy sessions
+//!
+//! With `language-server --trace <file>`, every message between the editor,
+//! the proxy and the Dart server is appended to `<file>` as one JSON object
+//! per line, with its direction and timestamps. The `replay` command feeds
+//! the editor's side of such a trace into a fresh proxy, so sessions from
+//! bug reports can be reproduced against the fake or the real Dart server.
+
+use anyhow::{Context, Result};
+use bytes::BytesMut;
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{LineWriter, Write};
+use std::path::Path;
+use std::process::{ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use tokio_util::codec::{Decoder, FramedRead, FramedWrite};
+
+use super::framing::LspCodec;
+
+/// Which way a traced message went
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// Editor to proxy
+    FromClient,
+    /// Proxy to editor
+    ToClient,
+    /// Proxy to Dart server
+    ToServer,
+    /// Dart server to proxy
+    FromServer,
+}
+
+/// One line of a trace file
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TraceEntry {
+    /// Milliseconds since the Unix epoch
+    pub timestamp: u64,
+    /// Milliseconds since tracing started
+    pub elapsed: u64,
+    pub direction: Direction,
+    /// The message, or its raw text if it is not valid JSON
+    pub message: Value,
+}
+
+#[derive(Debug)]
+struct TraceFile {
+    writer: LineWriter<File>,
+    start: Instant,
+}
+
+/// Appends traced messages to a file; clones share the file
+#[derive(Debug, Clone)]
+pub struct Tracer {
+    file: Arc<Mutex<TraceFile>>,
+}
+
+impl Tracer {
+    /// Start a trace in `path`, replacing any previous one
+    pub fn create(path: &Path) -> Result<Self> {
+        let file = File::create(path)
+            .with_context(|| format!("Failed to create trace file {}", path.display()))?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(TraceFile {
+                writer: LineWriter::new(file),
+                start: Instant::now(),
+            })),
+        })
+    }
+
+    /// Record a message
+    ///
+    /// Write errors are logged rather than returned: tracing never stops
+    /// the session it traces.
+    pub fn record(&self, direction: Direction, content: &str) {
+        let mut file = self.file.lock().unwrap();
+        let entry = TraceEntry {
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64),
+            elapsed: file.start.elapsed().as_millis() as u64,
+            direction,
+            message: serde_json::from_str(content)
+                .unwrap_or_else(|_| Value::String(content.to_string())),
+        };
+        let line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(e) => return eprintln!("Error tracing message: {}", e),
+        };
+        if let Err(e) = writeln!(file.writer, "{}", line) {
+            eprintln!("Error writing trace: {}", e);
+        }
+    }
+}
+
+/// Read the entries of a trace file
+pub fn read_trace(path: &Path) -> Result<Vec<TraceEntry>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read trace file {}", path.display()))?;
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            serde_json::from_str(line)
+                .with_context(|| format!("Invalid trace entry on line {}", i + 1))
+        })
+        .collect()
+}
+
+/// A writer to the editor that traces every framed message written to it
+pub struct TracedWriter<W> {
+    inner: W,
+    tracer: Option<Tracer>,
+    codec: LspCodec,
+    /// Bytes of a message not completely written yet
+    pending: BytesMut,
+}
+
+impl<W: Write> TracedWriter<W> {
+    pub fn new(inner: W, tracer: Option<Tracer>) -> Self {
+        Self {
+            inner,
+            tracer,
+            codec: LspCodec::default(),
+            pending: BytesMut::new(),
+        }
+    }
+
+    /// Record the complete messages in `pending`
+    fn record_complete(&mut self, tracer: &Tracer) {
+        loop {
+            match self.codec.decode(&mut self.pending) {
+                Ok(Some(body)) => tracer.record(Direction::ToClient, &body),
+                Ok(None) => return,
+                Err(_) => {
+                    // Not a frame we understand; stop tracking it
+                    self.codec = LspCodec::default();
+                    self.pending.clear();
+                    return;
+                }
+            }
+        }
+    }
+}
+
+impl<W: Write> Write for TracedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        if let Some(tracer) = self.tracer.clone() {
+            self.pending.extend_from_slice(&buf[..written]);
+            self.record_complete(&tracer);
+        }
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// How to replay a trace
+#[derive(Debug, Clone, Default)]
+pub struct ReplayOptions {
+    /// Skip the recorded delays between messages, only waiting for the
+    /// responses the editor had received
+    pub fast: bool,
+}
+
+/// How long a replay waits for a response the editor had received before
+/// its next message
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Replay the editor's messages from `entries` into the language server
+/// started by `command`
+///
+/// Before each message, waits for the responses the editor had received by
+/// then in the trace, and unless [`ReplayOptions::fast`] is set, for the
+/// recorded delay as well, so both ordering and timing issues reproduce.
+/// Each message the server writes back is passed to `output`. Stdin is
+/// closed after the last message, and the server's exit status is returned.
+pub async fn replay(
+    entries: &[TraceEntry],
+    mut command: Command,
+    options: &ReplayOptions,
+    mut output: impl FnMut(Value) + Send + 'static,
+) -> Result<ExitStatus> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to start the language server")?;
+    let stdin = child.stdin.take().context("Failed to capture stdin")?;
+    let stdout = child.stdout.take().context("Failed to capture stdout")?;
+
+    let (responses_tx, mut responses_rx) = mpsc::unbounded_channel::<String>();
+    let reader = tokio::spawn(async move {
+        let mut reader = FramedRead::new(stdout, LspCodec::default());
+        while let Some(Ok(content)) = reader.next().await {
+            let message = serde_json::from_str(&content).unwrap_or(Value::String(content));
+            if let Some(id) = response_id(&message) {
+                let _ = responses_tx.send(id);
+            }
+            output(message);
+        }
+    });
+
+    let mut writer = FramedWrite::new(stdin, LspCodec::default());
+    let mut received: HashSet<String> = HashSet::new();
+    let mut awaited: Vec<String> = Vec::new();
+    let mut previous: Option<u64> = None;
+    for entry in entries {
+        match entry.direction {
+            Direction::FromClient => {}
+            Direction::ToClient => {
+                awaited.extend(response_id(&entry.message));
+                continue;
+            }
+            Direction::ToServer | Direction::FromServer => continue,
+        }
+
+        let deadline = tokio::time::Instant::now() + RESPONSE_TIMEOUT;
+        for id in awaited.drain(..) {
+            while !received.contains(&id) {
+                match tokio::time::timeout_at(deadline, responses_rx.recv()).await {
+                    Ok(Some(response)) => {
+                        received.insert(response);
+                    }
+                    Ok(None) => break,
+                    Err(_) => {
+                        eprintln!("No response to {} in the replay, continuing", id);
+                        break;
+                    }
+                }
+            }
+        }
+        if let (false, Some(previous)) = (options.fast, previous) {
+            let delay = entry.elapsed.saturating_sub(previous);
+            tokio::time::sleep(Duration::from_millis(delay)).await;
+        }
+        previous = Some(entry.elapsed);
+
+        let content = match &entry.message {
+            Value::String(raw) => raw.clone(),
+            message => message.to_string(),
+        };
+        if let Err(e) = writer.send(content).await {
+            // The server exited, e.g. after the recorded `exit`
+            eprintln!("Stopped replaying: {}", e);
+            break;
+        }
+    }
+    drop(writer);
+
+    let status = child.wait().await?;
+    let _ = reader.await;
+    Ok(status)
+}
+
+/// Id of a response, as a key
+fn response_id(message: &Value) -> Option<String> {
+    if message.get("method").is_some() {
+        return None;
+    }
+    message.get("id").map(|id| id.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_traced_writer_records_framed_messages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("trace.jsonl");
+        let tracer = Tracer::create(&path).unwrap();
+        let mut writer = TracedWriter::new(Vec::new(), Some(tracer));
+
+        let first = r#"{"jsonrpc":"2.0","id":1,"result":null}"#;
+        let frame = format!("Content-Length: {}\r\n\r\n{}", first.len(), first);
+        // A frame split across writes is recorded once complete
+        writer.write_all(&frame.as_bytes()[..10]).unwrap();
+        writer.write_all(&frame.as_bytes()[10..]).unwrap();
+        write!(writer, "Content-Length: 8\r\n\r\nnot json").unwrap();
+        writer.flush().unwrap();
+
+        assert_eq!(
+            writer.inner,
+            format!("{}Content-Length: 8\r\n\r\nnot json", frame).into_bytes()
+        );
+        let entries = read_trace(&path).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].direction, Direction::ToClient);
+        assert_eq!(entries[0].message["id"], 1);
+        assert_eq!(entries[1].message, Value::String("not json".to_string()));
+    }
+
+    #[test]
+    fn test_trace_entry_format() {
+        let entry: TraceEntry = serde_json::from_str(
+            r#"{"timestamp":1700000000000,"elapsed":12,"direction":"from_server","message":{"method":"x"}}"#,
+        )
+        .unwrap();
+        assert_eq!(entry.direction, Direction::FromServer);
+        assert_eq!(entry.elapsed, 12);
+        assert_eq!(entry.message["method"], "x");
+    }
+}
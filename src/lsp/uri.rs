@@ -0,0 +1,262 @@
+//! Conversion between `file:` URIs and filesystem paths
+//!
+//! Editors and the Dart Analysis Server percent-encode URIs and may refer to
+//! a file through a symlink, while `parser::find_dart_files` yields paths
+//! relative to the workspace root. Every path is therefore canonicalized into
+//! a single cache key before diagnostics are stored or looked up.
+
+use lsp_types::Url;
+use serde_json::Value;
+use std::path::{Component, Path, PathBuf};
+
+/// Convert a `file:` URI into a filesystem path, decoding percent escapes
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let url = Url::parse(uri).ok()?;
+    if url.scheme() != "file" {
+        return None;
+    }
+    url.to_file_path().ok()
+}
+
+/// Convert a filesystem path into a `file:` URI
+pub fn path_to_uri(path: &Path) -> Option<String> {
+    Url::from_file_path(absolute(path))
+        .ok()
+        .map(|url| url.to_string())
+}
+
+/// Canonical key for a file, shared by every spelling of its path
+///
+/// Symlinks are resolved when the file exists. Files that do not exist yet
+/// (new, unsaved buffers) fall back to a canonicalized parent directory or a
+/// lexically normalized absolute path.
+pub fn cache_key(path: &Path) -> String {
+    canonical(path).to_string_lossy().to_string()
+}
+
+/// Canonical key for the file a URI refers to
+///
+/// Non-`file:` URIs are returned unchanged so they still key consistently.
+pub fn uri_cache_key(uri: &str) -> String {
+    match uri_to_path(uri) {
+        Some(path) => cache_key(&path),
+        None => uri.to_string(),
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+
+    let path = absolute(path);
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => match parent.canonicalize() {
+            Ok(parent) => parent.join(name),
+            Err(_) => path,
+        },
+        _ => path,
+    }
+}
+
+/// Make a path absolute against the current directory and remove `.` and
+/// `..` components without touching the filesystem
+pub fn absolute(path: &Path) -> PathBuf {
+    let path = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other),
+        }
+    }
+    normalized
+}
+
+/// Workspace roots declared in `initialize` params
+///
+/// `workspaceFolders` takes precedence over the deprecated `rootUri` and
+/// `rootPath`. Returns `fallback` when the client declares none.
+pub fn workspace_roots(params: &Value, fallback: &Path) -> Vec<PathBuf> {
+    let folders: Vec<PathBuf> = params
+        .get("workspaceFolders")
+        .and_then(|f| f.as_array())
+        .map(|folders| {
+            folders
+                .iter()
+                .filter_map(|f| f.get("uri").and_then(|u| u.as_str()))
+                .filter_map(uri_to_path)
+                .collect()
+        })
+        .unwrap_or_default();
+    if !folders.is_empty() {
+        return folders;
+    }
+
+    if let Some(root) = params
+        .get("rootUri")
+        .and_then(|u| u.as_str())
+        .and_then(uri_to_path)
+    {
+        return vec![root];
+    }
+
+    if let Some(root) = params.get("rootPath").and_then(|p| p.as_str()) {
+        return vec![PathBuf::from(root)];
+    }
+
+    vec![fallback.to_path_buf()]
+}
+
+/// Apply a `workspace/didChangeWorkspaceFolders` event to the roots
+pub fn apply_folder_changes(roots: &mut Vec<PathBuf>, event: &Value) {
+    let folders = |field: &str| -> Vec<PathBuf> {
+        event
+            .get(field)
+            .and_then(|f| f.as_array())
+            .map(|folders| {
+                folders
+                    .iter()
+                    .filter_map(|f| f.get("uri").and_then(|u| u.as_str()))
+                    .filter_map(uri_to_path)
+                    .collect()
+            })
+            .unwrap_or_default()
+    };
+
+    let removed = folders("removed");
+    roots.retain(|root| !removed.contains(root));
+    for added in folders("added") {
+        if !roots.contains(&added) {
+            roots.push(added);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[cfg(unix)]
+    #[test]
+    fn test_uri_to_path_decodes_percent_escapes() {
+        let path = uri_to_path("file:///home/dev/my%20app/lib/caf%C3%A9.dart").unwrap();
+        assert_eq!(path, PathBuf::from("/home/dev/my app/lib/café.dart"));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_path_to_uri_encodes() {
+        let uri = path_to_uri(Path::new("/home/dev/my app/lib/café.dart")).unwrap();
+        assert_eq!(uri, "file:///home/dev/my%20app/lib/caf%C3%A9.dart");
+        assert_eq!(
+            uri_to_path(&uri).unwrap(),
+            PathBuf::from("/home/dev/my app/lib/café.dart")
+        );
+    }
+
+    #[test]
+    fn test_non_file_uri() {
+        assert!(uri_to_path("untitled:Untitled-1").is_none());
+        assert_eq!(uri_cache_key("untitled:Untitled-1"), "untitled:Untitled-1");
+    }
+
+    #[test]
+    fn test_cache_key_matches_relative_and_encoded_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let lib = dir.path().join("my app");
+        std::fs::create_dir(&lib).unwrap();
+        let file = lib.join("main.dart");
+        std::fs::write(&file, "void main() {}").unwrap();
+
+        let uri = path_to_uri(&file).unwrap();
+        assert!(uri.contains("my%20app"));
+
+        let dotted = lib.join(".").join("..").join("my app").join("main.dart");
+        assert_eq!(uri_cache_key(&uri), cache_key(&file));
+        assert_eq!(cache_key(&dotted), cache_key(&file));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_cache_key_resolves_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let real = dir.path().join("real");
+        std::fs::create_dir(&real).unwrap();
+        std::fs::write(real.join("a.dart"), "").unwrap();
+        let link = dir.path().join("link");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        assert_eq!(
+            cache_key(&link.join("a.dart")),
+            cache_key(&real.join("a.dart"))
+        );
+        // Unsaved files in a symlinked directory resolve through the parent
+        assert_eq!(
+            cache_key(&link.join("new.dart")),
+            cache_key(&real.join("new.dart"))
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_workspace_roots() {
+        let fallback = Path::new("/fallback");
+
+        let params = json!({
+            "rootUri": "file:///ignored",
+            "workspaceFolders": [
+                {"uri": "file:///work/app", "name": "app"},
+                {"uri": "file:///work/my%20pkg", "name": "pkg"}
+            ]
+        });
+        assert_eq!(
+            workspace_roots(&params, fallback),
+            vec![PathBuf::from("/work/app"), PathBuf::from("/work/my pkg")]
+        );
+
+        let params = json!({"rootUri": "file:///work/app", "workspaceFolders": null});
+        assert_eq!(
+            workspace_roots(&params, fallback),
+            vec![PathBuf::from("/work/app")]
+        );
+
+        let params = json!({"rootPath": "/work/legacy"});
+        assert_eq!(
+            workspace_roots(&params, fallback),
+            vec![PathBuf::from("/work/legacy")]
+        );
+
+        assert_eq!(
+            workspace_roots(&json!({}), fallback),
+            vec![PathBuf::from("/fallback")]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_apply_folder_changes() {
+        let mut roots = vec![PathBuf::from("/work/a"), PathBuf::from("/work/b")];
+        let event = json!({
+            "added": [{"uri": "file:///work/my%20c", "name": "c"}],
+            "removed": [{"uri": "file:///work/a", "name": "a"}]
+        });
+
+        apply_folder_changes(&mut roots, &event);
+        assert_eq!(
+            roots,
+            vec![PathBuf::from("/work/b"), PathBuf::from("/work/my c")]
+        );
+    }
+}
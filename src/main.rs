@@ -1,20 +1,22 @@
-mod analyzer;
-mod config;
-mod error;
-mod lsp;
-mod mcp;
-mod parser;
-mod rules;
-
-use analyzer::Rule;
 use clap::{Parser, Subcommand};
-use config::AnalyzerConfig;
-use error::{Diagnostic, Result};
-use lsp::LspProxy;
-use mcp::{start_mcp_server, McpServer};
+use dart_re_analyzer::analyzer::index::ProjectIndex;
+use dart_re_analyzer::analyzer::{self, Rule};
+use dart_re_analyzer::config::AnalyzerConfig;
+use dart_re_analyzer::error::{self, Diagnostic, Result};
+use dart_re_analyzer::lsp::client::DartAnalysisServerConfig;
+use dart_re_analyzer::lsp::dart_semantic::DartSemanticAnalyzer;
+use dart_re_analyzer::lsp::semantic::SemanticAnalyzer;
+use dart_re_analyzer::lsp::settings;
+use dart_re_analyzer::lsp::standalone::StandaloneServer;
+use dart_re_analyzer::lsp::trace::{self, ReplayOptions, Tracer};
+use dart_re_analyzer::lsp::LspProxy;
+use dart_re_analyzer::mcp::{start_mcp_server, McpServer, McpServerOptions};
+use dart_re_analyzer::metrics::{self, FileMetrics};
+use dart_re_analyzer::{parser, rules};
 use rayon::prelude::*;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(name = "dart-re-analyzer")]
@@ -47,6 +49,25 @@ enum Commands {
         /// Configuration file path
         #[arg(long)]
         config: Option<PathBuf>,
+
+        /// Ask the Dart Analysis Server for resolved types where rules use them
+        #[arg(long)]
+        semantic: bool,
+
+        /// Path to dart binary used with --semantic (defaults to the project's SDK)
+        #[arg(long, requires = "semantic")]
+        dart_binary: Option<String>,
+    },
+    /// Compute complexity metrics of every function, method, getter, setter,
+    /// constructor and class
+    Metrics {
+        /// Path to the Dart/Flutter project
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
+        /// Output format (json, csv)
+        #[arg(long, default_value = "json")]
+        format: String,
     },
     /// Start MCP server for error fetching
     Serve {
@@ -54,6 +75,26 @@ enum Commands {
         #[arg(short, long, default_value = "9000")]
         port: u16,
 
+        /// Address to bind to
+        #[arg(long, default_value = "127.0.0.1")]
+        bind: String,
+
+        /// Shared secret clients must send in an `authenticate` request
+        #[arg(long)]
+        token: Option<String>,
+
+        /// Maximum size of a single request in bytes
+        #[arg(long, default_value = "1048576")]
+        max_request_size: usize,
+
+        /// Idle connection timeout in seconds
+        #[arg(long, default_value = "300")]
+        timeout: u64,
+
+        /// Listen on a Unix domain socket instead of TCP
+        #[arg(long)]
+        unix_socket: Option<PathBuf>,
+
         /// Path to the Dart/Flutter project to watch
         #[arg(default_value = ".")]
         path: PathBuf,
@@ -74,6 +115,31 @@ enum Commands {
         #[arg(default_value = ".")]
         path: PathBuf,
 
+        /// Path to dart binary (defaults to 'dart' in PATH)
+        #[arg(long, conflicts_with = "standalone")]
+        dart_binary: Option<String>,
+
+        /// Serve dart-re-analyzer features only, without the Dart SDK
+        #[arg(long)]
+        standalone: bool,
+
+        /// Configuration file path
+        #[arg(long)]
+        config: Option<PathBuf>,
+
+        /// Record every message of the session to this file, as JSON lines
+        #[arg(long, conflicts_with = "standalone")]
+        trace: Option<PathBuf>,
+    },
+    /// Replay the editor's messages from a trace into a new LSP proxy
+    Replay {
+        /// Trace file recorded with `language-server --trace`
+        trace_file: PathBuf,
+
+        /// Path to the Dart/Flutter project
+        #[arg(default_value = ".")]
+        path: PathBuf,
+
         /// Path to dart binary (defaults to 'dart' in PATH)
         #[arg(long)]
         dart_binary: Option<String>,
@@ -81,6 +147,14 @@ enum Commands {
         /// Configuration file path
         #[arg(long)]
         config: Option<PathBuf>,
+
+        /// Skip the recorded delays, only waiting for responses the editor had received
+        #[arg(long)]
+        fast: bool,
+
+        /// Record the replayed session to this file
+        #[arg(long)]
+        trace: Option<PathBuf>,
     },
 }
 
@@ -95,9 +169,16 @@ async fn main() -> Result<()> {
             runtime_only,
             format,
             config,
+            semantic,
+            dart_binary,
         } => {
             let config = load_config(config)?;
-            let diagnostics = analyze_project(&path, &config, style_only, runtime_only)?;
+            let semantic = if semantic {
+                Some(start_semantic_analyzer(&path, dart_binary)?)
+            } else {
+                None
+            };
+            let diagnostics = analyze_project(&path, &config, style_only, runtime_only, semantic)?;
 
             match format.as_str() {
                 "json" => {
@@ -118,16 +199,50 @@ async fn main() -> Result<()> {
                 std::process::exit(1);
             }
         }
-        Commands::Serve { port, path, config } => {
+        Commands::Metrics { path, format } => {
+            let files = parser::find_dart_files(&path)?;
+            let metrics = files
+                .par_iter()
+                .map(|file| FileMetrics::compute(&file.path, &file.content))
+                .collect::<Result<Vec<_>>>()?;
+
+            match format.as_str() {
+                "csv" => print!("{}", metrics::to_csv(&metrics)),
+                _ => {
+                    let json = serde_json::to_string_pretty(&metrics)
+                        .expect("Failed to serialize metrics");
+                    println!("{}", json);
+                }
+            }
+        }
+        Commands::Serve {
+            port,
+            bind,
+            token,
+            max_request_size,
+            timeout,
+            unix_socket,
+            path,
+            config,
+        } => {
             let config = load_config(config)?;
             let mcp = Arc::new(McpServer::new());
 
             // Initial analysis
-            let diagnostics = analyze_project(&path, &config, false, false)?;
+            let diagnostics = analyze_project(&path, &config, false, false, None)?;
             mcp.update_diagnostics(diagnostics).await;
 
-            println!("Starting MCP server on port {}...", port);
-            start_mcp_server(port, mcp).await?;
+            let options = McpServerOptions {
+                bind,
+                port,
+                auth_token: token,
+                max_request_size,
+                timeout: Duration::from_secs(timeout),
+                unix_socket,
+            };
+
+            println!("Starting MCP server...");
+            start_mcp_server(options, mcp).await?;
         }
         Commands::InitConfig { output } => {
             let config = AnalyzerConfig::default();
@@ -137,13 +252,72 @@ async fn main() -> Result<()> {
         Commands::LanguageServer {
             path,
             dart_binary,
+            standalone,
             config,
+            trace,
         } => {
-            let config = load_config(config)?;
-            let mut proxy = LspProxy::new(dart_binary, config, path);
+            let config_path = find_config(config);
+            let config = load_config(config_path.clone())?;
+
+            if standalone {
+                eprintln!("Starting standalone language server...");
+                let server = StandaloneServer::new(config, path);
+                tokio::task::spawn_blocking(move || server.run())
+                    .await
+                    .map_err(anyhow::Error::from)??;
+                return Ok(());
+            }
+
+            let tracer = trace.as_deref().map(Tracer::create).transpose()?;
+            let mut proxy = LspProxy::new(dart_binary, config, path)
+                .with_config_path(config_path)
+                .with_trace(tracer);
 
             eprintln!("Starting LSP proxy...");
-            proxy.run().await?;
+            let result = proxy.run().await;
+            let code = match result {
+                Ok(()) => proxy.exit_code(),
+                Err(e) => {
+                    eprintln!("Error: {:#}", e);
+                    1
+                }
+            };
+            drop(proxy);
+            // Exit without shutting the runtime down, which would wait for
+            // the pending read of stdin
+            std::process::exit(code);
+        }
+        Commands::Replay {
+            trace_file,
+            path,
+            dart_binary,
+            config,
+            fast,
+            trace,
+        } => {
+            let entries = trace::read_trace(&trace_file)?;
+
+            let mut command = tokio::process::Command::new(std::env::current_exe()?);
+            command.arg("language-server").arg(&path);
+            if let Some(dart_binary) = dart_binary {
+                command.arg("--dart-binary").arg(dart_binary);
+            }
+            if let Some(config) = config {
+                command.arg("--config").arg(config);
+            }
+            if let Some(trace) = trace {
+                command.arg("--trace").arg(trace);
+            }
+
+            // The proxy's messages go to stdout, one JSON object per line
+            let options = ReplayOptions { fast };
+            let status = trace::replay(&entries, command, &options, |message| {
+                println!("{}", message)
+            })
+            .await?;
+            if !status.success() {
+                std::process::exit(status.code().unwrap_or(1));
+            }
         }
     }
 
@@ -151,26 +325,41 @@ async fn main() -> Result<()> {
 }
 
 fn load_config(config_path: Option<PathBuf>) -> Result<AnalyzerConfig> {
-    if let Some(path) = config_path {
-        AnalyzerConfig::load_from_file(&path)
-    } else {
-        // Try to find config in current directory
-        let default_paths = ["analyzer_config.json", ".dart_analyzer_config.json"];
-        for path_str in &default_paths {
-            let path = PathBuf::from(path_str);
-            if path.exists() {
-                return AnalyzerConfig::load_from_file(&path);
-            }
-        }
-        Ok(AnalyzerConfig::default())
+    match find_config(config_path) {
+        Some(path) => AnalyzerConfig::load_from_file(&path),
+        None => Ok(AnalyzerConfig::default()),
     }
 }
 
+/// The given config file, or one found in the current directory
+fn find_config(config_path: Option<PathBuf>) -> Option<PathBuf> {
+    config_path.or_else(|| {
+        settings::CONFIG_FILE_NAMES
+            .iter()
+            .map(PathBuf::from)
+            .find(|path| path.exists())
+    })
+}
+
+/// Start a Dart Analysis Server for `path` to resolve types for the rules
+fn start_semantic_analyzer(
+    path: &Path,
+    dart_binary: Option<String>,
+) -> Result<Arc<dyn SemanticAnalyzer>> {
+    let mut config = DartAnalysisServerConfig::for_project(path);
+    config.dart_binary = dart_binary.map(PathBuf::from);
+
+    println!("Starting Dart Analysis Server...");
+    let analyzer = DartSemanticAnalyzer::start(config, path)?;
+    Ok(Arc::new(analyzer))
+}
+
 fn analyze_project(
     path: &Path,
     config: &AnalyzerConfig,
     style_only: bool,
     runtime_only: bool,
+    semantic: Option<Arc<dyn SemanticAnalyzer>>,
 ) -> Result<Vec<Diagnostic>> {
     println!("Analyzing Dart files in: {}", path.display());
 
@@ -178,24 +367,37 @@ fn analyze_project(
     let files = parser::find_dart_files(path)?;
     println!("Found {} Dart files", files.len());
 
-    // Select rules based on flags
-    let rules: Vec<Arc<dyn Rule>> = if style_only {
-        rules::get_style_rules()
+    // Select rules based on the config and flags
+    let mut config = config.clone();
+    if style_only {
+        config.runtime_rules.enabled = false;
+        config.flutter_rules.enabled = false;
     } else if runtime_only {
-        rules::get_runtime_rules()
-    } else {
-        rules::get_all_rules()
-    };
+        config.style_rules.enabled = false;
+        config.flutter_rules.enabled = false;
+    }
+    let mut rules: Vec<Arc<dyn Rule>> = rules::get_rules_for_config(&config);
+    if let Some(analyzer) = semantic {
+        rules = rules::with_semantic(rules, analyzer);
+    }
+
+    let project_rules = rules::get_project_rules_for_config(&config);
 
-    println!("Running {} rules", rules.len());
+    println!("Running {} rules", rules.len() + project_rules.len());
 
     // Run analysis
-    let diagnostics = if config.parallel {
+    let mut diagnostics = if config.parallel {
         analyze_parallel(&files, &rules)
     } else {
         analyze_sequential(&files, &rules)
     };
 
+    // Project rules, which see every file at once
+    if !project_rules.is_empty() {
+        let index = ProjectIndex::build(&files);
+        diagnostics.extend(analyzer::check_project(&index, &project_rules));
+    }
+
     println!("Analysis complete. Found {} issues", diagnostics.len());
 
     Ok(diagnostics)
@@ -206,10 +408,7 @@ fn analyze_parallel(files: &[parser::DartFile], rules: &[Arc<dyn Rule>]) -> Vec<
         .par_iter()
         .flat_map(|file| {
             let path = PathBuf::from(&file.path);
-            rules
-                .iter()
-                .flat_map(|rule| rule.check(&path, &file.content).unwrap_or_default())
-                .collect::<Vec<_>>()
+            analyzer::check_file(&path, &file.content, rules)
         })
         .collect()
 }
@@ -219,11 +418,7 @@ fn analyze_sequential(files: &[parser::DartFile], rules: &[Arc<dyn Rule>]) -> Ve
 
     for file in files {
         let path = PathBuf::from(&file.path);
-        for rule in rules {
-            if let Ok(file_diagnostics) = rule.check(&path, &file.content) {
-                diagnostics.extend(file_diagnostics);
-            }
-        }
+        diagnostics.extend(analyzer::check_file(&path, &file.content, rules));
     }
 
     diagnostics
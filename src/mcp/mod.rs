@@ -1,6 +1,11 @@
 use crate::error::{AnalyzerError, Diagnostic, Result};
 use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
 use tokio::sync::RwLock;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -17,6 +22,16 @@ pub struct McpResponse {
     pub error: Option<String>,
 }
 
+impl McpResponse {
+    fn failure(error: impl Into<String>) -> Self {
+        Self {
+            success: false,
+            data: serde_json::Value::Null,
+            error: Some(error.into()),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ErrorsQuery {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -134,6 +149,7 @@ pub struct DiagnosticsStats {
     pub info: usize,
     pub style_issues: usize,
     pub runtime_issues: usize,
+    pub flutter_issues: usize,
     pub files_with_issues: usize,
 }
 
@@ -163,6 +179,10 @@ impl DiagnosticsStats {
             .iter()
             .filter(|d| matches!(d.category, RuleCategory::Runtime))
             .count();
+        let flutter_issues = diagnostics
+            .iter()
+            .filter(|d| matches!(d.category, RuleCategory::Flutter))
+            .count();
 
         let files: HashSet<_> = diagnostics.iter().map(|d| &d.location.file).collect();
         let files_with_issues = files.len();
@@ -174,17 +194,77 @@ impl DiagnosticsStats {
             info,
             style_issues,
             runtime_issues,
+            flutter_issues,
             files_with_issues,
         }
     }
 }
 
-// Simple JSON-RPC server implementation
-pub async fn start_mcp_server(port: u16, mcp: Arc<McpServer>) -> Result<()> {
-    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+/// Transport and connection settings for the MCP server
+#[derive(Debug, Clone)]
+pub struct McpServerOptions {
+    /// Address to bind the TCP listener to
+    pub bind: String,
+    /// Port to listen on
+    pub port: u16,
+    /// Shared secret that clients must send in an `authenticate` request
+    /// before any other request is served
+    pub auth_token: Option<String>,
+    /// Maximum size in bytes of a single request line
+    pub max_request_size: usize,
+    /// Idle timeout for a connection waiting on the next request
+    pub timeout: Duration,
+    /// Listen on a Unix domain socket at this path instead of TCP
+    pub unix_socket: Option<PathBuf>,
+}
+
+impl Default for McpServerOptions {
+    fn default() -> Self {
+        Self {
+            bind: "127.0.0.1".to_string(),
+            port: 9000,
+            auth_token: None,
+            max_request_size: 1024 * 1024,
+            timeout: Duration::from_secs(300),
+            unix_socket: None,
+        }
+    }
+}
+
+impl McpServerOptions {
+    /// The `host:port` address the TCP listener binds to
+    pub fn address(&self) -> String {
+        if self.bind.contains(':') && !self.bind.starts_with('[') {
+            // Bare IPv6 address
+            format!("[{}]:{}", self.bind, self.port)
+        } else {
+            format!("{}:{}", self.bind, self.port)
+        }
+    }
+}
+
+/// Start the MCP server using the given transport options
+pub async fn start_mcp_server(options: McpServerOptions, mcp: Arc<McpServer>) -> Result<()> {
+    let options = Arc::new(options);
+
+    #[cfg(unix)]
+    if let Some(ref socket_path) = options.unix_socket {
+        return serve_unix(socket_path.clone(), options.clone(), mcp).await;
+    }
+    #[cfg(not(unix))]
+    if options.unix_socket.is_some() {
+        return Err(AnalyzerError::McpServer(
+            "Unix domain sockets are not supported on this platform".to_string(),
+        ));
+    }
+
+    serve_tcp(options, mcp).await
+}
+
+async fn serve_tcp(options: Arc<McpServerOptions>, mcp: Arc<McpServer>) -> Result<()> {
     use tokio::net::TcpListener;
 
-    let addr = format!("127.0.0.1:{}", port);
+    let addr = options.address();
     let listener = TcpListener::bind(&addr)
         .await
         .map_err(|e| AnalyzerError::McpServer(format!("Failed to bind to {}: {}", addr, e)))?;
@@ -198,27 +278,192 @@ pub async fn start_mcp_server(port: u16, mcp: Arc<McpServer>) -> Result<()> {
             .map_err(|e| AnalyzerError::McpServer(format!("Failed to accept connection: {}", e)))?;
 
         let mcp_clone = mcp.clone();
+        let options_clone = options.clone();
+        tokio::spawn(async move {
+            serve_connection(socket, mcp_clone, &options_clone).await;
+        });
+    }
+}
+
+#[cfg(unix)]
+async fn serve_unix(
+    socket_path: PathBuf,
+    options: Arc<McpServerOptions>,
+    mcp: Arc<McpServer>,
+) -> Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+    use tokio::net::UnixListener;
+
+    // A stale socket from a previous run would make bind fail, but anything
+    // else at the path is left alone
+    if let Ok(metadata) = std::fs::symlink_metadata(&socket_path) {
+        if !metadata.file_type().is_socket() {
+            return Err(AnalyzerError::McpServer(format!(
+                "Failed to bind to {}: address in use and not a socket",
+                socket_path.display()
+            )));
+        }
+        std::fs::remove_file(&socket_path)?;
+    }
+
+    let listener = UnixListener::bind(&socket_path).map_err(|e| {
+        AnalyzerError::McpServer(format!(
+            "Failed to bind to {}: {}",
+            socket_path.display(),
+            e
+        ))
+    })?;
+
+    println!("MCP server listening on {}", socket_path.display());
+
+    loop {
+        let (socket, _) = listener
+            .accept()
+            .await
+            .map_err(|e| AnalyzerError::McpServer(format!("Failed to accept connection: {}", e)))?;
 
+        let mcp_clone = mcp.clone();
+        let options_clone = options.clone();
         tokio::spawn(async move {
-            let (reader, mut writer) = socket.into_split();
-            let mut reader = BufReader::new(reader);
-            let mut line = String::new();
+            serve_connection(socket, mcp_clone, &options_clone).await;
+        });
+    }
+}
 
-            while let Ok(n) = reader.read_line(&mut line).await {
-                if n == 0 {
+/// Serve newline-delimited JSON requests on a single connection until the
+/// peer disconnects, times out, fails authentication or sends an oversized
+/// request.
+pub async fn serve_connection<S>(stream: S, mcp: Arc<McpServer>, options: &McpServerOptions)
+where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let (reader, mut writer) = tokio::io::split(stream);
+    let mut reader = BufReader::new(reader);
+    let mut authenticated = options.auth_token.is_none();
+
+    loop {
+        let line = match read_request_line(&mut reader, options).await {
+            RequestLine::Line(line) => line,
+            RequestLine::Eof | RequestLine::TimedOut => break,
+            RequestLine::TooLarge => {
+                let response = McpResponse::failure(format!(
+                    "Request exceeds maximum size of {} bytes",
+                    options.max_request_size
+                ));
+                let _ = write_response(&mut writer, &response).await;
+                break;
+            }
+            RequestLine::Invalid(e) => {
+                let response = McpResponse::failure(format!("Invalid request: {}", e));
+                if write_response(&mut writer, &response).await.is_err() {
                     break;
                 }
+                continue;
+            }
+        };
 
-                if let Ok(request) = serde_json::from_str::<McpRequest>(&line) {
-                    let response = mcp_clone.handle_request(request).await;
-                    if let Ok(response_json) = serde_json::to_string(&response) {
-                        let _ = writer.write_all(response_json.as_bytes()).await;
-                        let _ = writer.write_all(b"\n").await;
-                    }
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let request = match serde_json::from_str::<McpRequest>(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                let response = McpResponse::failure(format!("Invalid request: {}", e));
+                if write_response(&mut writer, &response).await.is_err() {
+                    break;
+                }
+                continue;
+            }
+        };
+
+        if !authenticated {
+            let expected = options.auth_token.as_deref().unwrap_or_default();
+            let token = request
+                .params
+                .get("token")
+                .and_then(|t| t.as_str())
+                .unwrap_or_default();
+
+            if request.method == "authenticate" && constant_time_eq(token, expected) {
+                authenticated = true;
+                let response = McpResponse {
+                    success: true,
+                    data: serde_json::Value::Null,
+                    error: None,
+                };
+                if write_response(&mut writer, &response).await.is_err() {
+                    break;
                 }
+                continue;
+            }
+
+            let response = McpResponse::failure("Authentication required");
+            let _ = write_response(&mut writer, &response).await;
+            break;
+        }
+
+        let response = mcp.handle_request(request).await;
+        if write_response(&mut writer, &response).await.is_err() {
+            break;
+        }
+    }
+}
+
+enum RequestLine {
+    Line(String),
+    Eof,
+    TimedOut,
+    TooLarge,
+    Invalid(String),
+}
 
-                line.clear();
+async fn read_request_line<R>(reader: &mut R, options: &McpServerOptions) -> RequestLine
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut buf = Vec::new();
+    // Read one byte past the limit so an oversized request can be detected
+    let limit = options.max_request_size as u64 + 1;
+    let read = tokio::time::timeout(
+        options.timeout,
+        (&mut *reader).take(limit).read_until(b'\n', &mut buf),
+    )
+    .await;
+
+    match read {
+        Err(_) => RequestLine::TimedOut,
+        Ok(Err(_)) | Ok(Ok(0)) => RequestLine::Eof,
+        Ok(Ok(_)) => {
+            if buf.last() == Some(&b'\n') {
+                buf.pop();
             }
-        });
+            if buf.len() > options.max_request_size {
+                return RequestLine::TooLarge;
+            }
+            match String::from_utf8(buf) {
+                Ok(line) => RequestLine::Line(line),
+                Err(e) => RequestLine::Invalid(e.to_string()),
+            }
+        }
+    }
+}
+
+async fn write_response<W>(writer: &mut W, response: &McpResponse) -> std::io::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut response_json = serde_json::to_vec(response)?;
+    response_json.push(b'\n');
+    writer.write_all(&response_json).await?;
+    writer.flush().await
+}
+
+/// Compare two secrets without short-circuiting on the first mismatch
+fn constant_time_eq(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
     }
+    a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
 }
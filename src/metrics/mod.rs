@@ -0,0 +1,573 @@
+//! Code complexity metrics computed on the tree-sitter CST
+//!
+//! Functions and methods are those found by [`treesitter::extract_methods`],
+//! along with getters, setters and constructors, factories included. Named
+//! local functions are measured on their own, so they don't add to the
+//! metrics of the function declaring them; closures do.
+
+use crate::error::{AnalyzerError, Result};
+use crate::treesitter;
+use serde::Serialize;
+use std::fmt::Write;
+use tree_sitter::{Node, Tree};
+
+/// Metrics of a function, method, getter, setter or constructor
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FunctionMetrics {
+    /// The name, like `A.named` for a named constructor
+    pub name: String,
+    /// Class declaring the method, `None` for functions
+    pub class: Option<String>,
+    /// 1-based position of the name
+    pub line: usize,
+    pub column: usize,
+    /// Last line of the body
+    pub end_line: usize,
+    /// One plus the number of branches: conditions, loops, cases, catch
+    /// clauses and `&&`, `||` and `??` operators
+    pub cyclomatic_complexity: usize,
+    /// Branches weighted by how deeply they're nested, plus `else`
+    /// branches and sequences of boolean operators
+    pub cognitive_complexity: usize,
+    /// Deepest nesting of `if`, loop, `switch` and `try` statements
+    pub max_nesting_depth: usize,
+    pub parameters: usize,
+    /// Lines that are neither blank nor only comments
+    pub lines_of_code: usize,
+}
+
+/// Metrics of a class
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClassMetrics {
+    pub name: String,
+    /// 1-based position of the name
+    pub line: usize,
+    pub column: usize,
+    /// Methods, leaving out getters, setters and constructors
+    pub methods: usize,
+    pub fields: usize,
+}
+
+/// Metrics of a file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FileMetrics {
+    pub file: String,
+    pub functions: Vec<FunctionMetrics>,
+    pub classes: Vec<ClassMetrics>,
+}
+
+impl FileMetrics {
+    pub fn compute(file: &str, content: &str) -> Result<Self> {
+        let tree =
+            treesitter::parse_dart(content).map_err(|e| AnalyzerError::Parse(e.to_string()))?;
+        Ok(Self::from_tree(file, &tree, content))
+    }
+
+    pub fn from_tree(file: &str, tree: &Tree, content: &str) -> Self {
+        let classes = treesitter::extract_classes(tree, content);
+        let fields = treesitter::extract_fields(tree, content);
+        let methods = treesitter::extract_methods(tree, content);
+        let class_of = |node: Node| {
+            classes
+                .iter()
+                .find(|c| c.start_byte <= node.start_byte() && node.start_byte() < c.end_byte)
+        };
+
+        let signatures = methods
+            .iter()
+            .filter_map(|method| {
+                let name = method.node.child_by_field_name("name")?;
+                Some((method.name.clone(), name, method.node))
+            })
+            .chain(accessors_and_constructors(tree, content));
+        let mut functions: Vec<FunctionMetrics> = signatures
+            .map(|(name, name_node, signature)| {
+                let position = name_node.start_position();
+                let body = body(signature);
+                // Local functions aren't members, even when declared in a method
+                let is_member = matches!(
+                    signature.parent().map(|p| p.kind()),
+                    Some("method_signature" | "declaration")
+                );
+                let mut function = FunctionMetrics {
+                    name,
+                    class: is_member
+                        .then(|| class_of(signature).map(|c| c.name.clone()))
+                        .flatten(),
+                    line: position.row + 1,
+                    column: position.column + 1,
+                    end_line: body.unwrap_or(signature).end_position().row + 1,
+                    cyclomatic_complexity: 1,
+                    cognitive_complexity: 0,
+                    max_nesting_depth: 0,
+                    parameters: parameters(signature),
+                    lines_of_code: lines_of_code(
+                        content,
+                        signature.start_position().row,
+                        body.unwrap_or(signature).end_position().row,
+                    ),
+                };
+                if let Some(body) = body {
+                    let mut walker = Walker::default();
+                    walker.visit(body, 0);
+                    function.cyclomatic_complexity += walker.branches;
+                    function.cognitive_complexity = walker.cognitive;
+                    function.max_nesting_depth = walker.max_depth;
+                }
+                function
+            })
+            .collect();
+        functions.sort_by_key(|f| (f.line, f.column));
+
+        let classes = classes
+            .iter()
+            .map(|class| {
+                let in_class = |start: usize| class.start_byte <= start && start < class.end_byte;
+                let position = class
+                    .node
+                    .child_by_field_name("name")
+                    .unwrap_or(class.node)
+                    .start_position();
+                ClassMetrics {
+                    name: class.name.clone(),
+                    line: position.row + 1,
+                    column: position.column + 1,
+                    methods: methods
+                        .iter()
+                        .filter(|m| {
+                            in_class(m.start_byte)
+                                && m.node.parent().map(|p| p.kind()) == Some("method_signature")
+                        })
+                        .count(),
+                    fields: fields.iter().filter(|f| in_class(f.start_byte)).count(),
+                }
+            })
+            .collect();
+
+        Self {
+            file: file.to_string(),
+            functions,
+            classes,
+        }
+    }
+}
+
+/// Signatures of getters, setters and constructors, with their name and
+/// the node it starts at
+fn accessors_and_constructors<'a>(
+    tree: &'a Tree,
+    source: &str,
+) -> Vec<(String, Node<'a>, Node<'a>)> {
+    let mut signatures = Vec::new();
+    let mut pending = vec![tree.root_node()];
+    while let Some(node) = pending.pop() {
+        let kind = node.kind();
+        let mut cursor = node.walk();
+        let children: Vec<Node> = node.named_children(&mut cursor).collect();
+        let name = match kind {
+            "getter_signature" | "setter_signature" => node
+                .child_by_field_name("name")
+                .or_else(|| children.iter().copied().find(|n| n.kind() == "identifier"))
+                .map(|name| (name, name.end_byte())),
+            // `A`, `A.named`, or `A.named` as a single `qualified` node, up
+            // to the parameters
+            "constructor_signature"
+            | "constant_constructor_signature"
+            | "factory_constructor_signature"
+            | "redirecting_factory_constructor_signature" => {
+                let name = children
+                    .iter()
+                    .copied()
+                    .find(|n| matches!(n.kind(), "identifier" | "qualified"));
+                let end = children
+                    .iter()
+                    .find(|n| n.kind() == "formal_parameter_list")
+                    .map(|n| n.start_byte());
+                name.zip(end)
+            }
+            _ => None,
+        };
+        if let Some((name, end)) = name {
+            if let Some(text) = source.get(name.start_byte()..end) {
+                signatures.push((text.trim().to_string(), name, node));
+            }
+        }
+        pending.extend(children.into_iter().rev());
+    }
+    signatures
+}
+
+/// The body of the function declared by a signature
+fn body(signature: Node) -> Option<Node> {
+    let declaration = match signature.parent() {
+        Some(parent) if parent.kind() == "method_signature" => parent,
+        _ => signature,
+    };
+    declaration
+        .next_named_sibling()
+        .filter(|n| n.kind() == "function_body")
+}
+
+/// Parameters declared by a signature, positional, optional and named
+fn parameters(signature: Node) -> usize {
+    let mut cursor = signature.walk();
+    let Some(list) = signature
+        .named_children(&mut cursor)
+        .find(|n| n.kind() == "formal_parameter_list")
+    else {
+        return 0;
+    };
+
+    // Parameters of function-typed parameters don't count
+    let mut count = 0;
+    let mut pending = vec![list];
+    while let Some(node) = pending.pop() {
+        if node.kind() == "formal_parameter" {
+            count += 1;
+            continue;
+        }
+        let mut cursor = node.walk();
+        pending.extend(node.named_children(&mut cursor));
+    }
+    count
+}
+
+/// Lines between two 0-based rows that aren't blank or only comments
+fn lines_of_code(content: &str, first: usize, last: usize) -> usize {
+    content
+        .lines()
+        .skip(first)
+        .take(last + 1 - first)
+        .map(str::trim)
+        .filter(|line| {
+            !line.is_empty()
+                && !line.starts_with("//")
+                && !line.starts_with("/*")
+                && !line.starts_with('*')
+        })
+        .count()
+}
+
+/// Accumulates the metrics of a function body
+#[derive(Default)]
+struct Walker {
+    branches: usize,
+    cognitive: usize,
+    max_depth: usize,
+}
+
+impl Walker {
+    /// Visit `node`, nested in `depth` control structures
+    fn visit(&mut self, node: Node, depth: usize) {
+        let mut nested = depth;
+        match node.kind() {
+            // Measured separately
+            "lambda_expression" => return,
+            "if_statement" => {
+                self.branches += 1;
+                if is_else_if(node) {
+                    // Continues the chain at the same depth
+                    self.cognitive += 1;
+                    nested = depth - 1;
+                } else {
+                    self.cognitive += 1 + depth;
+                }
+                nested += 1;
+                if has_else_block(node) {
+                    self.cognitive += 1;
+                }
+            }
+            "for_statement" | "while_statement" | "do_statement" => {
+                self.branches += 1;
+                self.cognitive += 1 + depth;
+                nested += 1;
+            }
+            "switch_statement" => {
+                self.cognitive += 1 + depth;
+                nested += 1;
+            }
+            "try_statement" => nested += 1,
+            "catch_clause" => {
+                self.branches += 1;
+                self.cognitive += depth;
+            }
+            "conditional_expression" => {
+                self.branches += 1;
+                self.cognitive += 1 + depth;
+            }
+            // `default` labels don't branch
+            "switch_label" if node.child(0).map(|c| c.kind()) == Some("case_builtin") => {
+                self.branches += 1;
+            }
+            "logical_and_expression" | "logical_or_expression" | "if_null_expression" => {
+                let mut cursor = node.walk();
+                self.branches += node
+                    .children(&mut cursor)
+                    .filter(|c| matches!(c.kind(), "&&" | "||" | "??"))
+                    .count();
+                // A sequence of the same operator counts once
+                if node.parent().map(|p| p.kind()) != Some(node.kind()) {
+                    self.cognitive += 1;
+                }
+            }
+            // Closures nest in the function around them
+            "function_expression" => nested += 1,
+            _ => {}
+        }
+        if matches!(
+            node.kind(),
+            "if_statement"
+                | "for_statement"
+                | "while_statement"
+                | "do_statement"
+                | "switch_statement"
+                | "try_statement"
+        ) {
+            self.max_depth = self.max_depth.max(nested);
+        }
+
+        let mut cursor = node.walk();
+        for child in node.named_children(&mut cursor) {
+            self.visit(child, nested);
+        }
+    }
+}
+
+/// Whether an `if` statement is the `else if` branch of another
+fn is_else_if(node: Node) -> bool {
+    node.parent().is_some_and(|p| p.kind() == "if_statement")
+        && node.prev_sibling().map(|s| s.kind()) == Some("else")
+}
+
+/// Whether an `if` statement ends with a plain `else` block
+fn has_else_block(node: Node) -> bool {
+    let mut cursor = node.walk();
+    let mut children = node
+        .children(&mut cursor)
+        .skip_while(|c| c.kind() != "else");
+    children.next().is_some() && children.next().is_some_and(|c| c.kind() != "if_statement")
+}
+
+/// Column names of [`to_csv`]
+const CSV_HEADER: &str = "file,kind,class,name,line,cyclomatic_complexity,cognitive_complexity,\
+                          max_nesting_depth,parameters,lines_of_code,methods,fields";
+
+/// One CSV row per function and class, leaving out the metrics that don't
+/// apply to the row's kind
+pub fn to_csv(files: &[FileMetrics]) -> String {
+    let mut csv = String::new();
+    csv.push_str(CSV_HEADER);
+    csv.push('\n');
+    for file in files {
+        for f in &file.functions {
+            let _ = writeln!(
+                csv,
+                "{},function,{},{},{},{},{},{},{},{},,",
+                csv_field(&file.file),
+                csv_field(f.class.as_deref().unwrap_or_default()),
+                csv_field(&f.name),
+                f.line,
+                f.cyclomatic_complexity,
+                f.cognitive_complexity,
+                f.max_nesting_depth,
+                f.parameters,
+                f.lines_of_code,
+            );
+        }
+        for c in &file.classes {
+            let _ = writeln!(
+                csv,
+                "{},class,,{},{},,,,,,{},{}",
+                csv_field(&file.file),
+                csv_field(&c.name),
+                c.line,
+                c.methods,
+                c.fields,
+            );
+        }
+    }
+    csv
+}
+
+/// Quote a CSV field if it contains a separator, quote or newline
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metrics(source: &str) -> FileMetrics {
+        FileMetrics::compute("test.dart", source).expect("Failed to compute metrics")
+    }
+
+    #[test]
+    fn test_branchless_function() {
+        let file = metrics("int add(int a, int b) {\n  // Sum\n\n  return a + b;\n}\n");
+        assert_eq!(
+            file.functions,
+            vec![FunctionMetrics {
+                name: "add".to_string(),
+                class: None,
+                line: 1,
+                column: 5,
+                end_line: 5,
+                cyclomatic_complexity: 1,
+                cognitive_complexity: 0,
+                max_nesting_depth: 0,
+                parameters: 2,
+                lines_of_code: 3,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_complexity_and_nesting() {
+        let source = r#"
+void f(int a, {required int b, int? c}) {
+  if (a > 0 && b > 0) {
+    for (final x in xs) {
+      while (x > 0) {}
+    }
+  } else if (c ?? false) {
+  } else {
+    try {
+      g();
+    } catch (e) {}
+  }
+  switch (a) {
+    case 1:
+      break;
+    default:
+      break;
+  }
+  void inner() {
+    if (a > 0) {}
+  }
+  xs.forEach((x) => x > 0 ? 1 : 2);
+}
+"#;
+        let file = metrics(source);
+        let f = &file.functions[0];
+        assert_eq!(f.name, "f");
+        assert_eq!(f.parameters, 3);
+        // if, &&, for, while, else if, ??, catch, case, ?:
+        assert_eq!(f.cyclomatic_complexity, 10);
+        // if 1, && 1, for 2, while 3, else if 1, ?? 1, else 1, catch 2,
+        // switch 1, ?: 2
+        assert_eq!(f.cognitive_complexity, 15);
+        // if > for > while
+        assert_eq!(f.max_nesting_depth, 3);
+
+        // The local function is measured on its own
+        let inner = &file.functions[1];
+        assert_eq!(inner.name, "inner");
+        assert_eq!(inner.cyclomatic_complexity, 2);
+        assert_eq!(inner.max_nesting_depth, 1);
+    }
+
+    #[test]
+    fn test_class_metrics() {
+        let source = r#"
+class Counter {
+  static const step = 1;
+  int _count = 0, _max = 10;
+
+  int get count => _count;
+
+  void increment() {
+    _count += step;
+  }
+
+  void reset() => _count = 0;
+}
+"#;
+        let file = metrics(source);
+        assert_eq!(
+            file.classes,
+            vec![ClassMetrics {
+                name: "Counter".to_string(),
+                line: 2,
+                column: 7,
+                methods: 2,
+                fields: 3,
+            }]
+        );
+        let classes: Vec<_> = file.functions.iter().map(|f| f.class.as_deref()).collect();
+        assert_eq!(
+            classes,
+            vec![Some("Counter"), Some("Counter"), Some("Counter")]
+        );
+    }
+
+    #[test]
+    fn test_accessors_and_constructors() {
+        let source = r#"
+class A {
+  A(int a, int b) : x = a {
+    if (a > b) {
+      for (final i in xs) {}
+    }
+  }
+  const A.fixed(this.x);
+  factory A.parse(String s) {
+    return s.isEmpty ? A(0, 0) : A(1, 1);
+  }
+  int get double => x * 2;
+  set value(int v) {
+    if (v < 0) {}
+  }
+  int x;
+}
+int get top => 1;
+"#;
+        let file = metrics(source);
+        let found: Vec<_> = file
+            .functions
+            .iter()
+            .map(|f| {
+                (
+                    f.name.as_str(),
+                    f.class.as_deref(),
+                    f.line,
+                    f.cyclomatic_complexity,
+                    f.max_nesting_depth,
+                    f.parameters,
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("A", Some("A"), 3, 3, 2, 2),
+                ("A.fixed", Some("A"), 8, 1, 0, 1),
+                ("A.parse", Some("A"), 9, 2, 0, 1),
+                ("double", Some("A"), 12, 1, 0, 0),
+                ("value", Some("A"), 13, 2, 1, 1),
+                ("top", None, 18, 1, 0, 0),
+            ]
+        );
+        assert_eq!(file.functions[0].end_line, 7);
+        assert_eq!(file.functions[0].lines_of_code, 5);
+        // Only methods count towards the class
+        assert_eq!(file.classes[0].methods, 0);
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let file = metrics("class A {\n  void m(int a) {}\n}\n");
+        let csv = to_csv(&[FileMetrics {
+            file: "lib/a,b.dart".to_string(),
+            ..file
+        }]);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], CSV_HEADER);
+        assert_eq!(lines[1], "\"lib/a,b.dart\",function,A,m,2,1,0,0,1,1,,");
+        assert_eq!(lines[2], "\"lib/a,b.dart\",class,,A,1,,,,,,1,0");
+    }
+}
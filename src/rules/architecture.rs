@@ -0,0 +1,193 @@
+//! Architecture rules: which layers of a package may import which
+//!
+//! Layers are declared in the config by globs relative to the package root,
+//! the directory of the `pubspec.yaml` owning a file. Imports of the
+//! package's own libraries, relative or `package:<name>/`, are resolved to
+//! the layer of the imported file.
+
+use crate::analyzer::imports::{self, normalize};
+use crate::analyzer::Rule;
+use crate::config::{ArchitectureConfig, LayerConfig};
+use crate::error::{AnalyzerError, Diagnostic, Location, Result, RuleCategory, Severity};
+use crate::treesitter;
+use std::path::Path;
+
+// Rule: Layers may only import the layers and packages the config allows
+#[derive(Default)]
+pub struct LayerDependenciesRule {
+    config: ArchitectureConfig,
+}
+
+impl LayerDependenciesRule {
+    pub fn new(config: ArchitectureConfig) -> Self {
+        Self { config }
+    }
+
+    /// The layer of a file in the package at `root`
+    fn layer_of(&self, root: &Path, file: &Path) -> Option<&LayerConfig> {
+        let relative = normalize(file).strip_prefix(root).ok()?.to_path_buf();
+        let relative = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        self.config.layers.iter().find(|layer| {
+            layer
+                .paths
+                .iter()
+                .any(|pattern| glob_matches(pattern, &relative))
+        })
+    }
+}
+
+impl Rule for LayerDependenciesRule {
+    fn name(&self) -> &str {
+        "layer_dependencies"
+    }
+
+    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
+        if self.config.layers.is_empty() {
+            return Ok(Vec::new());
+        }
+        let Some(root) = imports::package_root(file_path).map(|root| normalize(&root)) else {
+            return Ok(Vec::new());
+        };
+        let Some(layer) = self.layer_of(&root, file_path) else {
+            return Ok(Vec::new());
+        };
+
+        let tree =
+            treesitter::parse_dart(content).map_err(|e| AnalyzerError::Parse(e.to_string()))?;
+        let mut diagnostics = Vec::new();
+
+        for import in treesitter::extract_imports(&tree, content) {
+            let Some(uri) = imports::uri_string(&import.uri) else {
+                continue;
+            };
+
+            let (message, suggestion) = if let Some(banned) = layer
+                .banned_packages
+                .iter()
+                .find(|banned| is_library_of(uri, banned))
+            {
+                (
+                    format!("Layer '{}' may not import '{}'", layer.name, uri),
+                    format!(
+                        "Move the code depending on '{}' out of layer '{}'",
+                        banned, layer.name
+                    ),
+                )
+            } else {
+                let target = imports::resolve(file_path, uri)
+                    .and_then(|imported| self.layer_of(&root, &imported));
+                match target {
+                    Some(target)
+                        if target.name != layer.name
+                            && !layer.may_import.contains(&target.name) =>
+                    {
+                        (
+                            format!(
+                                "Layer '{}' may not import layer '{}' ('{}')",
+                                layer.name, target.name, uri
+                            ),
+                            format!(
+                                "Depend on an abstraction in a layer '{}' may import",
+                                layer.name
+                            ),
+                        )
+                    }
+                    _ => continue,
+                }
+            };
+
+            let start = import.node.start_position();
+            let end = import.node.end_position();
+            diagnostics.push(
+                Diagnostic::new(
+                    self.name(),
+                    message,
+                    Severity::Warning,
+                    RuleCategory::Style,
+                    Location {
+                        file: file_path.to_string_lossy().to_string(),
+                        line: start.row + 1,
+                        column: start.column + 1,
+                        end_line: Some(end.row + 1),
+                        end_column: Some(end.column + 1),
+                    },
+                )
+                .with_suggestion(suggestion),
+            );
+        }
+
+        Ok(diagnostics)
+    }
+}
+
+/// Whether `uri` is a library of `package`, a package name like `flutter`
+/// or a `dart:` library like `dart:io`
+fn is_library_of(uri: &str, package: &str) -> bool {
+    if package.starts_with("dart:") {
+        return uri == package;
+    }
+    uri.strip_prefix("package:")
+        .and_then(|path| path.split_once('/'))
+        .is_some_and(|(name, _)| name == package)
+}
+
+/// Whether a `/`-separated path matches a glob, where `**` matches any
+/// number of directories, `*` any characters but `/` and `?` one character
+fn glob_matches(pattern: &str, path: &str) -> bool {
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let path: Vec<&str> = path.split('/').collect();
+    segments_match(&pattern, &path)
+}
+
+fn segments_match(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|i| segments_match(rest, &path[i..])),
+        Some((segment, rest)) => path.split_first().is_some_and(|(name, path)| {
+            segment_matches(segment.as_bytes(), name.as_bytes()) && segments_match(rest, path)
+        }),
+    }
+}
+
+fn segment_matches(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.split_first() {
+        None => name.is_empty(),
+        Some((b'*', rest)) => (0..=name.len()).any(|i| segment_matches(rest, &name[i..])),
+        Some((b'?', rest)) => !name.is_empty() && segment_matches(rest, &name[1..]),
+        Some((c, rest)) => name.first() == Some(c) && segment_matches(rest, &name[1..]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob_matches("lib/domain/**", "lib/domain/user.dart"));
+        assert!(glob_matches("lib/domain/**", "lib/domain/models/user.dart"));
+        assert!(glob_matches(
+            "lib/**/*_repository.dart",
+            "lib/data/user_repository.dart"
+        ));
+        assert!(glob_matches(
+            "lib/**/*_repository.dart",
+            "lib/user_repository.dart"
+        ));
+        assert!(glob_matches("lib/?i/*.dart", "lib/ui/page.dart"));
+        assert!(!glob_matches("lib/domain/**", "lib/domainx/user.dart"));
+        assert!(!glob_matches("lib/*.dart", "lib/ui/page.dart"));
+    }
+
+    #[test]
+    fn test_is_library_of() {
+        assert!(is_library_of("package:flutter/material.dart", "flutter"));
+        assert!(!is_library_of("package:flutter_bloc/bloc.dart", "flutter"));
+        assert!(is_library_of("dart:io", "dart:io"));
+        assert!(!is_library_of("dart:isolate", "dart:io"));
+    }
+}
@@ -0,0 +1,828 @@
+//! Rules for Flutter widget code
+//!
+//! The pack only runs on files of packages whose `pubspec.yaml` depends on
+//! Flutter, see [`for_flutter_packages`]. The rules find widget code with
+//! tree-sitter queries; without resolved types, constructors are recognized
+//! by name, from the Flutter classes listed here and the classes declared in
+//! the file itself.
+
+use crate::analyzer::imports;
+use crate::analyzer::Rule;
+use crate::error::{AnalyzerError, Diagnostic, Location, Result, RuleCategory, Severity};
+use crate::treesitter::{self, QueryMatch};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::SystemTime;
+use tree_sitter::{Node, Tree};
+
+/// Calls of unnamed constructors, e.g. `Text('hi')`
+const CONSTRUCTOR_CALLS: &str = r#"
+    (member_access
+      . (identifier) @class (#match? @class "^[A-Z]")
+      . (selector (argument_part (arguments) @args)) .) @call
+"#;
+
+/// Calls of named constructors, e.g. `EdgeInsets.all(8)`
+const NAMED_CONSTRUCTOR_CALLS: &str = r#"
+    (member_access
+      . (identifier) @class (#match? @class "^[A-Z]")
+      . (selector (unconditional_assignable_selector (identifier) @constructor))
+      . (selector (argument_part (arguments) @args)) .) @call
+"#;
+
+/// Bodies of `async` functions and closures
+const ASYNC_BODIES: &str = r#"
+    [(function_body "async") (function_expression_body "async")] @body
+"#;
+
+const AWAITS: &str = "(await_expression) @await";
+
+/// `mounted` and `context.mounted`, as used to check for async gaps
+const MOUNTED_CHECKS: &str = r#"((identifier) @mounted (#eq? @mounted "mounted"))"#;
+
+const SET_STATE_CALLS: &str = r#"
+    (member_access
+      . (identifier) @name (#eq? @name "setState")
+      . (selector (argument_part))) @call
+"#;
+
+/// Identifiers that may refer to a `BuildContext`
+const IDENTIFIERS: &str = "(identifier) @identifier";
+
+/// Parameters declared with a `BuildContext` type
+const CONTEXT_PARAMETERS: &str = r#"
+    (formal_parameter
+      (type_identifier) @type (#eq? @type "BuildContext")
+      (identifier) @name)
+"#;
+
+const STATEFUL_WIDGETS: &str = r#"
+    (class_definition
+      name: (identifier) @name
+      (superclass (type_identifier) @super (#eq? @super "StatefulWidget")))
+"#;
+
+const STATE_CLASSES: &str = r#"
+    (class_definition
+      name: (identifier) @name
+      (superclass
+        (type_identifier) @super (#eq? @super "State")
+        (type_arguments (type_identifier) @widget))
+      body: (class_body) @body) @class
+"#;
+
+const CONST_CONSTRUCTORS: &str = r#"
+    (constant_constructor_signature (qualified . (identifier) @class))
+"#;
+
+const ENUMS: &str = "(enum_declaration name: (identifier) @name)";
+
+/// Flutter and Dart classes whose unnamed constructor is const
+const CONST_CLASSES: &[&str] = &[
+    "Align",
+    "Alignment",
+    "AspectRatio",
+    "BorderSide",
+    "BoxConstraints",
+    "BoxDecoration",
+    "Center",
+    "CircularProgressIndicator",
+    "Color",
+    "ColoredBox",
+    "Column",
+    "DecoratedBox",
+    "Divider",
+    "Duration",
+    "Expanded",
+    "Flexible",
+    "Icon",
+    "LinearProgressIndicator",
+    "Offset",
+    "Opacity",
+    "Padding",
+    "Placeholder",
+    "Positioned",
+    "Row",
+    "SafeArea",
+    "Size",
+    "SizedBox",
+    "Spacer",
+    "Stack",
+    "Text",
+    "TextStyle",
+    "ValueKey",
+    "VerticalDivider",
+    "Wrap",
+];
+
+/// Named constructors that are const
+const CONST_NAMED_CONSTRUCTORS: &[&str] = &[
+    "BorderRadius.all",
+    "EdgeInsets.all",
+    "EdgeInsets.fromLTRB",
+    "EdgeInsets.only",
+    "EdgeInsets.symmetric",
+    "EdgeInsetsDirectional.all",
+    "EdgeInsetsDirectional.fromSTEB",
+    "EdgeInsetsDirectional.only",
+    "Radius.circular",
+    "Radius.elliptical",
+    "SizedBox.expand",
+    "SizedBox.shrink",
+    "SizedBox.square",
+];
+
+/// Classes whose static members used in widget code are constants, e.g.
+/// `Colors.blue` or `MainAxisAlignment.center`
+const CONST_HOLDERS: &[&str] = &[
+    "Alignment",
+    "AlignmentDirectional",
+    "Axis",
+    "BorderRadius",
+    "BoxFit",
+    "BoxShape",
+    "Clip",
+    "Colors",
+    "CrossAxisAlignment",
+    "Curves",
+    "Duration",
+    "EdgeInsets",
+    "FontStyle",
+    "FontWeight",
+    "Icons",
+    "MainAxisAlignment",
+    "MainAxisSize",
+    "Offset",
+    "Size",
+    "StackFit",
+    "TextAlign",
+    "TextDirection",
+    "TextOverflow",
+    "VerticalDirection",
+    "WrapAlignment",
+    "double",
+];
+
+/// `State` methods that manage resources or react to changes, and so
+/// justify a `StatefulWidget` on their own
+const LIFECYCLE_METHODS: &[&str] = &[
+    "initState",
+    "didChangeDependencies",
+    "didUpdateWidget",
+    "deactivate",
+    "dispose",
+];
+
+/// Wrap `rule` so it only checks files of packages that depend on Flutter
+pub fn for_flutter_packages(rule: Arc<dyn Rule>) -> Arc<dyn Rule> {
+    Arc::new(FlutterOnly(rule))
+}
+
+struct FlutterOnly(Arc<dyn Rule>);
+
+impl Rule for FlutterOnly {
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
+        if is_flutter_package(file_path) {
+            self.0.check(file_path, content)
+        } else {
+            Ok(Vec::new())
+        }
+    }
+}
+
+/// When a package's `pubspec.yaml` was modified, and whether it depends on
+/// Flutter
+type CachedPubspec = (Option<SystemTime>, bool);
+
+/// Whether the package containing `file` depends on Flutter
+///
+/// The answer is cached per package until its `pubspec.yaml` changes.
+pub fn is_flutter_package(file: &Path) -> bool {
+    static CACHE: OnceLock<Mutex<HashMap<PathBuf, CachedPubspec>>> = OnceLock::new();
+
+    let Some(root) = imports::package_root(file) else {
+        return false;
+    };
+    let pubspec = root.join("pubspec.yaml");
+    let modified = std::fs::metadata(&pubspec).and_then(|m| m.modified()).ok();
+
+    let mut cache = CACHE.get_or_init(Default::default).lock().unwrap();
+    if let Some((cached_modified, depends)) = cache.get(&root) {
+        if *cached_modified == modified {
+            return *depends;
+        }
+    }
+    let depends = std::fs::read_to_string(&pubspec)
+        .map(|content| depends_on_flutter(&content))
+        .unwrap_or(false);
+    cache.insert(root, (modified, depends));
+    depends
+}
+
+/// Whether a `pubspec.yaml` lists `flutter` under `dependencies`
+pub fn depends_on_flutter(pubspec: &str) -> bool {
+    let mut in_dependencies = false;
+    let mut entry_indent = None;
+    for line in pubspec.lines() {
+        let content = line.split('#').next().unwrap_or_default().trim_end();
+        if content.trim().is_empty() {
+            continue;
+        }
+        let indent = content.len() - content.trim_start().len();
+        if indent == 0 {
+            in_dependencies = content == "dependencies:";
+            entry_indent = None;
+            continue;
+        }
+        if in_dependencies {
+            // Only direct entries, not the keys nested in them
+            let entry_indent = *entry_indent.get_or_insert(indent);
+            if indent == entry_indent && content.trim_start().starts_with("flutter:") {
+                return true;
+            }
+        }
+    }
+    false
+}
+
+fn parse(content: &str) -> Result<Tree> {
+    treesitter::parse_dart(content).map_err(|e| AnalyzerError::Parse(e.to_string()))
+}
+
+fn query<'a>(tree: &'a Tree, content: &str, query: &str) -> Result<Vec<QueryMatch<'a>>> {
+    treesitter::query_tree(tree, content, query).map_err(|e| AnalyzerError::Parse(e.to_string()))
+}
+
+/// The node captured as `name` in a match
+fn capture<'a>(m: &QueryMatch<'a>, name: &str) -> Option<Node<'a>> {
+    m.captures.iter().find(|c| c.name == name).map(|c| c.node)
+}
+
+fn text<'a>(node: Node, content: &'a str) -> &'a str {
+    node.utf8_text(content.as_bytes()).unwrap_or_default()
+}
+
+fn diagnostic(
+    rule: &str,
+    file_path: &Path,
+    node: Node,
+    message: String,
+    severity: Severity,
+    suggestion: &str,
+) -> Diagnostic {
+    let start = node.start_position();
+    let end = node.end_position();
+    Diagnostic::new(
+        rule,
+        message,
+        severity,
+        RuleCategory::Flutter,
+        Location {
+            file: file_path.to_string_lossy().to_string(),
+            line: start.row + 1,
+            column: start.column + 1,
+            end_line: Some(end.row + 1),
+            end_column: Some(end.column + 1),
+        },
+    )
+    .with_suggestion(suggestion)
+}
+
+/// Innermost function body containing `node`
+fn enclosing_body(node: Node) -> Option<Node> {
+    let mut current = node.parent();
+    while let Some(parent) = current {
+        if matches!(parent.kind(), "function_body" | "function_expression_body") {
+            return Some(parent);
+        }
+        current = parent.parent();
+    }
+    None
+}
+
+/// Nodes among `uses` that run after an `await` in the same async body,
+/// with no `mounted` check since
+fn after_async_gap<'a>(
+    tree: &'a Tree,
+    content: &str,
+    uses: Vec<Node<'a>>,
+) -> Result<Vec<Node<'a>>> {
+    let bodies: HashSet<usize> = query(tree, content, ASYNC_BODIES)?
+        .iter()
+        .filter_map(|m| capture(m, "body"))
+        .map(|n| n.id())
+        .collect();
+    let events_in = |pattern: &str, name: &str| -> Result<Vec<(usize, usize)>> {
+        Ok(query(tree, content, pattern)?
+            .iter()
+            .filter_map(|m| capture(m, name))
+            .filter_map(|n| Some((enclosing_body(n)?.id(), n.end_byte())))
+            .collect())
+    };
+    let awaits = events_in(AWAITS, "await")?;
+    let checks = events_in(MOUNTED_CHECKS, "mounted")?;
+
+    Ok(uses
+        .into_iter()
+        .filter(|node| {
+            let Some(body) = enclosing_body(*node).filter(|b| bodies.contains(&b.id())) else {
+                return false;
+            };
+            let before = |events: &[(usize, usize)]| {
+                events
+                    .iter()
+                    .filter(|(b, end)| *b == body.id() && *end <= node.start_byte())
+                    .map(|(_, end)| *end)
+                    .max()
+            };
+            match (before(&awaits), before(&checks)) {
+                (Some(gap), Some(check)) => check < gap,
+                (Some(_), None) => true,
+                (None, _) => false,
+            }
+        })
+        .collect())
+}
+
+// Rule: Prefer const for constructors whose arguments are all constant
+pub struct PreferConstConstructorsRule;
+
+/// What is known to be constant in a file
+struct Constants<'a> {
+    content: &'a str,
+    /// Classes declared in the file with a const constructor
+    const_classes: HashSet<String>,
+    enums: HashSet<String>,
+}
+
+impl Constants<'_> {
+    /// Whether `call`, a constructor call with `class`, `constructor` and
+    /// `args`, could be made const
+    fn is_const_call(&self, class: &str, constructor: Option<&str>, args: Node) -> bool {
+        let const_constructor = match constructor {
+            Some(constructor) => {
+                CONST_NAMED_CONSTRUCTORS.contains(&format!("{}.{}", class, constructor).as_str())
+            }
+            None => CONST_CLASSES.contains(&class) || self.const_classes.contains(class),
+        };
+        if !const_constructor {
+            return false;
+        }
+        let mut cursor = args.walk();
+        let all_const = args.named_children(&mut cursor).all(|argument| {
+            let value = match argument.kind() {
+                "named_argument" => argument.named_child(1),
+                _ => argument.named_child(0),
+            };
+            value.is_some_and(|value| self.is_const(value))
+        });
+        all_const
+    }
+
+    fn is_const(&self, node: Node) -> bool {
+        match node.kind() {
+            "decimal_integer_literal"
+            | "decimal_floating_point_literal"
+            | "hex_integer_literal"
+            | "true"
+            | "false"
+            | "null_literal"
+            | "const_object_expression"
+            | "symbol_literal" => true,
+            "string_literal" => {
+                let mut cursor = node.walk();
+                let interpolated = node
+                    .named_children(&mut cursor)
+                    .any(|n| n.kind() == "template_substitution");
+                !interpolated
+            }
+            "unary_expression" => {
+                text(node, self.content).starts_with('-')
+                    && node.named_child(1).is_some_and(|n| self.is_const(n))
+            }
+            "list_literal" => {
+                let mut cursor = node.walk();
+                let all_const = node
+                    .named_children(&mut cursor)
+                    .all(|n| n.kind() == "const_builtin" || self.is_const(n));
+                all_const
+            }
+            "member_access" => self.is_const_member_access(node),
+            _ => false,
+        }
+    }
+
+    /// `Colors.blue`, an enum value, or a const-capable constructor call
+    fn is_const_member_access<'t>(&self, node: Node<'t>) -> bool {
+        let mut cursor = node.walk();
+        let children: Vec<Node<'t>> = node.named_children(&mut cursor).collect();
+        let Some(class) = children.first().map(|n| text(*n, self.content)) else {
+            return false;
+        };
+        let member = |selector: &Node<'t>| {
+            selector
+                .named_child(0)
+                .filter(|n| n.kind() == "unconditional_assignable_selector")
+                .and_then(|n| n.named_child(0))
+                .map(|n| text(n, self.content))
+        };
+        let arguments = |selector: &Node<'t>| -> Option<Node<'t>> {
+            selector
+                .named_child(0)
+                .filter(|n| n.kind() == "argument_part")
+                .and_then(|n| n.named_child(0))
+        };
+        match children.as_slice() {
+            [_, selector] => match (member(selector), arguments(selector)) {
+                (Some(_), _) => CONST_HOLDERS.contains(&class) || self.enums.contains(class),
+                (None, Some(args)) => self.is_const_call(class, None, args),
+                _ => false,
+            },
+            [_, name, call] => match (member(name), arguments(call)) {
+                (Some(constructor), Some(args)) => {
+                    self.is_const_call(class, Some(constructor), args)
+                }
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+}
+
+/// Whether `node` is in a const context already, e.g. an argument of a
+/// const constructor call or an element of a const list
+fn in_const_context(node: Node) -> bool {
+    let mut current = node.parent();
+    while let Some(parent) = current {
+        if parent.kind() == "const_object_expression" {
+            return true;
+        }
+        let mut cursor = parent.walk();
+        let is_const = parent
+            .children(&mut cursor)
+            .any(|n| n.kind() == "const_builtin");
+        if is_const {
+            return true;
+        }
+        if matches!(parent.kind(), "function_body" | "function_expression_body") {
+            return false;
+        }
+        current = parent.parent();
+    }
+    false
+}
+
+/// The constructor call `call` is an argument of, if any
+fn enclosing_call(call: Node) -> Option<Node> {
+    let argument = call
+        .parent()
+        .filter(|n| matches!(n.kind(), "argument" | "named_argument"))?;
+    let arguments = argument.parent().filter(|n| n.kind() == "arguments")?;
+    let owner = arguments.parent()?;
+    match owner.kind() {
+        "argument_part" => owner.parent()?.parent(),
+        _ => Some(owner),
+    }
+}
+
+impl Rule for PreferConstConstructorsRule {
+    fn name(&self) -> &str {
+        "prefer_const_constructors"
+    }
+
+    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
+        let tree = parse(content)?;
+        let names = |pattern: &str, name: &str| -> Result<HashSet<String>> {
+            Ok(query(&tree, content, pattern)?
+                .iter()
+                .filter_map(|m| capture(m, name))
+                .map(|n| text(n, content).to_string())
+                .collect())
+        };
+        let constants = Constants {
+            content,
+            const_classes: names(CONST_CONSTRUCTORS, "class")?,
+            enums: names(ENUMS, "name")?,
+        };
+
+        let mut calls = query(&tree, content, CONSTRUCTOR_CALLS)?;
+        calls.extend(query(&tree, content, NAMED_CONSTRUCTOR_CALLS)?);
+
+        let mut diagnostics = Vec::new();
+        for call in calls {
+            let (Some(node), Some(class), Some(args)) = (
+                capture(&call, "call"),
+                capture(&call, "class"),
+                capture(&call, "args"),
+            ) else {
+                continue;
+            };
+            let constructor = capture(&call, "constructor").map(|n| text(n, content));
+            if !constants.is_const_call(text(class, content), constructor, args)
+                || in_const_context(node)
+            {
+                continue;
+            }
+            // Only the outermost call needs `const`
+            if enclosing_call(node).is_some_and(|owner| constants.is_const(owner)) {
+                continue;
+            }
+            diagnostics.push(diagnostic(
+                self.name(),
+                file_path,
+                node,
+                format!(
+                    "'{}' can be const: all of its arguments are constant",
+                    text(class, content)
+                ),
+                Severity::Info,
+                "Add 'const' so the instance is canonicalized at compile time",
+            ));
+        }
+        diagnostics.sort_by_key(|d| (d.location.line, d.location.column));
+
+        Ok(diagnostics)
+    }
+}
+
+// Rule: setState after an await needs a mounted check
+pub struct AvoidSetStateAfterAwaitRule;
+
+impl Rule for AvoidSetStateAfterAwaitRule {
+    fn name(&self) -> &str {
+        "avoid_setstate_after_await"
+    }
+
+    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
+        let tree = parse(content)?;
+        let calls = query(&tree, content, SET_STATE_CALLS)?
+            .iter()
+            .filter_map(|m| capture(m, "name"))
+            .collect();
+
+        Ok(after_async_gap(&tree, content, calls)?
+            .into_iter()
+            .map(|node| {
+                diagnostic(
+                    self.name(),
+                    file_path,
+                    node,
+                    "setState is called after an await without checking 'mounted'".to_string(),
+                    Severity::Warning,
+                    "Add 'if (!mounted) return;' after the await",
+                )
+            })
+            .collect())
+    }
+}
+
+// Rule: BuildContext must not be used across async gaps
+pub struct UseBuildContextSynchronouslyRule;
+
+impl Rule for UseBuildContextSynchronouslyRule {
+    fn name(&self) -> &str {
+        "use_build_context_synchronously"
+    }
+
+    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
+        let tree = parse(content)?;
+        let mut names: HashSet<&str> = query(&tree, content, CONTEXT_PARAMETERS)?
+            .iter()
+            .filter_map(|m| capture(m, "name"))
+            .map(|n| text(n, content))
+            .collect();
+        names.insert("context");
+
+        let uses = query(&tree, content, IDENTIFIERS)?
+            .iter()
+            .filter_map(|m| capture(m, "identifier"))
+            .filter(|n| names.contains(text(*n, content)))
+            .filter(|n| {
+                // Labels, members named `context`, and `context.mounted` itself
+                let parent = n.parent().map(|p| p.kind());
+                let checks_mounted = n
+                    .next_named_sibling()
+                    .and_then(|s| s.named_child(0))
+                    .and_then(|s| s.named_child(0))
+                    .is_some_and(|member| text(member, content) == "mounted");
+                !matches!(
+                    parent,
+                    Some("label" | "unconditional_assignable_selector" | "formal_parameter")
+                ) && !checks_mounted
+            })
+            .collect();
+
+        Ok(after_async_gap(&tree, content, uses)?
+            .into_iter()
+            .map(|node| {
+                diagnostic(
+                    self.name(),
+                    file_path,
+                    node,
+                    format!(
+                        "'{}' is used after an await without checking 'mounted'",
+                        text(node, content)
+                    ),
+                    Severity::Warning,
+                    "Check 'context.mounted' (or 'mounted' in a State) after the await",
+                )
+            })
+            .collect())
+    }
+}
+
+// Rule: StatefulWidgets should have mutable state
+pub struct AvoidUnnecessaryStatefulWidgetsRule;
+
+impl AvoidUnnecessaryStatefulWidgetsRule {
+    /// Whether a `State` class body holds or changes state, `set_state` being
+    /// the `setState` calls of the file
+    fn has_state(body: Node, class: Node, set_state: &[Node], content: &str) -> bool {
+        if class
+            .child_by_field_name("superclass")
+            .or_else(|| {
+                let mut cursor = class.walk();
+                let superclass = class
+                    .named_children(&mut cursor)
+                    .find(|n| n.kind() == "superclass");
+                superclass
+            })
+            .is_some_and(|s| {
+                let mut cursor = s.walk();
+                let has_mixins = s.named_children(&mut cursor).any(|n| n.kind() == "mixins");
+                has_mixins
+            })
+        {
+            // Mixins like TickerProviderStateMixin need the State
+            return true;
+        }
+
+        let mut cursor = body.walk();
+        for member in body.named_children(&mut cursor) {
+            let Some(definition) = member.named_child(0) else {
+                continue;
+            };
+            match definition.kind() {
+                "declaration" => {
+                    let mut declaration_cursor = definition.walk();
+                    let kinds: Vec<&str> = definition
+                        .children(&mut declaration_cursor)
+                        .map(|n| n.kind())
+                        .collect();
+                    let is_field = kinds.contains(&"initialized_identifier_list");
+                    let immutable = kinds
+                        .iter()
+                        .any(|k| matches!(*k, "final_builtin" | "const_builtin" | "static"));
+                    if is_field && !immutable {
+                        return true;
+                    }
+                }
+                "method_signature" => {
+                    let name = definition
+                        .named_child(0)
+                        .and_then(|s| s.child_by_field_name("name"))
+                        .map(|n| text(n, content));
+                    if name.is_some_and(|n| LIFECYCLE_METHODS.contains(&n)) {
+                        return true;
+                    }
+                }
+                _ => {}
+            }
+        }
+        set_state.iter().any(|call| {
+            call.start_byte() >= body.start_byte() && call.end_byte() <= body.end_byte()
+        })
+    }
+}
+
+impl Rule for AvoidUnnecessaryStatefulWidgetsRule {
+    fn name(&self) -> &str {
+        "avoid_unnecessary_stateful_widgets"
+    }
+
+    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
+        let tree = parse(content)?;
+        let states: HashMap<&str, (Node, Node)> = query(&tree, content, STATE_CLASSES)?
+            .iter()
+            .filter_map(|m| {
+                Some((
+                    text(capture(m, "widget")?, content),
+                    (capture(m, "class")?, capture(m, "body")?),
+                ))
+            })
+            .collect();
+        let set_state: Vec<Node> = query(&tree, content, SET_STATE_CALLS)?
+            .iter()
+            .filter_map(|m| capture(m, "call"))
+            .collect();
+
+        let mut diagnostics = Vec::new();
+        for widget in query(&tree, content, STATEFUL_WIDGETS)? {
+            let Some(name) = capture(&widget, "name") else {
+                continue;
+            };
+            // State classes in other files can't be checked
+            let Some((class, body)) = states.get(text(name, content)) else {
+                continue;
+            };
+            if !Self::has_state(*body, *class, &set_state, content) {
+                diagnostics.push(diagnostic(
+                    self.name(),
+                    file_path,
+                    name,
+                    format!(
+                        "'{}' is a StatefulWidget but its State has no mutable state",
+                        text(name, content)
+                    ),
+                    Severity::Info,
+                    "Make it a StatelessWidget",
+                ));
+            }
+        }
+
+        Ok(diagnostics)
+    }
+}
+
+// Rule: Container used only for padding or color
+pub struct AvoidContainerForPaddingOrColorRule;
+
+impl Rule for AvoidContainerForPaddingOrColorRule {
+    fn name(&self) -> &str {
+        "avoid_container_for_padding_or_color"
+    }
+
+    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
+        let tree = parse(content)?;
+        let mut diagnostics = Vec::new();
+
+        for call in query(&tree, content, CONSTRUCTOR_CALLS)? {
+            let (Some(class), Some(args)) = (capture(&call, "class"), capture(&call, "args"))
+            else {
+                continue;
+            };
+            if text(class, content) != "Container" {
+                continue;
+            }
+            let mut cursor = args.walk();
+            let labels: Option<Vec<&str>> = args
+                .named_children(&mut cursor)
+                .map(|argument| {
+                    (argument.kind() == "named_argument")
+                        .then(|| argument.named_child(0))
+                        .flatten()
+                        .and_then(|label| label.named_child(0))
+                        .map(|name| text(name, content))
+                })
+                .collect();
+            let Some(labels) = labels else {
+                continue;
+            };
+            let styling: Vec<&str> = labels
+                .into_iter()
+                .filter(|label| !matches!(*label, "child" | "key"))
+                .collect();
+
+            let replacement = match styling.as_slice() {
+                ["padding"] => "Padding",
+                ["color"] => "ColoredBox",
+                _ => continue,
+            };
+            diagnostics.push(diagnostic(
+                self.name(),
+                file_path,
+                class,
+                format!("Container is only used for its {}", styling[0]),
+                Severity::Info,
+                &format!("Use {} instead", replacement),
+            ));
+        }
+
+        Ok(diagnostics)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_depends_on_flutter() {
+        let flutter = "name: app\ndependencies:\n  flutter:\n    sdk: flutter\n  http: ^1.0.0\n";
+        assert!(depends_on_flutter(flutter));
+
+        // Only dev dependencies, the `flutter:` config section, or nested keys
+        let dev_only = "name: app\ndev_dependencies:\n  flutter:\n    sdk: flutter\n";
+        assert!(!depends_on_flutter(dev_only));
+        let section =
+            "name: app\ndependencies:\n  http: ^1.0.0\nflutter:\n  uses-material-design: true\n";
+        assert!(!depends_on_flutter(section));
+        let nested = "dependencies:\n  foo:\n    flutter: x\n";
+        assert!(!depends_on_flutter(nested));
+    }
+}
@@ -0,0 +1,200 @@
+//! Threshold rules on the complexity metrics of [`crate::metrics`]
+
+use crate::analyzer::Rule;
+use crate::config::MetricsConfig;
+use crate::error::{Diagnostic, Location, Result, RuleCategory, Severity};
+use crate::metrics::FileMetrics;
+use std::path::Path;
+use std::sync::Arc;
+
+/// A metric checked against a threshold
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Metric {
+    CyclomaticComplexity,
+    CognitiveComplexity,
+    NestingDepth,
+    Parameters,
+    FunctionLines,
+    ClassMethods,
+    ClassFields,
+}
+
+impl Metric {
+    pub const ALL: [Metric; 7] = [
+        Metric::CyclomaticComplexity,
+        Metric::CognitiveComplexity,
+        Metric::NestingDepth,
+        Metric::Parameters,
+        Metric::FunctionLines,
+        Metric::ClassMethods,
+        Metric::ClassFields,
+    ];
+
+    pub fn rule_name(self) -> &'static str {
+        match self {
+            Metric::CyclomaticComplexity => "cyclomatic_complexity",
+            Metric::CognitiveComplexity => "cognitive_complexity",
+            Metric::NestingDepth => "max_nesting_depth",
+            Metric::Parameters => "max_parameters",
+            Metric::FunctionLines => "max_function_lines",
+            Metric::ClassMethods => "max_class_methods",
+            Metric::ClassFields => "max_class_fields",
+        }
+    }
+
+    /// The metric's threshold in `config`
+    pub fn threshold(self, config: &MetricsConfig) -> usize {
+        match self {
+            Metric::CyclomaticComplexity => config.max_cyclomatic_complexity,
+            Metric::CognitiveComplexity => config.max_cognitive_complexity,
+            Metric::NestingDepth => config.max_nesting_depth,
+            Metric::Parameters => config.max_parameters,
+            Metric::FunctionLines => config.max_function_lines,
+            Metric::ClassMethods => config.max_class_methods,
+            Metric::ClassFields => config.max_class_fields,
+        }
+    }
+
+    fn describe(self, value: usize) -> String {
+        match self {
+            Metric::CyclomaticComplexity => format!("a cyclomatic complexity of {}", value),
+            Metric::CognitiveComplexity => format!("a cognitive complexity of {}", value),
+            Metric::NestingDepth => format!("control flow nested {} levels deep", value),
+            Metric::Parameters => plural(value, "parameter"),
+            Metric::FunctionLines => format!("{} of code", plural(value, "line")),
+            Metric::ClassMethods => plural(value, "method"),
+            Metric::ClassFields => plural(value, "field"),
+        }
+    }
+
+    fn suggestion(self) -> &'static str {
+        match self {
+            Metric::CyclomaticComplexity | Metric::CognitiveComplexity | Metric::FunctionLines => {
+                "Split it into smaller functions"
+            }
+            Metric::NestingDepth => "Return early, or extract the nested blocks into functions",
+            Metric::Parameters => "Group related parameters into a class",
+            Metric::ClassMethods | Metric::ClassFields => "Split the class by responsibility",
+        }
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    if count == 1 {
+        format!("1 {}", noun)
+    } else {
+        format!("{} {}s", count, noun)
+    }
+}
+
+// Rule: A function or class metric must not exceed its threshold
+pub struct MetricRule {
+    metric: Metric,
+    max: usize,
+}
+
+impl MetricRule {
+    pub fn new(metric: Metric, max: usize) -> Self {
+        Self { metric, max }
+    }
+
+    /// A rule for every metric, with the thresholds of `config`
+    pub fn all(config: &MetricsConfig) -> Vec<Arc<dyn Rule>> {
+        Metric::ALL
+            .into_iter()
+            .map(|metric| Arc::new(Self::new(metric, metric.threshold(config))) as Arc<dyn Rule>)
+            .collect()
+    }
+
+    fn diagnostic(
+        &self,
+        file_path: &Path,
+        name: &str,
+        line: usize,
+        column: usize,
+        length: usize,
+        value: usize,
+    ) -> Diagnostic {
+        Diagnostic::new(
+            self.name(),
+            format!(
+                "'{}' has {} (max {})",
+                name,
+                self.metric.describe(value),
+                self.max
+            ),
+            Severity::Info,
+            RuleCategory::Style,
+            Location {
+                file: file_path.to_string_lossy().to_string(),
+                line,
+                column,
+                end_line: Some(line),
+                end_column: Some(column + length),
+            },
+        )
+        .with_suggestion(self.metric.suggestion())
+    }
+}
+
+impl Rule for MetricRule {
+    fn name(&self) -> &str {
+        self.metric.rule_name()
+    }
+
+    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
+        let metrics = FileMetrics::compute(&file_path.to_string_lossy(), content)?;
+        let mut diagnostics = Vec::new();
+
+        for function in &metrics.functions {
+            let value = match self.metric {
+                Metric::CyclomaticComplexity => function.cyclomatic_complexity,
+                Metric::CognitiveComplexity => function.cognitive_complexity,
+                Metric::NestingDepth => function.max_nesting_depth,
+                Metric::Parameters => function.parameters,
+                Metric::FunctionLines => function.lines_of_code,
+                Metric::ClassMethods | Metric::ClassFields => continue,
+            };
+            if value > self.max {
+                // Constructors are already named `A` or `A.named`
+                let name = match &function.class {
+                    Some(class)
+                        if function.name != *class
+                            && !function.name.starts_with(&format!("{}.", class)) =>
+                    {
+                        format!("{}.{}", class, function.name)
+                    }
+                    _ => function.name.clone(),
+                };
+                diagnostics.push(self.diagnostic(
+                    file_path,
+                    &name,
+                    function.line,
+                    function.column,
+                    function.name.len(),
+                    value,
+                ));
+            }
+        }
+
+        for class in &metrics.classes {
+            let value = match self.metric {
+                Metric::ClassMethods => class.methods,
+                Metric::ClassFields => class.fields,
+                _ => continue,
+            };
+            if value > self.max {
+                diagnostics.push(self.diagnostic(
+                    file_path,
+                    &class.name,
+                    class.line,
+                    class.column,
+                    class.name.len(),
+                    value,
+                ));
+            }
+        }
+
+        Ok(diagnostics)
+    }
+}
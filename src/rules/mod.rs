@@ -1,32 +1,101 @@
+pub mod architecture;
+pub mod flutter;
+pub mod metrics;
+pub mod project;
 pub mod runtime;
 pub mod style;
 
-use crate::analyzer::Rule;
+use crate::analyzer::{ProjectRule, Rule};
+use crate::config::{AnalyzerConfig, MetricsConfig};
+use crate::error::RuleCategory;
+use crate::lsp::semantic::SemanticAnalyzer;
 use std::sync::Arc;
 
 pub fn get_all_rules() -> Vec<Arc<dyn Rule>> {
-    vec![
+    let rules: Vec<Arc<dyn Rule>> = vec![
         // Style rules
         Arc::new(style::CamelCaseClassNameRule),
         Arc::new(style::SnakeCaseFileNameRule),
-        Arc::new(style::PrivateFieldUnderscoreRule),
+        Arc::new(style::PrivateFieldUnderscoreRule::default()),
         Arc::new(style::LineLengthRule::new(120)),
+        Arc::new(architecture::LayerDependenciesRule::default()),
         // Runtime rules
         Arc::new(runtime::AvoidDynamicRule),
         Arc::new(runtime::AvoidEmptyCatchRule),
         Arc::new(runtime::UnusedImportRule),
         Arc::new(runtime::AvoidPrintRule),
-        Arc::new(runtime::AvoidNullCheckOnNullableRule),
-    ]
+        Arc::new(runtime::AvoidNullCheckOnNullableRule::new()),
+        Arc::new(runtime::UnawaitedFuturesRule::new()),
+        Arc::new(runtime::AvoidVoidAsyncRule),
+        Arc::new(runtime::AvoidFutureInForEachRule::new()),
+        Arc::new(runtime::AvoidSyncIoInAsyncRule),
+        Arc::new(runtime::AvoidUndisposedResourcesRule::default()),
+    ];
+    // Complexity metric rules with the default thresholds, and Flutter rules,
+    // which skip packages that don't depend on Flutter
+    rules
+        .into_iter()
+        .chain(metrics::MetricRule::all(&MetricsConfig::default()))
+        .chain(get_flutter_rules())
+        .collect()
+}
+
+/// Rules enabled by `config`, with its line length, private field checks,
+/// disposable types, metric thresholds and layers
+pub fn get_rules_for_config(config: &AnalyzerConfig) -> Vec<Arc<dyn Rule>> {
+    if !config.enabled {
+        return Vec::new();
+    }
+
+    let style: Vec<Arc<dyn Rule>> = vec![
+        Arc::new(style::CamelCaseClassNameRule),
+        Arc::new(style::SnakeCaseFileNameRule),
+        Arc::new(style::PrivateFieldUnderscoreRule::new(
+            config.private_fields,
+        )),
+        Arc::new(style::LineLengthRule::new(config.max_line_length)),
+        Arc::new(architecture::LayerDependenciesRule::new(
+            config.architecture.clone(),
+        )),
+    ];
+    let enabled = |category: RuleCategory| {
+        move |rule: &Arc<dyn Rule>| config.is_rule_enabled(rule.name(), category.clone())
+    };
+    style
+        .into_iter()
+        .chain(metrics::MetricRule::all(&config.metrics))
+        .filter(enabled(RuleCategory::Style))
+        .chain(
+            get_runtime_rules()
+                .into_iter()
+                .map(|rule| match rule.name() {
+                    "avoid_undisposed_resources" => Arc::new(
+                        runtime::AvoidUndisposedResourcesRule::new(config.disposable_types.clone()),
+                    ) as Arc<dyn Rule>,
+                    _ => rule,
+                })
+                .filter(enabled(RuleCategory::Runtime)),
+        )
+        .chain(
+            get_flutter_rules()
+                .into_iter()
+                .filter(enabled(RuleCategory::Flutter)),
+        )
+        .collect()
 }
 
 pub fn get_style_rules() -> Vec<Arc<dyn Rule>> {
-    vec![
+    let rules: Vec<Arc<dyn Rule>> = vec![
         Arc::new(style::CamelCaseClassNameRule),
         Arc::new(style::SnakeCaseFileNameRule),
-        Arc::new(style::PrivateFieldUnderscoreRule),
+        Arc::new(style::PrivateFieldUnderscoreRule::default()),
         Arc::new(style::LineLengthRule::new(120)),
-    ]
+        Arc::new(architecture::LayerDependenciesRule::default()),
+    ];
+    rules
+        .into_iter()
+        .chain(metrics::MetricRule::all(&MetricsConfig::default()))
+        .collect()
 }
 
 pub fn get_runtime_rules() -> Vec<Arc<dyn Rule>> {
@@ -35,6 +104,87 @@ pub fn get_runtime_rules() -> Vec<Arc<dyn Rule>> {
         Arc::new(runtime::AvoidEmptyCatchRule),
         Arc::new(runtime::UnusedImportRule),
         Arc::new(runtime::AvoidPrintRule),
-        Arc::new(runtime::AvoidNullCheckOnNullableRule),
+        Arc::new(runtime::AvoidNullCheckOnNullableRule::new()),
+        Arc::new(runtime::UnawaitedFuturesRule::new()),
+        Arc::new(runtime::AvoidVoidAsyncRule),
+        Arc::new(runtime::AvoidFutureInForEachRule::new()),
+        Arc::new(runtime::AvoidSyncIoInAsyncRule),
+        Arc::new(runtime::AvoidUndisposedResourcesRule::default()),
     ]
 }
+
+/// Flutter widget rules, which only check files of packages that depend
+/// on Flutter
+pub fn get_flutter_rules() -> Vec<Arc<dyn Rule>> {
+    let rules: Vec<Arc<dyn Rule>> = vec![
+        Arc::new(flutter::PreferConstConstructorsRule),
+        Arc::new(flutter::AvoidSetStateAfterAwaitRule),
+        Arc::new(flutter::UseBuildContextSynchronouslyRule),
+        Arc::new(flutter::AvoidUnnecessaryStatefulWidgetsRule),
+        Arc::new(flutter::AvoidContainerForPaddingOrColorRule),
+    ];
+    rules
+        .into_iter()
+        .map(flutter::for_flutter_packages)
+        .collect()
+}
+
+/// Rules run over the whole project after the per-file rules
+pub fn get_project_rules() -> Vec<Arc<dyn ProjectRule>> {
+    get_style_project_rules()
+        .into_iter()
+        .chain(get_runtime_project_rules())
+        .collect()
+}
+
+/// Project rules enabled by `config`
+pub fn get_project_rules_for_config(config: &AnalyzerConfig) -> Vec<Arc<dyn ProjectRule>> {
+    if !config.enabled {
+        return Vec::new();
+    }
+    let enabled = |category: RuleCategory| {
+        move |rule: &Arc<dyn ProjectRule>| config.is_rule_enabled(rule.name(), category.clone())
+    };
+    get_style_project_rules()
+        .into_iter()
+        .filter(enabled(RuleCategory::Style))
+        .chain(
+            get_runtime_project_rules()
+                .into_iter()
+                .filter(enabled(RuleCategory::Runtime)),
+        )
+        .collect()
+}
+
+fn get_style_project_rules() -> Vec<Arc<dyn ProjectRule>> {
+    vec![Arc::new(project::ImportCyclesRule)]
+}
+
+fn get_runtime_project_rules() -> Vec<Arc<dyn ProjectRule>> {
+    vec![
+        Arc::new(project::UnusedPrivateDeclarationRule),
+        Arc::new(project::UnreferencedPublicDeclarationRule),
+    ]
+}
+
+/// Switch the rules that can consult resolved types over to `analyzer`
+pub fn with_semantic(
+    rules: Vec<Arc<dyn Rule>>,
+    analyzer: Arc<dyn SemanticAnalyzer>,
+) -> Vec<Arc<dyn Rule>> {
+    rules
+        .into_iter()
+        .map(|rule| match rule.name() {
+            "avoid_null_check_on_nullable" => Arc::new(
+                runtime::AvoidNullCheckOnNullableRule::with_semantic(analyzer.clone()),
+            ) as Arc<dyn Rule>,
+            "unawaited_futures" => Arc::new(runtime::UnawaitedFuturesRule::with_semantic(
+                analyzer.clone(),
+            )),
+            "avoid_future_in_foreach" => Arc::new(
+                runtime::AvoidFutureInForEachRule::with_semantic(analyzer.clone()),
+            ),
+            _ => rule,
+        })
+        .collect()
+}
@@ -0,0 +1,339 @@
+//! Project rules, which see every file of the project at once
+
+use std::collections::VecDeque;
+use std::path::Path;
+
+use crate::analyzer::graph::ImportGraph;
+use crate::analyzer::imports;
+use crate::analyzer::index::{Declaration, DeclarationKind, IndexedFile, ProjectIndex};
+use crate::analyzer::ProjectRule;
+use crate::error::{Diagnostic, Fix, FixEdit, Result, RuleCategory, Severity};
+
+// Rule: Files must not import or export each other in a cycle
+pub struct ImportCyclesRule;
+
+impl ProjectRule for ImportCyclesRule {
+    fn name(&self) -> &str {
+        "import_cycles"
+    }
+
+    /// One diagnostic per cycle, on the directive closing it
+    fn check_project(&self, index: &ProjectIndex) -> Result<Vec<Diagnostic>> {
+        let graph = ImportGraph::new(index);
+        let mut diagnostics = Vec::new();
+
+        for component in graph.cycles() {
+            let start = component[0];
+            let cycle = graph.shortest_cycle(start, &component);
+            let last = cycle[cycle.len() - 1];
+            let Some(closing) = graph.directive(last, start) else {
+                continue;
+            };
+
+            let root = imports::package_root(&index.path(start));
+            let display = |file: usize| display_path(&index.path(file), root.as_deref());
+            let path: Vec<String> = cycle
+                .iter()
+                .chain([&start])
+                .map(|&file| display(file))
+                .collect();
+            let mut message = format!("Import cycle: {}", path.join(" -> "));
+            if component.len() > cycle.len() {
+                message.push_str(&format!(
+                    " ({} files import each other in this cycle)",
+                    component.len()
+                ));
+            }
+
+            diagnostics.push(
+                Diagnostic::new(
+                    self.name(),
+                    message,
+                    Severity::Warning,
+                    RuleCategory::Style,
+                    closing.location.clone(),
+                )
+                .with_suggestion(format!(
+                    "Remove the dependency of '{}' on '{}', or move what both use into a separate library",
+                    display(last),
+                    closing.uri
+                )),
+            );
+        }
+
+        Ok(diagnostics)
+    }
+}
+
+// Rule: Private declarations must be used within their library
+pub struct UnusedPrivateDeclarationRule;
+
+impl ProjectRule for UnusedPrivateDeclarationRule {
+    fn name(&self) -> &str {
+        "unused_private_declaration"
+    }
+
+    /// Private classes, functions, variables, fields and methods no file of
+    /// their library references
+    ///
+    /// Files of libraries with parts that aren't indexed are skipped, since
+    /// the references in those parts are unknown.
+    fn check_project(&self, index: &ProjectIndex) -> Result<Vec<Diagnostic>> {
+        let mut diagnostics = Vec::new();
+
+        for (i, file) in index.files.iter().enumerate() {
+            let Some(library) = index.library(i) else {
+                continue;
+            };
+            for declaration in file.declarations.iter().filter(|d| d.is_private()) {
+                let used = library.iter().any(|&other| {
+                    let skip = (other == i).then_some(declaration);
+                    is_referenced(file, &index.files[other], declaration, skip)
+                });
+                if used {
+                    continue;
+                }
+
+                let mut diagnostic = Diagnostic::new(
+                    self.name(),
+                    format!(
+                        "Private {} '{}' is never used",
+                        kind_name(declaration.kind),
+                        qualified_name(declaration)
+                    ),
+                    Severity::Warning,
+                    RuleCategory::Runtime,
+                    declaration.location.clone(),
+                );
+                diagnostic = match deletion(file, declaration) {
+                    Some(fix) => diagnostic.with_suggestion("Remove it").with_fix(fix),
+                    None => diagnostic.with_suggestion(format!(
+                        "Remove '{}' from its declaration",
+                        declaration.name
+                    )),
+                };
+                diagnostics.push(diagnostic);
+            }
+        }
+
+        Ok(diagnostics)
+    }
+}
+
+// Rule: Public declarations of `lib/src` must be used outside their file
+pub struct UnreferencedPublicDeclarationRule;
+
+impl ProjectRule for UnreferencedPublicDeclarationRule {
+    fn name(&self) -> &str {
+        "unreferenced_public_declaration"
+    }
+
+    /// Public top-level declarations of files under a package's `lib/src`
+    /// that no file outside their library references, and no public library
+    /// of the package exports
+    ///
+    /// Other packages shouldn't import `lib/src`, so every use of these
+    /// declarations is in the project. Files of libraries with parts that
+    /// aren't indexed are skipped, like for [`UnusedPrivateDeclarationRule`].
+    fn check_project(&self, index: &ProjectIndex) -> Result<Vec<Diagnostic>> {
+        let mut diagnostics = Vec::new();
+
+        for (i, file) in index.files.iter().enumerate() {
+            let path = index.path(i);
+            let Some(root) = imports::package_root(&path) else {
+                continue;
+            };
+            if !path.starts_with(root.join("lib").join("src")) {
+                continue;
+            }
+            let Some(library) = index.library(i) else {
+                continue;
+            };
+
+            for declaration in file.declarations.iter().filter(|d| {
+                !d.is_private()
+                    && matches!(
+                        d.kind,
+                        DeclarationKind::Class
+                            | DeclarationKind::Function
+                            | DeclarationKind::Variable
+                    )
+            }) {
+                let referenced = index.files.iter().enumerate().any(|(other, other_file)| {
+                    !library.contains(&other)
+                        && other_file.references.contains_key(&declaration.name)
+                });
+                if referenced || is_exported(index, i, &declaration.name, &root) {
+                    continue;
+                }
+
+                let mut diagnostic = Diagnostic::new(
+                    self.name(),
+                    format!(
+                        "Public {} '{}' is never used outside {}",
+                        kind_name(declaration.kind),
+                        declaration.name,
+                        display_path(&index.path(library[0]), Some(&root))
+                    ),
+                    Severity::Info,
+                    RuleCategory::Runtime,
+                    declaration.location.clone(),
+                );
+                // Only deleted if its own library doesn't use it either
+                let used = library.iter().any(|&other| {
+                    let skip = (other == i).then_some(declaration);
+                    is_referenced(file, &index.files[other], declaration, skip)
+                });
+                let fix = (!used).then(|| deletion(file, declaration)).flatten();
+                diagnostic = match fix {
+                    Some(fix) => diagnostic.with_suggestion("Remove it").with_fix(fix),
+                    None => diagnostic.with_suggestion(format!(
+                        "Make it private by renaming it to '_{}'",
+                        declaration.name
+                    )),
+                };
+                diagnostics.push(diagnostic);
+            }
+        }
+
+        Ok(diagnostics)
+    }
+}
+
+/// A path relative to the package root, or as analyzed outside one
+fn display_path(path: &Path, root: Option<&Path>) -> String {
+    match root.and_then(|root| path.strip_prefix(root).ok()) {
+        Some(relative) => relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/"),
+        None => path.to_string_lossy().to_string(),
+    }
+}
+
+fn kind_name(kind: DeclarationKind) -> &'static str {
+    match kind {
+        DeclarationKind::Class => "class",
+        DeclarationKind::Function => "function",
+        DeclarationKind::Method => "method",
+        DeclarationKind::Field => "field",
+        DeclarationKind::Variable => "variable",
+    }
+}
+
+fn qualified_name(declaration: &Declaration) -> String {
+    match &declaration.class {
+        Some(class) => format!("{}.{}", class, declaration.name),
+        None => declaration.name.clone(),
+    }
+}
+
+/// Whether `other` references the name of `declaration`, a declaration of
+/// `file`, leaving out references inside `skip`
+///
+/// References inside a declaration shared with other names, like `a` in
+/// `int a = 0, b = a;`, are kept.
+fn is_referenced(
+    file: &IndexedFile,
+    other: &IndexedFile,
+    declaration: &Declaration,
+    skip: Option<&Declaration>,
+) -> bool {
+    let skipped = skip
+        .filter(|skip| !is_shared(file, skip))
+        .map(|skip| skip.start_byte..skip.end_byte);
+    other
+        .references
+        .get(&declaration.name)
+        .is_some_and(|bytes| {
+            bytes
+                .iter()
+                .any(|byte| !skipped.as_ref().is_some_and(|range| range.contains(byte)))
+        })
+}
+
+/// Whether `declaration` is declared together with other names
+fn is_shared(file: &IndexedFile, declaration: &Declaration) -> bool {
+    file.declarations.iter().any(|other| {
+        other.name != declaration.name
+            && (other.start_byte, other.end_byte) == (declaration.start_byte, declaration.end_byte)
+    })
+}
+
+/// Whether a public library of the package at `root`, outside `lib/src`,
+/// exports `name` from `file`, directly or through other exports
+fn is_exported(index: &ProjectIndex, file: usize, name: &str, root: &Path) -> bool {
+    let start = index.library(file).map_or(file, |library| library[0]);
+    let mut visited = vec![start];
+    let mut queue = VecDeque::from([start]);
+    while let Some(target) = queue.pop_front() {
+        let exporters = index.files.iter().enumerate().filter(|(_, exporter)| {
+            exporter
+                .directives
+                .iter()
+                .any(|d| d.is_export && d.target == Some(target) && d.exposes(name))
+        });
+        for (exporter, _) in exporters {
+            let path = index.path(exporter);
+            if path.starts_with(root.join("lib")) && !path.starts_with(root.join("lib").join("src"))
+            {
+                return true;
+            }
+            if !visited.contains(&exporter) {
+                visited.push(exporter);
+                queue.push_back(exporter);
+            }
+        }
+    }
+    false
+}
+
+/// A fix deleting `declaration`, with the lines it is alone on
+///
+/// Returns `None` for declarations shared with other names, which can't be
+/// deleted whole.
+fn deletion(file: &IndexedFile, declaration: &Declaration) -> Option<Fix> {
+    if is_shared(file, declaration) {
+        return None;
+    }
+    let content = file.content.as_str();
+    let (mut start, mut end) = (declaration.start_byte, declaration.end_byte);
+
+    let line_start = content[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = content[end..]
+        .find('\n')
+        .map_or(content.len(), |i| end + i + 1);
+    let rest = content[end..line_end].trim();
+    if content[line_start..start].trim().is_empty() && (rest.is_empty() || rest.starts_with("//")) {
+        start = line_start;
+        end = line_end;
+        // Don't leave two blank lines, or one at the start of the file
+        let next_line = content[end..]
+            .find('\n')
+            .map(|i| &content[end..end + i + 1]);
+        let previous_blank = start == 0
+            || content[..start - 1]
+                .rfind('\n')
+                .map_or(&content[..start], |i| &content[i + 1..start])
+                .trim()
+                .is_empty();
+        if let Some(next_line) = next_line.filter(|line| line.trim().is_empty()) {
+            if previous_blank {
+                end += next_line.len();
+            }
+        }
+    }
+
+    Some(Fix {
+        description: format!(
+            "Remove unused {} '{}'",
+            kind_name(declaration.kind),
+            declaration.name
+        ),
+        edits: vec![FixEdit {
+            location: file.location(start, end),
+            replacement: String::new(),
+        }],
+    })
+}
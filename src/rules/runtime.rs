@@ -1,8 +1,15 @@
+use crate::analyzer::imports::{self, Directive};
 use crate::analyzer::Rule;
-use crate::error::{Diagnostic, Location, Result, RuleCategory, Severity};
+use crate::error::{
+    AnalyzerError, Diagnostic, Fix, FixEdit, Location, Result, RuleCategory, Severity,
+};
+use crate::lsp::semantic::SemanticAnalyzer;
+use crate::treesitter::{self, DartField, DartMethod};
 use regex::Regex;
+use std::collections::HashSet;
 use std::path::Path;
-use std::sync::OnceLock;
+use std::sync::{Arc, OnceLock};
+use tree_sitter::{Node, Tree};
 
 // Static regex patterns compiled once
 fn dynamic_regex() -> &'static Regex {
@@ -15,14 +22,6 @@ fn catch_regex() -> &'static Regex {
     REGEX.get_or_init(|| Regex::new(r"catch\s*\([^)]*\)\s*\{\s*\}").expect("Invalid regex pattern"))
 }
 
-fn import_regex() -> &'static Regex {
-    static REGEX: OnceLock<Regex> = OnceLock::new();
-    REGEX.get_or_init(|| {
-        Regex::new(r#"(?m)^import\s+['"]([^'"]+)['"](?:\s+as\s+(\w+))?;"#)
-            .expect("Invalid regex pattern")
-    })
-}
-
 fn print_regex() -> &'static Regex {
     static REGEX: OnceLock<Regex> = OnceLock::new();
     REGEX.get_or_init(|| Regex::new(r"\bprint\s*\(").expect("Invalid regex pattern"))
@@ -112,77 +111,120 @@ impl Rule for AvoidEmptyCatchRule {
 }
 
 // Rule: Detect unused imports
-// Note: This is a simplified heuristic-based implementation
-// A full implementation would require semantic analysis with an AST
+//
+// An import is used if one of the names it brings into scope is referenced:
+// its prefix, the names it shows, or the declarations of the imported
+// library that it doesn't hide. Libraries outside the workspace can't be
+// read, so plain imports of them are reported as unknown instead.
 pub struct UnusedImportRule;
 
-impl Rule for UnusedImportRule {
-    fn name(&self) -> &str {
-        "unused_import"
-    }
-
-    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
-        let mut diagnostics = Vec::new();
-
-        let mut imports = Vec::new();
-
-        // Collect all imports
-        for (line_num, line) in content.lines().enumerate() {
-            if let Some(caps) = import_regex().captures(line) {
-                let import_path = caps.get(1).unwrap().as_str();
-                let alias = caps.get(2).map(|m| m.as_str());
-
-                // Extract the imported symbols to check
-                let symbol_to_check = if let Some(alias) = alias {
-                    alias.to_string()
-                } else if let Some(last_part) = import_path.split('/').next_back() {
-                    last_part.trim_end_matches(".dart").to_string()
-                } else {
-                    continue;
-                };
+/// Whether an import is referenced
+enum ImportUsage {
+    Used,
+    Unused,
+    Unknown,
+}
 
-                imports.push((line_num, symbol_to_check, line.to_string()));
+impl UnusedImportRule {
+    fn usage(file_path: &Path, import: &Directive, referenced: &HashSet<String>) -> ImportUsage {
+        let used = |used: bool| {
+            if used {
+                ImportUsage::Used
+            } else {
+                ImportUsage::Unused
             }
+        };
+        if let Some(prefix) = &import.prefix {
+            return used(referenced.contains(prefix));
+        }
+        if !import.show.is_empty() {
+            return used(import.show.iter().any(|name| referenced.contains(name)));
         }
 
-        // Check if each import is used in the file
-        // Note: This is a simple heuristic that checks for literal string matches
-        // It may produce false positives for:
-        // - Imports used only in type annotations
-        // - Imports used through qualified access
-        // - Transitive dependencies
-        for (line_num, symbol, import_line) in imports {
-            let mut is_used = false;
-
-            // Check if symbol appears elsewhere in the file
-            for (check_line_num, line) in content.lines().enumerate() {
-                if check_line_num == line_num {
-                    continue; // Skip the import line itself
-                }
-
-                if line.contains(&symbol) {
-                    is_used = true;
-                    break;
+        match imports::resolve(file_path, &import.uri).and_then(|p| imports::library_names(&p)) {
+            Some(library) => {
+                if library
+                    .names
+                    .iter()
+                    .any(|name| import.exposes(name) && referenced.contains(name))
+                {
+                    ImportUsage::Used
+                } else if library.complete {
+                    ImportUsage::Unused
+                } else {
+                    ImportUsage::Unknown
                 }
             }
+            None => ImportUsage::Unknown,
+        }
+    }
+}
 
-            if !is_used {
-                diagnostics.push(
+impl Rule for UnusedImportRule {
+    fn name(&self) -> &str {
+        "unused_import"
+    }
+
+    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
+        let mut diagnostics = Vec::new();
+        let tree =
+            treesitter::parse_dart(content).map_err(|e| AnalyzerError::Parse(e.to_string()))?;
+        let referenced = imports::referenced_names(&tree, content);
+
+        for import in imports::directives(&tree, content)
+            .iter()
+            .filter(|d| !d.is_export)
+        {
+            let start = import.node.start_position();
+            let end = import.node.end_position();
+            let location = Location {
+                file: file_path.to_string_lossy().to_string(),
+                line: start.row + 1,
+                column: start.column + 1,
+                end_line: Some(end.row + 1),
+                end_column: Some(end.column + 1),
+            };
+
+            match Self::usage(file_path, import, &referenced) {
+                ImportUsage::Used => {}
+                ImportUsage::Unused => diagnostics.push(
                     Diagnostic::new(
                         self.name(),
-                        format!("Import '{}' is unused", symbol),
+                        format!("Import '{}' is unused", import.uri),
                         Severity::Warning,
                         RuleCategory::Runtime,
-                        Location {
-                            file: file_path.to_string_lossy().to_string(),
-                            line: line_num + 1,
-                            column: 1,
-                            end_line: Some(line_num + 1),
-                            end_column: Some(import_line.len()),
-                        },
+                        location,
                     )
-                    .with_suggestion("Remove this unused import"),
-                );
+                    .with_suggestion("Remove this unused import")
+                    .with_fix(Fix {
+                        description: "Remove unused import".to_string(),
+                        edits: vec![FixEdit {
+                            location: Location {
+                                file: file_path.to_string_lossy().to_string(),
+                                line: start.row + 1,
+                                column: 1,
+                                end_line: Some(end.row + 2),
+                                end_column: Some(1),
+                            },
+                            replacement: String::new(),
+                        }],
+                    }),
+                ),
+                ImportUsage::Unknown => diagnostics.push(
+                    Diagnostic::new(
+                        self.name(),
+                        format!(
+                            "Cannot tell whether import '{}' is used: what it declares is unknown",
+                            import.uri
+                        ),
+                        Severity::Info,
+                        RuleCategory::Runtime,
+                        location,
+                    )
+                    .with_suggestion(
+                        "Add a 'show' combinator or a prefix so its usage can be checked",
+                    ),
+                ),
             }
         }
 
@@ -232,7 +274,37 @@ impl Rule for AvoidPrintRule {
 }
 
 // Rule: Avoid null check operator on nullable types without null checking
-pub struct AvoidNullCheckOnNullableRule;
+#[derive(Default)]
+pub struct AvoidNullCheckOnNullableRule {
+    semantic: Option<Arc<dyn SemanticAnalyzer>>,
+}
+
+impl AvoidNullCheckOnNullableRule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only report `!` on receivers whose resolved type is nullable
+    ///
+    /// Receivers whose type cannot be resolved are still reported.
+    pub fn with_semantic(analyzer: Arc<dyn SemanticAnalyzer>) -> Self {
+        Self {
+            semantic: Some(analyzer),
+        }
+    }
+
+    /// Whether the analyzer resolved the receiver at a position to a
+    /// non-nullable type
+    fn is_non_nullable(&self, file_path: &Path, line: usize, column: usize) -> bool {
+        let Some(semantic) = &self.semantic else {
+            return false;
+        };
+        matches!(
+            semantic.resolve_type(file_path, line, column),
+            Ok(Some(type_info)) if !type_info.is_nullable
+        )
+    }
+}
 
 impl Rule for AvoidNullCheckOnNullableRule {
     fn name(&self) -> &str {
@@ -242,6 +314,14 @@ impl Rule for AvoidNullCheckOnNullableRule {
     fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
         let mut diagnostics = Vec::new();
 
+        // Without the current content, resolved types could be stale
+        let use_semantic = match &self.semantic {
+            Some(semantic) if null_check_regex().is_match(content) => {
+                semantic.update_file(file_path, content).is_ok()
+            }
+            _ => false,
+        };
+
         for (line_num, line) in content.lines().enumerate() {
             // Skip comments
             if line.trim_start().starts_with("//") {
@@ -249,6 +329,9 @@ impl Rule for AvoidNullCheckOnNullableRule {
             }
 
             for mat in null_check_regex().find_iter(line) {
+                if use_semantic && self.is_non_nullable(file_path, line_num + 1, mat.start() + 1) {
+                    continue;
+                }
                 diagnostics.push(
                     Diagnostic::new(
                         self.name(),
@@ -271,3 +354,664 @@ impl Rule for AvoidNullCheckOnNullableRule {
         Ok(diagnostics)
     }
 }
+
+// Async correctness rules. Whether a call returns a `Future` is resolved by
+// the semantic analyzer when one is available. Otherwise it's approximated
+// from the signatures declared in the file, the `Future` constructors and
+// the `Future` methods that return another `Future`.
+
+/// `Future` methods that return another `Future`
+const CHAINED_FUTURE_METHODS: &[&str] = &["then", "catchError", "whenComplete", "timeout"];
+
+/// `dart:io` methods that block the isolate until they're done
+const BLOCKING_IO_METHODS: &[&str] = &[
+    "readAsStringSync",
+    "readAsBytesSync",
+    "readAsLinesSync",
+    "writeAsStringSync",
+    "writeAsBytesSync",
+    "existsSync",
+    "createSync",
+    "deleteSync",
+    "renameSync",
+    "copySync",
+    "statSync",
+    "lengthSync",
+    "lastModifiedSync",
+    "listSync",
+    "openSync",
+    "resolveSymbolicLinksSync",
+    "runSync",
+];
+
+/// A call in a `member_access` chain, e.g. `save(1)` or `repo.save(1)`
+struct Call<'a> {
+    /// The chain the call is part of
+    chain: Node<'a>,
+    /// Identifier naming the called function or method
+    callee: Node<'a>,
+    /// The selector holding the arguments
+    arguments: Node<'a>,
+    /// Index of `arguments` among the chain's children
+    index: usize,
+    /// Whether the callee is a member of a receiver
+    is_method: bool,
+}
+
+impl<'a> Call<'a> {
+    /// Every call in a `member_access` chain, in order
+    fn all_in(chain: Node<'a>) -> Vec<Call<'a>> {
+        let mut cursor = chain.walk();
+        let children: Vec<Node> = chain.named_children(&mut cursor).collect();
+        let mut calls = Vec::new();
+        for (index, arguments) in children.iter().enumerate().skip(1) {
+            if arguments.named_child(0).map(|n| n.kind()) != Some("argument_part") {
+                continue;
+            }
+            let previous = children[index - 1];
+            let (callee, is_method) = if index == 1 {
+                (Some(previous), false)
+            } else {
+                let member = previous.named_child(0).filter(|n| {
+                    matches!(
+                        n.kind(),
+                        "unconditional_assignable_selector" | "conditional_assignable_selector"
+                    )
+                });
+                (member.and_then(|m| m.named_child(0)), true)
+            };
+            if let Some(callee) = callee.filter(|n| n.kind() == "identifier") {
+                calls.push(Call {
+                    chain,
+                    callee,
+                    arguments: *arguments,
+                    index,
+                    is_method,
+                });
+            }
+        }
+        calls
+    }
+
+    /// The call that ends a `member_access` chain, if it ends in one
+    fn ending(chain: Node<'a>) -> Option<Call<'a>> {
+        Self::all_in(chain)
+            .pop()
+            .filter(|call| call.arguments.end_byte() == chain.end_byte())
+    }
+
+    fn name<'c>(&self, content: &'c str) -> &'c str {
+        node_text(self.callee, content)
+    }
+
+    /// The function literal passed as the first argument
+    fn callback(&self) -> Option<Node<'a>> {
+        let arguments = self.arguments.named_child(0)?.named_child(0)?;
+        let argument = arguments
+            .named_child(0)
+            .filter(|n| n.kind() == "argument")?;
+        argument
+            .named_child(0)
+            .filter(|n| n.kind() == "function_expression")
+    }
+}
+
+/// Decides which calls of a file return a `Future`
+struct Futures<'a> {
+    file_path: &'a Path,
+    /// Functions and methods of the file declared to return a `Future`
+    declared: HashSet<String>,
+    semantic: Option<&'a dyn SemanticAnalyzer>,
+}
+
+impl<'a> Futures<'a> {
+    fn new(
+        file_path: &'a Path,
+        tree: &Tree,
+        content: &str,
+        semantic: &'a Option<Arc<dyn SemanticAnalyzer>>,
+    ) -> Self {
+        let declared = treesitter::extract_methods(tree, content)
+            .into_iter()
+            .filter(|method| {
+                let declared = return_type(method, content);
+                is_future_type(declared)
+                    || (declared.is_empty() && function_body(method.node).is_some_and(is_async))
+            })
+            .map(|method| method.name)
+            .collect();
+        // Without the current content, resolved types could be stale
+        let semantic = semantic
+            .as_deref()
+            .filter(|semantic| semantic.update_file(file_path, content).is_ok());
+        Self {
+            file_path,
+            declared,
+            semantic,
+        }
+    }
+
+    fn returns_future(&self, call: &Call, content: &str) -> bool {
+        if let Some(semantic) = self.semantic {
+            let position = call.callee.start_position();
+            if let Ok(Some(type_info)) =
+                semantic.resolve_type(self.file_path, position.row + 1, position.column + 1)
+            {
+                let returned = if type_info.is_function {
+                    type_info.return_type.as_deref()
+                } else {
+                    Some(&type_info)
+                };
+                return returned.is_some_and(|t| is_future_type(&t.name));
+            }
+        }
+
+        let name = call.name(content);
+        // `Future(...)` and `Future.delayed(...)`
+        let constructs_future = call
+            .chain
+            .named_child(0)
+            .is_some_and(|receiver| node_text(receiver, content) == "Future")
+            && (call.index == 1 || (call.index == 2 && call.is_method));
+        constructs_future
+            || (call.is_method && CHAINED_FUTURE_METHODS.contains(&name))
+            || self.declared.contains(name)
+    }
+}
+
+fn node_text<'c>(node: Node, content: &'c str) -> &'c str {
+    node.utf8_text(content.as_bytes()).unwrap_or_default()
+}
+
+fn node_location(file_path: &Path, node: Node) -> Location {
+    let start = node.start_position();
+    let end = node.end_position();
+    Location {
+        file: file_path.to_string_lossy().to_string(),
+        line: start.row + 1,
+        column: start.column + 1,
+        end_line: Some(end.row + 1),
+        end_column: Some(end.column + 1),
+    }
+}
+
+fn is_future_type(name: &str) -> bool {
+    name == "Future" || name.starts_with("Future<") || name.starts_with("FutureOr")
+}
+
+/// The return type written before a function's name, empty if omitted
+fn return_type<'c>(method: &DartMethod, content: &'c str) -> &'c str {
+    match method.node.child_by_field_name("name") {
+        Some(name) => content[method.start_byte..name.start_byte()].trim(),
+        None => "",
+    }
+}
+
+/// The body of the function declared by a `function_signature`
+fn function_body(signature: Node) -> Option<Node> {
+    let declaration = match signature.parent() {
+        Some(parent) if parent.kind() == "method_signature" => parent,
+        _ => signature,
+    };
+    declaration
+        .next_named_sibling()
+        .filter(|n| n.kind() == "function_body")
+}
+
+/// Whether a function body is `async`, which generators (`async*`) aren't
+fn is_async(body: Node) -> bool {
+    let mut cursor = body.walk();
+    let is_async = body.children(&mut cursor).any(|c| c.kind() == "async");
+    is_async
+}
+
+/// Whether the innermost function around `node` is `async`
+fn in_async_body(node: Node) -> bool {
+    let mut current = node.parent();
+    while let Some(parent) = current {
+        if matches!(parent.kind(), "function_body" | "function_expression_body") {
+            return is_async(parent);
+        }
+        current = parent.parent();
+    }
+    false
+}
+
+/// Calls of a file, from the `member_access` expressions
+fn calls<'a>(tree: &'a Tree, content: &str) -> Vec<Call<'a>> {
+    treesitter::extract_expressions(tree, content)
+        .into_iter()
+        .filter(|e| e.kind == "member_access")
+        .flat_map(|e| Call::all_in(e.node))
+        .collect()
+}
+
+// Rule: Futures must be awaited or explicitly marked as unawaited
+#[derive(Default)]
+pub struct UnawaitedFuturesRule {
+    semantic: Option<Arc<dyn SemanticAnalyzer>>,
+}
+
+impl UnawaitedFuturesRule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decide which calls return a `Future` from their resolved types
+    ///
+    /// Calls whose type cannot be resolved are approximated syntactically.
+    pub fn with_semantic(analyzer: Arc<dyn SemanticAnalyzer>) -> Self {
+        Self {
+            semantic: Some(analyzer),
+        }
+    }
+}
+
+impl Rule for UnawaitedFuturesRule {
+    fn name(&self) -> &str {
+        "unawaited_futures"
+    }
+
+    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
+        let tree =
+            treesitter::parse_dart(content).map_err(|e| AnalyzerError::Parse(e.to_string()))?;
+        let futures = Futures::new(file_path, &tree, content, &self.semantic);
+        let mut diagnostics = Vec::new();
+
+        for expression in treesitter::extract_expressions(&tree, content) {
+            if expression.kind != "member_access"
+                || expression.node.parent().map(|p| p.kind()) != Some("expression_statement")
+            {
+                continue;
+            }
+            let Some(call) = Call::ending(expression.node) else {
+                continue;
+            };
+            if !futures.returns_future(&call, content) {
+                continue;
+            }
+
+            let diagnostic = Diagnostic::new(
+                self.name(),
+                format!(
+                    "The Future returned by '{}' is not awaited",
+                    call.name(content)
+                ),
+                Severity::Warning,
+                RuleCategory::Runtime,
+                node_location(file_path, expression.node),
+            );
+            let start = expression.node.start_position();
+            diagnostics.push(if in_async_body(expression.node) {
+                diagnostic
+                    .with_suggestion(
+                        "Await it, or wrap it in 'unawaited()' if it should run in the background",
+                    )
+                    .with_fix(Fix {
+                        description: "Await the future".to_string(),
+                        edits: vec![FixEdit {
+                            location: Location {
+                                file: file_path.to_string_lossy().to_string(),
+                                line: start.row + 1,
+                                column: start.column + 1,
+                                end_line: Some(start.row + 1),
+                                end_column: Some(start.column + 1),
+                            },
+                            replacement: "await ".to_string(),
+                        }],
+                    })
+            } else {
+                diagnostic.with_suggestion(
+                    "Make the function async and await it, or wrap it in 'unawaited()'",
+                )
+            });
+        }
+
+        Ok(diagnostics)
+    }
+}
+
+// Rule: Async functions should return a Future that callers can await
+pub struct AvoidVoidAsyncRule;
+
+impl Rule for AvoidVoidAsyncRule {
+    fn name(&self) -> &str {
+        "avoid_void_async"
+    }
+
+    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
+        let tree =
+            treesitter::parse_dart(content).map_err(|e| AnalyzerError::Parse(e.to_string()))?;
+        let mut diagnostics = Vec::new();
+
+        for method in treesitter::extract_methods(&tree, content) {
+            // Nothing awaits `main` anyway
+            if method.name == "main"
+                || return_type(&method, content) != "void"
+                || !function_body(method.node).is_some_and(is_async)
+            {
+                continue;
+            }
+            let Some(void_type) = method.node.named_child(0) else {
+                continue;
+            };
+            let location = node_location(file_path, void_type);
+            diagnostics.push(
+                Diagnostic::new(
+                    self.name(),
+                    format!(
+                        "'{}' is async but returns void, so callers can't await it or catch its errors",
+                        method.name
+                    ),
+                    Severity::Warning,
+                    RuleCategory::Runtime,
+                    location.clone(),
+                )
+                .with_suggestion("Return 'Future<void>' instead")
+                .with_fix(Fix {
+                    description: "Return Future<void>".to_string(),
+                    edits: vec![FixEdit {
+                        location,
+                        replacement: "Future<void>".to_string(),
+                    }],
+                }),
+            );
+        }
+
+        Ok(diagnostics)
+    }
+}
+
+// Rule: forEach doesn't wait for the futures returned by its callback
+#[derive(Default)]
+pub struct AvoidFutureInForEachRule {
+    semantic: Option<Arc<dyn SemanticAnalyzer>>,
+}
+
+impl AvoidFutureInForEachRule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Decide which arrow callbacks return a `Future` from resolved types
+    ///
+    /// Calls whose type cannot be resolved are approximated syntactically.
+    pub fn with_semantic(analyzer: Arc<dyn SemanticAnalyzer>) -> Self {
+        Self {
+            semantic: Some(analyzer),
+        }
+    }
+}
+
+impl Rule for AvoidFutureInForEachRule {
+    fn name(&self) -> &str {
+        "avoid_future_in_foreach"
+    }
+
+    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
+        let tree =
+            treesitter::parse_dart(content).map_err(|e| AnalyzerError::Parse(e.to_string()))?;
+        let futures = Futures::new(file_path, &tree, content, &self.semantic);
+        let mut diagnostics = Vec::new();
+
+        for call in calls(&tree, content) {
+            if !call.is_method || call.name(content) != "forEach" {
+                continue;
+            }
+            let Some(callback) = call.callback() else {
+                continue;
+            };
+            let mut cursor = callback.walk();
+            let Some(body) = callback
+                .named_children(&mut cursor)
+                .find(|n| n.kind() == "function_expression_body")
+            else {
+                continue;
+            };
+            // An async callback, or an arrow returning a call's Future
+            let returns_future = is_async(body)
+                || body
+                    .named_child(0)
+                    .filter(|n| n.kind() == "member_access")
+                    .and_then(Call::ending)
+                    .is_some_and(|returned| futures.returns_future(&returned, content));
+            if !returns_future {
+                continue;
+            }
+
+            diagnostics.push(
+                Diagnostic::new(
+                    self.name(),
+                    "'forEach' doesn't wait for the Futures its callback returns",
+                    Severity::Warning,
+                    RuleCategory::Runtime,
+                    node_location(file_path, callback),
+                )
+                .with_suggestion(
+                    "Await each one in a for-in loop, or use Future.wait with map to run them concurrently",
+                ),
+            );
+        }
+
+        Ok(diagnostics)
+    }
+}
+
+// Rule: Avoid blocking I/O inside async functions
+pub struct AvoidSyncIoInAsyncRule;
+
+impl Rule for AvoidSyncIoInAsyncRule {
+    fn name(&self) -> &str {
+        "avoid_sync_io_in_async"
+    }
+
+    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
+        let tree =
+            treesitter::parse_dart(content).map_err(|e| AnalyzerError::Parse(e.to_string()))?;
+        let mut diagnostics = Vec::new();
+
+        for call in calls(&tree, content) {
+            let name = call.name(content);
+            let suggestion = if !call.is_method && name == "sleep" {
+                "Use 'await Future.delayed(...)' instead".to_string()
+            } else if call.is_method && BLOCKING_IO_METHODS.contains(&name) {
+                format!(
+                    "Use '{}' and await it instead",
+                    name.trim_end_matches("Sync")
+                )
+            } else {
+                continue;
+            };
+            if !in_async_body(call.chain) {
+                continue;
+            }
+
+            diagnostics.push(
+                Diagnostic::new(
+                    self.name(),
+                    format!("'{}' blocks the isolate inside an async function", name),
+                    Severity::Warning,
+                    RuleCategory::Runtime,
+                    node_location(file_path, call.callee),
+                )
+                .with_suggestion(suggestion),
+            );
+        }
+
+        Ok(diagnostics)
+    }
+}
+
+/// Built-in types that hold resources, with the method releasing them
+const DISPOSABLE_TYPES: &[(&str, &str)] = &[
+    ("StreamSubscription", "cancel"),
+    ("Timer", "cancel"),
+    ("StreamController", "close"),
+    ("AnimationController", "dispose"),
+    ("TextEditingController", "dispose"),
+    ("ScrollController", "dispose"),
+    ("PageController", "dispose"),
+    ("TabController", "dispose"),
+    ("FocusNode", "dispose"),
+];
+
+/// Methods that release a resource
+const RELEASE_METHODS: &[&str] = &["cancel", "dispose", "close"];
+
+/// Methods in which a class releases its resources
+const DISPOSE_METHODS: &[&str] = &["dispose", "close"];
+
+// Rule: Fields holding subscriptions, controllers or timers must be released
+// in the class's dispose or close method
+#[derive(Default)]
+pub struct AvoidUndisposedResourcesRule {
+    extra_types: Vec<String>,
+}
+
+impl AvoidUndisposedResourcesRule {
+    /// Also check fields of `extra_types`, released by any of `cancel`,
+    /// `dispose` or `close`
+    pub fn new(extra_types: Vec<String>) -> Self {
+        Self { extra_types }
+    }
+
+    /// The disposable type of a field, from its annotation or initializer
+    fn disposable_type(&self, field: &DartField, content: &str) -> Option<String> {
+        let is_disposable = |name: &str| {
+            DISPOSABLE_TYPES.iter().any(|(t, _)| *t == name)
+                || self.extra_types.iter().any(|t| t == name)
+        };
+        if let Some(annotation) = &field.type_annotation {
+            return is_disposable(annotation).then(|| annotation.clone());
+        }
+
+        // `TextEditingController()`, `Timer.periodic(...)` or `stream.listen(...)`
+        let initializer = field
+            .name_node
+            .next_named_sibling()
+            .filter(|n| n.kind() == "member_access")?;
+        let constructed = node_text(initializer.named_child(0)?, content);
+        if is_disposable(constructed) {
+            return Some(constructed.to_string());
+        }
+        Call::ending(initializer)
+            .filter(|call| call.is_method && call.name(content) == "listen")
+            .map(|_| "StreamSubscription".to_string())
+    }
+}
+
+/// Names of the fields released in a method body, e.g. by `_sub.cancel()`,
+/// `this._sub?.cancel()` or `_controller..removeListener(f)..dispose()`
+fn released_fields(body: Node, content: &str) -> HashSet<String> {
+    let mut released = HashSet::new();
+    let mut pending = vec![body];
+    while let Some(node) = pending.pop() {
+        match node.kind() {
+            "member_access" => {
+                let mut cursor = node.walk();
+                let chain: Vec<Node> = node.named_children(&mut cursor).collect();
+                for call in Call::all_in(node) {
+                    if !call.is_method
+                        || !RELEASE_METHODS.contains(&call.name(content))
+                        || call.index < 2
+                    {
+                        continue;
+                    }
+                    let receiver = match call.index - 2 {
+                        0 => Some(chain[0]),
+                        1 if chain[0].kind() == "this" => chain[1]
+                            .named_child(0)
+                            .and_then(|selector| selector.named_child(0)),
+                        _ => None,
+                    };
+                    if let Some(receiver) = receiver.filter(|n| n.kind() == "identifier") {
+                        released.insert(node_text(receiver, content).to_string());
+                    }
+                }
+            }
+            "cascade_section" => {
+                let releases = node
+                    .named_child(0)
+                    .filter(|n| n.kind() == "cascade_selector")
+                    .is_some_and(|n| RELEASE_METHODS.contains(&node_text(n, content)));
+                let receiver = node
+                    .parent()
+                    .and_then(|p| p.named_child(0))
+                    .filter(|n| n.kind() == "identifier");
+                if let (true, Some(receiver)) = (releases, receiver) {
+                    released.insert(node_text(receiver, content).to_string());
+                }
+            }
+            _ => {}
+        }
+        let mut cursor = node.walk();
+        pending.extend(node.named_children(&mut cursor));
+    }
+    released
+}
+
+impl Rule for AvoidUndisposedResourcesRule {
+    fn name(&self) -> &str {
+        "avoid_undisposed_resources"
+    }
+
+    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
+        let tree =
+            treesitter::parse_dart(content).map_err(|e| AnalyzerError::Parse(e.to_string()))?;
+        let fields = treesitter::extract_fields(&tree, content);
+        let methods = treesitter::extract_methods(&tree, content);
+        let mut diagnostics = Vec::new();
+
+        for class in treesitter::extract_classes(&tree, content) {
+            let in_class = |start: usize| class.start_byte <= start && start < class.end_byte;
+            let dispose = methods
+                .iter()
+                .filter(|m| in_class(m.start_byte) && DISPOSE_METHODS.contains(&m.name.as_str()))
+                .find_map(|m| Some((m.name.as_str(), function_body(m.node)?)));
+            let released = dispose
+                .map(|(_, body)| released_fields(body, content))
+                .unwrap_or_default();
+
+            for field in fields
+                .iter()
+                .filter(|f| in_class(f.start_byte) && !f.is_static)
+            {
+                let Some(disposable) = self.disposable_type(field, content) else {
+                    continue;
+                };
+                if released.contains(&field.name) {
+                    continue;
+                }
+                let release = DISPOSABLE_TYPES
+                    .iter()
+                    .find(|(t, _)| *t == disposable)
+                    .map_or("dispose", |(_, method)| method);
+                let message = match dispose {
+                    Some((method, _)) => format!(
+                        "'{}' ({}) is never released in '{}'",
+                        field.name, disposable, method
+                    ),
+                    None => format!(
+                        "'{}' ({}) is never released: '{}' has no dispose or close method",
+                        field.name, disposable, class.name
+                    ),
+                };
+                diagnostics.push(
+                    Diagnostic::new(
+                        self.name(),
+                        message,
+                        Severity::Warning,
+                        RuleCategory::Runtime,
+                        node_location(file_path, field.name_node),
+                    )
+                    .with_suggestion(format!(
+                        "Call '{}.{}()' in the class's dispose or close method",
+                        field.name, release
+                    )),
+                );
+            }
+        }
+
+        Ok(diagnostics)
+    }
+}
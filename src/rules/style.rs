@@ -1,8 +1,11 @@
 use crate::analyzer::Rule;
-use crate::error::{Diagnostic, Location, Result, RuleCategory, Severity};
+use crate::config::PrivateFieldConfig;
+use crate::error::{AnalyzerError, Diagnostic, Location, Result, RuleCategory, Severity};
+use crate::treesitter::{self, DartField};
 use regex::Regex;
 use std::path::Path;
 use std::sync::OnceLock;
+use tree_sitter::Node;
 
 // Static regex patterns compiled once
 fn class_regex() -> &'static Regex {
@@ -13,14 +16,6 @@ fn class_regex() -> &'static Regex {
     })
 }
 
-fn field_regex() -> &'static Regex {
-    static REGEX: OnceLock<Regex> = OnceLock::new();
-    REGEX.get_or_init(|| {
-        Regex::new(r"(?m)^\s*(?:final\s+|const\s+|static\s+)?(?:late\s+)?[A-Z][a-zA-Z0-9<>,\s]*\s+(_[a-zA-Z][a-zA-Z0-9_]*)\s*[;=]")
-            .expect("Invalid regex pattern")
-    })
-}
-
 // Rule: Class names should use CamelCase
 pub struct CamelCaseClassNameRule;
 
@@ -80,7 +75,7 @@ impl Rule for SnakeCaseFileNameRule {
         "snake_case_file_names"
     }
 
-    fn check(&self, file_path: &Path, _content: &str) -> Result<Vec<Diagnostic>> {
+    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
         let mut diagnostics = Vec::new();
 
         if let Some(file_name) = file_path.file_stem().and_then(|s| s.to_str()) {
@@ -92,12 +87,13 @@ impl Rule for SnakeCaseFileNameRule {
                         format!("File name '{}' should use snake_case", file_name),
                         Severity::Warning,
                         RuleCategory::Style,
+                        // The whole first line, standing for the file
                         Location {
                             file: file_path.to_string_lossy().to_string(),
                             line: 1,
                             column: 1,
-                            end_line: None,
-                            end_column: None,
+                            end_line: Some(1),
+                            end_column: Some(content.lines().next().unwrap_or_default().len() + 1),
                         },
                     )
                     .with_suggestion(format!(
@@ -112,32 +108,382 @@ impl Rule for SnakeCaseFileNameRule {
     }
 }
 
-// Rule: Private fields should start with underscore
-pub struct PrivateFieldUnderscoreRule;
+// Rule: Field visibility should follow Dart's underscore convention
+pub struct PrivateFieldUnderscoreRule {
+    config: PrivateFieldConfig,
+}
+
+impl Default for PrivateFieldUnderscoreRule {
+    fn default() -> Self {
+        Self::new(PrivateFieldConfig::default())
+    }
+}
+
+impl PrivateFieldUnderscoreRule {
+    pub fn new(config: PrivateFieldConfig) -> Self {
+        Self { config }
+    }
+
+    fn diagnostic(
+        &self,
+        file_path: &Path,
+        node: Node,
+        message: String,
+        severity: Severity,
+        suggestion: String,
+    ) -> Diagnostic {
+        let start = node.start_position();
+        let end = node.end_position();
+        Diagnostic::new(
+            self.name(),
+            message,
+            severity,
+            RuleCategory::Style,
+            Location {
+                file: file_path.to_string_lossy().to_string(),
+                line: start.row + 1,
+                column: start.column + 1,
+                end_line: Some(end.row + 1),
+                end_column: Some(end.column + 1),
+            },
+        )
+        .with_suggestion(suggestion)
+    }
+
+    /// Public fields of public types that can be reassigned from anywhere
+    fn check_public_mutable_fields(
+        &self,
+        file_path: &Path,
+        fields: &[DartField],
+        source: &str,
+    ) -> Vec<Diagnostic> {
+        fields
+            .iter()
+            .filter(|f| !f.is_final && !f.is_const && !f.name.starts_with('_'))
+            .filter(|f| {
+                enclosing_type_name(f.node, source).is_some_and(|name| !name.starts_with('_'))
+            })
+            .map(|f| {
+                let type_name = f.type_annotation.as_deref().unwrap_or("var");
+                self.diagnostic(
+                    file_path,
+                    f.name_node,
+                    format!(
+                        "Public field '{}' is mutable; make it final or private",
+                        f.name
+                    ),
+                    Severity::Warning,
+                    format!(
+                        "Rename to '_{}' and expose it with '{} get {} => _{};'",
+                        f.name, type_name, f.name, f.name
+                    ),
+                )
+            })
+            .collect()
+    }
+
+    /// Private fields whose same-named public getter only returns them
+    ///
+    /// A getter alone is still a read-only view of a mutable field, so only
+    /// final fields, or fields that also have a setter with no logic, are
+    /// flagged: those are equivalent to a public field.
+    fn check_trivial_getters(
+        &self,
+        file_path: &Path,
+        fields: &[DartField],
+        source: &str,
+    ) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+        for field in fields {
+            let Some(public_name) = field.name.strip_prefix('_') else {
+                continue;
+            };
+            if public_name.is_empty() || public_name.starts_with('_') {
+                continue;
+            }
+            let Some(body) = field.node.parent().and_then(|m| m.parent()) else {
+                continue;
+            };
+            let accessors = accessors(body, source);
+            let is_accessor = |kind: &str| {
+                accessors.iter().any(|a| {
+                    a.kind == kind
+                        && a.name == public_name
+                        && a.is_static == field.is_static
+                        && a.trivial_for == Some(field.name.as_str())
+                })
+            };
+            if !is_accessor("getter_signature") {
+                continue;
+            }
+
+            let read_only = field.is_final || field.is_const;
+            if read_only || is_accessor("setter_signature") {
+                let replacement = if read_only {
+                    "a public final field"
+                } else {
+                    "a public field"
+                };
+                diagnostics.push(self.diagnostic(
+                    file_path,
+                    field.name_node,
+                    format!(
+                        "Private field '{}' is exposed through a getter with no logic; use {} '{}' instead",
+                        field.name, replacement, public_name
+                    ),
+                    Severity::Info,
+                    format!(
+                        "Rename '{}' to '{}' and remove its accessors",
+                        field.name, public_name
+                    ),
+                ));
+            }
+        }
+        diagnostics
+    }
+
+    /// Locals and parameters named with a leading underscore, which has no
+    /// meaning outside of library members
+    fn check_underscore_locals(
+        &self,
+        file_path: &Path,
+        root: Node,
+        source: &str,
+    ) -> Vec<Diagnostic> {
+        let mut names = Vec::new();
+        collect_local_names(root, &mut names);
+
+        names
+            .into_iter()
+            .filter_map(|(node, what)| {
+                let name = node.utf8_text(source.as_bytes()).ok()?;
+                let stripped = name.trim_start_matches('_');
+                // `_` and `__` are the conventional names for unused parameters
+                if stripped.len() == name.len() || stripped.is_empty() {
+                    return None;
+                }
+                Some(self.diagnostic(
+                    file_path,
+                    node,
+                    format!("{} '{}' should not start with an underscore", what, name),
+                    Severity::Info,
+                    format!("Rename to '{}'", stripped),
+                ))
+            })
+            .collect()
+    }
+}
 
 impl Rule for PrivateFieldUnderscoreRule {
     fn name(&self) -> &str {
         "private_field_underscore"
     }
 
-    fn check(&self, _file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
-        let diagnostics = Vec::new();
+    fn check(&self, file_path: &Path, content: &str) -> Result<Vec<Diagnostic>> {
+        let config = &self.config;
+        if !config.public_mutable_fields && !config.trivial_getters && !config.underscore_locals {
+            return Ok(Vec::new());
+        }
 
-        // Note: This is a placeholder implementation
-        // A full implementation would require AST parsing to properly distinguish
-        // between private and public fields based on Dart's visibility rules
-        // (fields without _ are public, fields with _ are private)
+        let tree =
+            treesitter::parse_dart(content).map_err(|e| AnalyzerError::Parse(e.to_string()))?;
+        let fields = treesitter::extract_fields(&tree, content);
 
-        // The simple regex check is insufficient because it can't determine
-        // the intent - whether a field without _ should be private or not
+        let mut diagnostics = Vec::new();
+        if config.public_mutable_fields {
+            diagnostics.extend(self.check_public_mutable_fields(file_path, &fields, content));
+        }
+        if config.trivial_getters {
+            diagnostics.extend(self.check_trivial_getters(file_path, &fields, content));
+        }
+        if config.underscore_locals {
+            diagnostics.extend(self.check_underscore_locals(file_path, tree.root_node(), content));
+        }
+        diagnostics.sort_by_key(|d| (d.location.line, d.location.column));
 
-        for line in content.lines() {
-            if let Some(_caps) = field_regex().captures(line) {
-                // Placeholder: would need semantic analysis here
+        Ok(diagnostics)
+    }
+}
+
+/// Name of the class, mixin or enum declaring a member
+fn enclosing_type_name<'a>(node: Node, source: &'a str) -> Option<&'a str> {
+    let mut current = node.parent();
+    while let Some(parent) = current {
+        match parent.kind() {
+            "class_definition" | "mixin_declaration" | "enum_declaration" => {
+                let name = parent.child_by_field_name("name").or_else(|| {
+                    let mut cursor = parent.walk();
+                    let name = parent
+                        .named_children(&mut cursor)
+                        .find(|n| n.kind() == "identifier");
+                    name
+                })?;
+                return name.utf8_text(source.as_bytes()).ok();
             }
+            // Extensions can't declare instance fields
+            "extension_declaration" | "function_body" => return None,
+            _ => current = parent.parent(),
         }
+    }
+    None
+}
 
-        Ok(diagnostics)
+/// A getter or setter declared in a class body
+struct Accessor<'a> {
+    /// `getter_signature` or `setter_signature`
+    kind: &'static str,
+    name: &'a str,
+    is_static: bool,
+    /// The field it only reads or writes, if it has no other logic
+    trivial_for: Option<&'a str>,
+}
+
+fn accessors<'a>(body: Node, source: &'a str) -> Vec<Accessor<'a>> {
+    let text = |node: Node| node.utf8_text(source.as_bytes()).ok();
+    let mut accessors = Vec::new();
+    let mut cursor = body.walk();
+    for member in body.named_children(&mut cursor) {
+        let Some(signature) = member.named_child(0) else {
+            continue;
+        };
+        if signature.kind() != "method_signature" {
+            continue;
+        }
+        let Some(accessor) = signature.named_child(0) else {
+            continue;
+        };
+        let kind = match accessor.kind() {
+            "getter_signature" => "getter_signature",
+            "setter_signature" => "setter_signature",
+            _ => continue,
+        };
+        let Some(name) = accessor.child_by_field_name("name").and_then(text) else {
+            continue;
+        };
+        let mut signature_cursor = signature.walk();
+        let is_static = signature
+            .children(&mut signature_cursor)
+            .any(|n| n.kind() == "static");
+
+        let body = member
+            .child_by_field_name("body")
+            .or_else(|| member.named_child(1))
+            .filter(|n| n.kind() == "function_body");
+        let trivial_for = body.and_then(|body| match kind {
+            "getter_signature" => returned_identifier(body).and_then(text),
+            _ => {
+                let parameter = accessor
+                    .named_child(1)
+                    .and_then(|list| list.named_child(0))
+                    .and_then(parameter_name)
+                    .and_then(text)?;
+                let (target, value) = assignment(body)?;
+                (text(value)? == parameter).then_some(text(target)?)
+            }
+        });
+        accessors.push(Accessor {
+            kind,
+            name,
+            is_static,
+            trivial_for,
+        });
+    }
+    accessors
+}
+
+/// The only statement or expression of a function body
+fn sole_expression(body: Node) -> Option<Node> {
+    let inner = body.named_child(0)?;
+    if inner.kind() != "block" {
+        return Some(inner);
+    }
+    if inner.named_child_count() != 1 {
+        return None;
+    }
+    let statement = inner.named_child(0)?;
+    match statement.kind() {
+        "return_statement" | "expression_statement" => statement.named_child(0),
+        _ => None,
+    }
+}
+
+/// The identifier a getter body returns, for `=> _x` and `{ return _x; }`
+fn returned_identifier(body: Node) -> Option<Node> {
+    sole_expression(body).filter(|n| n.kind() == "identifier")
+}
+
+/// Target and value of a setter body that only assigns an identifier to a
+/// field, for `=> _x = v` and `{ _x = v; }`
+fn assignment(body: Node) -> Option<(Node, Node)> {
+    let expression = sole_expression(body).filter(|n| n.kind() == "assignment_expression")?;
+    let target = expression.child_by_field_name("left")?;
+    let target = match target.kind() {
+        "assignable_expression" if target.named_child_count() == 1 => target.named_child(0)?,
+        _ => target,
+    };
+    let value = expression.child_by_field_name("right")?;
+    (target.kind() == "identifier" && value.kind() == "identifier").then_some((target, value))
+}
+
+/// Name of a formal parameter, unless it initializes a field or a
+/// superclass parameter
+fn parameter_name(parameter: Node) -> Option<Node> {
+    if let Some(name) = parameter.child_by_field_name("name") {
+        return Some(name);
+    }
+    // Untyped and function-typed parameters have no `name` field
+    let mut cursor = parameter.walk();
+    let name = parameter
+        .named_children(&mut cursor)
+        .filter(|n| n.kind() == "identifier")
+        .last();
+    name
+}
+
+/// Identifiers declaring locals and parameters under `node`, with what they
+/// declare
+fn collect_local_names<'a>(node: Node<'a>, names: &mut Vec<(Node<'a>, &'static str)>) {
+    match node.kind() {
+        // Top-level variables parse as local declarations, but are library members
+        "local_variable_declaration" if node.parent().is_some_and(|p| p.kind() == "program") => {
+            return;
+        }
+        "initialized_variable_definition" => {
+            let mut cursor = node.walk();
+            for child in node.named_children(&mut cursor) {
+                match child.kind() {
+                    "identifier" => names.push((child, "Local variable")),
+                    // Further variables declared together, e.g. `var a = 1, b = 2;`
+                    "initialized_identifier" => {
+                        if let Some(name) =
+                            child.named_child(0).filter(|n| n.kind() == "identifier")
+                        {
+                            names.push((name, "Local variable"));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        "formal_parameter" => {
+            if let Some(name) = parameter_name(node) {
+                names.push((name, "Parameter"));
+            }
+        }
+        "catch_parameters" => {
+            let mut cursor = node.walk();
+            for child in node.named_children(&mut cursor) {
+                if child.kind() == "identifier" {
+                    names.push((child, "Catch parameter"));
+                }
+            }
+        }
+        _ => {}
+    }
+
+    let mut cursor = node.walk();
+    for child in node.children(&mut cursor) {
+        collect_local_names(child, names);
     }
 }
 
@@ -161,23 +507,29 @@ impl Rule for LineLengthRule {
         let mut diagnostics = Vec::new();
 
         for (line_num, line) in content.lines().enumerate() {
-            if line.len() > self.max_length {
+            let length = line.chars().count();
+            if length > self.max_length {
+                // Byte offset of the first character past the limit
+                let overflow = line
+                    .char_indices()
+                    .nth(self.max_length)
+                    .map(|(i, _)| i)
+                    .unwrap_or(line.len());
                 diagnostics.push(
                     Diagnostic::new(
                         self.name(),
                         format!(
                             "Line exceeds maximum length of {} characters (actual: {})",
-                            self.max_length,
-                            line.len()
+                            self.max_length, length
                         ),
                         Severity::Info,
                         RuleCategory::Style,
                         Location {
                             file: file_path.to_string_lossy().to_string(),
                             line: line_num + 1,
-                            column: self.max_length + 1,
+                            column: overflow + 1,
                             end_line: Some(line_num + 1),
-                            end_column: Some(line.len()),
+                            end_column: Some(line.len() + 1),
                         },
                     )
                     .with_suggestion("Consider breaking this line into multiple lines"),
@@ -457,6 +457,8 @@ pub struct DartMethod<'a> {
 #[derive(Debug)]
 pub struct DartImport<'a> {
     pub uri: String,
+    /// Whether this is an `export` directive
+    pub is_export: bool,
     pub node: Node<'a>,
     pub start_byte: usize,
     pub end_byte: usize,
@@ -470,7 +472,10 @@ pub struct DartField<'a> {
     pub is_static: bool,
     pub is_final: bool,
     pub is_const: bool,
+    /// The whole declaration, shared by fields declared together
     pub node: Node<'a>,
+    /// The field's identifier
+    pub name_node: Node<'a>,
     pub start_byte: usize,
     pub end_byte: usize,
 }
@@ -578,7 +583,7 @@ fn extract_methods_recursive<'a>(node: Node<'a>, source: &str, methods: &mut Vec
     }
 }
 
-/// Extract all import statements from the CST
+/// Extract all import and export directives from the CST
 pub fn extract_imports<'a>(tree: &'a Tree, source: &str) -> Vec<DartImport<'a>> {
     let mut imports = Vec::new();
     let root = tree.root_node();
@@ -587,7 +592,8 @@ pub fn extract_imports<'a>(tree: &'a Tree, source: &str) -> Vec<DartImport<'a>>
 }
 
 fn extract_imports_recursive<'a>(node: Node<'a>, source: &str, imports: &mut Vec<DartImport<'a>>) {
-    if node.kind() == "import_or_export" || node.kind() == "import_specification" {
+    let kind = node.kind();
+    if kind == "import_or_export" || kind == "import_specification" || kind == "library_export" {
         // Try to find the URI string
         let mut cursor = node.walk();
         for child in node.children(&mut cursor) {
@@ -595,6 +601,7 @@ fn extract_imports_recursive<'a>(node: Node<'a>, source: &str, imports: &mut Vec
                 if let Ok(uri) = child.utf8_text(source.as_bytes()) {
                     imports.push(DartImport {
                         uri: uri.to_string(),
+                        is_export: kind == "library_export",
                         node,
                         start_byte: node.start_byte(),
                         end_byte: node.end_byte(),
@@ -631,7 +638,7 @@ fn extract_fields_recursive<'a>(node: Node<'a>, source: &str, fields: &mut Vec<D
                 let mut is_final = false;
                 let mut is_const = false;
                 let mut type_annotation = None;
-                let mut field_name = None;
+                let mut names = Vec::new();
 
                 let mut decl_cursor = child.walk();
                 for decl_child in child.children(&mut decl_cursor) {
@@ -645,32 +652,19 @@ fn extract_fields_recursive<'a>(node: Node<'a>, source: &str, fields: &mut Vec<D
                             }
                         }
                         "initialized_identifier_list" | "static_final_declaration_list" => {
-                            // Find the identifier within this list
+                            // Every identifier declared in this list, e.g. `int a, b;`
                             let mut id_cursor = decl_child.walk();
                             for id_child in decl_child.children(&mut id_cursor) {
-                                if id_child.kind() == "initialized_identifier"
-                                    || id_child.kind() == "static_final_declaration"
-                                    || id_child.kind() == "identifier"
-                                {
-                                    // Get the first identifier
-                                    let mut name_cursor = id_child.walk();
-                                    for name_child in id_child.children(&mut name_cursor) {
-                                        if name_child.kind() == "identifier" {
-                                            if let Ok(name) =
-                                                name_child.utf8_text(source.as_bytes())
-                                            {
-                                                field_name = Some(name.to_string());
-                                                break;
-                                            }
-                                        }
+                                let name_node = match id_child.kind() {
+                                    "initialized_identifier" | "static_final_declaration" => {
+                                        id_child.named_child(0).filter(|n| n.kind() == "identifier")
                                     }
-                                    if field_name.is_none() && id_child.kind() == "identifier" {
-                                        if let Ok(name) = id_child.utf8_text(source.as_bytes()) {
-                                            field_name = Some(name.to_string());
-                                        }
-                                    }
-                                    if field_name.is_some() {
-                                        break;
+                                    "identifier" => Some(id_child),
+                                    _ => None,
+                                };
+                                if let Some(name_node) = name_node {
+                                    if let Ok(name) = name_node.utf8_text(source.as_bytes()) {
+                                        names.push((name.to_string(), name_node));
                                     }
                                 }
                             }
@@ -679,14 +673,15 @@ fn extract_fields_recursive<'a>(node: Node<'a>, source: &str, fields: &mut Vec<D
                     }
                 }
 
-                if let Some(name) = field_name {
+                for (name, name_node) in names {
                     fields.push(DartField {
                         name,
-                        type_annotation,
+                        type_annotation: type_annotation.clone(),
                         is_static,
                         is_final,
                         is_const,
                         node: child,
+                        name_node,
                         start_byte: child.start_byte(),
                         end_byte: child.end_byte(),
                     });
@@ -735,11 +730,9 @@ fn extract_variables_recursive<'a>(
                                 type_annotation = Some(text.to_string());
                             }
                         }
-                        "identifier" => {
-                            if var_name.is_none() {
-                                if let Ok(name) = def_child.utf8_text(source.as_bytes()) {
-                                    var_name = Some(name.to_string());
-                                }
+                        "identifier" if var_name.is_none() => {
+                            if let Ok(name) = def_child.utf8_text(source.as_bytes()) {
+                                var_name = Some(name.to_string());
                             }
                         }
                         "inferred_type" => {
@@ -899,6 +892,7 @@ fn extract_expressions_recursive<'a>(
         "as_expression",
         "postfix_expression",
         "selector_expression",
+        "member_access",
         "parenthesized_expression",
         "list_literal",
         "map_literal",
@@ -1023,6 +1017,7 @@ class SecondClass {
         let source = r#"
 import 'dart:core';
 import 'package:flutter/material.dart';
+export 'src/my_class.dart';
 
 class MyClass {}
         "#;
@@ -1030,7 +1025,18 @@ class MyClass {}
         let tree = parse_dart(source).expect("Failed to parse");
         let imports = extract_imports(&tree, source);
 
-        assert!(!imports.is_empty());
+        let uris: Vec<(&str, bool)> = imports
+            .iter()
+            .map(|i| (i.uri.as_str(), i.is_export))
+            .collect();
+        assert_eq!(
+            uris,
+            vec![
+                ("'dart:core'", false),
+                ("'package:flutter/material.dart'", false),
+                ("'src/my_class.dart'", true),
+            ]
+        );
     }
 
     #[test]
@@ -1085,6 +1091,18 @@ class MyClass {
         );
     }
 
+    #[test]
+    fn test_extract_fields_declared_together() {
+        let source = "class Point {\n  late int x, y;\n}\n";
+        let tree = parse_dart(source).expect("Failed to parse");
+        let fields = extract_fields(&tree, source);
+
+        let names: Vec<&str> = fields.iter().map(|f| f.name.as_str()).collect();
+        assert_eq!(names, vec!["x", "y"]);
+        assert_eq!(fields[1].name_node.start_position().column, 14);
+        assert_eq!(fields[1].type_annotation.as_deref(), Some("int"));
+    }
+
     #[test]
     fn test_extract_variables() {
         let source = r#"
@@ -0,0 +1,61 @@
+//! Helpers shared by the integration tests: temporary Dart packages, and the
+//! fake Dart Analysis Server
+#![allow(dead_code)]
+
+use dart_re_analyzer::lsp::framing::FrameReader;
+use serde_json::Value;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// The fake Dart server from `examples/`, which `cargo test` builds
+pub fn fake_dart_server() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    // target/<profile>/deps/<test> -> target/<profile>/examples
+    let path = exe
+        .parent()
+        .and_then(Path::parent)
+        .unwrap()
+        .join("examples")
+        .join(format!("fake_dart_server{}", std::env::consts::EXE_SUFFIX));
+    assert!(
+        path.exists(),
+        "{} is missing; build it with `cargo build --example fake_dart_server`",
+        path.display()
+    );
+    path
+}
+
+/// Messages the fake Dart server received, in order
+pub fn logged(log: &Path) -> Vec<Value> {
+    std::fs::read_to_string(log)
+        .unwrap_or_default()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect()
+}
+
+/// The next message a language server wrote, or `None` once its output
+/// ends or isn't a valid frame
+pub fn read_message(reader: &mut FrameReader<impl Read>) -> Option<Value> {
+    let content = reader.read_message().ok()??;
+    serde_json::from_str(&content).ok()
+}
+
+/// Write a script of canned replies for the fake Dart server
+pub fn script(dir: &Path, rules: Value) -> PathBuf {
+    let path = dir.join("script.json");
+    std::fs::write(&path, rules.to_string()).unwrap();
+    path
+}
+
+/// A package named `app` with `files`, as paths relative to its root
+pub fn package(files: &[(&str, &str)]) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(dir.path().join("pubspec.yaml"), "name: app\n").unwrap();
+    for (path, content) in files {
+        let path = dir.path().join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    dir
+}
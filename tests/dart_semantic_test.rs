@@ -0,0 +1,122 @@
+mod common;
+
+use common::{fake_dart_server, logged, script};
+use dart_re_analyzer::lsp::client::DartAnalysisServerConfig;
+use dart_re_analyzer::lsp::dart_semantic::DartSemanticAnalyzer;
+use dart_re_analyzer::lsp::semantic::{DiagnosticSeverity, SemanticAnalyzer, SymbolKind};
+use dart_re_analyzer::lsp::uri::path_to_uri;
+use serde_json::json;
+use std::time::Duration;
+
+const SOURCE: &str = "void main() {\n  String? name;\n  name!.length;\n}\n";
+
+#[test]
+fn test_answers_come_from_the_dart_server() {
+    let dir = tempfile::tempdir().unwrap();
+    let file = dir.path().join("main.dart");
+    std::fs::write(&file, SOURCE).unwrap();
+    let uri = path_to_uri(&file).unwrap();
+    let range = |line: u32, character: u32| {
+        json!({
+            "start": {"line": line, "character": character},
+            "end": {"line": line, "character": character + 4}
+        })
+    };
+
+    let log = dir.path().join("dart.log");
+    let script = script(
+        dir.path(),
+        json!([
+            {
+                "method": "textDocument/didOpen",
+                "send": [
+                    {
+                        "jsonrpc": "2.0",
+                        "method": "textDocument/publishDiagnostics",
+                        "params": {"uri": uri, "diagnostics": [{
+                            "range": range(2, 2),
+                            "severity": 2,
+                            "code": "unchecked_use_of_nullable_value",
+                            "message": "The value can be null"
+                        }]}
+                    },
+                    {"jsonrpc": "2.0", "method": "$/analyzerStatus", "params": {"isAnalyzing": false}}
+                ]
+            },
+            {
+                "method": "textDocument/hover",
+                "result": {"contents": {"kind": "markdown", "value": "```dart\nString? name\n```"}}
+            },
+            {"method": "textDocument/definition", "result": {"uri": uri, "range": range(1, 10)}},
+            {"method": "textDocument/references", "result": [{"uri": uri, "range": range(2, 2)}]}
+        ]),
+    );
+
+    let mut config = DartAnalysisServerConfig::from_sdk(dir.path().join("no-sdk"));
+    config.dart_binary = Some(fake_dart_server());
+    config.vm_args = vec![
+        format!("--fake-log={}", log.display()),
+        format!("--fake-script={}", script.display()),
+    ];
+    let analyzer = DartSemanticAnalyzer::start(config, dir.path())
+        .unwrap()
+        .with_diagnostics_timeout(Duration::from_millis(200));
+
+    let resolved = analyzer.resolve_type(&file, 3, 3).unwrap().unwrap();
+    assert_eq!(resolved.name, "String");
+    assert!(resolved.is_nullable);
+
+    let hover = analyzer.get_hover(&file, 3, 3).unwrap().unwrap();
+    assert!(hover.contains("String? name"));
+
+    let definition = analyzer.find_definition(&file, 3, 3).unwrap().unwrap();
+    assert_eq!(definition.name, "name");
+    assert_eq!(definition.kind, SymbolKind::Variable);
+    assert_eq!(definition.resolved_type.as_deref(), Some("String?"));
+    assert!(definition.is_nullable);
+    assert_eq!(
+        (definition.definition_line, definition.definition_column),
+        (2, 11)
+    );
+
+    let references = analyzer.find_references(&file, 3, 3).unwrap();
+    assert_eq!(references.len(), 1);
+    assert_eq!(
+        (
+            references[0].definition_line,
+            references[0].definition_column
+        ),
+        (3, 3)
+    );
+
+    let diagnostics = analyzer.get_diagnostics(&file).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].severity, DiagnosticSeverity::Warning);
+    assert_eq!(
+        (diagnostics[0].start_line, diagnostics[0].start_column),
+        (3, 3)
+    );
+
+    // New content makes the published diagnostics stale, and this server
+    // never reports on the changed file
+    analyzer.update_file(&file, "void main() {}\n").unwrap();
+    assert!(analyzer.get_diagnostics(&file).unwrap().is_empty());
+
+    drop(analyzer);
+    let methods: Vec<String> = logged(&log)
+        .iter()
+        .filter_map(|m| m["method"].as_str().map(str::to_string))
+        .filter(|m| !m.starts_with("textDocument/") || m.starts_with("textDocument/did"))
+        .collect();
+    assert_eq!(
+        methods,
+        vec![
+            "initialize",
+            "initialized",
+            "textDocument/didOpen",
+            "textDocument/didChange",
+            "shutdown",
+            "exit"
+        ]
+    );
+}
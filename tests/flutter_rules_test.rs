@@ -0,0 +1,203 @@
+use dart_re_analyzer::analyzer::Rule;
+use dart_re_analyzer::error::{RuleCategory, Severity};
+use dart_re_analyzer::rules::flutter::*;
+use dart_re_analyzer::rules::get_flutter_rules;
+use std::path::Path;
+
+fn lines(rule: &dyn Rule, content: &str) -> Vec<usize> {
+    rule.check(Path::new("widget.dart"), content)
+        .unwrap()
+        .iter()
+        .map(|d| d.location.line)
+        .collect()
+}
+
+#[test]
+fn test_prefer_const_constructors() {
+    let content = r#"enum Level { low }
+
+class Badge {
+  const Badge(this.level);
+  final Level level;
+}
+
+Widget build(BuildContext context) {
+  return Column(
+    children: [
+      Padding(padding: EdgeInsets.all(8), child: Text('hi')),
+      Text('$name'),
+      const Text('done'),
+      Icon(Icons.add, color: Colors.grey[300]),
+      SizedBox(height: -4.5),
+      Badge(Level.low),
+      TextEditingController(),
+    ],
+  );
+}
+"#;
+    let rule = PreferConstConstructorsRule;
+    // Only the outermost of nested const-capable calls is reported
+    assert_eq!(lines(&rule, content), vec![11, 15, 16]);
+
+    let diagnostics = rule.check(Path::new("widget.dart"), content).unwrap();
+    assert_eq!(diagnostics[0].rule_id, "prefer_const_constructors");
+    assert!(matches!(diagnostics[0].category, RuleCategory::Flutter));
+    assert!(matches!(diagnostics[0].severity, Severity::Info));
+    assert!(diagnostics[0].message.contains("'Padding'"));
+}
+
+#[test]
+fn test_avoid_setstate_after_await() {
+    let content = r#"class _PageState extends State<Page> {
+  Future<void> load() async {
+    setState(() => loading = true);
+    final data = await fetch();
+    setState(() => this.data = data);
+    if (!mounted) return;
+    setState(() => loading = false);
+  }
+
+  Future<void> refresh() async {
+    await fetch();
+    save(() => setState(() {}));
+  }
+
+  void sync() {
+    setState(() {});
+  }
+}
+"#;
+    assert_eq!(lines(&AvoidSetStateAfterAwaitRule, content), vec![5]);
+}
+
+#[test]
+fn test_use_build_context_synchronously() {
+    let content = r#"Future<void> save(BuildContext ctx) async {
+  Navigator.of(ctx).push(route);
+  await repository.save();
+  ScaffoldMessenger.of(ctx).showSnackBar(bar);
+  if (!ctx.mounted) return;
+  Navigator.of(ctx).pop();
+}
+
+Future<void> open(BuildContext context) async {
+  await Future.delayed(delay);
+  showDialog(context: context, builder: (_) => dialog);
+  onTap(() => Navigator.of(context).pop());
+}
+"#;
+    assert_eq!(
+        lines(&UseBuildContextSynchronouslyRule, content),
+        vec![4, 11]
+    );
+}
+
+#[test]
+fn test_avoid_unnecessary_stateful_widgets() {
+    let content = r#"class Label extends StatefulWidget {
+  const Label({super.key});
+  @override
+  State<Label> createState() => _LabelState();
+}
+
+class _LabelState extends State<Label> {
+  final String text = 'label';
+
+  @override
+  Widget build(BuildContext context) => Text(text);
+}
+
+class Counter extends StatefulWidget {
+  @override
+  State<Counter> createState() => _CounterState();
+}
+
+class _CounterState extends State<Counter> {
+  int count = 0;
+}
+
+class Clock extends StatefulWidget {
+  @override
+  State<Clock> createState() => _ClockState();
+}
+
+class _ClockState extends State<Clock> {
+  @override
+  void dispose() {
+    super.dispose();
+  }
+}
+
+class Elsewhere extends StatefulWidget {
+  @override
+  State<Elsewhere> createState() => ElsewhereState();
+}
+
+class Toggle extends StatefulWidget {
+  @override
+  State<Toggle> createState() => _ToggleState();
+}
+
+class _ToggleState extends State<Toggle> {
+  void toggle() => setState(() {});
+}
+
+class Hint extends StatefulWidget {
+  @override
+  State<Hint> createState() => _HintState();
+}
+
+class _HintState extends State<Hint> {
+  // Never calls setState
+  final String text = 'call setState to refresh';
+}
+"#;
+    assert_eq!(
+        lines(&AvoidUnnecessaryStatefulWidgetsRule, content),
+        vec![1, 49]
+    );
+}
+
+#[test]
+fn test_avoid_container_for_padding_or_color() {
+    let content = r#"Widget build() => Column(children: [
+  Container(padding: EdgeInsets.all(8), child: child),
+  Container(color: Colors.red, key: key),
+  Container(padding: padding, color: Colors.red, child: child),
+  Container(width: 10, child: child),
+  Container(child: child),
+]);
+"#;
+    let rule = AvoidContainerForPaddingOrColorRule;
+    assert_eq!(lines(&rule, content), vec![2, 3]);
+
+    let diagnostics = rule.check(Path::new("widget.dart"), content).unwrap();
+    assert_eq!(
+        diagnostics[0].suggestion.as_deref(),
+        Some("Use Padding instead")
+    );
+    assert_eq!(
+        diagnostics[1].suggestion.as_deref(),
+        Some("Use ColoredBox instead")
+    );
+}
+
+#[test]
+fn test_flutter_rules_only_run_in_flutter_packages() {
+    let content = "Widget build() => Container(color: Colors.red);\n";
+    let check = |pubspec: &str| {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("pubspec.yaml"), pubspec).unwrap();
+        let path = dir.path().join("lib/main.dart");
+        get_flutter_rules()
+            .iter()
+            .flat_map(|rule| rule.check(&path, content).unwrap())
+            .count()
+    };
+
+    assert_eq!(
+        check("name: app\ndependencies:\n  flutter:\n    sdk: flutter\n"),
+        1
+    );
+    assert_eq!(check("name: app\ndependencies:\n  http: ^1.0.0\n"), 0);
+}
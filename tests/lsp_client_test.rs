@@ -0,0 +1,192 @@
+mod common;
+
+use common::{fake_dart_server, logged, script};
+use dart_re_analyzer::lsp::client::{
+    ClientError, DartAnalysisServerClient, DartAnalysisServerConfig, ServerEvent,
+};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::time::Duration;
+
+/// Start a client against the fake Dart server, with `--fake-*` settings
+async fn start(dir: &Path, settings: &[(&str, &Path)]) -> DartAnalysisServerClient {
+    let mut config = DartAnalysisServerConfig::from_sdk(dir.join("no-sdk"));
+    config.dart_binary = Some(fake_dart_server());
+    config.vm_args = settings
+        .iter()
+        .map(|(name, value)| format!("--fake-{}={}", name, value.display()))
+        .collect();
+
+    let mut client = DartAnalysisServerClient::new(config).unwrap();
+    client.start().await.unwrap();
+    assert!(client.is_running());
+    client
+}
+
+fn client_error(error: &anyhow::Error) -> &ClientError {
+    error.downcast_ref::<ClientError>().unwrap()
+}
+
+#[tokio::test]
+async fn test_requests_and_server_errors() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("dart.log");
+    let script = script(
+        dir.path(),
+        json!([
+            {"method": "textDocument/hover", "result": {"contents": "docs"}},
+            {"method": "textDocument/definition", "error": {"code": -32602, "message": "bad position"}}
+        ]),
+    );
+    let mut client = start(dir.path(), &[("log", &log), ("script", &script)]).await;
+
+    let result = client.initialize(None, json!({})).await.unwrap();
+    assert_eq!(result["serverInfo"]["name"], "fake-dart");
+
+    let hover = client
+        .send_request("textDocument/hover", json!({}))
+        .await
+        .unwrap();
+    assert_eq!(hover, json!({"contents": "docs"}));
+
+    let error = client
+        .send_request("textDocument/definition", json!({}))
+        .await
+        .unwrap_err();
+    match client_error(&error) {
+        ClientError::Server { code, message } => {
+            assert_eq!(*code, -32602);
+            assert_eq!(message, "bad position");
+        }
+        other => panic!("Unexpected error: {:?}", other),
+    }
+
+    client.shutdown().await.unwrap();
+    assert!(!client.is_running());
+
+    let messages = logged(&log);
+    let methods: Vec<&str> = messages
+        .iter()
+        .filter_map(|m| m["method"].as_str())
+        .collect();
+    assert_eq!(
+        methods,
+        vec![
+            "initialize",
+            "initialized",
+            "textDocument/hover",
+            "textDocument/definition",
+            "shutdown",
+            "exit"
+        ]
+    );
+    // Our ids never clash with ids of forwarded editor requests
+    assert!(messages[0]["id"]
+        .as_str()
+        .unwrap()
+        .starts_with("dart-re-analyzer/"));
+}
+
+#[tokio::test]
+async fn test_timed_out_and_dropped_requests_are_cancelled() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("dart.log");
+    let script = script(dir.path(), json!([{"method": "slow", "respond": false}]));
+    let mut client = start(dir.path(), &[("log", &log), ("script", &script)]).await;
+
+    let error = client
+        .send_request_with_timeout("slow", Value::Null, Duration::from_millis(100))
+        .await
+        .unwrap_err();
+    assert!(matches!(client_error(&error), ClientError::Timeout { .. }));
+
+    // Giving up on a request without a timeout cancels it as well
+    tokio::select! {
+        _ = client.send_request("slow", Value::Null) => panic!("slow request was answered"),
+        _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+    }
+
+    client.shutdown().await.unwrap();
+
+    let messages = logged(&log);
+    let slow: Vec<&Value> = messages.iter().filter(|m| m["method"] == "slow").collect();
+    let cancelled: Vec<&Value> = messages
+        .iter()
+        .filter(|m| m["method"] == "$/cancelRequest")
+        .map(|m| &m["params"]["id"])
+        .collect();
+    assert_eq!(slow.len(), 2);
+    assert_eq!(cancelled, vec![&slow[0]["id"], &slow[1]["id"]]);
+}
+
+#[tokio::test]
+async fn test_notifications_and_server_requests() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("dart.log");
+    let script = script(
+        dir.path(),
+        json!([{
+            "method": "initialized",
+            "send": [
+                {
+                    "jsonrpc": "2.0",
+                    "method": "textDocument/publishDiagnostics",
+                    "params": {"uri": "file:///a.dart", "diagnostics": []}
+                },
+                {
+                    "jsonrpc": "2.0",
+                    "id": 99,
+                    "method": "workspace/configuration",
+                    "params": {"items": []}
+                }
+            ]
+        }]),
+    );
+    let mut client = start(dir.path(), &[("log", &log), ("script", &script)]).await;
+
+    let mut diagnostics = client.subscribe("textDocument/publishDiagnostics");
+    client.initialize(None, json!({})).await.unwrap();
+
+    let params = tokio::time::timeout(Duration::from_secs(10), diagnostics.recv())
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(params["uri"], "file:///a.dart");
+
+    // Round trip so the reply to the server's request has been sent
+    client
+        .send_request("textDocument/hover", json!({}))
+        .await
+        .unwrap();
+    client.shutdown().await.unwrap();
+
+    // Nobody took the event stream, so the client answered for us
+    let messages = logged(&log);
+    assert!(messages
+        .iter()
+        .any(|m| m["id"] == 99 && m["result"] == Value::Null && m.get("method").is_none()));
+}
+
+#[tokio::test]
+async fn test_server_exit_fails_pending_requests() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut client = start(dir.path(), &[]).await;
+    let mut events = client.take_events().unwrap();
+
+    let error = client
+        .send_request("fake/crash", Value::Null)
+        .await
+        .unwrap_err();
+    assert!(matches!(client_error(&error), ClientError::Exited(method) if method == "fake/crash"));
+
+    loop {
+        let event = tokio::time::timeout(Duration::from_secs(10), events.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        if matches!(event, ServerEvent::Exited) {
+            break;
+        }
+    }
+    client.stop(Duration::from_secs(1)).await;
+}
@@ -0,0 +1,98 @@
+//! Property tests fuzzing the LSP framing codec
+
+use bytes::BytesMut;
+use dart_re_analyzer::lsp::framing::{FrameReader, LspCodec};
+use proptest::prelude::*;
+use std::io::Read;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// A reader returning its bytes in the given chunk sizes
+struct Chunked {
+    bytes: Vec<u8>,
+    chunks: Vec<usize>,
+    position: usize,
+}
+
+impl Read for Chunked {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let chunk = self.chunks.pop().unwrap_or(usize::MAX);
+        let end = (self.position + chunk.min(buf.len())).min(self.bytes.len());
+        let read = end - self.position;
+        buf[..read].copy_from_slice(&self.bytes[self.position..end]);
+        self.position = end;
+        Ok(read)
+    }
+}
+
+/// Header name in a random mix of cases
+fn header_name(name: &'static str) -> impl Strategy<Value = String> {
+    proptest::collection::vec(any::<bool>(), name.len()).prop_map(move |upper| {
+        name.chars()
+            .zip(upper)
+            .map(|(c, upper)| {
+                if upper {
+                    c.to_ascii_uppercase()
+                } else {
+                    c.to_ascii_lowercase()
+                }
+            })
+            .collect()
+    })
+}
+
+proptest! {
+    #[test]
+    fn messages_round_trip_across_any_chunking(
+        messages in proptest::collection::vec(".*", 0..8),
+        chunks in proptest::collection::vec(1usize..64, 0..64),
+    ) {
+        let mut bytes = BytesMut::new();
+        for message in &messages {
+            LspCodec::default().encode(message, &mut bytes).unwrap();
+        }
+
+        let mut reader = FrameReader::new(Chunked { bytes: bytes.to_vec(), chunks, position: 0 });
+        let mut decoded = Vec::new();
+        while let Some(message) = reader.read_message().unwrap() {
+            decoded.push(message);
+        }
+        prop_assert_eq!(decoded, messages);
+    }
+
+    #[test]
+    fn headers_match_in_any_case(
+        length_name in header_name("Content-Length"),
+        type_name in header_name("Content-Type"),
+        charset in prop_oneof![Just("utf-8"), Just("UTF-8"), Just("utf8")],
+        body in "[a-z{}\":,0-9]*",
+        type_first in any::<bool>(),
+    ) {
+        let length = format!("{}: {}\r\n", length_name, body.len());
+        let content_type = format!("{}: application/vscode-jsonrpc; charset={}\r\n", type_name, charset);
+        let headers = if type_first { content_type + &length } else { length + &content_type };
+        let mut buf = BytesMut::from(format!("{}\r\n{}", headers, body).as_bytes());
+
+        prop_assert_eq!(LspCodec::default().decode_eof(&mut buf).unwrap(), Some(body));
+    }
+
+    #[test]
+    fn arbitrary_input_never_panics_or_over_reads(
+        input in proptest::collection::vec(any::<u8>(), 0..512),
+        max_size in 0usize..256,
+    ) {
+        let mut codec = LspCodec::with_max_size(max_size);
+        let mut buf = BytesMut::from(&input[..]);
+        let mut decoded = 0;
+        while let Ok(Some(message)) = codec.decode_eof(&mut buf) {
+            prop_assert!(message.len() <= max_size);
+            decoded += message.len();
+        }
+        prop_assert!(decoded <= input.len());
+    }
+
+    #[test]
+    fn oversized_messages_are_rejected(size in 1usize..10_000) {
+        let mut buf = BytesMut::from(format!("Content-Length: {}\r\n\r\n", size).as_bytes());
+        prop_assert!(LspCodec::with_max_size(size - 1).decode(&mut buf).is_err());
+    }
+}
@@ -0,0 +1,741 @@
+mod common;
+
+use common::{fake_dart_server, logged, read_message, script};
+use dart_re_analyzer::lsp::framing::FrameReader;
+use dart_re_analyzer::lsp::uri;
+use serde_json::{json, Value};
+use std::ffi::OsStr;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Child, ChildStdin, Command, ExitStatus, Stdio};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant};
+
+/// The proxy binary running against the fake Dart server, driven over pipes
+struct Proxy {
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<Value>,
+    /// Messages received while waiting for something else
+    unclaimed: Vec<Value>,
+    next_id: i64,
+}
+
+impl Proxy {
+    fn start(workspace: &Path, env: &[(&str, &OsStr)]) -> Self {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_dart-re-analyzer"));
+        command
+            .arg("language-server")
+            .arg(workspace)
+            .arg("--dart-binary")
+            .arg(fake_dart_server())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null());
+        for (key, value) in env {
+            command.env(key, value);
+        }
+        let mut child = command.spawn().unwrap();
+
+        let stdin = child.stdin.take().unwrap();
+        let mut stdout = FrameReader::new(child.stdout.take().unwrap());
+        let (tx, messages) = channel();
+        std::thread::spawn(move || {
+            while let Some(message) = read_message(&mut stdout) {
+                if tx.send(message).is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self {
+            child,
+            stdin,
+            messages,
+            unclaimed: Vec::new(),
+            next_id: 0,
+        }
+    }
+
+    fn send(&mut self, message: Value) {
+        let content = message.to_string();
+        self.send_raw(format!("Content-Length: {}\r\n\r\n{}", content.len(), content).as_bytes());
+    }
+
+    /// Write bytes to the proxy's stdin as they are
+    fn send_raw(&mut self, bytes: &[u8]) {
+        self.stdin.write_all(bytes).unwrap();
+        self.stdin.flush().unwrap();
+    }
+
+    /// Send a request and wait for its response
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(json!({"jsonrpc": "2.0", "id": id, "method": method, "params": params}));
+        self.wait_for(|m| m["id"] == id && m.get("method").is_none())
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        self.send(json!({"jsonrpc": "2.0", "method": method, "params": params}));
+    }
+
+    /// Next message from the proxy, in the order it was written
+    fn next(&mut self) -> Value {
+        self.wait_for(|_| true)
+    }
+
+    /// Wait for the first message matching `predicate`
+    fn wait_for(&mut self, predicate: impl Fn(&Value) -> bool) -> Value {
+        if let Some(i) = self.unclaimed.iter().position(&predicate) {
+            return self.unclaimed.remove(i);
+        }
+        loop {
+            let message = self
+                .messages
+                .recv_timeout(Duration::from_secs(10))
+                .expect("Timed out waiting for the proxy");
+            if predicate(&message) {
+                return message;
+            }
+            self.unclaimed.push(message);
+        }
+    }
+
+    fn initialize(&mut self, workspace: &Path) -> Value {
+        self.initialize_with(workspace, json!({"capabilities": {}}))
+    }
+
+    /// Initialize with extra `initialize` params, such as capabilities
+    fn initialize_with(&mut self, workspace: &Path, extra: Value) -> Value {
+        let mut params = json!({
+            "processId": null,
+            "rootUri": uri::path_to_uri(workspace).unwrap(),
+        });
+        for (key, value) in extra.as_object().unwrap() {
+            params[key] = value.clone();
+        }
+        let response = self.request("initialize", params);
+        self.notify("initialized", json!({}));
+        response
+    }
+
+    /// Send `exit` and wait for the proxy to terminate
+    fn exit(mut self) -> ExitStatus {
+        self.notify("exit", Value::Null);
+        let deadline = Instant::now() + Duration::from_secs(15);
+        loop {
+            if let Some(status) = self.child.try_wait().unwrap() {
+                return status;
+            }
+            if Instant::now() > deadline {
+                let _ = self.child.kill();
+                panic!("The proxy did not exit");
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+    }
+}
+
+fn open_notification(uri: &str, text: &str) -> Value {
+    json!({
+        "textDocument": {"uri": uri, "languageId": "dart", "version": 1, "text": text}
+    })
+}
+
+fn count(messages: &[Value], method: &str) -> usize {
+    messages.iter().filter(|m| m["method"] == method).count()
+}
+
+/// Rule ids in a `publishDiagnostics` notification
+fn codes(publish: &Value) -> Vec<String> {
+    publish["params"]["diagnostics"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|d| d["code"].as_str().map(str::to_string))
+        .collect()
+}
+
+fn is_publish_for(message: &Value, uri: &str) -> bool {
+    message["method"] == "textDocument/publishDiagnostics" && message["params"]["uri"] == uri
+}
+
+#[test]
+fn test_restarts_crashed_server_and_replays_session() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("dart.log");
+    let mut proxy = Proxy::start(dir.path(), &[("FAKE_DART_LOG", log.as_os_str())]);
+
+    let response = proxy.initialize(dir.path());
+    assert_eq!(response["result"]["serverInfo"]["name"], "fake-dart");
+
+    let uri = uri::path_to_uri(&dir.path().join("open.dart")).unwrap();
+    proxy.notify(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": {
+                "uri": uri,
+                "languageId": "dart",
+                "version": 7,
+                "text": "void main() {\n  print('hi');\n}\n"
+            }
+        }),
+    );
+    proxy.wait_for(|m| m["method"] == "textDocument/publishDiagnostics");
+
+    // A request the server dies on fails instead of hanging
+    let response = proxy.request("fake/crash", json!({}));
+    assert_eq!(response["error"]["code"], -32803);
+
+    // The next request is answered by a restarted server
+    let response = proxy.request(
+        "textDocument/hover",
+        json!({"textDocument": {"uri": uri}, "position": {"line": 1, "character": 3}}),
+    );
+    assert!(response.get("error").is_none(), "{}", response);
+
+    let messages = logged(&log);
+    assert_eq!(count(&messages, "initialize"), 2);
+    assert_eq!(count(&messages, "initialized"), 2);
+    assert_eq!(count(&messages, "textDocument/didOpen"), 2);
+
+    // The replay reopens the document as the editor last described it
+    let reopened = messages
+        .iter()
+        .rfind(|m| m["method"] == "textDocument/didOpen")
+        .unwrap();
+    assert_eq!(reopened["params"]["textDocument"]["version"], 7);
+    assert_eq!(
+        reopened["params"]["textDocument"]["text"],
+        "void main() {\n  print('hi');\n}\n"
+    );
+    // ...and the replayed initialize stays between the proxy and the server
+    let reinitialize = messages
+        .iter()
+        .rfind(|m| m["method"] == "initialize")
+        .unwrap();
+    assert!(reinitialize["id"].is_string());
+    assert!(proxy
+        .unclaimed
+        .iter()
+        .all(|m| m["id"] != reinitialize["id"]));
+
+    let response = proxy.request("shutdown", Value::Null);
+    assert_eq!(response["result"], Value::Null);
+    assert!(proxy.exit().success());
+}
+
+#[test]
+fn test_reports_server_that_cannot_be_restarted() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut proxy = Proxy::start(dir.path(), &[("FAKE_DART_CRASH_ON_START", OsStr::new("1"))]);
+
+    let root_uri = uri::path_to_uri(dir.path()).unwrap();
+    let response = proxy.request(
+        "initialize",
+        json!({"processId": null, "rootUri": root_uri, "capabilities": {}}),
+    );
+    assert_eq!(response["error"]["code"], -32803);
+
+    let message = proxy.wait_for(|m| m["method"] == "window/showMessage");
+    assert_eq!(message["params"]["type"], 1);
+
+    // Without a server the proxy answers shutdown itself
+    let response = proxy.request("shutdown", Value::Null);
+    assert_eq!(response["result"], Value::Null);
+    assert!(proxy.exit().success());
+}
+
+#[test]
+fn test_answers_shutdown_for_unresponsive_server() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut proxy = Proxy::start(
+        dir.path(),
+        &[("FAKE_DART_IGNORE_SHUTDOWN", OsStr::new("1"))],
+    );
+    proxy.initialize(dir.path());
+
+    let started = Instant::now();
+    let response = proxy.request("shutdown", Value::Null);
+    assert_eq!(response["result"], Value::Null);
+    assert!(started.elapsed() >= Duration::from_secs(2));
+
+    assert!(proxy.exit().success());
+}
+
+#[test]
+fn test_exit_without_shutdown_kills_server() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut proxy = Proxy::start(
+        dir.path(),
+        &[("FAKE_DART_IGNORE_SHUTDOWN", OsStr::new("1"))],
+    );
+    proxy.initialize(dir.path());
+
+    let status = proxy.exit();
+    assert_eq!(status.code(), Some(1));
+}
+
+#[test]
+fn test_framing_counts_bytes_and_splits_batched_messages() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("dart.log");
+    let script = script(
+        dir.path(),
+        json!([{"method": "textDocument/hover", "result": {"contents": "é 😀 docs"}}]),
+    );
+    let mut proxy = Proxy::start(
+        dir.path(),
+        &[
+            ("FAKE_DART_LOG", log.as_os_str()),
+            ("FAKE_DART_SCRIPT", script.as_os_str()),
+        ],
+    );
+    proxy.initialize(dir.path());
+
+    // Two requests in a single write, one with an extra header and one with
+    // a lowercase header name, both with multi-byte content
+    let mut batch = Vec::new();
+    for (id, length_header, header) in [
+        (
+            10,
+            "Content-Length",
+            "Content-Type: application/vscode-jsonrpc; charset=utf-8\r\n",
+        ),
+        (11, "content-length", ""),
+    ] {
+        let content = json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": "textDocument/hover",
+            "params": {"note": "ünïcödé 🎯"}
+        })
+        .to_string();
+        batch.extend(
+            format!(
+                "{}: {}\r\n{}\r\n{}",
+                length_header,
+                content.len(),
+                header,
+                content
+            )
+            .bytes(),
+        );
+    }
+    proxy.send_raw(&batch);
+
+    for id in [10, 11] {
+        let response = proxy.wait_for(|m| m["id"] == id);
+        assert_eq!(response["result"]["contents"], "é 😀 docs");
+    }
+    let hovers: Vec<Value> = logged(&log)
+        .into_iter()
+        .filter(|m| m["method"] == "textDocument/hover")
+        .collect();
+    assert_eq!(hovers.len(), 2);
+    assert_eq!(hovers[0]["params"]["note"], "ünïcödé 🎯");
+
+    proxy.request("shutdown", Value::Null);
+    assert!(proxy.exit().success());
+}
+
+#[test]
+fn test_injects_our_diagnostics_into_dart_publish() {
+    let dir = tempfile::tempdir().unwrap();
+    let uri = uri::path_to_uri(&dir.path().join("main.dart")).unwrap();
+    let script = script(
+        dir.path(),
+        json!([{
+            "method": "textDocument/didOpen",
+            "send": [{
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": {
+                    "uri": uri,
+                    "version": 1,
+                    "diagnostics": [{
+                        "range": {"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 4}},
+                        "severity": 1,
+                        "source": "dart",
+                        "message": "Undefined name 'x'"
+                    }]
+                }
+            }]
+        }]),
+    );
+    let mut proxy = Proxy::start(dir.path(), &[("FAKE_DART_SCRIPT", script.as_os_str())]);
+    proxy.initialize(dir.path());
+
+    proxy.notify(
+        "textDocument/didOpen",
+        open_notification(&uri, "void main() {\n  print('hi');\n}\n"),
+    );
+
+    // Our diagnostics go out as soon as the buffer is analyzed...
+    let first = proxy.wait_for(|m| m["method"] == "textDocument/publishDiagnostics");
+    let diagnostics = first["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["code"], "avoid_print");
+
+    // ...and the Dart server's publish is replaced by the merged set
+    let merged = proxy.wait_for(|m| m["method"] == "textDocument/publishDiagnostics");
+    assert_eq!(merged["params"]["uri"], uri);
+    assert_eq!(merged["params"]["version"], 1);
+    let diagnostics = merged["params"]["diagnostics"].as_array().unwrap();
+    assert_eq!(diagnostics.len(), 2);
+    assert_eq!(diagnostics[0]["message"], "Undefined name 'x'");
+    assert_eq!(diagnostics[1]["source"], "dart-re-analyzer");
+    assert_eq!(
+        diagnostics[1]["range"],
+        json!({"start": {"line": 1, "character": 2}, "end": {"line": 1, "character": 8}})
+    );
+
+    proxy.request("shutdown", Value::Null);
+    assert!(proxy.exit().success());
+}
+
+#[test]
+fn test_pull_diagnostics_with_result_ids() {
+    let dir = tempfile::tempdir().unwrap();
+    let uri = uri::path_to_uri(&dir.path().join("main.dart")).unwrap();
+    let script = script(
+        dir.path(),
+        json!([{
+            "method": "textDocument/didOpen",
+            "send": [{
+                "jsonrpc": "2.0",
+                "method": "textDocument/publishDiagnostics",
+                "params": {
+                    "uri": uri,
+                    "diagnostics": [{
+                        "range": {"start": {"line": 0, "character": 0}, "end": {"line": 0, "character": 7}},
+                        "severity": 1,
+                        "source": "dart",
+                        "code": "undefined_class",
+                        "message": "Undefined class"
+                    }]
+                }
+            }]
+        }]),
+    );
+    let mut proxy = Proxy::start(dir.path(), &[("FAKE_DART_SCRIPT", script.as_os_str())]);
+    let response = proxy.initialize_with(
+        dir.path(),
+        json!({"capabilities": {
+            "textDocument": {"diagnostic": {"dynamicRegistration": false}},
+            "workspace": {"diagnostics": {"refreshSupport": true}}
+        }}),
+    );
+    assert_eq!(
+        response["result"]["capabilities"]["diagnosticProvider"]["workspaceDiagnostics"],
+        true
+    );
+
+    proxy.notify(
+        "textDocument/didOpen",
+        open_notification(&uri, "dynamic x = 1;\n"),
+    );
+
+    // Each change asks the client to pull again, until both sides are in
+    let report = loop {
+        let refresh = proxy.wait_for(|m| m["method"] == "workspace/diagnostic/refresh");
+        proxy.send(json!({"jsonrpc": "2.0", "id": refresh["id"], "result": null}));
+
+        let report = proxy.request(
+            "textDocument/diagnostic",
+            json!({"textDocument": {"uri": uri}}),
+        )["result"]
+            .clone();
+        if report["items"].as_array().unwrap().len() == 2 {
+            break report;
+        }
+    };
+    assert_eq!(report["kind"], "full");
+    let codes: Vec<&str> = report["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|d| d["code"].as_str())
+        .collect();
+    assert_eq!(codes, vec!["undefined_class", "avoid_dynamic"]);
+
+    // Asking again with the result id gets an unchanged report
+    let result_id = report["resultId"].as_str().unwrap();
+    let unchanged = proxy.request(
+        "textDocument/diagnostic",
+        json!({"textDocument": {"uri": uri}, "previousResultId": result_id}),
+    );
+    assert_eq!(
+        unchanged["result"],
+        json!({"kind": "unchanged", "resultId": result_id})
+    );
+
+    let workspace = proxy.request(
+        "workspace/diagnostic",
+        json!({"previousResultIds": [{"uri": uri, "value": result_id}]}),
+    );
+    let items = workspace["result"]["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["uri"], uri);
+    assert_eq!(items[0]["kind"], "unchanged");
+
+    // Nothing is pushed to a client that pulls
+    assert!(!proxy
+        .unclaimed
+        .iter()
+        .any(|m| m["method"] == "textDocument/publishDiagnostics"));
+
+    proxy.request("shutdown", Value::Null);
+    assert!(proxy.exit().success());
+}
+
+#[test]
+fn test_preserves_server_message_order() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("dart.log");
+    let script = script(
+        dir.path(),
+        json!([
+            {
+                "method": "textDocument/hover",
+                "send": [
+                    {"jsonrpc": "2.0", "method": "window/logMessage", "params": {"type": 4, "message": "first"}},
+                    {"jsonrpc": "2.0", "method": "window/logMessage", "params": {"type": 4, "message": "second"}}
+                ],
+                "result": "hover"
+            },
+            {
+                "method": "initialized",
+                "send": [{
+                    "jsonrpc": "2.0",
+                    "id": "register-1",
+                    "method": "client/registerCapability",
+                    "params": {"registrations": []}
+                }]
+            }
+        ]),
+    );
+    let mut proxy = Proxy::start(
+        dir.path(),
+        &[
+            ("FAKE_DART_LOG", log.as_os_str()),
+            ("FAKE_DART_SCRIPT", script.as_os_str()),
+        ],
+    );
+    proxy.initialize(dir.path());
+
+    // Requests from the server reach the client, and the answer goes back
+    let register = proxy.wait_for(|m| m["method"] == "client/registerCapability");
+    assert_eq!(register["id"], "register-1");
+    proxy.send(json!({"jsonrpc": "2.0", "id": "register-1", "result": null}));
+
+    // Pipelined requests, each preceded by the server's log messages
+    for id in 1..=3 {
+        proxy.send(
+            json!({"jsonrpc": "2.0", "id": id, "method": "textDocument/hover", "params": {}}),
+        );
+    }
+    let mut order = Vec::new();
+    while order.len() < 9 {
+        let message = proxy.next();
+        match message["method"].as_str() {
+            Some("window/logMessage") => order.push(message["params"]["message"].clone()),
+            Some(_) => continue,
+            None => order.push(message["id"].clone()),
+        }
+    }
+    assert_eq!(
+        order,
+        vec![
+            json!("first"),
+            json!("second"),
+            json!(1),
+            json!("first"),
+            json!("second"),
+            json!(2),
+            json!("first"),
+            json!("second"),
+            json!(3),
+        ]
+    );
+
+    proxy.request("shutdown", Value::Null);
+    assert!(proxy.exit().success());
+    let messages = logged(&log);
+    assert!(messages
+        .iter()
+        .any(|m| m["id"] == "register-1" && m.get("result").is_some()));
+}
+
+#[test]
+fn test_appends_our_code_actions_to_dart_results() {
+    let dir = tempfile::tempdir().unwrap();
+    let script = script(
+        dir.path(),
+        json!([{
+            "method": "textDocument/codeAction",
+            "result": [{"title": "Dart fix", "kind": "quickfix"}]
+        }]),
+    );
+    let mut proxy = Proxy::start(dir.path(), &[("FAKE_DART_SCRIPT", script.as_os_str())]);
+    proxy.initialize(dir.path());
+
+    let uri = uri::path_to_uri(&dir.path().join("main.dart")).unwrap();
+    proxy.notify(
+        "textDocument/didOpen",
+        open_notification(&uri, "void main() {\n  print('hi');\n}\n"),
+    );
+    proxy.wait_for(|m| m["method"] == "textDocument/publishDiagnostics");
+
+    let response = proxy.request(
+        "textDocument/codeAction",
+        json!({
+            "textDocument": {"uri": uri},
+            "range": {"start": {"line": 1, "character": 3}, "end": {"line": 1, "character": 3}},
+            "context": {"diagnostics": []}
+        }),
+    );
+    let titles: Vec<&str> = response["result"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|a| a["title"].as_str())
+        .collect();
+    assert_eq!(
+        titles,
+        vec![
+            "Dart fix",
+            "Ignore 'avoid_print' for this line",
+            "Ignore 'avoid_print' for this file"
+        ]
+    );
+
+    proxy.request("shutdown", Value::Null);
+    assert!(proxy.exit().success());
+}
+
+#[test]
+fn test_forwards_shutdown_and_exit() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("dart.log");
+    let mut proxy = Proxy::start(dir.path(), &[("FAKE_DART_LOG", log.as_os_str())]);
+    proxy.initialize(dir.path());
+
+    let response = proxy.request("shutdown", Value::Null);
+    assert_eq!(response["result"], Value::Null);
+    assert!(response.get("error").is_none());
+    assert!(proxy.exit().success());
+
+    let methods: Vec<Value> = logged(&log)
+        .into_iter()
+        .map(|m| m["method"].clone())
+        .collect();
+    assert_eq!(
+        methods,
+        vec![
+            json!("initialize"),
+            json!("initialized"),
+            json!("shutdown"),
+            json!("exit")
+        ]
+    );
+}
+
+#[test]
+fn test_settings_from_initialization_options_and_configuration_changes() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut proxy = Proxy::start(dir.path(), &[]);
+    proxy.initialize_with(
+        dir.path(),
+        json!({
+            "capabilities": {},
+            "initializationOptions": {
+                "dartReAnalyzer": {"runtime_rules": {"disabled_rules": ["avoid_print"]}}
+            }
+        }),
+    );
+
+    let uri = uri::path_to_uri(&dir.path().join("main.dart")).unwrap();
+    // `dynamic` keeps a diagnostic around, so every change is published
+    proxy.notify(
+        "textDocument/didOpen",
+        open_notification(&uri, "void main(dynamic x) {\n  print('hi');\n}\n"),
+    );
+    let publish = proxy.wait_for(|m| is_publish_for(m, &uri));
+    assert_eq!(codes(&publish), vec!["avoid_dynamic"]);
+
+    // Dropping the override brings the rule back without reopening
+    proxy.notify(
+        "workspace/didChangeConfiguration",
+        json!({"settings": {"dartReAnalyzer": {}}}),
+    );
+    let publish = proxy.wait_for(|m| is_publish_for(m, &uri));
+    assert!(codes(&publish).contains(&"avoid_print".to_string()));
+
+    proxy.request("shutdown", Value::Null);
+    assert!(proxy.exit().success());
+}
+
+#[test]
+fn test_pulls_configuration_and_watches_config_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let log = dir.path().join("dart.log");
+    let mut proxy = Proxy::start(dir.path(), &[("FAKE_DART_LOG", log.as_os_str())]);
+    proxy.initialize_with(
+        dir.path(),
+        json!({"capabilities": {"workspace": {
+            "configuration": true,
+            "didChangeWatchedFiles": {"dynamicRegistration": true}
+        }}}),
+    );
+
+    let uri = uri::path_to_uri(&dir.path().join("main.dart")).unwrap();
+    proxy.notify(
+        "textDocument/didOpen",
+        open_notification(&uri, "void main() {\n  print('a long line');\n}\n"),
+    );
+    let publish = proxy.wait_for(|m| is_publish_for(m, &uri));
+    assert!(!codes(&publish).contains(&"line_length".to_string()));
+
+    // The proxy registers a watch for the config file ...
+    let watch = proxy.wait_for(|m| m["method"] == "client/registerCapability");
+    proxy.send(json!({"jsonrpc": "2.0", "id": watch["id"], "result": null}));
+
+    // ... and asks for its settings, which apply right away
+    let request = proxy.wait_for(|m| m["method"] == "workspace/configuration");
+    assert_eq!(request["params"]["items"][0]["section"], "dartReAnalyzer");
+    proxy.send(json!({
+        "jsonrpc": "2.0",
+        "id": request["id"],
+        "result": [{"max_line_length": 20}]
+    }));
+    let publish = proxy.wait_for(|m| is_publish_for(m, &uri));
+    assert!(codes(&publish).contains(&"line_length".to_string()));
+
+    // A config file created in the workspace root is picked up
+    let config = dir.path().join("analyzer_config.json");
+    std::fs::write(
+        &config,
+        r#"{"runtime_rules": {"disabled_rules": ["avoid_print"]}}"#,
+    )
+    .unwrap();
+    proxy.notify(
+        "workspace/didChangeWatchedFiles",
+        json!({"changes": [{"uri": uri::path_to_uri(&config).unwrap(), "type": 1}]}),
+    );
+    let publish = proxy.wait_for(|m| is_publish_for(m, &uri));
+    let codes = codes(&publish);
+    assert!(codes.contains(&"line_length".to_string()), "{:?}", codes);
+    assert!(!codes.contains(&"avoid_print".to_string()), "{:?}", codes);
+
+    proxy.request("shutdown", Value::Null);
+    assert!(proxy.exit().success());
+
+    // Responses to the proxy's own requests never reach the Dart server
+    let messages = logged(&log);
+    assert!(messages.iter().all(|m| !m["id"]
+        .as_str()
+        .is_some_and(|id| id.starts_with("dart-re-analyzer/"))));
+    assert_eq!(count(&messages, "workspace/didChangeWatchedFiles"), 1);
+}
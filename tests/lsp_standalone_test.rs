@@ -0,0 +1,390 @@
+use dart_re_analyzer::config::AnalyzerConfig;
+use dart_re_analyzer::lsp::standalone::StandaloneServer;
+use dart_re_analyzer::lsp::uri;
+use lsp_server::{Connection, Message, Notification, Request, RequestId};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// In-process LSP client talking to a standalone server on another thread
+struct Client {
+    connection: Connection,
+    server: Option<JoinHandle<()>>,
+    next_id: i32,
+    notifications: Vec<Notification>,
+    /// Requests from the server
+    requests: Vec<Request>,
+}
+
+impl Client {
+    fn start(workspace: &Path, initialize_params: Value) -> (Self, Value) {
+        let (client, server) = Connection::memory();
+        let root = workspace.to_path_buf();
+        let handle = std::thread::spawn(move || {
+            StandaloneServer::new(AnalyzerConfig::default(), root)
+                .serve(&server)
+                .unwrap();
+        });
+
+        let mut client = Self {
+            connection: client,
+            server: Some(handle),
+            next_id: 0,
+            notifications: Vec::new(),
+            requests: Vec::new(),
+        };
+        let result = client.request("initialize", initialize_params);
+        client.notify("initialized", json!({}));
+        (client, result)
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.next_id += 1;
+        let id = RequestId::from(self.next_id);
+        let request = Request::new(id.clone(), method.to_string(), params);
+        self.connection.sender.send(request.into()).unwrap();
+
+        loop {
+            match self.recv() {
+                Message::Response(response) if response.id == id => {
+                    assert!(response.error.is_none(), "{:?}", response.error);
+                    return response.result.unwrap_or(Value::Null);
+                }
+                Message::Notification(notification) => self.notifications.push(notification),
+                Message::Request(request) => self.requests.push(request),
+                other => panic!("Unexpected message: {:?}", other),
+            }
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: Value) {
+        let notification = Notification::new(method.to_string(), params);
+        self.connection.sender.send(notification.into()).unwrap();
+    }
+
+    fn recv(&self) -> Message {
+        self.connection
+            .receiver
+            .recv_timeout(Duration::from_secs(10))
+            .expect("Timed out waiting for the server")
+    }
+
+    /// Wait for the next `publishDiagnostics` for a URI
+    fn diagnostics_for(&mut self, uri: &str) -> Vec<Value> {
+        loop {
+            if let Some(i) = self.notifications.iter().position(|n| {
+                n.method == "textDocument/publishDiagnostics" && n.params["uri"] == uri
+            }) {
+                let notification = self.notifications.remove(i);
+                return notification.params["diagnostics"]
+                    .as_array()
+                    .cloned()
+                    .unwrap_or_default();
+            }
+            match self.recv() {
+                Message::Notification(notification) => self.notifications.push(notification),
+                other => panic!("Unexpected message: {:?}", other),
+            }
+        }
+    }
+
+    fn shutdown(mut self) {
+        self.request("shutdown", Value::Null);
+        self.notify("exit", Value::Null);
+        self.server.take().unwrap().join().unwrap();
+    }
+}
+
+fn initialize_params(root_uri: &str, encodings: &[&str]) -> Value {
+    json!({
+        "processId": null,
+        "rootUri": root_uri,
+        "capabilities": {"general": {"positionEncodings": encodings}}
+    })
+}
+
+#[test]
+fn test_standalone_end_to_end() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::write(
+        dir.path().join("on_disk.dart"),
+        "void main() {\n  print('disk');\n}\n",
+    )
+    .unwrap();
+    let root_uri = uri::path_to_uri(dir.path()).unwrap();
+
+    let (mut client, result) = Client::start(dir.path(), initialize_params(&root_uri, &[]));
+    let capabilities = &result["capabilities"];
+    assert_eq!(capabilities["positionEncoding"], "utf-16");
+    assert_eq!(capabilities["documentSymbolProvider"], true);
+    assert_eq!(capabilities["foldingRangeProvider"], true);
+    assert_eq!(result["serverInfo"]["name"], "dart-re-analyzer");
+
+    // Workspace analysis publishes diagnostics for files on disk
+    let disk_uri = uri::path_to_uri(&dir.path().join("on_disk.dart")).unwrap();
+    let diagnostics = client.diagnostics_for(&disk_uri);
+    assert!(diagnostics.iter().any(|d| d["code"] == "avoid_print"));
+
+    // Unsaved buffers are analyzed as they change
+    let uri = uri::path_to_uri(&dir.path().join("buffer.dart")).unwrap();
+    client.notify(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": {
+                "uri": uri,
+                "languageId": "dart",
+                "version": 1,
+                "text": "class Greeter {\n  void greet() {\n  }\n}\n"
+            }
+        }),
+    );
+
+    // A clean buffer has nothing to publish; the first edit adds a print
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": {"uri": uri, "version": 2},
+            "contentChanges": [{
+                "range": {
+                    "start": {"line": 1, "character": 16},
+                    "end": {"line": 1, "character": 16}
+                },
+                "text": "\n    print('hi');"
+            }]
+        }),
+    );
+    let diagnostics = client.diagnostics_for(&uri);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["code"], "avoid_print");
+    assert_eq!(
+        diagnostics[0]["range"],
+        json!({"start": {"line": 2, "character": 4}, "end": {"line": 2, "character": 10}})
+    );
+
+    // Code actions for the diagnostic under the cursor
+    let actions = client.request(
+        "textDocument/codeAction",
+        json!({
+            "textDocument": {"uri": uri},
+            "range": {"start": {"line": 2, "character": 5}, "end": {"line": 2, "character": 5}},
+            "context": {"diagnostics": diagnostics}
+        }),
+    );
+    let titles: Vec<&str> = actions
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter_map(|a| a["title"].as_str())
+        .collect();
+    assert_eq!(
+        titles,
+        vec![
+            "Ignore 'avoid_print' for this line",
+            "Ignore 'avoid_print' for this file"
+        ]
+    );
+
+    // Document symbols and folding ranges come from tree-sitter
+    let symbols = client.request(
+        "textDocument/documentSymbol",
+        json!({"textDocument": {"uri": uri}}),
+    );
+    assert_eq!(symbols[0]["name"], "Greeter");
+    assert_eq!(symbols[0]["children"][0]["name"], "greet");
+
+    let ranges = client.request(
+        "textDocument/foldingRange",
+        json!({"textDocument": {"uri": uri}}),
+    );
+    assert!(ranges
+        .as_array()
+        .unwrap()
+        .iter()
+        .any(|r| r["startLine"] == 0 && r["endLine"] == 3));
+
+    // Closing a buffer with no file on disk clears its diagnostics
+    client.notify(
+        "textDocument/didClose",
+        json!({"textDocument": {"uri": uri}}),
+    );
+    assert!(client.diagnostics_for(&uri).is_empty());
+
+    client.shutdown();
+}
+
+#[test]
+fn test_standalone_negotiates_position_encoding() {
+    let dir = tempfile::tempdir().unwrap();
+    let root_uri = uri::path_to_uri(dir.path()).unwrap();
+
+    let (mut client, result) = Client::start(
+        dir.path(),
+        initialize_params(&root_uri, &["utf-8", "utf-16"]),
+    );
+    assert_eq!(result["capabilities"]["positionEncoding"], "utf-8");
+
+    let uri = uri::path_to_uri(&dir.path().join("emoji.dart")).unwrap();
+    client.notify(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": {
+                "uri": uri,
+                "languageId": "dart",
+                "version": 1,
+                "text": "var s = '😀'; dynamic x;\n"
+            }
+        }),
+    );
+    let diagnostics = client.diagnostics_for(&uri);
+    let dynamic = diagnostics
+        .iter()
+        .find(|d| d["code"] == "avoid_dynamic")
+        .unwrap();
+    // Byte columns are reported as-is in UTF-8
+    assert_eq!(dynamic["range"]["start"]["character"], 16);
+
+    client.shutdown();
+}
+
+#[test]
+fn test_standalone_rejects_unknown_requests() {
+    let dir = tempfile::tempdir().unwrap();
+    let root_uri = uri::path_to_uri(dir.path()).unwrap();
+    let (client, _) = Client::start(dir.path(), initialize_params(&root_uri, &[]));
+
+    let id = RequestId::from(99);
+    let request = Request::new(id.clone(), "textDocument/hover".to_string(), json!({}));
+    client.connection.sender.send(request.into()).unwrap();
+    loop {
+        match client.recv() {
+            Message::Response(response) if response.id == id => {
+                let error = response.error.unwrap();
+                assert_eq!(error.code, lsp_server::ErrorCode::MethodNotFound as i32);
+                break;
+            }
+            _ => continue,
+        }
+    }
+
+    client.shutdown();
+}
+
+#[test]
+fn test_standalone_pull_diagnostics() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir_all(dir.path().join("lib")).unwrap();
+    std::fs::write(dir.path().join("lib/a.dart"), "dynamic a = 1;\n").unwrap();
+    let root_uri = uri::path_to_uri(dir.path()).unwrap();
+    let a_uri = uri::path_to_uri(&dir.path().join("lib/a.dart")).unwrap();
+    let b_uri = uri::path_to_uri(&dir.path().join("lib/b.dart")).unwrap();
+
+    let mut params = initialize_params(&root_uri, &[]);
+    params["capabilities"]["textDocument"] = json!({"diagnostic": {}});
+    params["capabilities"]["workspace"] = json!({"diagnostics": {"refreshSupport": true}});
+    let (mut client, result) = Client::start(dir.path(), params);
+    assert_eq!(
+        result["capabilities"]["diagnosticProvider"]["identifier"],
+        "dart-re-analyzer"
+    );
+
+    let workspace = client.request("workspace/diagnostic", json!({"previousResultIds": []}));
+    let items = workspace["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["uri"], a_uri);
+    assert_eq!(items[0]["kind"], "full");
+    assert_eq!(items[0]["items"][0]["code"], "avoid_dynamic");
+
+    // The workspace analysis asked the client to pull
+    assert_eq!(client.requests.len(), 1);
+    assert_eq!(client.requests[0].method, "workspace/diagnostic/refresh");
+
+    client.notify(
+        "textDocument/didOpen",
+        json!({"textDocument": {"uri": b_uri, "languageId": "dart", "version": 1, "text": "dynamic b;\n"}}),
+    );
+    let report = client.request(
+        "textDocument/diagnostic",
+        json!({"textDocument": {"uri": b_uri}}),
+    );
+    assert_eq!(report["kind"], "full");
+    assert_eq!(report["items"][0]["code"], "avoid_dynamic");
+
+    let result_id = report["resultId"].clone();
+    let unchanged = client.request(
+        "textDocument/diagnostic",
+        json!({"textDocument": {"uri": b_uri}, "previousResultId": result_id}),
+    );
+    assert_eq!(unchanged["kind"], "unchanged");
+
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": {"uri": b_uri, "version": 2},
+            "contentChanges": [{"text": "int b = 0;\n"}]
+        }),
+    );
+    let cleared = client.request(
+        "textDocument/diagnostic",
+        json!({"textDocument": {"uri": b_uri}, "previousResultId": result_id}),
+    );
+    assert_eq!(cleared["kind"], "full");
+    assert_eq!(cleared["items"], json!([]));
+
+    // Diagnostics are never pushed to a client that pulls
+    assert!(client
+        .notifications
+        .iter()
+        .all(|n| n.method != "textDocument/publishDiagnostics"));
+    client.shutdown();
+}
+
+#[test]
+fn test_standalone_project_rules() {
+    let dir = tempfile::tempdir().unwrap();
+    let b = "import 'a.dart';\n\nclass B {\n  A? make() => null;\n}\n";
+    std::fs::write(
+        dir.path().join("a.dart"),
+        "import 'b.dart';\n\nclass A extends B {}\n",
+    )
+    .unwrap();
+    std::fs::write(dir.path().join("b.dart"), b).unwrap();
+    let root_uri = uri::path_to_uri(dir.path()).unwrap();
+
+    let (mut client, _) = Client::start(dir.path(), initialize_params(&root_uri, &[]));
+
+    // The cycle is reported on the import in b.dart closing it
+    let b_uri = uri::path_to_uri(&dir.path().join("b.dart")).unwrap();
+    let diagnostics = client.diagnostics_for(&b_uri);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0]["code"], "import_cycles");
+
+    // Editing the buffer keeps project diagnostics until it is saved, and
+    // only moves their range into the new text
+    client.notify(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": {
+                "uri": b_uri,
+                "languageId": "dart",
+                "version": 1,
+                "text": b
+            }
+        }),
+    );
+    client.notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": {"uri": b_uri, "version": 2},
+            "contentChanges": [{"text": "class B {}\n"}]
+        }),
+    );
+    client.notify(
+        "textDocument/didSave",
+        json!({"textDocument": {"uri": b_uri}}),
+    );
+    // Saving re-runs them on the buffer, which no longer imports a.dart
+    while !client.diagnostics_for(&b_uri).is_empty() {}
+
+    client.shutdown();
+}
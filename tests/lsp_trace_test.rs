@@ -0,0 +1,149 @@
+mod common;
+
+use common::{fake_dart_server, read_message};
+use dart_re_analyzer::lsp::framing::{self, FrameReader};
+use dart_re_analyzer::lsp::trace::{read_trace, Direction, TraceEntry};
+use dart_re_analyzer::lsp::uri;
+use serde_json::{json, Value};
+use std::ffi::OsStr;
+use std::path::Path;
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+/// A message to send, and what to wait for after it
+type Step = (Value, Option<fn(&Value) -> bool>);
+
+/// Run the language server with `args` through `session`, waiting after
+/// each message for a message matching its predicate, if any,, like an editor would
+fn record(args: &[&OsStr], session: &[Step]) -> ExitStatus {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dart-re-analyzer"))
+        .arg("language-server")
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let mut stdin = child.stdin.take().unwrap();
+    let mut stdout = FrameReader::new(child.stdout.take().unwrap());
+    let (tx, rx) = channel();
+    std::thread::spawn(move || {
+        while let Some(message) = read_message(&mut stdout) {
+            if tx.send(message).is_err() {
+                break;
+            }
+        }
+    });
+
+    for (message, awaited) in session {
+        framing::write_message(&mut stdin, &message.to_string()).unwrap();
+        if let Some(awaited) = awaited {
+            while !awaited(&rx.recv_timeout(Duration::from_secs(10)).unwrap()) {}
+        }
+    }
+    drop(stdin);
+    child.wait().unwrap()
+}
+
+fn messages(entries: &[TraceEntry], direction: Direction) -> Vec<&Value> {
+    entries
+        .iter()
+        .filter(|e| e.direction == direction)
+        .map(|e| &e.message)
+        .collect()
+}
+
+fn session(workspace: &Path) -> Vec<Step> {
+    let uri = uri::path_to_uri(&workspace.join("lib/main.dart")).unwrap();
+    vec![
+        (
+            json!({"jsonrpc": "2.0", "id": 1, "method": "initialize", "params": {
+                "rootUri": uri::path_to_uri(workspace).unwrap(), "capabilities": {}
+            }}),
+            Some(|m| m["id"] == 1),
+        ),
+        (
+            json!({"jsonrpc": "2.0", "method": "initialized", "params": {}}),
+            None,
+        ),
+        (
+            json!({"jsonrpc": "2.0", "method": "textDocument/didOpen", "params": {"textDocument": {
+                "uri": uri, "languageId": "dart", "version": 1, "text": "dynamic x = 1;\n"
+            }}}),
+            Some(|m| m["method"] == "textDocument/publishDiagnostics"),
+        ),
+        (
+            json!({"jsonrpc": "2.0", "id": 2, "method": "shutdown"}),
+            Some(|m| m["id"] == 2),
+        ),
+        (json!({"jsonrpc": "2.0", "method": "exit"}), None),
+    ]
+}
+
+#[test]
+fn test_trace_records_all_directions_and_replays() {
+    let dir = tempfile::tempdir().unwrap();
+    let workspace = dir.path().join("app");
+    std::fs::create_dir_all(workspace.join("lib")).unwrap();
+    let trace = dir.path().join("trace.jsonl");
+    let session = session(&workspace);
+    let status = record(
+        &[
+            workspace.as_os_str(),
+            "--dart-binary".as_ref(),
+            fake_dart_server().as_os_str(),
+            "--trace".as_ref(),
+            trace.as_os_str(),
+        ],
+        &session,
+    );
+    assert!(status.success());
+    let sent: Vec<&Value> = session.iter().map(|(message, _)| message).collect();
+
+    let entries = read_trace(&trace).unwrap();
+    assert_eq!(messages(&entries, Direction::FromClient), sent);
+    assert!(messages(&entries, Direction::ToServer)
+        .iter()
+        .any(|m| m["method"] == "initialize"));
+    assert!(messages(&entries, Direction::FromServer)
+        .iter()
+        .any(|m| m["result"]["serverInfo"]["name"] == "fake-dart"));
+    let to_client = messages(&entries, Direction::ToClient);
+    assert!(to_client.iter().any(|m| m["id"] == 1));
+    assert!(to_client
+        .iter()
+        .any(|m| m["method"] == "textDocument/publishDiagnostics"));
+    assert!(entries.windows(2).all(|w| w[0].elapsed <= w[1].elapsed));
+
+    // Replaying the trace drives a new proxy through the same session
+    let replayed = dir.path().join("replayed.jsonl");
+    let output = Command::new(env!("CARGO_BIN_EXE_dart-re-analyzer"))
+        .args([
+            "replay".as_ref(),
+            trace.as_os_str(),
+            workspace.as_os_str(),
+            "--dart-binary".as_ref(),
+            fake_dart_server().as_os_str(),
+            "--fast".as_ref(),
+            "--trace".as_ref(),
+            replayed.as_os_str(),
+        ])
+        .stderr(Stdio::null())
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let printed: Vec<Value> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    assert!(printed
+        .iter()
+        .any(|m| m["id"] == 1 && m["result"]["serverInfo"].is_object()));
+    assert!(printed.iter().any(|m| m["id"] == 2));
+
+    let replayed = read_trace(&replayed).unwrap();
+    assert_eq!(messages(&replayed, Direction::FromClient), sent);
+}
@@ -1,6 +1,14 @@
 use dart_re_analyzer::error::{Diagnostic, Location, RuleCategory, Severity};
-use dart_re_analyzer::mcp::{DiagnosticsStats, ErrorsQuery, McpRequest, McpServer};
+#[cfg(unix)]
+use dart_re_analyzer::mcp::start_mcp_server;
+use dart_re_analyzer::mcp::{
+    serve_connection, DiagnosticsStats, ErrorsQuery, McpRequest, McpResponse, McpServer,
+    McpServerOptions,
+};
 use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
 
 #[tokio::test]
 async fn test_mcp_server_creation() {
@@ -26,6 +34,7 @@ async fn test_mcp_update_diagnostics() {
         rule_id: "test_rule".to_string(),
         message: "Test error".to_string(),
         suggestion: Some("Fix it".to_string()),
+        fix: None,
     };
 
     mcp.update_diagnostics(vec![diag.clone()]).await;
@@ -348,6 +357,192 @@ async fn test_filtered_diagnostics_query() {
     assert_eq!(filtered[0].rule_id, "rule1");
 }
 
+#[tokio::test]
+async fn test_connection_reports_unparsable_request() {
+    let responses = exchange(
+        McpServerOptions::default(),
+        &["not json", r#"{"method": "get_stats", "params": {}}"#],
+    )
+    .await;
+
+    assert_eq!(responses.len(), 2);
+    assert!(!responses[0].success);
+    assert!(responses[0]
+        .error
+        .as_ref()
+        .unwrap()
+        .contains("Invalid request"));
+    assert!(responses[1].success);
+}
+
+#[tokio::test]
+async fn test_connection_requires_token() {
+    let options = McpServerOptions {
+        auth_token: Some("secret".to_string()),
+        ..McpServerOptions::default()
+    };
+
+    let responses = exchange(
+        options,
+        &[
+            r#"{"method": "get_stats", "params": {}}"#,
+            r#"{"method": "get_stats", "params": {}}"#,
+        ],
+    )
+    .await;
+
+    // The connection is closed after the first unauthenticated request
+    assert_eq!(responses.len(), 1);
+    assert!(!responses[0].success);
+    assert_eq!(
+        responses[0].error.as_deref(),
+        Some("Authentication required")
+    );
+}
+
+#[tokio::test]
+async fn test_connection_accepts_valid_token() {
+    let options = McpServerOptions {
+        auth_token: Some("secret".to_string()),
+        ..McpServerOptions::default()
+    };
+
+    let responses = exchange(
+        options,
+        &[
+            r#"{"method": "authenticate", "params": {"token": "secret"}}"#,
+            r#"{"method": "get_stats", "params": {}}"#,
+        ],
+    )
+    .await;
+
+    assert_eq!(responses.len(), 2);
+    assert!(responses[0].success);
+    assert!(responses[1].success);
+}
+
+#[tokio::test]
+async fn test_connection_rejects_wrong_token() {
+    let options = McpServerOptions {
+        auth_token: Some("secret".to_string()),
+        ..McpServerOptions::default()
+    };
+
+    let responses = exchange(
+        options,
+        &[r#"{"method": "authenticate", "params": {"token": "wrong"}}"#],
+    )
+    .await;
+
+    assert_eq!(responses.len(), 1);
+    assert!(!responses[0].success);
+}
+
+#[tokio::test]
+async fn test_connection_rejects_oversized_request() {
+    let options = McpServerOptions {
+        max_request_size: 16,
+        ..McpServerOptions::default()
+    };
+
+    let responses = exchange(
+        options,
+        &[
+            r#"{"method": "get_all_errors", "params": {}}"#,
+            r#"{"method": "get_stats", "params": {}}"#,
+        ],
+    )
+    .await;
+
+    assert_eq!(responses.len(), 1);
+    assert!(responses[0]
+        .error
+        .as_ref()
+        .unwrap()
+        .contains("maximum size"));
+}
+
+#[tokio::test]
+async fn test_connection_times_out_when_idle() {
+    let options = McpServerOptions {
+        timeout: Duration::from_millis(50),
+        ..McpServerOptions::default()
+    };
+    let (client, server) = tokio::io::duplex(1024);
+    let mcp = Arc::new(McpServer::new());
+
+    let handle = tokio::spawn(async move {
+        serve_connection(server, mcp, &options).await;
+    });
+
+    // The server should give up on the silent client on its own
+    tokio::time::timeout(Duration::from_secs(5), handle)
+        .await
+        .expect("connection should time out")
+        .unwrap();
+    drop(client);
+}
+
+#[test]
+fn test_server_options_address() {
+    let options = McpServerOptions {
+        bind: "0.0.0.0".to_string(),
+        port: 9100,
+        ..McpServerOptions::default()
+    };
+    assert_eq!(options.address(), "0.0.0.0:9100");
+
+    let options = McpServerOptions {
+        bind: "::1".to_string(),
+        ..McpServerOptions::default()
+    };
+    assert_eq!(options.address(), "[::1]:9000");
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_unix_socket_refuses_to_replace_a_regular_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("mcp.sock");
+    std::fs::write(&path, "keep me").unwrap();
+
+    let options = McpServerOptions {
+        unix_socket: Some(path.clone()),
+        ..McpServerOptions::default()
+    };
+    let result = start_mcp_server(options, Arc::new(McpServer::new())).await;
+
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("not a socket"), "{}", error);
+    assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep me");
+}
+
+// Send each line over an in-memory connection and collect the responses
+async fn exchange(options: McpServerOptions, lines: &[&str]) -> Vec<McpResponse> {
+    let (client, server) = tokio::io::duplex(64 * 1024);
+    let mcp = Arc::new(McpServer::new());
+
+    let handle = tokio::spawn(async move {
+        serve_connection(server, mcp, &options).await;
+    });
+
+    let (reader, mut writer) = tokio::io::split(client);
+    for line in lines {
+        writer.write_all(line.as_bytes()).await.unwrap();
+        writer.write_all(b"\n").await.unwrap();
+    }
+    writer.shutdown().await.unwrap();
+
+    let mut responses = Vec::new();
+    let mut reader = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = reader.next_line().await {
+        responses.push(serde_json::from_str(&line).unwrap());
+    }
+
+    handle.await.unwrap();
+    responses
+}
+
 // Helper function to create test diagnostics
 fn create_test_diagnostic(
     file: &str,
@@ -368,5 +563,6 @@ fn create_test_diagnostic(
         rule_id: rule_id.to_string(),
         message: format!("Test message for {}", rule_id),
         suggestion: Some("Fix it".to_string()),
+        fix: None,
     }
 }
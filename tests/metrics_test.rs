@@ -0,0 +1,163 @@
+use dart_re_analyzer::analyzer::Rule;
+use dart_re_analyzer::config::{AnalyzerConfig, MetricsConfig};
+use dart_re_analyzer::error::{RuleCategory, Severity};
+use dart_re_analyzer::rules::get_rules_for_config;
+use dart_re_analyzer::rules::metrics::{Metric, MetricRule};
+use serde_json::Value;
+use std::path::Path;
+use std::process::Command;
+
+const SOURCE: &str = r#"
+class Form {
+  String name = '';
+  String email = '';
+
+  bool validate(bool strict, int min, int max) {
+    if (name.isEmpty) {
+      return false;
+    }
+    for (final c in name.split('')) {
+      if (strict && c == ' ') {
+        return false;
+      }
+    }
+    return email.contains('@') || !strict;
+  }
+}
+
+int twice(int x) => x * 2;
+"#;
+
+#[test]
+fn test_metric_rules() {
+    let path = Path::new("form.dart");
+    let check = |metric: Metric, max: usize| MetricRule::new(metric, max).check(path, SOURCE);
+
+    let diagnostics = check(Metric::CyclomaticComplexity, 4).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].rule_id, "cyclomatic_complexity");
+    assert_eq!(
+        diagnostics[0].message,
+        "'Form.validate' has a cyclomatic complexity of 6 (max 4)"
+    );
+    assert!(matches!(diagnostics[0].severity, Severity::Info));
+    assert!(matches!(diagnostics[0].category, RuleCategory::Style));
+    // The name of the method
+    let location = &diagnostics[0].location;
+    assert_eq!((location.line, location.column), (6, 8));
+    assert_eq!(location.end_column, Some(16));
+
+    let messages = |metric: Metric, max: usize| -> Vec<String> {
+        check(metric, max)
+            .unwrap()
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    };
+    assert_eq!(
+        messages(Metric::CognitiveComplexity, 5),
+        vec!["'Form.validate' has a cognitive complexity of 6 (max 5)"]
+    );
+    assert_eq!(
+        messages(Metric::NestingDepth, 1),
+        vec!["'Form.validate' has control flow nested 2 levels deep (max 1)"]
+    );
+    assert_eq!(
+        messages(Metric::Parameters, 0),
+        vec![
+            "'Form.validate' has 3 parameters (max 0)",
+            "'twice' has 1 parameter (max 0)"
+        ]
+    );
+    assert_eq!(
+        messages(Metric::FunctionLines, 10),
+        vec!["'Form.validate' has 11 lines of code (max 10)"]
+    );
+    assert_eq!(
+        messages(Metric::ClassMethods, 0),
+        vec!["'Form' has 1 method (max 0)"]
+    );
+    assert_eq!(
+        messages(Metric::ClassFields, 1),
+        vec!["'Form' has 2 fields (max 1)"]
+    );
+
+    // Nothing exceeds the default thresholds
+    for rule in MetricRule::all(&MetricsConfig::default()) {
+        assert!(rule.check(path, SOURCE).unwrap().is_empty());
+    }
+}
+
+#[test]
+fn test_metric_rules_on_constructors() {
+    let source = "class A {\n  A(int a);\n  A.named(int a, int b);\n}\n";
+    let diagnostics = MetricRule::new(Metric::Parameters, 0)
+        .check(Path::new("a.dart"), source)
+        .unwrap();
+    let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "'A' has 1 parameter (max 0)",
+            "'A.named' has 2 parameters (max 0)"
+        ]
+    );
+    // The whole name of the named constructor
+    let location = &diagnostics[1].location;
+    assert_eq!((location.line, location.column), (3, 3));
+    assert_eq!(location.end_column, Some(10));
+}
+
+#[test]
+fn test_metric_thresholds_are_configurable() {
+    let config: AnalyzerConfig =
+        serde_json::from_str(r#"{"metrics": {"max_parameters": 2}}"#).unwrap();
+    assert_eq!(config.metrics.max_parameters, 2);
+    // Thresholds left out keep their defaults
+    assert_eq!(config.metrics.max_cyclomatic_complexity, 10);
+
+    let diagnostics: Vec<_> = get_rules_for_config(&config)
+        .iter()
+        .flat_map(|rule| rule.check(Path::new("form.dart"), SOURCE).unwrap())
+        .filter(|d| d.rule_id.starts_with("max_"))
+        .collect();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].rule_id, "max_parameters");
+}
+
+#[test]
+fn test_metrics_subcommand() {
+    let dir = tempfile::tempdir().unwrap();
+    std::fs::create_dir(dir.path().join("lib")).unwrap();
+    std::fs::write(dir.path().join("lib/form.dart"), SOURCE).unwrap();
+    let metrics = |format: &str| {
+        let output = Command::new(env!("CARGO_BIN_EXE_dart-re-analyzer"))
+            .args(["metrics".as_ref(), dir.path().as_os_str()])
+            .args(["--format", format])
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        String::from_utf8(output.stdout).unwrap()
+    };
+
+    let json: Value = serde_json::from_str(&metrics("json")).unwrap();
+    let file = &json[0];
+    assert!(file["file"].as_str().unwrap().ends_with("form.dart"));
+    assert_eq!(file["functions"][0]["name"], "validate");
+    assert_eq!(file["functions"][0]["class"], "Form");
+    assert_eq!(file["functions"][0]["cyclomatic_complexity"], 6);
+    assert_eq!(file["functions"][1]["name"], "twice");
+    assert_eq!(file["functions"][1]["class"], Value::Null);
+    assert_eq!(file["classes"][0]["methods"], 1);
+    assert_eq!(file["classes"][0]["fields"], 2);
+
+    let csv = metrics("csv");
+    let rows: Vec<Vec<&str>> = csv.lines().map(|l| l.split(',').collect()).collect();
+    assert_eq!(rows.len(), 4);
+    assert_eq!(rows[0][5], "cyclomatic_complexity");
+    assert_eq!(
+        &rows[1][1..10],
+        &["function", "Form", "validate", "6", "6", "6", "2", "3", "11"]
+    );
+    assert_eq!(&rows[3][1..4], &["class", "", "Form"]);
+}
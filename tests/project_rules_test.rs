@@ -0,0 +1,335 @@
+mod common;
+
+use common::package;
+use dart_re_analyzer::analyzer::index::ProjectIndex;
+use dart_re_analyzer::analyzer::ProjectRule;
+use dart_re_analyzer::error::{Fix, RuleCategory, Severity};
+use dart_re_analyzer::parser;
+use dart_re_analyzer::rules::project::{
+    ImportCyclesRule, UnreferencedPublicDeclarationRule, UnusedPrivateDeclarationRule,
+};
+use serde_json::Value;
+use std::path::Path;
+use std::process::Command;
+
+fn index(dir: &Path) -> ProjectIndex {
+    ProjectIndex::build(&parser::find_dart_files(dir).unwrap())
+}
+
+#[test]
+fn test_import_cycles() {
+    let dir = package(&[
+        // a -> b -> c -> a, through relative, package and export directives
+        ("lib/a.dart", "import 'dart:io';\nimport 'src/b.dart';\n"),
+        ("lib/src/b.dart", "import 'package:app/src/c.dart';\n"),
+        ("lib/src/c.dart", "import 'd.dart';\nexport '../a.dart';\n"),
+        // c imports d, but d imports nothing of the project
+        (
+            "lib/src/d.dart",
+            "import 'package:collection/collection.dart';\n",
+        ),
+        ("lib/e.dart", "import 'e.dart';\n"),
+        ("lib/f.dart", "import 'a.dart';\n"),
+    ]);
+
+    let diagnostics = ImportCyclesRule.check_project(&index(dir.path())).unwrap();
+    let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "Import cycle: lib/a.dart -> lib/src/b.dart -> lib/src/c.dart -> lib/a.dart",
+            "Import cycle: lib/e.dart -> lib/e.dart",
+        ]
+    );
+
+    // On the export closing the cycle
+    let cycle = &diagnostics[0];
+    assert_eq!(cycle.rule_id, "import_cycles");
+    assert!(cycle.location.file.ends_with("c.dart"));
+    assert_eq!((cycle.location.line, cycle.location.column), (2, 1));
+    assert_eq!(cycle.location.end_column, Some(20));
+    assert!(matches!(cycle.severity, Severity::Warning));
+    assert!(matches!(cycle.category, RuleCategory::Style));
+    assert_eq!(
+        cycle.suggestion.as_deref(),
+        Some("Remove the dependency of 'lib/src/c.dart' on '../a.dart', or move what both use into a separate library")
+    );
+}
+
+#[test]
+fn test_import_cycles_reports_shortest_cycle() {
+    let dir = package(&[
+        ("lib/a.dart", "import 'b.dart';\n"),
+        ("lib/b.dart", "import 'c.dart';\nimport 'a.dart';\n"),
+        ("lib/c.dart", "import 'a.dart';\n"),
+    ]);
+
+    let diagnostics = ImportCyclesRule.check_project(&index(dir.path())).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].message,
+        "Import cycle: lib/a.dart -> lib/b.dart -> lib/a.dart (3 files import each other in this cycle)"
+    );
+    assert!(diagnostics[0].location.file.ends_with("b.dart"));
+    assert_eq!(diagnostics[0].location.line, 2);
+}
+
+#[test]
+fn test_analyze_reports_import_cycles() {
+    let dir = package(&[
+        ("lib/a.dart", "import 'b.dart';\n"),
+        ("lib/b.dart", "import 'a.dart';\n"),
+        ("lib/c.dart", "// ignore: import_cycles\nimport 'd.dart';\n"),
+        ("lib/d.dart", "import 'c.dart'; // ignore: import_cycles\n"),
+    ]);
+    let analyze = |config: Option<&str>| -> Vec<Value> {
+        let mut command = Command::new(env!("CARGO_BIN_EXE_dart-re-analyzer"));
+        command.args(["analyze".as_ref(), dir.path().as_os_str()]);
+        command.args(["--format", "json"]);
+        if let Some(config) = config {
+            let path = dir.path().join("analyzer_config.json");
+            std::fs::write(&path, config).unwrap();
+            command.arg("--config").arg(path);
+        }
+        let output = command.output().unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        let json = &stdout[stdout.find("\n[").unwrap()..];
+        let diagnostics: Vec<Value> = serde_json::from_str(json).unwrap();
+        diagnostics
+            .into_iter()
+            .filter(|d| d["rule_id"] == "import_cycles")
+            .collect()
+    };
+
+    // The c <-> d cycle is silenced on the directive closing it
+    let diagnostics = analyze(None);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0]["message"],
+        "Import cycle: lib/a.dart -> lib/b.dart -> lib/a.dart"
+    );
+
+    let config = r#"{"style_rules": {"enabled": true, "disabled_rules": ["import_cycles"]}}"#;
+    assert!(analyze(Some(config)).is_empty());
+}
+
+/// `content` with the edits of a fix applied, for fixes of one edit
+fn apply(content: &str, fix: &Fix) -> String {
+    let offset = |line: usize, column: usize| {
+        content
+            .split_inclusive('\n')
+            .take(line - 1)
+            .map(str::len)
+            .sum::<usize>()
+            + column
+            - 1
+    };
+    let location = &fix.edits[0].location;
+    let start = offset(location.line, location.column);
+    let end = offset(location.end_line.unwrap(), location.end_column.unwrap());
+    format!(
+        "{}{}{}",
+        &content[..start],
+        fix.edits[0].replacement,
+        &content[end..]
+    )
+}
+
+#[test]
+fn test_unused_private_declarations() {
+    let a = "part 'a_part.dart';\n\n\
+             void _unused() {\n  _unused();\n}\n\n\
+             /// Used by the part\n@pragma('vm:prefer-inline')\nvoid _helper() {}\n\n\
+             class _Unused {}\n\n\
+             int _a = 0, _b = _a;\n\n\
+             class Counter {\n  int _count = 0; // reset below\n  int _total = 0;\n\n  void _reset() => _count = 0;\n}\n";
+    let dir = package(&[
+        ("lib/a.dart", a),
+        (
+            "lib/a_part.dart",
+            "part of 'a.dart';\n\nvoid run() => _helper();\n",
+        ),
+        // Private names of another library don't count
+        ("lib/b.dart", "void _other() => _total;\n"),
+        // The part with the references may be missing
+        ("lib/c.dart", "part 'c.g.dart';\n\nclass _Generated {}\n"),
+    ]);
+    let index = index(dir.path());
+
+    let diagnostics = UnusedPrivateDeclarationRule.check_project(&index).unwrap();
+    let mut found: Vec<(&str, usize)> = diagnostics
+        .iter()
+        .filter(|d| d.location.file.ends_with("a.dart"))
+        .map(|d| (d.message.as_str(), d.location.line))
+        .collect();
+    found.sort_by_key(|f| f.1);
+    assert_eq!(
+        found,
+        vec![
+            ("Private function '_unused' is never used", 3),
+            ("Private class '_Unused' is never used", 11),
+            ("Private variable '_b' is never used", 13),
+            ("Private field 'Counter._total' is never used", 17),
+            ("Private method 'Counter._reset' is never used", 19),
+        ]
+    );
+    assert!(diagnostics
+        .iter()
+        .any(|d| d.message == "Private function '_other' is never used"));
+    assert!(diagnostics
+        .iter()
+        .all(|d| !d.message.contains("_Generated")));
+    assert!(diagnostics
+        .iter()
+        .all(|d| d.rule_id == "unused_private_declaration"
+            && matches!(d.category, RuleCategory::Runtime)));
+
+    let fix = |name: &str| {
+        diagnostics
+            .iter()
+            .find(|d| d.message.contains(&format!("'{}'", name)))
+            .and_then(|d| d.fix.as_ref())
+    };
+    // `_b` is declared together with `_a`
+    assert!(fix("_b").is_none());
+    assert_eq!(
+        fix("_unused").unwrap().description,
+        "Remove unused function '_unused'"
+    );
+
+    let mut fixed = a.to_string();
+    for name in ["Counter._reset", "Counter._total", "_Unused", "_unused"] {
+        fixed = apply(&fixed, fix(name).unwrap());
+    }
+    assert_eq!(
+        fixed,
+        "part 'a_part.dart';\n\n\
+         /// Used by the part\n@pragma('vm:prefer-inline')\nvoid _helper() {}\n\n\
+         int _a = 0, _b = _a;\n\n\
+         class Counter {\n  int _count = 0; // reset below\n\n}\n"
+    );
+}
+
+#[test]
+fn test_unreferenced_public_declarations() {
+    let dir = package(&[
+        (
+            "lib/app.dart",
+            "export 'src/api.dart' hide Internal;\n\nvoid main() => Used();\n",
+        ),
+        // Exported through another file of lib/src
+        ("lib/src/api.dart", "export 'models.dart' show Model;\n\nclass Internal {}\n"),
+        ("lib/src/models.dart", "class Model {}\n\nclass Hidden {}\n"),
+        (
+            "lib/src/widgets.dart",
+            "class Used {}\n\nclass Unused {}\n\nint helper() => 1;\n\nfinal value = helper();\n\nvoid _private() {}\n",
+        ),
+        // Outside lib/src
+        ("lib/other.dart", "class Other {}\n"),
+    ]);
+
+    let diagnostics = UnreferencedPublicDeclarationRule
+        .check_project(&index(dir.path()))
+        .unwrap();
+    let mut messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    messages.sort();
+    assert_eq!(
+        messages,
+        vec![
+            "Public class 'Hidden' is never used outside lib/src/models.dart",
+            "Public class 'Internal' is never used outside lib/src/api.dart",
+            "Public class 'Unused' is never used outside lib/src/widgets.dart",
+            "Public function 'helper' is never used outside lib/src/widgets.dart",
+            "Public variable 'value' is never used outside lib/src/widgets.dart",
+        ]
+    );
+
+    let diagnostic = |name: &str| {
+        diagnostics
+            .iter()
+            .find(|d| d.message.contains(&format!("'{}'", name)))
+            .unwrap()
+    };
+    assert!(matches!(diagnostic("Unused").severity, Severity::Info));
+    assert_eq!(diagnostic("Unused").location.line, 3);
+    // `helper` is still used by `value`
+    assert!(diagnostic("helper").fix.is_none());
+    assert_eq!(
+        diagnostic("helper").suggestion.as_deref(),
+        Some("Make it private by renaming it to '_helper'")
+    );
+    let content = std::fs::read_to_string(dir.path().join("lib/src/widgets.dart")).unwrap();
+    assert_eq!(
+        apply(&content, diagnostic("Unused").fix.as_ref().unwrap()),
+        "class Used {}\n\nint helper() => 1;\n\nfinal value = helper();\n\nvoid _private() {}\n"
+    );
+}
+
+#[test]
+fn test_unreferenced_public_declarations_in_part_files() {
+    let dir = package(&[
+        (
+            "lib/main.dart",
+            "import 'src/forms.dart';\n\nvoid main() => Form();\n",
+        ),
+        (
+            "lib/src/forms.dart",
+            "part 'form_field.dart';\n\nclass Form {\n  final fields = <FormField>[];\n}\n",
+        ),
+        (
+            "lib/src/form_field.dart",
+            "part of 'forms.dart';\n\nclass FormField {}\n\nclass Validator {}\n",
+        ),
+        // The part with the references may be missing
+        (
+            "lib/src/generated.dart",
+            "part 'generated.g.dart';\n\nclass Generated {}\n",
+        ),
+    ]);
+
+    let diagnostics = UnreferencedPublicDeclarationRule
+        .check_project(&index(dir.path()))
+        .unwrap();
+    let mut found: Vec<(&str, bool)> = diagnostics
+        .iter()
+        .map(|d| (d.message.as_str(), d.fix.is_some()))
+        .collect();
+    found.sort();
+    // Named after the library, and only deleted when the library doesn't
+    // use it
+    assert_eq!(
+        found,
+        vec![
+            (
+                "Public class 'FormField' is never used outside lib/src/forms.dart",
+                false
+            ),
+            (
+                "Public class 'Validator' is never used outside lib/src/forms.dart",
+                true
+            ),
+        ]
+    );
+    assert!(diagnostics[0].location.file.ends_with("form_field.dart"));
+}
+
+#[test]
+fn test_declarations_used_in_interpolations() {
+    let dir = package(&[
+        (
+            "lib/app.dart",
+            "import 'src/strings.dart';\n\n\
+             class Counter {\n  int _count = 0;\n\n  String label() => 'count: $_count';\n}\n\n\
+             String welcome() => '$greeting!';\n",
+        ),
+        ("lib/src/strings.dart", "const greeting = 'hi';\n"),
+    ]);
+    let index = index(dir.path());
+
+    let diagnostics = UnusedPrivateDeclarationRule.check_project(&index).unwrap();
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+    let diagnostics = UnreferencedPublicDeclarationRule
+        .check_project(&index)
+        .unwrap();
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+}
@@ -1,7 +1,13 @@
+mod common;
+
+use anyhow::Result;
+use common::package;
 use dart_re_analyzer::analyzer::Rule;
 use dart_re_analyzer::error::{RuleCategory, Severity};
+use dart_re_analyzer::lsp::semantic::{SemanticAnalyzer, SemanticDiagnostic, SymbolInfo, TypeInfo};
 use dart_re_analyzer::rules::runtime::*;
 use std::path::Path;
+use std::sync::Arc;
 
 #[test]
 fn test_avoid_dynamic_detects_usage() {
@@ -16,6 +22,20 @@ fn test_avoid_dynamic_detects_usage() {
     assert!(matches!(diagnostics[0].category, RuleCategory::Runtime));
 }
 
+#[test]
+fn test_avoid_dynamic_span() {
+    let rule = AvoidDynamicRule;
+    let content = "void test(dynamic param) {}\n";
+    let path = Path::new("test.dart");
+
+    let diagnostics = rule.check(path, content).unwrap();
+    let location = &diagnostics[0].location;
+    // 1-based byte columns with an exclusive end
+    assert_eq!(location.column, 11);
+    assert_eq!(location.end_line, Some(1));
+    assert_eq!(location.end_column, Some(18));
+}
+
 #[test]
 fn test_avoid_dynamic_no_usage() {
     let rule = AvoidDynamicRule;
@@ -62,7 +82,7 @@ fn test_avoid_print_detects_usage() {
 
 #[test]
 fn test_avoid_null_check_detects_operator() {
-    let rule = AvoidNullCheckOnNullableRule;
+    let rule = AvoidNullCheckOnNullableRule::new();
     let content = "void test(String? value) {\n  print(value!.length);\n}\n";
     let path = Path::new("test.dart");
 
@@ -72,26 +92,383 @@ fn test_avoid_null_check_detects_operator() {
     assert!(matches!(diagnostics[0].severity, Severity::Warning));
 }
 
+/// Resolves the receiver on each line to a nullable or non-nullable type
+struct ResolvedTypes(Vec<(usize, bool)>);
+
+impl SemanticAnalyzer for ResolvedTypes {
+    fn resolve_type(&self, _file: &Path, line: usize, _column: usize) -> Result<Option<TypeInfo>> {
+        Ok(self
+            .0
+            .iter()
+            .find(|(l, _)| *l == line)
+            .map(|(_, nullable)| TypeInfo {
+                name: "String".to_string(),
+                is_nullable: *nullable,
+                type_arguments: vec![],
+                is_function: false,
+                return_type: None,
+                parameter_types: vec![],
+            }))
+    }
+
+    fn get_diagnostics(&self, _file: &Path) -> Result<Vec<SemanticDiagnostic>> {
+        Ok(vec![])
+    }
+
+    fn find_definition(
+        &self,
+        _file: &Path,
+        _line: usize,
+        _column: usize,
+    ) -> Result<Option<SymbolInfo>> {
+        Ok(None)
+    }
+
+    fn find_references(
+        &self,
+        _file: &Path,
+        _line: usize,
+        _column: usize,
+    ) -> Result<Vec<SymbolInfo>> {
+        Ok(vec![])
+    }
+
+    fn get_hover(&self, _file: &Path, _line: usize, _column: usize) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
 #[test]
-fn test_unused_import_detects_unused() {
-    let rule = UnusedImportRule;
-    let content = "import 'dart:async';\n\nvoid test() {\n  print('hello');\n}\n";
+fn test_avoid_null_check_uses_resolved_types() {
+    // Line 2 resolves to a non-nullable type, line 3 to a nullable one and
+    // line 4 not at all
+    let analyzer = ResolvedTypes(vec![(2, false), (3, true)]);
+    let rule = AvoidNullCheckOnNullableRule::with_semantic(Arc::new(analyzer));
+    let content = "void test() {\n  a!.length;\n  b!.length;\n  c!.length;\n}\n";
     let path = Path::new("test.dart");
 
     let diagnostics = rule.check(path, content).unwrap();
+    let lines: Vec<usize> = diagnostics.iter().map(|d| d.location.line).collect();
+    assert_eq!(lines, vec![3, 4]);
+}
+
+#[test]
+fn test_unused_import_detects_unused() {
+    let dir = package(&[("lib/models.dart", "class User {}\n")]);
+    let rule = UnusedImportRule;
+    // Neither `models` nor a `[User]` reference in a plain comment uses the
+    // import, only doc comment references do
+    let content =
+        "import 'models.dart';\n\n// models used [User]\nvoid test() {\n  print('hello');\n}\n";
+    let path = dir.path().join("lib/main.dart");
+
+    let diagnostics = rule.check(&path, content).unwrap();
     assert_eq!(diagnostics.len(), 1);
     assert_eq!(diagnostics[0].rule_id, "unused_import");
+    assert!(matches!(diagnostics[0].severity, Severity::Warning));
+
+    // The fix removes the whole import line
+    let fix = diagnostics[0].fix.as_ref().unwrap();
+    assert_eq!(fix.edits.len(), 1);
+    assert_eq!(fix.edits[0].location.line, 1);
+    assert_eq!(fix.edits[0].location.end_line, Some(2));
+    assert_eq!(fix.edits[0].location.end_column, Some(1));
+    assert!(fix.edits[0].replacement.is_empty());
 }
 
 #[test]
 fn test_unused_import_allows_used() {
+    let dir = package(&[
+        ("lib/models.dart", "export 'src/user.dart' hide Admin;\n"),
+        ("lib/src/user.dart", "class User {}\nclass Admin {}\n"),
+        (
+            "lib/strings.dart",
+            "extension Shout on String {\n  String shout() => toUpperCase();\n}\n",
+        ),
+        ("lib/config.dart", "const appName = 'app';\n"),
+    ]);
+    let rule = UnusedImportRule;
+    let content = "import 'package:app/models.dart';\nimport 'strings.dart';\n\n\
+                   String greet(User user) => 'hi'.shout();\n";
+    let path = dir.path().join("lib/main.dart");
+
+    let diagnostics = rule.check(&path, content).unwrap();
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    // A `$name` interpolation uses the import
+    let content = "import 'config.dart';\n\nString title() => 'Welcome to $appName';\n";
+    let diagnostics = rule.check(&path, content).unwrap();
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
+
+    // Only the hidden class is used, which the import doesn't bring in
+    let content = "import 'models.dart';\n\nAdmin? admin;\n";
+    let diagnostics = rule.check(&path, content).unwrap();
+    assert_eq!(diagnostics.len(), 1);
+}
+
+#[test]
+fn test_unused_import_combinators_and_prefixes() {
+    let rule = UnusedImportRule;
+    let content = "import 'package:http/http.dart' as http;\n\
+                   import 'dart:math' show max, min;\n\
+                   import 'dart:io' as io;\n\
+                   import 'dart:convert' show jsonDecode;\n\n\
+                   int f() => http.get(max(1, 2));\n";
+    let path = Path::new("test.dart");
+
+    let diagnostics = rule.check(path, content).unwrap();
+    let unused: Vec<usize> = diagnostics.iter().map(|d| d.location.line).collect();
+    assert_eq!(unused, vec![3, 4]);
+}
+
+#[test]
+fn test_unused_import_reports_unresolvable_imports_as_unknown() {
     let rule = UnusedImportRule;
-    let content = "import 'dart:async';\n\nvoid test() {\n  Timer.run(() {});\n}\n";
+    let content = "import 'package:http/http.dart';\nimport 'dart:async';\n\nvoid f() {}\n";
+    let path = Path::new("test.dart");
+
+    let diagnostics = rule.check(path, content).unwrap();
+    assert_eq!(diagnostics.len(), 2);
+    for diagnostic in &diagnostics {
+        assert!(matches!(diagnostic.severity, Severity::Info));
+        assert!(diagnostic.message.contains("unknown"));
+        assert!(diagnostic.fix.is_none());
+    }
+}
+
+const ASYNC: &str = "Future<void> save(int id) async {}\n\
+                     load() async {}\n\
+                     int count() => 0;\n\
+                     \n\
+                     Future<void> run(Repo repo) async {\n\
+                     \x20 save(1);\n\
+                     \x20 load();\n\
+                     \x20 count();\n\
+                     \x20 await save(2);\n\
+                     \x20 unawaited(save(3));\n\
+                     \x20 Future.delayed(Duration.zero);\n\
+                     \x20 repo.fetch().then(print);\n\
+                     }\n\
+                     \n\
+                     void sync() {\n\
+                     \x20 save(4);\n\
+                     }\n";
+
+#[test]
+fn test_unawaited_futures() {
+    let rule = UnawaitedFuturesRule::new();
     let path = Path::new("test.dart");
 
+    let diagnostics = rule.check(path, ASYNC).unwrap();
+    let lines: Vec<usize> = diagnostics.iter().map(|d| d.location.line).collect();
+    assert_eq!(lines, vec![6, 7, 11, 12, 16]);
+    assert_eq!(diagnostics[0].rule_id, "unawaited_futures");
+    assert!(matches!(diagnostics[0].severity, Severity::Warning));
+
+    // Inside async functions the fix awaits the call
+    let fix = diagnostics[0].fix.as_ref().unwrap();
+    assert_eq!(fix.edits[0].location.column, 3);
+    assert_eq!(fix.edits[0].location.end_column, Some(3));
+    assert_eq!(fix.edits[0].replacement, "await ");
+    assert!(diagnostics[4].fix.is_none());
+}
+
+/// Resolves every position to a function returning the given type
+struct ReturnTypes(&'static str);
+
+impl SemanticAnalyzer for ReturnTypes {
+    fn resolve_type(&self, _file: &Path, _line: usize, _column: usize) -> Result<Option<TypeInfo>> {
+        let returned = TypeInfo {
+            name: self.0.to_string(),
+            is_nullable: false,
+            type_arguments: vec![],
+            is_function: false,
+            return_type: None,
+            parameter_types: vec![],
+        };
+        Ok(Some(TypeInfo {
+            name: "Function".to_string(),
+            is_nullable: false,
+            type_arguments: vec![],
+            is_function: true,
+            return_type: Some(Box::new(returned)),
+            parameter_types: vec![],
+        }))
+    }
+
+    fn get_diagnostics(&self, _file: &Path) -> Result<Vec<SemanticDiagnostic>> {
+        Ok(vec![])
+    }
+
+    fn find_definition(
+        &self,
+        _file: &Path,
+        _line: usize,
+        _column: usize,
+    ) -> Result<Option<SymbolInfo>> {
+        Ok(None)
+    }
+
+    fn find_references(
+        &self,
+        _file: &Path,
+        _line: usize,
+        _column: usize,
+    ) -> Result<Vec<SymbolInfo>> {
+        Ok(vec![])
+    }
+
+    fn get_hover(&self, _file: &Path, _line: usize, _column: usize) -> Result<Option<String>> {
+        Ok(None)
+    }
+}
+
+#[test]
+fn test_unawaited_futures_uses_resolved_types() {
+    let content = "void f(Repo repo) {\n  repo.fetch();\n  save(1);\n}\n";
+    let path = Path::new("test.dart");
+
+    // Neither call is known to return a Future without resolved types
+    let rule = UnawaitedFuturesRule::new();
+    assert!(rule.check(path, content).unwrap().is_empty());
+
+    let rule = UnawaitedFuturesRule::with_semantic(Arc::new(ReturnTypes("Future")));
+    assert_eq!(rule.check(path, content).unwrap().len(), 2);
+
+    // Resolved types override the declarations in the file
+    let rule = UnawaitedFuturesRule::with_semantic(Arc::new(ReturnTypes("void")));
+    assert!(rule.check(path, ASYNC).unwrap().is_empty());
+}
+
+#[test]
+fn test_avoid_void_async() {
+    let rule = AvoidVoidAsyncRule;
+    let content = "void main() async {}\n\
+                   void log() {}\n\
+                   void send() async {}\n\
+                   Future<void> save() async {}\n\
+                   class Page {\n\
+                   \x20 void refresh() async {}\n\
+                   \x20 Stream<int> ticks() async* {}\n\
+                   }\n";
+    let path = Path::new("test.dart");
+
+    let diagnostics = rule.check(path, content).unwrap();
+    let lines: Vec<usize> = diagnostics.iter().map(|d| d.location.line).collect();
+    assert_eq!(lines, vec![3, 6]);
+    assert!(diagnostics[0].message.contains("'send'"));
+
+    let fix = diagnostics[1].fix.as_ref().unwrap();
+    assert_eq!(fix.edits[0].location.column, 3);
+    assert_eq!(fix.edits[0].location.end_column, Some(7));
+    assert_eq!(fix.edits[0].replacement, "Future<void>");
+}
+
+#[test]
+fn test_avoid_future_in_foreach() {
+    let rule = AvoidFutureInForEachRule::new();
+    let content = "Future<void> save(int id) async {}\n\n\
+                   void f(List<int> ids) {\n\
+                   \x20 ids.forEach((id) async {\n    await save(id);\n  });\n\
+                   \x20 ids.where((id) => id > 0).forEach((id) => save(id));\n\
+                   \x20 ids.forEach((id) => print(id));\n\
+                   \x20 ids.forEach(print);\n\
+                   }\n";
+    let path = Path::new("test.dart");
+
+    let diagnostics = rule.check(path, content).unwrap();
+    let lines: Vec<usize> = diagnostics.iter().map(|d| d.location.line).collect();
+    assert_eq!(lines, vec![4, 7]);
+    assert_eq!(diagnostics[0].location.end_line, Some(6));
+}
+
+#[test]
+fn test_avoid_sync_io_in_async() {
+    let rule = AvoidSyncIoInAsyncRule;
+    let content = "Future<int> f(File file) async {\n\
+                   \x20 sleep(Duration(seconds: 1));\n\
+                   \x20 final length = file.readAsStringSync().length;\n\
+                   \x20 final later = () {\n    sleep(Duration.zero);\n  };\n\
+                   \x20 return length + (await file.readAsString()).length;\n\
+                   }\n\
+                   \n\
+                   String g(File file) => file.readAsStringSync();\n";
+    let path = Path::new("test.dart");
+
+    let diagnostics = rule.check(path, content).unwrap();
+    let lines: Vec<usize> = diagnostics.iter().map(|d| d.location.line).collect();
+    assert_eq!(lines, vec![2, 3]);
+    assert!(diagnostics[1].message.contains("'readAsStringSync'"));
+    assert_eq!(diagnostics[1].location.column, 23);
+    assert_eq!(
+        diagnostics[1].suggestion.as_deref(),
+        Some("Use 'readAsString' and await it instead")
+    );
+}
+
+const RESOURCES: &str = "class _PageState extends State<Page> {\n\
+                         \x20 late final StreamSubscription<int> _sub;\n\
+                         \x20 final _text = TextEditingController(), _timer = Timer.periodic(d, tick);\n\
+                         \x20 var _events = stream.listen(print);\n\
+                         \x20 late AnimationController _animation;\n\
+                         \x20 final _session = Session();\n\
+                         \n\
+                         \x20 @override\n\
+                         \x20 void dispose() {\n\
+                         \x20   this._sub.cancel();\n\
+                         \x20   _text..removeListener(update)..dispose();\n\
+                         \x20   _timer?.cancel();\n\
+                         \x20   super.dispose();\n\
+                         \x20 }\n\
+                         }\n\
+                         \n\
+                         class Poller {\n\
+                         \x20 Timer? _timer;\n\
+                         }\n";
+
+#[test]
+fn test_avoid_undisposed_resources() {
+    let rule = AvoidUndisposedResourcesRule::default();
+    let path = Path::new("test.dart");
+
+    let diagnostics = rule.check(path, RESOURCES).unwrap();
+    let messages: Vec<&str> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        vec![
+            "'_events' (StreamSubscription) is never released in 'dispose'",
+            "'_animation' (AnimationController) is never released in 'dispose'",
+            "'_timer' (Timer) is never released: 'Poller' has no dispose or close method",
+        ]
+    );
+    assert_eq!(diagnostics[0].rule_id, "avoid_undisposed_resources");
+    assert!(matches!(diagnostics[0].severity, Severity::Warning));
+    assert_eq!(diagnostics[0].location.line, 4);
+    assert_eq!(diagnostics[0].location.column, 7);
+    assert_eq!(
+        diagnostics[0].suggestion.as_deref(),
+        Some("Call '_events.cancel()' in the class's dispose or close method")
+    );
+}
+
+#[test]
+fn test_avoid_undisposed_resources_extra_types() {
+    let rule = AvoidUndisposedResourcesRule::new(vec!["Session".to_string()]);
+    let path = Path::new("test.dart");
+
+    let diagnostics = rule.check(path, RESOURCES).unwrap();
+    assert_eq!(diagnostics.len(), 4);
+    assert!(diagnostics[2].message.starts_with("'_session' (Session)"));
+
+    // A bloc closing its controller and subscription
+    let content = "class Bloc {\n\
+                   \x20 final _controller = StreamController<int>();\n\
+                   \x20 final Session session;\n\
+                   \x20 Future<void> close() async {\n\
+                   \x20   await _controller.close();\n\
+                   \x20   session.dispose();\n\
+                   \x20 }\n\
+                   }\n";
     let diagnostics = rule.check(path, content).unwrap();
-    // The simple check should find that 'async' appears in the import and Timer uses it
-    // This is a simplified check so it might still report as unused
-    // A full AST-based implementation would be more accurate
-    assert!(diagnostics.len() <= 1);
+    assert!(diagnostics.is_empty(), "{:?}", diagnostics);
 }
@@ -1,5 +1,10 @@
+mod common;
+
+use common::package;
 use dart_re_analyzer::analyzer::Rule;
+use dart_re_analyzer::config::{ArchitectureConfig, PrivateFieldConfig};
 use dart_re_analyzer::error::{RuleCategory, Severity};
+use dart_re_analyzer::rules::architecture::LayerDependenciesRule;
 use dart_re_analyzer::rules::style::*;
 use std::path::Path;
 
@@ -39,12 +44,23 @@ fn test_snake_case_file_name_valid() {
 #[test]
 fn test_snake_case_file_name_invalid() {
     let rule = SnakeCaseFileNameRule;
-    let content = "";
+    let content = "import 'dart:io';\n\nvoid main() {}\n";
     let path = Path::new("MyTestFile.dart");
 
     let diagnostics = rule.check(path, content).unwrap();
     assert_eq!(diagnostics.len(), 1);
     assert_eq!(diagnostics[0].rule_id, "snake_case_file_names");
+    // Spans the first line
+    let location = &diagnostics[0].location;
+    assert_eq!(
+        (
+            location.line,
+            location.column,
+            location.end_line,
+            location.end_column
+        ),
+        (1, 1, Some(1), Some(18))
+    );
 }
 
 #[test]
@@ -68,3 +84,194 @@ fn test_line_length_invalid() {
     assert_eq!(diagnostics[0].rule_id, "line_length");
     assert!(matches!(diagnostics[0].severity, Severity::Info));
 }
+
+#[test]
+fn test_line_length_counts_characters() {
+    let rule = LineLengthRule::new(10);
+    let path = Path::new("test.dart");
+
+    // Ten accented characters are twenty bytes but within the limit
+    let diagnostics = rule.check(path, "éééééééééé\n").unwrap();
+    assert!(diagnostics.is_empty());
+
+    let diagnostics = rule.check(path, "// éééééééééé\n").unwrap();
+    assert_eq!(diagnostics.len(), 1);
+    let location = &diagnostics[0].location;
+    // The span starts at the eleventh character and runs to the line end
+    assert_eq!(location.column, "// ééééééé".len() + 1);
+    assert_eq!(location.end_column, Some("// éééééééééé".len() + 1));
+}
+
+const FIELDS: &str = r#"final _registry = <Counter>[];
+
+class Counter {
+  int count = 0;
+  final String name;
+  static int instances = 0;
+  static const int limit = 10;
+  late int first, second;
+
+  final int _id;
+  int get id => _id;
+
+  int _total = 0;
+  int get total { return _total; }
+  set total(int value) { _total = value; }
+
+  int _hits = 0;
+  int get hits => _hits;
+
+  final int _max;
+  int get max => _max + 1;
+
+  Counter(this.name, this._id, this._max);
+
+  void add(int _amount, void Function(int) _) {
+    var _next = count + 1;
+    try {
+      count = _next;
+    } catch (_error) {}
+  }
+}
+
+class _Internal {
+  int value = 0;
+}
+"#;
+
+fn messages(rule: &PrivateFieldUnderscoreRule) -> Vec<String> {
+    rule.check(Path::new("counter.dart"), FIELDS)
+        .unwrap()
+        .into_iter()
+        .map(|d| format!("{}: {}", d.location.line, d.message))
+        .collect()
+}
+
+#[test]
+fn test_private_field_underscore() {
+    let messages = messages(&PrivateFieldUnderscoreRule::default());
+    assert_eq!(
+        messages,
+        vec![
+            "4: Public field 'count' is mutable; make it final or private",
+            "6: Public field 'instances' is mutable; make it final or private",
+            "8: Public field 'first' is mutable; make it final or private",
+            "8: Public field 'second' is mutable; make it final or private",
+            "10: Private field '_id' is exposed through a getter with no logic; use a public final field 'id' instead",
+            "13: Private field '_total' is exposed through a getter with no logic; use a public field 'total' instead",
+            "25: Parameter '_amount' should not start with an underscore",
+            "26: Local variable '_next' should not start with an underscore",
+            "29: Catch parameter '_error' should not start with an underscore",
+        ]
+    );
+}
+
+#[test]
+fn test_private_field_underscore_checks_are_configurable() {
+    let only = |public_mutable_fields, trivial_getters, underscore_locals| {
+        messages(&PrivateFieldUnderscoreRule::new(PrivateFieldConfig {
+            public_mutable_fields,
+            trivial_getters,
+            underscore_locals,
+        }))
+    };
+
+    assert!(only(true, false, false)
+        .iter()
+        .all(|m| m.contains("Public field")));
+    assert!(only(false, true, false)
+        .iter()
+        .all(|m| m.contains("Private field")));
+    assert!(only(false, false, true)
+        .iter()
+        .all(|m| m.contains("should not start with an underscore")));
+    assert!(only(false, false, false).is_empty());
+
+    let diagnostics = PrivateFieldUnderscoreRule::default()
+        .check(Path::new("counter.dart"), FIELDS)
+        .unwrap();
+    assert_eq!(diagnostics[0].location.column, 7);
+    assert_eq!(
+        diagnostics[0].suggestion.as_deref(),
+        Some("Rename to '_count' and expose it with 'int get count => _count;'")
+    );
+}
+
+/// `domain` may import nothing and not Flutter, `data` may import `domain`,
+/// `ui` may import both
+fn layers() -> ArchitectureConfig {
+    serde_json::from_value(serde_json::json!({
+        "layers": [
+            {"name": "domain", "paths": ["lib/domain/**"], "banned_packages": ["flutter", "dart:io"]},
+            {"name": "data", "paths": ["lib/data/**"], "may_import": ["domain"]},
+            {"name": "ui", "paths": ["lib/ui/**", "lib/main.dart"], "may_import": ["domain", "data"]},
+        ]
+    }))
+    .unwrap()
+}
+
+#[test]
+fn test_layer_dependencies() {
+    let dir = package(&[]);
+    let rule = LayerDependenciesRule::new(layers());
+    let check = |file: &str, content: &str| -> Vec<String> {
+        rule.check(&dir.path().join(file), content)
+            .unwrap()
+            .into_iter()
+            .map(|d| d.message)
+            .collect()
+    };
+
+    let content = "import 'user.dart';\n\
+                   import '../data/user_repository.dart';\n\
+                   import 'package:app/ui/user_page.dart';\n\
+                   import 'package:app/util/strings.dart';\n\
+                   import 'package:collection/collection.dart';\n";
+    assert_eq!(
+        check("lib/domain/user_service.dart", content),
+        vec![
+            "Layer 'domain' may not import layer 'data' ('../data/user_repository.dart')",
+            "Layer 'domain' may not import layer 'ui' ('package:app/ui/user_page.dart')",
+        ]
+    );
+
+    let content = "import '../domain/user.dart';\nimport 'package:app/ui/user_page.dart';\n";
+    assert_eq!(
+        check("lib/data/user_repository.dart", content),
+        vec!["Layer 'data' may not import layer 'ui' ('package:app/ui/user_page.dart')"]
+    );
+    let content = "import 'domain/user.dart';\nimport 'data/user_repository.dart';\n";
+    assert!(check("lib/main.dart", content).is_empty());
+
+    // Files outside every layer are not checked
+    let content = "import '../ui/user_page.dart';\n";
+    assert!(check("lib/util/strings.dart", content).is_empty());
+}
+
+#[test]
+fn test_layer_dependencies_banned_packages() {
+    let dir = package(&[]);
+    let rule = LayerDependenciesRule::new(layers());
+    let content = "import 'dart:async';\n\
+                   import 'dart:io';\n\
+                   import 'package:flutter/foundation.dart';\n\
+                   import 'package:flutter_bloc/flutter_bloc.dart';\n";
+
+    let diagnostics = rule
+        .check(&dir.path().join("lib/domain/user.dart"), content)
+        .unwrap();
+    let lines: Vec<usize> = diagnostics.iter().map(|d| d.location.line).collect();
+    assert_eq!(lines, vec![2, 3]);
+    assert_eq!(diagnostics[1].rule_id, "layer_dependencies");
+    assert_eq!(
+        diagnostics[1].message,
+        "Layer 'domain' may not import 'package:flutter/foundation.dart'"
+    );
+    assert!(matches!(diagnostics[1].severity, Severity::Warning));
+
+    // Without layers, nothing is checked
+    let diagnostics = LayerDependenciesRule::default()
+        .check(&dir.path().join("lib/domain/user.dart"), content)
+        .unwrap();
+    assert!(diagnostics.is_empty());
+}